@@ -8,8 +8,7 @@ use std::process::{Command, ExitStatus};
 
 use anyhow::{Context, Error, bail};
 use clap::Parser;
-use glob;
-use glob::Paths;
+use glob::{self, Paths};
 use paris::LogIcon;
 use target_lexicon::{HOST, OperatingSystem};
 
@@ -77,6 +76,28 @@ fn run_build_command() -> Result<(), Error>
 	let glob_str: String = format!("{lib_prefix}*ext{lib_ext}");
 	copy_glob(&src_dir, &dist_dir.join("extensions"), glob_str.as_str())?;
 
+	copy_games(&dist_dir.join("games"))?;
+
+	Ok(())
+}
+
+// Each game's directory holds its config file, named after the directory.
+fn copy_games(dest: &PathBuf) -> Result<(), Error>
+{
+	let glob_str: String = format!("{}/games/*", project_root().to_str().unwrap());
+	let glob_result: Paths = glob::glob(glob_str.as_str()).unwrap();
+
+	for path in glob_result
+		.filter_map(|path| path.ok())
+		.filter(|path| path.is_dir())
+	{
+		let name: &str = path.file_name().unwrap().to_str().unwrap();
+		let game_dir: PathBuf = dest.join(name);
+
+		create_dir(&game_dir)?;
+		copy_file(&path, &game_dir, format!("{name}.cfg").as_str())?;
+	}
+
 	Ok(())
 }
 
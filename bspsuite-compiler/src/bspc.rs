@@ -15,12 +15,23 @@ fn main()
 	let parsed_args: cli::Cli = cli::Cli::parse();
 
 	init_logger(&parsed_args);
-	print_banner();
+
+	// JSON output is meant to be read by other tools, so is printed alone.
+	if !prints_json(&parsed_args)
+	{
+		print_banner();
+	}
 
 	let subcommand: &cli::Subcommand = &parsed_args.command;
 	let result_code: Cmds::ResultCode = match subcommand
 	{
-		cli::Subcommand::Compile(args) => run_compile_command(&args),
+		cli::Subcommand::Compile(args) => run_compile_command(&args, None),
+		cli::Subcommand::Csg(args) => run_compile_command(&args, Some(Cmds::CompileStage::Csg)),
+		cli::Subcommand::Bsp(args) => run_compile_command(&args, Some(Cmds::CompileStage::Bsp)),
+		cli::Subcommand::Hulls(args) => run_compile_command(&args, Some(Cmds::CompileStage::Hulls)),
+		cli::Subcommand::Vis(args) => run_compile_command(&args, Some(Cmds::CompileStage::Vis)),
+		cli::Subcommand::Rad(args) => run_compile_command(&args, Some(Cmds::CompileStage::Rad)),
+		cli::Subcommand::Info(args) => run_info_command(&args),
 	};
 
 	match result_code
@@ -35,16 +46,35 @@ fn main()
 	std::process::exit(result_code as i32);
 }
 
-fn run_compile_command(args: &cli::CompileCommandArgs) -> Cmds::ResultCode
+fn run_compile_command(
+	args: &cli::CompileCommandArgs,
+	final_stage: Option<Cmds::CompileStage>,
+) -> Cmds::ResultCode
 {
 	let args: Cmds::CompileArgs = Cmds::CompileArgs {
 		base: Cmds::BaseArgs::default(),
 		input_file: args.input_file.clone(),
+		game: args.game.clone(),
+		final_stage: final_stage,
+		debug_portals: args.debug_portals,
+		fast_vis: args.fast,
 	};
 
 	return Cmds::bspcore_run_compile(&args);
 }
 
+fn run_info_command(args: &cli::InfoCommandArgs) -> Cmds::ResultCode
+{
+	let args: Cmds::InfoArgs = Cmds::InfoArgs {
+		base: Cmds::BaseArgs::default(),
+		input_file: args.input_file.clone(),
+		game: args.game.clone(),
+		json: args.json,
+	};
+
+	return Cmds::bspcore_run_info(&args);
+}
+
 fn init_logger(parsed_args: &cli::Cli)
 {
 	lazy_static! {
@@ -54,8 +84,11 @@ fn init_logger(parsed_args: &cli::Cli)
 		pub static ref ERROR_PREFIX: String = colorize_string("<b><red>");
 	}
 
+	// Only warnings and errors are written alongside JSON output, as these go
+	// to stderr rather than stdout.
 	let log_filter: LevelFilter = match parsed_args.debug
 	{
+		_ if prints_json(parsed_args) => LevelFilter::Warn,
 		Some(DebugLevel::Off) => LevelFilter::Info,
 		Some(DebugLevel::On) => LevelFilter::Debug,
 		Some(DebugLevel::Trace) => LevelFilter::Trace,
@@ -126,6 +159,11 @@ fn init_logger(parsed_args: &cli::Cli)
 		.expect("Could not initialise logger");
 }
 
+fn prints_json(parsed_args: &cli::Cli) -> bool
+{
+	return matches!(&parsed_args.command, cli::Subcommand::Info(args) if args.json);
+}
+
 fn print_banner()
 {
 	let build_id_ptr: *const c_char = Cmds::bspcore_get_build_identifier_string();
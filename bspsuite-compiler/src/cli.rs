@@ -34,6 +34,25 @@ pub enum Subcommand
 {
 	/// Compile a map from a source file.
 	Compile(CompileCommandArgs),
+
+	/// Run the compile up to and including the CSG stage.
+	Csg(CompileCommandArgs),
+
+	/// Run the compile up to and including the BSP tree construction stage.
+	Bsp(CompileCommandArgs),
+
+	/// Run the compile up to and including the clipping hull stage.
+	Hulls(CompileCommandArgs),
+
+	/// Run the compile up to and including the visibility stage.
+	Vis(CompileCommandArgs),
+
+	/// Run the compile up to and including the lighting stage.
+	Rad(CompileCommandArgs),
+
+	/// Summarise a compiled BSP file, including how much of the engine's
+	/// limits it uses.
+	Info(InfoCommandArgs),
 }
 
 #[derive(clap::Args)]
@@ -42,4 +61,37 @@ pub struct CompileCommandArgs
 	/// Path to map source file that will be compiled.
 	#[arg()]
 	pub input_file: PathBuf,
+
+	/// Name of the game to compile for, whose config is read from
+	/// games/<GAME>/<GAME>.cfg under the toolchain root. If not specified,
+	/// defaults suitable for GoldSrc are used.
+	#[arg(long)]
+	pub game: Option<String>,
+
+	/// Write every portal between leaves of the world to a .debug.prt file,
+	/// for viewing in an editor.
+	#[arg(long)]
+	pub debug_portals: bool,
+
+	/// Only flood through portals when computing visibility, rather than
+	/// clipping them to lines of sight. This is much faster, but less precise.
+	#[arg(long)]
+	pub fast: bool,
+}
+
+#[derive(clap::Args)]
+pub struct InfoCommandArgs
+{
+	/// Path to the BSP file that will be inspected.
+	#[arg()]
+	pub input_file: PathBuf,
+
+	/// Name of the game whose config overrides the limits of the format,
+	/// read from games/<GAME>/<GAME>.cfg under the toolchain root.
+	#[arg(long)]
+	pub game: Option<String>,
+
+	/// Print the summary as JSON, without the banner or other logging.
+	#[arg(long)]
+	pub json: bool,
 }
@@ -1,6 +1,7 @@
-use super::types::{BaseArgs, ResultCode};
-use super::utils::wrap_panics;
-use crate::pipeline::{ExtensionFeature, PipelineBuilder};
+use super::types::{BaseArgs, CompileStage, ResultCode};
+use super::utils::{log_compiler_error, wrap_panics};
+use crate::compiler_error::CompilerError;
+use crate::pipeline::{ExtensionFeature, Pipeline, PipelineBuilder};
 use log::info;
 use std::path::PathBuf;
 
@@ -9,17 +10,55 @@ pub struct CompileArgs
 {
 	pub base: BaseArgs,
 	pub input_file: PathBuf,
+
+	/// Name of the game to compile the map for, whose config is read from the
+	/// toolchain's games directory. If not set, defaults suitable for GoldSrc
+	/// are used.
+	pub game: Option<String>,
+
+	/// If set, compiling stops once this stage has completed. Otherwise, all
+	/// stages are run.
+	pub final_stage: Option<CompileStage>,
+
+	/// If set, every portal between leaves of the world is written to a
+	/// .debug.prt file next to the map, so that it can be viewed in an editor.
+	pub debug_portals: bool,
+
+	/// If set, VIS only floods through portals rather than clipping them to
+	/// lines of sight. This is much faster, but less precise.
+	pub fast_vis: bool,
 }
 
 #[unsafe(no_mangle)]
 pub extern "C" fn bspcore_run_compile(args: &CompileArgs) -> ResultCode
 {
 	return wrap_panics(|| {
-		let pipeline = PipelineBuilder::new(&args.base.toolchain_root)
-			.require_feature(ExtensionFeature::DummyFeature)
-			.finalise();
+		if let Err(err) = run_compile(args)
+		{
+			log_compiler_error(&err);
+			return err.code.get_result_code();
+		}
 
 		info!("Compile complete");
 		return ResultCode::Ok;
 	});
 }
+
+fn run_compile(args: &CompileArgs) -> Result<(), CompilerError>
+{
+	let mut builder: PipelineBuilder = PipelineBuilder::new(&args.base.toolchain_root);
+
+	if let Some(game) = &args.game
+	{
+		builder = builder.game(game)?;
+	}
+
+	let pipeline: Pipeline = builder
+		.require_feature(ExtensionFeature::StageHooks)
+		.final_stage(args.final_stage)
+		.debug_portals(args.debug_portals)
+		.fast_vis(args.fast_vis)
+		.finalise();
+
+	return pipeline.run(&args.input_file);
+}
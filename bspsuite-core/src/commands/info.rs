@@ -0,0 +1,128 @@
+use super::types::{BaseArgs, ResultCode};
+use super::utils::{log_compiler_error, wrap_panics};
+use crate::compiler_error::CompilerError;
+use crate::model::BspFileInfo;
+use crate::toolchain::Toolchain;
+use crate::work_units::read_bsp_info;
+use log::{error, info};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+#[repr(C)]
+pub struct InfoArgs
+{
+	pub base: BaseArgs,
+	pub input_file: PathBuf,
+
+	/// Name of the game whose config overrides the limits of the format, if
+	/// any.
+	pub game: Option<String>,
+
+	/// If set, the summary is printed to standard output as JSON, rather than
+	/// being logged as text.
+	pub json: bool,
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn bspcore_run_info(args: &InfoArgs) -> ResultCode
+{
+	return wrap_panics(|| {
+		let info: BspFileInfo = match run_info(args)
+		{
+			Ok(info) => info,
+			Err(err) =>
+			{
+				log_compiler_error(&err);
+				return err.code.get_result_code();
+			}
+		};
+
+		if !args.json
+		{
+			log_bsp_info(&info);
+			return ResultCode::Ok;
+		}
+
+		match serde_json::to_string_pretty(&info)
+		{
+			Ok(json) => println!("{json}"),
+			Err(err) =>
+			{
+				error!("Could not convert summary to JSON. {err}");
+				return ResultCode::InternalError;
+			}
+		};
+
+		return ResultCode::Ok;
+	});
+}
+
+// The format's limits come from whichever loaded extension writes it.
+fn run_info(args: &InfoArgs) -> Result<BspFileInfo, CompilerError>
+{
+	let toolchain: Toolchain = Toolchain::new(&args.base.toolchain_root);
+
+	let limit_overrides: BTreeMap<String, usize> = match &args.game
+	{
+		Some(game) => toolchain.load_game_config(game)?.limits,
+		None => BTreeMap::new(),
+	};
+
+	return read_bsp_info(&args.input_file, toolchain.extensions(), &limit_overrides);
+}
+
+fn log_bsp_info(info: &BspFileInfo)
+{
+	info!("Format: {} ({} bytes)", info.format, info.file_size);
+	info!("Entities: {}", info.entity_count);
+
+	info!("Lumps:");
+	for lump in &info.lumps
+	{
+		let count: String = lump
+			.count
+			.map(|count| format!(" ({count} records)"))
+			.unwrap_or_default();
+
+		info!("  {:<18}{:>10} bytes{count}", lump.name, lump.length);
+	}
+
+	if !info.limits.is_empty()
+	{
+		info!("Limits:");
+	}
+
+	for limit in &info.limits
+	{
+		info!(
+			"  {:<22}{:>8} / {:<8} {:>6.1}%",
+			limit.name, limit.count, limit.limit, limit.percentage
+		);
+	}
+
+	info!("Textures: {}", info.textures.len());
+	for texture in &info.textures
+	{
+		let size: String = texture
+			.size
+			.map(|[width, height]| format!(" {width}x{height}"))
+			.unwrap_or_default();
+		let embedded: &str = if texture.embedded { " (embedded)" } else { "" };
+
+		info!("  {}{size}{embedded}", texture.name);
+	}
+
+	info!(
+		"Lighting: {} bytes, {} samples",
+		info.lighting_bytes, info.lightmap_samples
+	);
+
+	match info.vis_compression_ratio
+	{
+		Some(ratio) => info!(
+			"Visibility: {} bytes, {} bytes uncompressed, compression ratio {ratio:.2}:1",
+			info.vis_bytes, info.vis_uncompressed_bytes
+		),
+		None => info!("Visibility: none"),
+	};
+}
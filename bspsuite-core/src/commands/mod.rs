@@ -1,11 +1,13 @@
 mod compile;
+mod info;
 mod types;
 mod utils;
 
 use std::ffi::c_char;
 
 pub use compile::{CompileArgs, bspcore_run_compile};
-pub use types::{BaseArgs, ResultCode};
+pub use info::{InfoArgs, bspcore_run_info};
+pub use types::{BaseArgs, CompileStage, ResultCode};
 
 use crate::BUILD_IDENTIFIER;
 
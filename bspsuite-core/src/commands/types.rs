@@ -19,6 +19,44 @@ pub enum ResultCode
 
 	/// There was an error reading from or writing to disk.
 	IoError = 4,
+
+	/// The map source file could not be parsed.
+	SyntaxError = 5,
+
+	/// The map contained geometry that could not be compiled.
+	GeometryError = 6,
+
+	/// The map was not sealed, so the void outside of it could be reached
+	/// from an entity.
+	LeakError = 7,
+
+	/// The compiled map exceeded the limits of the output format.
+	LimitError = 8,
+
+	/// An extension reported that it could not complete its work.
+	ExtensionError = 9,
+}
+
+/// Stages of the compile pipeline, in the order in which they run.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, strum::Display)]
+#[repr(C)]
+pub enum CompileStage
+{
+	/// Clip overlapping brushes against each other, leaving only the visible
+	/// faces.
+	Csg,
+
+	/// Partition the visible faces into BSP trees.
+	Bsp,
+
+	/// Build the clipping hulls used for collision.
+	Hulls,
+
+	/// Compute which parts of the world can see each other.
+	Vis,
+
+	/// Compute the lightmaps of visible faces.
+	Rad,
 }
 
 #[repr(C)]
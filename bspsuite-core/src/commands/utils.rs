@@ -1,4 +1,5 @@
 use super::types::ResultCode;
+use crate::compiler_error::CompilerError;
 use log::error;
 use paris::formatter::colorize_string;
 use std::any::Any;
@@ -36,3 +37,27 @@ where
 		}
 	};
 }
+
+// Logs a compiler error. If the error refers to a location in a source file,
+// the offending line is printed beneath the error, with the relevant text
+// highlighted.
+pub fn log_compiler_error(err: &CompilerError)
+{
+	let Some(diagnostic) = &err.diagnostic
+	else
+	{
+		error!("{err}");
+		return;
+	};
+
+	let (gutter, line_text, marker) = diagnostic.snippet();
+
+	// Only the parts we generate are colourised. The line of source text is
+	// printed verbatim, in case it contains anything that looks like a
+	// formatting tag.
+	error!(
+		"{err}\n{}{line_text}\n{}",
+		colorize_string(format!("<b><blue>{gutter}</>")),
+		colorize_string(format!("<b><blue>{marker}</>"))
+	);
+}
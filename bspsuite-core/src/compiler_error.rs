@@ -1,7 +1,9 @@
 use crate::commands::ResultCode;
+use crate::source_diagnostic::SourceDiagnostic;
+use anyhow;
 use std::error::Error;
 use std::fmt;
-use {anyhow, strum};
+use strum;
 
 #[derive(Debug, Copy, Clone, strum::Display)]
 pub enum CompilerErrorCode
@@ -10,6 +12,11 @@ pub enum CompilerErrorCode
 	ArgumentError,
 	ConfigError,
 	IoError,
+	SyntaxError,
+	GeometryError,
+	LeakError,
+	LimitError,
+	ExtensionError,
 }
 
 impl CompilerErrorCode
@@ -22,6 +29,11 @@ impl CompilerErrorCode
 			CompilerErrorCode::ArgumentError => ResultCode::ArgumentError,
 			CompilerErrorCode::ConfigError => ResultCode::ConfigError,
 			CompilerErrorCode::IoError => ResultCode::IoError,
+			CompilerErrorCode::SyntaxError => ResultCode::SyntaxError,
+			CompilerErrorCode::GeometryError => ResultCode::GeometryError,
+			CompilerErrorCode::LeakError => ResultCode::LeakError,
+			CompilerErrorCode::LimitError => ResultCode::LimitError,
+			CompilerErrorCode::ExtensionError => ResultCode::ExtensionError,
 		};
 	}
 }
@@ -31,6 +43,10 @@ pub struct CompilerError
 {
 	pub code: CompilerErrorCode,
 	pub description: String,
+
+	/// If the error refers to a specific location in a source file,
+	/// this describes the location.
+	pub diagnostic: Option<Box<SourceDiagnostic>>,
 }
 
 impl CompilerError
@@ -40,6 +56,16 @@ impl CompilerError
 		return Self {
 			code: code,
 			description: description,
+			diagnostic: None,
+		};
+	}
+
+	pub fn from_diagnostic(code: CompilerErrorCode, diagnostic: SourceDiagnostic) -> Self
+	{
+		return Self {
+			code: code,
+			description: diagnostic.to_string(),
+			diagnostic: Some(Box::new(diagnostic)),
 		};
 	}
 
@@ -0,0 +1,368 @@
+use crate::model::{BspData, BspDataChild, BspDataModel, Contents, TextureAxis};
+use bspextifc::bsp_writer_api::{
+	BrushArgs, BrushSideArgs, ClipNodeArgs, FaceArgs, LeafArgs, LeafContents, LimitArgs, ModelArgs,
+	NodeArgs, NodeChild, PlaneArgs, TextureArgs, TextureInfoArgs,
+};
+use bspextifc::{SliceRef, StringRef, bsp_writer_api};
+use std::collections::BTreeMap;
+use std::ffi::c_void;
+use std::marker::PhantomData;
+use std::path::PathBuf;
+
+/// Limit of the output format that the map exceeds.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ExceededLimit
+{
+	pub name: String,
+	pub count: usize,
+	pub limit: usize,
+}
+
+pub enum BspWriterError
+{
+	/// Writing failed for a general reason, described by the message.
+	Failed(String),
+
+	/// The map exceeded one or more limits of the format.
+	LimitsExceeded(Vec<ExceededLimit>),
+}
+
+struct BspWriterApiImpl
+{
+	error: Option<String>,
+	exceeded_limits: Vec<ExceededLimit>,
+}
+
+impl BspWriterApiImpl
+{
+	pub fn set_error(&mut self, message: String)
+	{
+		self.error = Some(message);
+	}
+
+	pub fn report_exceeded_limit(&mut self, args: &bsp_writer_api::ExceededLimitArgs)
+	{
+		self.exceeded_limits.push(ExceededLimit {
+			name: args.name.to_string(),
+			count: args.count,
+			limit: args.limit,
+		});
+	}
+}
+
+// Arrays of the map converted into the types used by the API, which need to
+// outlive the arguments that refer to them.
+struct ArgBuffers
+{
+	planes: Vec<PlaneArgs>,
+	texture_infos: Vec<TextureInfoArgs>,
+	nodes: Vec<NodeArgs>,
+	clip_nodes: Vec<ClipNodeArgs>,
+	face_points: Vec<Vec<[f64; 3]>>,
+	leaf_faces: Vec<Vec<u32>>,
+	leaf_brushes: Vec<Vec<u32>>,
+	brush_sides: Vec<Vec<BrushSideArgs>>,
+	clip_head_nodes: Vec<Vec<NodeChild>>,
+	cluster_visibility: Vec<u8>,
+}
+
+impl ArgBuffers
+{
+	fn new(data: &BspData) -> Self
+	{
+		return Self {
+			planes: data
+				.planes
+				.iter()
+				.map(|plane| PlaneArgs {
+					normal: plane.normal.to_array(),
+					distance: plane.distance,
+				})
+				.collect(),
+			texture_infos: data
+				.texture_infos
+				.iter()
+				.map(|info| {
+					let (u_axis, u_offset): ([f64; 3], f64) = texture_axis_args(&info.u_axis);
+					let (v_axis, v_offset): ([f64; 3], f64) = texture_axis_args(&info.v_axis);
+
+					TextureInfoArgs {
+						u_axis: u_axis,
+						u_offset: u_offset,
+						v_axis: v_axis,
+						v_offset: v_offset,
+						texture: info.texture as u32,
+						special: info.special,
+					}
+				})
+				.collect(),
+			nodes: data
+				.nodes
+				.iter()
+				.map(|node| NodeArgs {
+					plane: node.plane as u32,
+					children: node.children.map(node_child),
+					mins: node.mins.to_array(),
+					maxs: node.maxs.to_array(),
+					first_face: node.faces.start as u32,
+					face_count: node.faces.len() as u32,
+				})
+				.collect(),
+			clip_nodes: data
+				.clip_nodes
+				.iter()
+				.map(|node| ClipNodeArgs {
+					plane: node.plane as u32,
+					children: node.children.map(node_child),
+				})
+				.collect(),
+			face_points: data
+				.faces
+				.iter()
+				.map(|face| face.points.iter().map(|point| point.to_array()).collect())
+				.collect(),
+			leaf_faces: data
+				.leaves
+				.iter()
+				.map(|leaf| leaf.faces.iter().map(|face| *face as u32).collect())
+				.collect(),
+			leaf_brushes: data
+				.leaves
+				.iter()
+				.map(|leaf| leaf.brushes.iter().map(|brush| *brush as u32).collect())
+				.collect(),
+			brush_sides: data
+				.brushes
+				.iter()
+				.map(|brush| {
+					brush
+						.sides
+						.iter()
+						.map(|side| BrushSideArgs {
+							plane: side.plane as u32,
+							face: side.face.map(|face| face as i32).unwrap_or(-1),
+						})
+						.collect()
+				})
+				.collect(),
+			clip_head_nodes: data
+				.models
+				.iter()
+				.map(|model| {
+					model
+						.clip_head_nodes
+						.iter()
+						.copied()
+						.map(node_child)
+						.collect()
+				})
+				.collect(),
+			cluster_visibility: data.visibility.rows.concat(),
+		};
+	}
+}
+
+/// Passes the compiled map to the extension's callback, to be written to the
+/// given path. Limits named in the overrides replace those of the format.
+pub fn call_bsp_writer_api(
+	write_bsp_fn: bsp_writer_api::WriteBspFn,
+	path: &PathBuf,
+	data: &BspData,
+	limit_overrides: &BTreeMap<String, usize>,
+) -> Result<(), BspWriterError>
+{
+	let path_string: String = path.to_string_lossy().to_string();
+	let buffers: ArgBuffers = ArgBuffers::new(data);
+
+	let textures: Vec<TextureArgs> = data
+		.textures
+		.iter()
+		.map(|texture| TextureArgs {
+			name: StringRef::from(texture.name.as_str()),
+			width: texture.size[0],
+			height: texture.size[1],
+			pixels: SliceRef::from(texture.pixels.as_slice()),
+			palette: SliceRef::from(texture.palette.as_slice()),
+		})
+		.collect();
+
+	let faces: Vec<FaceArgs> = data
+		.faces
+		.iter()
+		.zip(&buffers.face_points)
+		.map(|(face, points)| {
+			let mut styles: [u8; 4] = [255; 4];
+			styles
+				.iter_mut()
+				.zip(&face.styles)
+				.for_each(|(slot, style)| *slot = *style);
+
+			FaceArgs {
+				plane: face.plane as u32,
+				points: SliceRef::from(points.as_slice()),
+				texture_info: face.texture_info as u32,
+				styles: styles,
+				lighting_offset: face
+					.lighting_offset
+					.map(|offset| offset as i32)
+					.unwrap_or(-1),
+				lightmap_mins: face.lightmap_mins,
+				lightmap_size: face.lightmap_size.map(|size| size as u32),
+			}
+		})
+		.collect();
+
+	let leaves: Vec<LeafArgs> = data
+		.leaves
+		.iter()
+		.zip(buffers.leaf_faces.iter().zip(&buffers.leaf_brushes))
+		.map(|(leaf, (faces, brushes))| LeafArgs {
+			contents: leaf_contents(leaf.contents),
+			cluster: leaf.cluster.map(|cluster| cluster as i32).unwrap_or(-1),
+			mins: leaf.mins.to_array(),
+			maxs: leaf.maxs.to_array(),
+			faces: SliceRef::from(faces.as_slice()),
+			brushes: SliceRef::from(brushes.as_slice()),
+		})
+		.collect();
+
+	let brushes: Vec<BrushArgs> = data
+		.brushes
+		.iter()
+		.zip(&buffers.brush_sides)
+		.map(|(brush, sides)| BrushArgs {
+			contents: leaf_contents(brush.contents),
+			sides: SliceRef::from(sides.as_slice()),
+			mins: brush.mins.to_array(),
+			maxs: brush.maxs.to_array(),
+		})
+		.collect();
+
+	let models: Vec<ModelArgs> = data
+		.models
+		.iter()
+		.zip(&buffers.clip_head_nodes)
+		.map(|(model, clip_head_nodes)| model_args(model, clip_head_nodes))
+		.collect();
+
+	let limits: Vec<LimitArgs> = limit_overrides
+		.iter()
+		.map(|(name, value)| LimitArgs {
+			name: StringRef::from(name.as_str()),
+			value: *value,
+		})
+		.collect();
+
+	let args: bsp_writer_api::WriteBspArgs = bsp_writer_api::WriteBspArgs {
+		path: StringRef::from(path_string.as_str()),
+		entities: StringRef::from(data.entities.as_str()),
+		planes: SliceRef::from(buffers.planes.as_slice()),
+		textures: SliceRef::from(textures.as_slice()),
+		texture_infos: SliceRef::from(buffers.texture_infos.as_slice()),
+		faces: SliceRef::from(faces.as_slice()),
+		nodes: SliceRef::from(buffers.nodes.as_slice()),
+		leaves: SliceRef::from(leaves.as_slice()),
+		clip_nodes: SliceRef::from(buffers.clip_nodes.as_slice()),
+		brushes: SliceRef::from(brushes.as_slice()),
+		models: SliceRef::from(models.as_slice()),
+		cluster_count: data.visibility.cluster_count as u32,
+		cluster_visibility: SliceRef::from(buffers.cluster_visibility.as_slice()),
+		lighting: SliceRef::from(data.lighting.as_slice()),
+		luxel_size: data.luxel_size,
+		limits: SliceRef::from(limits.as_slice()),
+	};
+
+	let mut api_impl: BspWriterApiImpl = BspWriterApiImpl {
+		error: None,
+		exceeded_limits: Vec::new(),
+	};
+
+	let mut core_fns: bsp_writer_api::internal::BspWriterApiCoreFns =
+		bsp_writer_api::internal::BspWriterApiCoreFns {
+			context: &mut api_impl as *mut BspWriterApiImpl as *mut c_void,
+			phantom: PhantomData,
+			set_error_fn: set_error,
+			report_exceeded_limit_fn: report_exceeded_limit,
+		};
+
+	let mut api: bsp_writer_api::BspWriterApi =
+		bsp_writer_api::internal::create_bsp_writer_api(&mut core_fns);
+
+	let result: bsp_writer_api::WriteBspResult = write_bsp_fn(&mut api, &args);
+
+	if let bsp_writer_api::WriteBspResult::Failure = result
+	{
+		if !api_impl.exceeded_limits.is_empty()
+		{
+			return Err(BspWriterError::LimitsExceeded(api_impl.exceeded_limits));
+		}
+
+		return Err(BspWriterError::Failed(api_impl.error.unwrap_or_else(
+			|| String::from("Extension did not provide a reason for the failure"),
+		)));
+	}
+
+	return Ok(());
+}
+
+fn model_args<'l>(model: &BspDataModel, clip_head_nodes: &'l [NodeChild]) -> ModelArgs<'l>
+{
+	return ModelArgs {
+		mins: model.mins.to_array(),
+		maxs: model.maxs.to_array(),
+		origin: model.origin.to_array(),
+		head_node: node_child(model.head_node),
+		clip_head_nodes: SliceRef::from(clip_head_nodes),
+		first_face: model.faces.start as u32,
+		face_count: model.faces.len() as u32,
+		first_node: model.nodes.start as u32,
+		node_count: model.nodes.len() as u32,
+		first_leaf: model.leaves.start as u32,
+		leaf_count: model.leaves.len() as u32,
+		first_brush: model.brushes.start as u32,
+		brush_count: model.brushes.len() as u32,
+	};
+}
+
+fn texture_axis_args(axis: &TextureAxis) -> ([f64; 3], f64)
+{
+	return (axis.axis.to_array(), axis.offset);
+}
+
+fn node_child(child: BspDataChild) -> NodeChild
+{
+	return match child
+	{
+		BspDataChild::Node(index) => NodeChild::Node(index as u32),
+		BspDataChild::Leaf(index) => NodeChild::Leaf(index as u32),
+		BspDataChild::Contents(contents) => NodeChild::Contents(leaf_contents(contents)),
+	};
+}
+
+fn leaf_contents(contents: Contents) -> LeafContents
+{
+	return match contents
+	{
+		Contents::Empty => LeafContents::Empty,
+		// Clip brushes are expanded into solid brushes, so never give a leaf
+		// or clipping node their own contents.
+		Contents::Solid | Contents::Clip => LeafContents::Solid,
+		Contents::Water => LeafContents::Water,
+		Contents::Slime => LeafContents::Slime,
+		Contents::Lava => LeafContents::Lava,
+		Contents::Sky => LeafContents::Sky,
+	};
+}
+
+unsafe extern "C" fn set_error(context: *mut c_void, message: StringRef)
+{
+	unsafe { (*context.cast::<BspWriterApiImpl>()).set_error(message.into()) };
+}
+
+unsafe extern "C" fn report_exceeded_limit(
+	context: *mut c_void,
+	args: &bsp_writer_api::ExceededLimitArgs,
+)
+{
+	unsafe { (*context.cast::<BspWriterApiImpl>()).report_exceeded_limit(args) };
+}
@@ -0,0 +1,122 @@
+use crate::game_config::HullSize;
+use bspextifc::hull_api;
+use std::ffi::c_void;
+use std::marker::PhantomData;
+
+struct HullApiImpl
+{
+	hulls: Vec<HullSize>,
+}
+
+impl HullApiImpl
+{
+	pub fn hull_count(&self) -> usize
+	{
+		return self.hulls.len();
+	}
+
+	pub fn hull(&self, index: usize, args: &mut hull_api::HullArgs) -> bool
+	{
+		let Some(hull) = self.hulls.get(index)
+		else
+		{
+			return false;
+		};
+
+		args.mins = hull.mins;
+		args.maxs = hull.maxs;
+		return true;
+	}
+
+	pub fn clear_hulls(&mut self)
+	{
+		self.hulls.clear();
+	}
+
+	pub fn add_hull(&mut self, args: &hull_api::HullArgs)
+	{
+		self.hulls.push(HullSize::new(args.mins, args.maxs));
+	}
+}
+
+/// Passes the hulls to the extension's callback, and returns the hulls as
+/// they were left by the extension.
+pub fn call_hull_api(
+	define_hulls_fn: hull_api::DefineHullsFn,
+	hulls: Vec<HullSize>,
+) -> Vec<HullSize>
+{
+	let mut api_impl: HullApiImpl = HullApiImpl { hulls: hulls };
+
+	let mut core_fns: hull_api::internal::HullApiCoreFns = hull_api::internal::HullApiCoreFns {
+		context: &mut api_impl as *mut HullApiImpl as *mut c_void,
+		phantom: PhantomData,
+		hull_count_fn: hull_count,
+		hull_fn: hull,
+		clear_hulls_fn: clear_hulls,
+		add_hull_fn: add_hull,
+	};
+
+	let mut api: hull_api::HullApi = hull_api::internal::create_hull_api(&mut core_fns);
+	define_hulls_fn(&mut api);
+
+	return api_impl.hulls;
+}
+
+unsafe extern "C" fn hull_count(context: *const c_void) -> usize
+{
+	return unsafe { (*context.cast::<HullApiImpl>()).hull_count() };
+}
+
+unsafe extern "C" fn hull(
+	context: *const c_void,
+	index: usize,
+	args: &mut hull_api::HullArgs,
+) -> bool
+{
+	return unsafe { (*context.cast::<HullApiImpl>()).hull(index, args) };
+}
+
+unsafe extern "C" fn clear_hulls(context: *mut c_void)
+{
+	unsafe { (*context.cast::<HullApiImpl>()).clear_hulls() };
+}
+
+unsafe extern "C" fn add_hull(context: *mut c_void, args: &hull_api::HullArgs)
+{
+	unsafe { (*context.cast::<HullApiImpl>()).add_hull(args) };
+}
+
+#[cfg(test)]
+mod tests
+{
+	use super::*;
+
+	extern "C" fn replace_last_hull(api: &mut hull_api::HullApi)
+	{
+		let kept: Vec<hull_api::HullArgs> = (0..api.hull_count() - 1)
+			.filter_map(|index| api.hull(index))
+			.collect();
+
+		assert!(api.hull(api.hull_count()).is_none());
+
+		api.clear_hulls();
+		kept.iter().for_each(|args| api.add_hull(args));
+		api.add_hull(&hull_api::HullArgs {
+			mins: [-8.0; 3],
+			maxs: [8.0; 3],
+		});
+	}
+
+	#[test]
+	fn extension_replaces_hulls()
+	{
+		let first: HullSize = HullSize::new([-16.0; 3], [16.0; 3]);
+		let second: HullSize = HullSize::new([-32.0; 3], [32.0; 3]);
+
+		assert_eq!(
+			call_hull_api(replace_last_hull, vec![first, second]),
+			vec![first, HullSize::new([-8.0; 3], [8.0; 3])]
+		);
+	}
+}
@@ -0,0 +1,300 @@
+use crate::model::{
+	BlueprintBrush, BlueprintBrushFace, BlueprintEntity, DPlane3, EntityKeyValues, MapBlueprint,
+	PlanePool, TextureAxis, TextureProjection,
+};
+use crate::source_diagnostic::SourceDiagnostic;
+use bspextifc::{StringRef, map_reader_api};
+use glam::{DVec2, DVec3};
+use std::collections::HashMap;
+use std::ffi::c_void;
+use std::marker::PhantomData;
+use std::path::PathBuf;
+
+pub enum MapReaderError
+{
+	/// Reading failed for a general reason, described by the message.
+	Failed(String),
+
+	/// The map file contained a syntax error.
+	SyntaxError(SourceDiagnostic),
+}
+
+struct MapReaderApiImpl
+{
+	path: PathBuf,
+	blueprint: MapBlueprint,
+	current_entity: Option<BlueprintEntity>,
+	current_brush: Option<BlueprintBrush>,
+	error: Option<MapReaderError>,
+}
+
+impl MapReaderApiImpl
+{
+	pub fn new(path: &PathBuf) -> Self
+	{
+		return Self {
+			path: path.clone(),
+			blueprint: MapBlueprint {
+				entities: Vec::new(),
+				planes: PlanePool::new(),
+				textures: HashMap::new(),
+			},
+			current_entity: None,
+			current_brush: None,
+			error: None,
+		};
+	}
+
+	pub fn begin_entity(&mut self, line: u32)
+	{
+		if self.current_entity.is_some()
+		{
+			self.set_error_if_unset("Began a new entity before ending the previous one");
+			return;
+		}
+
+		self.current_entity = Some(BlueprintEntity {
+			brushes: Vec::new(),
+			keyvalues: EntityKeyValues::new(),
+			source_line: line,
+		});
+	}
+
+	pub fn add_keyvalue(&mut self, key: String, value: String)
+	{
+		match &mut self.current_entity
+		{
+			Some(entity) =>
+			{
+				entity.keyvalues.push(key, value);
+			}
+			None => self.set_error_if_unset("Added a keyvalue outside of an entity"),
+		}
+	}
+
+	pub fn begin_brush(&mut self, line: u32)
+	{
+		if self.current_entity.is_none()
+		{
+			self.set_error_if_unset("Began a brush outside of an entity");
+			return;
+		}
+
+		if self.current_brush.is_some()
+		{
+			self.set_error_if_unset("Began a new brush before ending the previous one");
+			return;
+		}
+
+		self.current_brush = Some(BlueprintBrush {
+			faces: Vec::new(),
+			source_line: line,
+			colinear_face_count: 0,
+		});
+	}
+
+	pub fn add_brush_face(&mut self, args: &map_reader_api::BrushFaceArgs)
+	{
+		let Some(brush) = &mut self.current_brush
+		else
+		{
+			self.set_error_if_unset("Added a brush face outside of a brush");
+			return;
+		};
+
+		let plane_points: [DVec3; 3] = args.plane_points.map(DVec3::from_array);
+		let plane: DPlane3 = DPlane3::from_points(&plane_points);
+
+		// Such faces are common in maps produced by some editors. They are left
+		// out here, and reported later when the brushes are validated.
+		if plane.is_null()
+		{
+			brush.colinear_face_count += 1;
+			return;
+		}
+
+		let projection: TextureProjection = match args.has_texture_axes
+		{
+			true => TextureProjection {
+				u_axis: TextureAxis {
+					axis: DVec3::from_array(args.u_axis),
+					offset: args.u_offset,
+				},
+				v_axis: TextureAxis {
+					axis: DVec3::from_array(args.v_axis),
+					offset: args.v_offset,
+				},
+				rotation: args.rotation,
+				scale: DVec2::from_array(args.scale),
+			},
+			false => TextureProjection::from_quake_alignment(
+				plane.normal,
+				DVec2::new(args.u_offset, args.v_offset),
+				args.rotation,
+				DVec2::from_array(args.scale),
+			),
+		};
+
+		brush.faces.push(BlueprintBrushFace {
+			plane: self.blueprint.planes.find_or_insert(&plane),
+			material: args.material.to_string(),
+			projection: projection,
+		});
+	}
+
+	pub fn end_brush(&mut self)
+	{
+		match (self.current_brush.take(), &mut self.current_entity)
+		{
+			(Some(brush), Some(entity)) => entity.brushes.push(brush),
+			_ => self.set_error_if_unset("Ended a brush that was not begun"),
+		}
+	}
+
+	pub fn end_entity(&mut self)
+	{
+		if self.current_brush.is_some()
+		{
+			self.set_error_if_unset("Ended an entity before ending its brush");
+			return;
+		}
+
+		match self.current_entity.take()
+		{
+			Some(entity) => self.blueprint.entities.push(entity),
+			None => self.set_error_if_unset("Ended an entity that was not begun"),
+		}
+	}
+
+	pub fn set_error(&mut self, message: String)
+	{
+		self.error = Some(MapReaderError::Failed(message));
+	}
+
+	pub fn report_syntax_error(&mut self, args: &map_reader_api::SyntaxErrorArgs)
+	{
+		self.error = Some(MapReaderError::SyntaxError(SourceDiagnostic {
+			file: self.path.clone(),
+			line: args.line,
+			column: args.column,
+			span: args.span_start..args.span_end,
+			message: args.message.to_string(),
+			line_text: args.line_text.to_string(),
+		}));
+	}
+
+	pub fn finish(self) -> Result<MapBlueprint, MapReaderError>
+	{
+		if let Some(error) = self.error
+		{
+			return Err(error);
+		}
+
+		if self.current_entity.is_some()
+		{
+			return Err(MapReaderError::Failed(String::from(
+				"Finished reading before ending the last entity",
+			)));
+		}
+
+		return Ok(self.blueprint);
+	}
+
+	// Only the first error is kept, since later errors are likely to be
+	// a consequence of it.
+	fn set_error_if_unset(&mut self, message: &str)
+	{
+		if self.error.is_none()
+		{
+			self.error = Some(MapReaderError::Failed(format!(
+				"Extension map reader error: {message}"
+			)));
+		}
+	}
+}
+
+pub fn call_map_reader_api(
+	read_map_fn: map_reader_api::ReadMapFn,
+	path: &PathBuf,
+) -> Result<MapBlueprint, MapReaderError>
+{
+	let path_string: String = path.to_string_lossy().to_string();
+	let mut api_impl: MapReaderApiImpl = MapReaderApiImpl::new(path);
+
+	let mut core_fns: map_reader_api::internal::MapReaderApiCoreFns =
+		map_reader_api::internal::MapReaderApiCoreFns {
+			context: &mut api_impl as *mut MapReaderApiImpl as *mut c_void,
+			phantom: PhantomData,
+			begin_entity_fn: begin_entity,
+			add_keyvalue_fn: add_keyvalue,
+			begin_brush_fn: begin_brush,
+			add_brush_face_fn: add_brush_face,
+			end_brush_fn: end_brush,
+			end_entity_fn: end_entity,
+			set_error_fn: set_error,
+			report_syntax_error_fn: report_syntax_error,
+		};
+
+	let mut api: map_reader_api::MapReaderApi =
+		map_reader_api::internal::create_map_reader_api(&mut core_fns);
+
+	let args: map_reader_api::ReadMapArgs = map_reader_api::ReadMapArgs {
+		path: StringRef::from(path_string.as_str()),
+	};
+
+	let result: map_reader_api::ReadMapResult = read_map_fn(&mut api, &args);
+
+	if let map_reader_api::ReadMapResult::Failure = result
+	{
+		return Err(api_impl.error.unwrap_or_else(|| {
+			MapReaderError::Failed(String::from(
+				"Extension did not provide a reason for the failure",
+			))
+		}));
+	}
+
+	return api_impl.finish();
+}
+
+unsafe extern "C" fn begin_entity(context: *mut c_void, line: u32)
+{
+	unsafe { (*context.cast::<MapReaderApiImpl>()).begin_entity(line) };
+}
+
+unsafe extern "C" fn add_keyvalue(context: *mut c_void, key: StringRef, value: StringRef)
+{
+	unsafe { (*context.cast::<MapReaderApiImpl>()).add_keyvalue(key.into(), value.into()) };
+}
+
+unsafe extern "C" fn begin_brush(context: *mut c_void, line: u32)
+{
+	unsafe { (*context.cast::<MapReaderApiImpl>()).begin_brush(line) };
+}
+
+unsafe extern "C" fn add_brush_face(context: *mut c_void, args: &map_reader_api::BrushFaceArgs)
+{
+	unsafe { (*context.cast::<MapReaderApiImpl>()).add_brush_face(args) };
+}
+
+unsafe extern "C" fn end_brush(context: *mut c_void)
+{
+	unsafe { (*context.cast::<MapReaderApiImpl>()).end_brush() };
+}
+
+unsafe extern "C" fn end_entity(context: *mut c_void)
+{
+	unsafe { (*context.cast::<MapReaderApiImpl>()).end_entity() };
+}
+
+unsafe extern "C" fn set_error(context: *mut c_void, message: StringRef)
+{
+	unsafe { (*context.cast::<MapReaderApiImpl>()).set_error(message.into()) };
+}
+
+unsafe extern "C" fn report_syntax_error(
+	context: *mut c_void,
+	args: &map_reader_api::SyntaxErrorArgs,
+)
+{
+	unsafe { (*context.cast::<MapReaderApiImpl>()).report_syntax_error(args) };
+}
@@ -1,2 +1,6 @@
-pub mod dummy_api;
+pub mod bsp_writer_api;
+pub mod hull_api;
 pub mod log_api;
+pub mod map_reader_api;
+pub mod stage_hook_api;
+pub mod texture_api;
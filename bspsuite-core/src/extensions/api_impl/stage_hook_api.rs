@@ -0,0 +1,679 @@
+use crate::model::{
+	BlueprintBrushFace, BspModel, BspModels, FaceLightmap, Lightmaps, MapBlueprint, VisPortals,
+	Visibility,
+};
+use bspextifc::StringRef;
+use bspextifc::stage_hook_api::{self, BspFaceArgs, BspModelArgs, HookPoint, KeyValueArgs};
+use std::ffi::c_void;
+use std::marker::PhantomData;
+
+/// Data that the stages of the pipeline have built by the time a hook is run.
+/// Stages that have not run yet leave their data unset.
+#[derive(Clone, Copy, Default)]
+pub struct StageData<'a>
+{
+	pub models: Option<&'a BspModels>,
+	pub vis_portals: Option<&'a VisPortals>,
+	pub visibility: Option<&'a Visibility>,
+	pub lightmaps: Option<&'a Lightmaps>,
+}
+
+struct StageHookApiImpl<'a>
+{
+	point: HookPoint,
+	bsp_format: &'a str,
+	blueprint: &'a mut MapBlueprint,
+	data: StageData<'a>,
+	error: Option<String>,
+}
+
+impl StageHookApiImpl<'_>
+{
+	pub fn hook_point(&self) -> HookPoint
+	{
+		return self.point;
+	}
+
+	pub fn bsp_format(&self, format: &mut StringRef)
+	{
+		*format = StringRef::from(self.bsp_format);
+	}
+
+	pub fn entity_count(&self) -> usize
+	{
+		return self.blueprint.entities.len();
+	}
+
+	pub fn keyvalue_count(&self, entity: usize) -> usize
+	{
+		return self
+			.blueprint
+			.entities
+			.get(entity)
+			.map_or(0, |entity| entity.keyvalues.len());
+	}
+
+	pub fn keyvalue(&self, entity: usize, index: usize, args: &mut KeyValueArgs) -> bool
+	{
+		let Some((key, value)) = self
+			.blueprint
+			.entities
+			.get(entity)
+			.and_then(|entity| entity.keyvalues.iter().nth(index))
+		else
+		{
+			return false;
+		};
+
+		args.key = StringRef::from(key.as_str());
+		args.value = StringRef::from(value.as_str());
+		return true;
+	}
+
+	pub fn set_keyvalue(&mut self, entity: usize, key: String, value: String) -> bool
+	{
+		let Some(entity) = self.blueprint.entities.get_mut(entity)
+		else
+		{
+			return false;
+		};
+
+		entity.keyvalues.set(key, value);
+		return true;
+	}
+
+	pub fn remove_keyvalues(&mut self, entity: usize, key: String) -> usize
+	{
+		return self
+			.blueprint
+			.entities
+			.get_mut(entity)
+			.map_or(0, |entity| entity.keyvalues.remove(&key));
+	}
+
+	pub fn brush_count(&self, entity: usize) -> usize
+	{
+		return self
+			.blueprint
+			.entities
+			.get(entity)
+			.map_or(0, |entity| entity.brushes.len());
+	}
+
+	pub fn face_count(&self, entity: usize, brush: usize) -> usize
+	{
+		return self
+			.blueprint
+			.entities
+			.get(entity)
+			.and_then(|entity| entity.brushes.get(brush))
+			.map_or(0, |brush| brush.faces.len());
+	}
+
+	pub fn face_material(
+		&self,
+		entity: usize,
+		brush: usize,
+		face: usize,
+		material: &mut StringRef,
+	) -> bool
+	{
+		let Some(face) = self.face(entity, brush, face)
+		else
+		{
+			return false;
+		};
+
+		*material = StringRef::from(face.material.as_str());
+		return true;
+	}
+
+	// Materials decide the contents of brushes and the textures that are
+	// looked up, so can only be changed before either of these happen.
+	pub fn set_face_material(
+		&mut self,
+		entity: usize,
+		brush: usize,
+		face: usize,
+		material: String,
+	) -> bool
+	{
+		if self.point != HookPoint::PreCsg
+		{
+			return false;
+		}
+
+		let Some(face) = self
+			.blueprint
+			.entities
+			.get_mut(entity)
+			.and_then(|entity| entity.brushes.get_mut(brush))
+			.and_then(|brush| brush.faces.get_mut(face))
+		else
+		{
+			return false;
+		};
+
+		face.material = material;
+		return true;
+	}
+
+	pub fn model_count(&self) -> usize
+	{
+		return self.data.models.map_or(0, |models| models.models.len());
+	}
+
+	pub fn model(&self, model: usize, args: &mut BspModelArgs) -> bool
+	{
+		let Some(model) = self.bsp_model(model)
+		else
+		{
+			return false;
+		};
+
+		*args = BspModelArgs {
+			entity: model.entity,
+			mins: model.tree.mins.to_array(),
+			maxs: model.tree.maxs.to_array(),
+			node_count: model.tree.nodes.len(),
+			leaf_count: model.tree.leaves.len(),
+			face_count: model.tree.faces.len(),
+		};
+
+		return true;
+	}
+
+	pub fn model_face(&self, model: usize, face: usize, args: &mut BspFaceArgs) -> bool
+	{
+		let Some(face) = self
+			.bsp_model(model)
+			.and_then(|model| model.tree.faces.get(face))
+		else
+		{
+			return false;
+		};
+
+		*args = BspFaceArgs {
+			entity: face.entity,
+			brush: face.brush,
+			face: face.face,
+		};
+
+		return true;
+	}
+
+	pub fn cluster_count(&self) -> usize
+	{
+		return self
+			.data
+			.vis_portals
+			.map_or(0, |vis_portals| vis_portals.cluster_count);
+	}
+
+	pub fn portal_count(&self) -> usize
+	{
+		return self
+			.data
+			.vis_portals
+			.map_or(0, |vis_portals| vis_portals.portals.len());
+	}
+
+	pub fn leaf_cluster(&self, leaf: usize, cluster: &mut usize) -> bool
+	{
+		let Some(leaf_cluster) = self
+			.data
+			.vis_portals
+			.and_then(|vis_portals| vis_portals.leaf_clusters.get(leaf).copied().flatten())
+		else
+		{
+			return false;
+		};
+
+		*cluster = leaf_cluster;
+		return true;
+	}
+
+	pub fn is_cluster_visible(&self, from: usize, to: usize, visible: &mut bool) -> bool
+	{
+		let Some(visibility) = self
+			.data
+			.visibility
+			.filter(|visibility| from < visibility.cluster_count && to < visibility.cluster_count)
+		else
+		{
+			return false;
+		};
+
+		*visible = visibility.is_visible(from, to);
+		return true;
+	}
+
+	pub fn face_lightmap_size(&self, model: usize, face: usize, size: &mut [usize; 2]) -> bool
+	{
+		let Some(lightmap) = self.face_lightmap(model, face)
+		else
+		{
+			return false;
+		};
+
+		*size = lightmap.size;
+		return true;
+	}
+
+	pub fn set_error(&mut self, message: String)
+	{
+		self.error = Some(message);
+	}
+
+	fn bsp_model(&self, model: usize) -> Option<&BspModel>
+	{
+		return self.data.models.and_then(|models| models.models.get(model));
+	}
+
+	fn face_lightmap(&self, model: usize, face: usize) -> Option<&FaceLightmap>
+	{
+		return self
+			.data
+			.lightmaps
+			.and_then(|lightmaps| lightmaps.models.get(model))
+			.and_then(|faces| faces.get(face))
+			.and_then(Option::as_ref);
+	}
+
+	fn face(&self, entity: usize, brush: usize, face: usize) -> Option<&BlueprintBrushFace>
+	{
+		return self
+			.blueprint
+			.entities
+			.get(entity)
+			.and_then(|entity| entity.brushes.get(brush))
+			.and_then(|brush| brush.faces.get(face));
+	}
+}
+
+/// Runs an extension's stage hook on the map being compiled, along with the
+/// data that has been built from it so far.
+pub fn call_stage_hook_api(
+	hook_fn: stage_hook_api::StageHookFn,
+	point: HookPoint,
+	bsp_format: &str,
+	blueprint: &mut MapBlueprint,
+	data: StageData,
+) -> Result<(), String>
+{
+	let mut api_impl: StageHookApiImpl = StageHookApiImpl {
+		point: point,
+		bsp_format: bsp_format,
+		blueprint: blueprint,
+		data: data,
+		error: None,
+	};
+
+	let mut core_fns: stage_hook_api::internal::StageHookApiCoreFns =
+		stage_hook_api::internal::StageHookApiCoreFns {
+			context: &mut api_impl as *mut StageHookApiImpl as *mut c_void,
+			phantom: PhantomData,
+			hook_point_fn: hook_point,
+			bsp_format_fn: bsp_format_of,
+			entity_count_fn: entity_count,
+			keyvalue_count_fn: keyvalue_count,
+			keyvalue_fn: keyvalue,
+			set_keyvalue_fn: set_keyvalue,
+			remove_keyvalues_fn: remove_keyvalues,
+			brush_count_fn: brush_count,
+			face_count_fn: face_count,
+			face_material_fn: face_material,
+			set_face_material_fn: set_face_material,
+			model_count_fn: model_count,
+			model_fn: model,
+			model_face_fn: model_face,
+			cluster_count_fn: cluster_count,
+			portal_count_fn: portal_count,
+			leaf_cluster_fn: leaf_cluster,
+			cluster_visible_fn: cluster_visible,
+			face_lightmap_size_fn: face_lightmap_size,
+			set_error_fn: set_error,
+		};
+
+	let mut api: stage_hook_api::StageHookApi =
+		stage_hook_api::internal::create_stage_hook_api(&mut core_fns);
+
+	let result: stage_hook_api::StageHookResult = hook_fn(&mut api);
+
+	if let stage_hook_api::StageHookResult::Failure = result
+	{
+		return Err(api_impl.error.unwrap_or_else(|| {
+			String::from("Extension did not provide a reason for the failure")
+		}));
+	}
+
+	return Ok(());
+}
+
+unsafe extern "C" fn hook_point(context: *const c_void) -> HookPoint
+{
+	return unsafe { (*context.cast::<StageHookApiImpl>()).hook_point() };
+}
+
+unsafe extern "C" fn bsp_format_of(context: *const c_void, format: &mut StringRef)
+{
+	unsafe { (*context.cast::<StageHookApiImpl>()).bsp_format(format) };
+}
+
+unsafe extern "C" fn entity_count(context: *const c_void) -> usize
+{
+	return unsafe { (*context.cast::<StageHookApiImpl>()).entity_count() };
+}
+
+unsafe extern "C" fn keyvalue_count(context: *const c_void, entity: usize) -> usize
+{
+	return unsafe { (*context.cast::<StageHookApiImpl>()).keyvalue_count(entity) };
+}
+
+unsafe extern "C" fn keyvalue(
+	context: *const c_void,
+	entity: usize,
+	index: usize,
+	args: &mut KeyValueArgs,
+) -> bool
+{
+	return unsafe { (*context.cast::<StageHookApiImpl>()).keyvalue(entity, index, args) };
+}
+
+unsafe extern "C" fn set_keyvalue(
+	context: *mut c_void,
+	entity: usize,
+	key: StringRef,
+	value: StringRef,
+) -> bool
+{
+	return unsafe {
+		(*context.cast::<StageHookApiImpl>()).set_keyvalue(entity, key.into(), value.into())
+	};
+}
+
+unsafe extern "C" fn remove_keyvalues(context: *mut c_void, entity: usize, key: StringRef)
+-> usize
+{
+	return unsafe { (*context.cast::<StageHookApiImpl>()).remove_keyvalues(entity, key.into()) };
+}
+
+unsafe extern "C" fn brush_count(context: *const c_void, entity: usize) -> usize
+{
+	return unsafe { (*context.cast::<StageHookApiImpl>()).brush_count(entity) };
+}
+
+unsafe extern "C" fn face_count(context: *const c_void, entity: usize, brush: usize) -> usize
+{
+	return unsafe { (*context.cast::<StageHookApiImpl>()).face_count(entity, brush) };
+}
+
+unsafe extern "C" fn face_material(
+	context: *const c_void,
+	entity: usize,
+	brush: usize,
+	face: usize,
+	material: &mut StringRef,
+) -> bool
+{
+	return unsafe {
+		(*context.cast::<StageHookApiImpl>()).face_material(entity, brush, face, material)
+	};
+}
+
+unsafe extern "C" fn set_face_material(
+	context: *mut c_void,
+	entity: usize,
+	brush: usize,
+	face: usize,
+	material: StringRef,
+) -> bool
+{
+	return unsafe {
+		(*context.cast::<StageHookApiImpl>()).set_face_material(
+			entity,
+			brush,
+			face,
+			material.into(),
+		)
+	};
+}
+
+unsafe extern "C" fn model_count(context: *const c_void) -> usize
+{
+	return unsafe { (*context.cast::<StageHookApiImpl>()).model_count() };
+}
+
+unsafe extern "C" fn model(context: *const c_void, model: usize, args: &mut BspModelArgs) -> bool
+{
+	return unsafe { (*context.cast::<StageHookApiImpl>()).model(model, args) };
+}
+
+unsafe extern "C" fn model_face(
+	context: *const c_void,
+	model: usize,
+	face: usize,
+	args: &mut BspFaceArgs,
+) -> bool
+{
+	return unsafe { (*context.cast::<StageHookApiImpl>()).model_face(model, face, args) };
+}
+
+unsafe extern "C" fn cluster_count(context: *const c_void) -> usize
+{
+	return unsafe { (*context.cast::<StageHookApiImpl>()).cluster_count() };
+}
+
+unsafe extern "C" fn portal_count(context: *const c_void) -> usize
+{
+	return unsafe { (*context.cast::<StageHookApiImpl>()).portal_count() };
+}
+
+unsafe extern "C" fn leaf_cluster(context: *const c_void, leaf: usize, cluster: &mut usize)
+-> bool
+{
+	return unsafe { (*context.cast::<StageHookApiImpl>()).leaf_cluster(leaf, cluster) };
+}
+
+unsafe extern "C" fn cluster_visible(
+	context: *const c_void,
+	from: usize,
+	to: usize,
+	visible: &mut bool,
+) -> bool
+{
+	return unsafe { (*context.cast::<StageHookApiImpl>()).is_cluster_visible(from, to, visible) };
+}
+
+unsafe extern "C" fn face_lightmap_size(
+	context: *const c_void,
+	model: usize,
+	face: usize,
+	size: &mut [usize; 2],
+) -> bool
+{
+	return unsafe { (*context.cast::<StageHookApiImpl>()).face_lightmap_size(model, face, size) };
+}
+
+unsafe extern "C" fn set_error(context: *mut c_void, message: StringRef)
+{
+	unsafe { (*context.cast::<StageHookApiImpl>()).set_error(message.into()) };
+}
+
+#[cfg(test)]
+mod tests
+{
+	use super::*;
+	use crate::game_config::BspConfig;
+	use crate::model::{CsgHull, DPlane3};
+	use crate::work_units::test_fixtures::{add_entity, box_planes, make_blueprint};
+	use crate::work_units::{build_bsp_trees, build_csg_hull};
+	use bspextifc::stage_hook_api::{StageHookApi, StageHookResult};
+	use glam::DVec3;
+
+	// Renames the door's materials and gives it a target, for the bsp30
+	// format only.
+	extern "C" fn edit_door(api: &mut StageHookApi) -> StageHookResult
+	{
+		if api.bsp_format() != "bsp30"
+		{
+			api.set_error("Unexpected format");
+			return StageHookResult::Failure;
+		}
+
+		let door: usize = api.entity_count() - 1;
+
+		for face in 0..api.face_count(door, 0)
+		{
+			let material: String = api.face_material(door, 0, face).unwrap();
+			api.set_face_material(door, 0, face, &material.replace("DOOR", "METAL"));
+		}
+
+		api.set_keyvalue(door, "target", "mm");
+		api.remove_keyvalues(0, "wad");
+		return StageHookResult::Success;
+	}
+
+	#[test]
+	fn modify_blueprint_from_hook()
+	{
+		let brushes: Vec<(Vec<DPlane3>, &str)> =
+			vec![(box_planes(DVec3::splat(-64.0), DVec3::splat(-32.0)), "WALL")];
+		let mut blueprint: MapBlueprint = make_blueprint(&brushes);
+		blueprint.entities[0]
+			.keyvalues
+			.push(String::from("wad"), String::from("halflife.wad"));
+		add_entity(
+			&mut blueprint,
+			"func_door",
+			&[(box_planes(DVec3::splat(-8.0), DVec3::splat(8.0)), "DOOR")],
+		);
+
+		assert!(
+			call_stage_hook_api(
+				edit_door,
+				HookPoint::PreCsg,
+				"bsp42",
+				&mut blueprint,
+				StageData::default()
+			)
+			.is_err()
+		);
+		assert!(
+			call_stage_hook_api(
+				edit_door,
+				HookPoint::PreCsg,
+				"bsp30",
+				&mut blueprint,
+				StageData::default()
+			)
+			.is_ok()
+		);
+
+		let door: &crate::model::BlueprintEntity = &blueprint.entities[1];
+		assert!(
+			door.brushes[0]
+				.faces
+				.iter()
+				.all(|face| face.material == "METAL")
+		);
+		assert_eq!(door.keyvalues.last("target"), Some("mm"));
+		assert!(!blueprint.entities[0].keyvalues.contains_key("wad"));
+
+		// Materials cannot be changed once CSG has run.
+		blueprint.entities[1].brushes[0].faces[0].material = String::from("DOOR");
+		assert!(
+			call_stage_hook_api(
+				edit_door,
+				HookPoint::PreWrite,
+				"bsp30",
+				&mut blueprint,
+				StageData::default()
+			)
+			.is_ok()
+		);
+		assert_eq!(blueprint.entities[1].brushes[0].faces[0].material, "DOOR");
+	}
+
+	// Describes the data that the hook can see in the world's keyvalues.
+	extern "C" fn describe_data(api: &mut StageHookApi) -> StageHookResult
+	{
+		let model: Option<BspModelArgs> = api.model(0);
+		let description: String = format!(
+			"{} {:?} {:?} {} {} {:?} {:?} {:?} {:?}",
+			api.model_count(),
+			model.map(|model| (model.entity, model.face_count)),
+			api.model_face(0, 0),
+			api.cluster_count(),
+			api.portal_count(),
+			api.leaf_cluster(1),
+			api.leaf_cluster(0),
+			api.is_cluster_visible(0, 1),
+			api.face_lightmap_size(0, 1),
+		);
+
+		api.set_keyvalue(0, "data", &description);
+		return StageHookResult::Success;
+	}
+
+	#[test]
+	fn read_stage_data_from_hook()
+	{
+		let brushes: Vec<(Vec<DPlane3>, &str)> =
+			vec![(box_planes(DVec3::splat(-64.0), DVec3::splat(-32.0)), "WALL")];
+		let mut blueprint: MapBlueprint = make_blueprint(&brushes);
+
+		let hull: CsgHull = build_csg_hull(&blueprint);
+		let models: BspModels = build_bsp_trees(&blueprint, &hull, &BspConfig::default());
+		let face_count: usize = models.models[0].tree.faces.len();
+
+		let vis_portals: VisPortals = VisPortals {
+			cluster_count: 2,
+			leaf_clusters: vec![None, Some(1)],
+			portals: Vec::new(),
+		};
+		let mut visibility: Visibility = Visibility::new(2);
+		visibility.set_visible(0, 1);
+
+		let lightmap: FaceLightmap = FaceLightmap {
+			mins: [0, 0],
+			size: [3, 2],
+			styles: vec![0],
+			samples: vec![Vec::new()],
+		};
+		let lightmaps: Lightmaps = Lightmaps {
+			models: vec![vec![None, Some(lightmap)]],
+		};
+
+		let mut describe = |data: StageData| -> String {
+			call_stage_hook_api(
+				describe_data,
+				HookPoint::PreWrite,
+				"bsp30",
+				&mut blueprint,
+				data,
+			)
+			.unwrap();
+			return String::from(blueprint.entities[0].keyvalues.last("data").unwrap());
+		};
+
+		assert_eq!(
+			describe(StageData::default()),
+			"0 None None 0 0 None None None None"
+		);
+
+		let data: StageData = StageData {
+			models: Some(&models),
+			vis_portals: Some(&vis_portals),
+			visibility: Some(&visibility),
+			lightmaps: Some(&lightmaps),
+		};
+		assert_eq!(
+			describe(data),
+			format!(
+				"1 Some((0, {face_count})) Some(BspFaceArgs {{ entity: 0, brush: 0, face: {} }}) 2 0 Some(1) None Some(true) Some([3, 2])",
+				models.models[0].tree.faces[0].face
+			)
+		);
+	}
+}
@@ -0,0 +1,122 @@
+use crate::model::{BlueprintTexture, EntityKeyValues};
+use bspextifc::texture_api::{self, KeyValueArgs};
+use bspextifc::{SliceRef, StringRef};
+use glam::DVec3;
+use std::collections::{HashMap, HashSet};
+use std::ffi::c_void;
+use std::marker::PhantomData;
+use std::path::PathBuf;
+
+struct TextureApiImpl
+{
+	// Lowercase names of the requested materials that have not been provided
+	// yet.
+	remaining: HashSet<String>,
+	textures: HashMap<String, BlueprintTexture>,
+	error: Option<String>,
+}
+
+impl TextureApiImpl
+{
+	pub fn add_texture(&mut self, args: &texture_api::TextureArgs)
+	{
+		let name: String = args.name.to_string().to_ascii_lowercase();
+
+		if !self.remaining.remove(&name)
+		{
+			return;
+		}
+
+		self.textures.insert(
+			name,
+			BlueprintTexture {
+				size: [args.width, args.height],
+				average_color: args
+					.has_average_color
+					.then(|| DVec3::from_array(args.average_color)),
+				pixels: args.pixels.as_slice().to_vec(),
+				palette: args.palette.as_slice().to_vec(),
+			},
+		);
+	}
+
+	pub fn set_error(&mut self, message: String)
+	{
+		self.error = Some(message);
+	}
+}
+
+/// Asks a texture provider for the given materials. The textures that it
+/// found are returned, keyed by their material's name in lower case.
+pub fn call_texture_api(
+	find_textures_fn: texture_api::FindTexturesFn,
+	map_path: &PathBuf,
+	world_keyvalues: &EntityKeyValues,
+	search_paths: &[String],
+	materials: &[String],
+) -> Result<HashMap<String, BlueprintTexture>, String>
+{
+	let path_string: String = map_path.to_string_lossy().to_string();
+	let mut api_impl: TextureApiImpl = TextureApiImpl {
+		remaining: materials
+			.iter()
+			.map(|material| material.to_ascii_lowercase())
+			.collect(),
+		textures: HashMap::new(),
+		error: None,
+	};
+
+	let mut core_fns: texture_api::internal::TextureApiCoreFns =
+		texture_api::internal::TextureApiCoreFns {
+			context: &mut api_impl as *mut TextureApiImpl as *mut c_void,
+			phantom: PhantomData,
+			add_texture_fn: add_texture,
+			set_error_fn: set_error,
+		};
+
+	let mut api: texture_api::TextureApi = texture_api::internal::create_texture_api(&mut core_fns);
+
+	let keyvalues: Vec<KeyValueArgs> = world_keyvalues
+		.iter()
+		.map(|(key, value)| KeyValueArgs {
+			key: StringRef::from(key.as_str()),
+			value: StringRef::from(value.as_str()),
+		})
+		.collect();
+	let search_path_refs: Vec<StringRef> = search_paths
+		.iter()
+		.map(|search_path| StringRef::from(search_path.as_str()))
+		.collect();
+	let material_refs: Vec<StringRef> = materials
+		.iter()
+		.map(|material| StringRef::from(material.as_str()))
+		.collect();
+
+	let args: texture_api::FindTexturesArgs = texture_api::FindTexturesArgs {
+		map_path: StringRef::from(path_string.as_str()),
+		world_keyvalues: SliceRef::from(keyvalues.as_slice()),
+		search_paths: SliceRef::from(search_path_refs.as_slice()),
+		materials: SliceRef::from(material_refs.as_slice()),
+	};
+
+	let result: texture_api::FindTexturesResult = find_textures_fn(&mut api, &args);
+
+	if let texture_api::FindTexturesResult::Failure = result
+	{
+		return Err(api_impl.error.unwrap_or_else(|| {
+			String::from("Extension did not provide a reason for the failure")
+		}));
+	}
+
+	return Ok(api_impl.textures);
+}
+
+unsafe extern "C" fn add_texture(context: *mut c_void, args: &texture_api::TextureArgs)
+{
+	unsafe { (*context.cast::<TextureApiImpl>()).add_texture(args) };
+}
+
+unsafe extern "C" fn set_error(context: *mut c_void, message: StringRef)
+{
+	unsafe { (*context.cast::<TextureApiImpl>()).set_error(message.into()) };
+}
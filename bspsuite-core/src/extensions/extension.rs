@@ -4,7 +4,8 @@ use bspextifc::probe_api::ProbeResult;
 use bspextifc::probe_api::internal::{ApiProvider, CallbacksContainer, ExportedApis};
 use bspextifc::{
 	EXTENSION_INFO_VERSION, ExtensionInfo, ExtensionInfoVersionType, SYMBOL_EXTENSION_INFO,
-	SYMBOL_EXTENSION_INFO_VERSION, dummy_api, log_api, probe_api,
+	SYMBOL_EXTENSION_INFO_VERSION, bsp_writer_api, hull_api, log_api, map_reader_api, probe_api,
+	stage_hook_api, texture_api,
 };
 use libloading::{Library, Symbol};
 use log::{debug, trace};
@@ -18,7 +19,11 @@ pub use libloading::os::windows::Symbol as UnsafeSymbol;
 
 pub struct ApiCallbacks
 {
-	pub dummy_api_callbacks: Option<dummy_api::DummyCallbacks>,
+	pub map_reader_api_callbacks: Option<map_reader_api::MapReaderCallbacks>,
+	pub hull_api_callbacks: Option<hull_api::HullCallbacks>,
+	pub bsp_writer_api_callbacks: Option<bsp_writer_api::BspWriterCallbacks>,
+	pub texture_api_callbacks: Option<texture_api::TextureProviderCallbacks>,
+	pub stage_hook_api_callbacks: Option<stage_hook_api::StageHookCallbacks>,
 }
 
 impl Default for ApiCallbacks
@@ -26,7 +31,11 @@ impl Default for ApiCallbacks
 	fn default() -> Self
 	{
 		return Self {
-			dummy_api_callbacks: None,
+			map_reader_api_callbacks: None,
+			hull_api_callbacks: None,
+			bsp_writer_api_callbacks: None,
+			texture_api_callbacks: None,
+			stage_hook_api_callbacks: None,
 		};
 	}
 }
@@ -132,7 +141,11 @@ impl Extension
 		}
 
 		self.api_callbacks = result.map_or(ApiCallbacks::default(), |callbacks| ApiCallbacks {
-			dummy_api_callbacks: callbacks.dummy_api.take_callbacks(),
+			map_reader_api_callbacks: callbacks.map_reader_api.take_callbacks(),
+			hull_api_callbacks: callbacks.hull_api.take_callbacks(),
+			bsp_writer_api_callbacks: callbacks.bsp_writer_api.take_callbacks(),
+			texture_api_callbacks: callbacks.texture_api.take_callbacks(),
+			stage_hook_api_callbacks: callbacks.stage_hook_api.take_callbacks(),
 		});
 
 		return Ok(());
@@ -158,7 +171,11 @@ impl Extension
 	{
 		return ExportedApis {
 			log_api: ApiProvider::new(&log_api::API_INFO, api_impl::log_api::create_api()),
-			dummy_api: CallbacksContainer::new(&dummy_api::API_INFO),
+			map_reader_api: CallbacksContainer::new(&map_reader_api::API_INFO),
+			hull_api: CallbacksContainer::new(&hull_api::API_INFO),
+			bsp_writer_api: CallbacksContainer::new(&bsp_writer_api::API_INFO),
+			texture_api: CallbacksContainer::new(&texture_api::API_INFO),
+			stage_hook_api: CallbacksContainer::new(&stage_hook_api::API_INFO),
 		};
 	}
 
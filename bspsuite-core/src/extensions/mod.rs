@@ -3,6 +3,8 @@ mod extension;
 mod extension_list;
 mod extension_logger;
 
-pub use api_impl::{dummy_api, log_api};
+pub use api_impl::{
+	bsp_writer_api, hull_api, log_api, map_reader_api, stage_hook_api, texture_api,
+};
 pub use extension_list::ExtensionList;
 pub use extension_logger::ExtensionLogger;
@@ -0,0 +1,355 @@
+use crate::compiler_error::{CompilerError, CompilerErrorCode};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+/// What the compiler should do when it finds a particular kind of problem in
+/// the map.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Deserialize, strum::Display)]
+#[serde(rename_all = "snake_case")]
+pub enum ProblemAction
+{
+	/// Carry on silently.
+	Ignore,
+
+	/// Log a warning, but leave the problem as it is.
+	Warn,
+
+	/// Log a warning, and fix the problem if possible. For example, faces
+	/// that do not contribute to a brush are removed. If the problem cannot
+	/// be fixed, the offending item is dropped instead.
+	Repair,
+
+	/// Log a warning, and remove the offending item from the map.
+	Drop,
+
+	/// Stop compiling.
+	Error,
+}
+
+/// Limits and policies used when validating brushes.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BrushValidationConfig
+{
+	/// Brushes must lie within this distance of the origin along each axis.
+	pub world_extent: f64,
+
+	/// Brushes smaller than this along any axis are considered micro-brushes.
+	pub min_brush_size: f64,
+
+	/// Faces whose three plane points lie along a line.
+	pub colinear_plane_points: ProblemAction,
+
+	/// Faces whose plane is the same as that of another face on the brush.
+	pub duplicate_planes: ProblemAction,
+
+	/// Faces that lie outside the convex hull formed by the other faces.
+	pub clipped_faces: ProblemAction,
+
+	/// Faces whose polygon is left with fewer than three distinct points.
+	pub degenerate_faces: ProblemAction,
+
+	/// Brushes whose faces do not enclose a volume.
+	pub open_brushes: ProblemAction,
+
+	pub micro_brushes: ProblemAction,
+	pub outside_world: ProblemAction,
+}
+
+impl Default for BrushValidationConfig
+{
+	// Defaults are suitable for GoldSrc.
+	fn default() -> Self
+	{
+		return Self {
+			world_extent: 4096.0,
+			min_brush_size: 1.0,
+			colinear_plane_points: ProblemAction::Repair,
+			duplicate_planes: ProblemAction::Repair,
+			clipped_faces: ProblemAction::Repair,
+			degenerate_faces: ProblemAction::Repair,
+			open_brushes: ProblemAction::Drop,
+			micro_brushes: ProblemAction::Warn,
+			outside_world: ProblemAction::Drop,
+		};
+	}
+}
+
+/// Settings used when building BSP trees.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BspConfig
+{
+	/// Cost of each face that a candidate splitting plane would split.
+	pub split_weight: f64,
+
+	/// Cost of each face by which the number of faces on either side of a
+	/// candidate splitting plane differs.
+	pub balance_weight: f64,
+
+	/// Bonus given to candidate splitting planes that are axial.
+	pub axial_weight: f64,
+
+	/// Brushes of entities with these classnames are merged into the world as
+	/// detail. Detail faces are only used to split the tree once all
+	/// structural faces have been used, and do not affect visibility.
+	pub detail_classnames: Vec<String>,
+
+	/// Faces with these materials are used to split the tree before any other
+	/// faces, but are not themselves visible. They may be used to guide how
+	/// the map is partitioned for visibility.
+	pub hint_materials: Vec<String>,
+
+	/// Visible faces are split so that they span no more than this many
+	/// texels along either texture axis. The engine cannot store lightmaps
+	/// for faces that are much larger than this.
+	pub subdivide_size: f64,
+}
+
+impl Default for BspConfig
+{
+	fn default() -> Self
+	{
+		return Self {
+			split_weight: 5.0,
+			balance_weight: 1.0,
+			axial_weight: 4.0,
+			detail_classnames: vec![String::from("func_detail")],
+			hint_materials: vec![String::from("hint")],
+			subdivide_size: 240.0,
+		};
+	}
+}
+
+/// Bounds of the box that a clipping hull is built for, relative to the
+/// origin of an entity colliding with the world.
+#[derive(Clone, Copy, PartialEq, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct HullSize
+{
+	pub mins: [f64; 3],
+	pub maxs: [f64; 3],
+}
+
+impl HullSize
+{
+	pub const fn new(mins: [f64; 3], maxs: [f64; 3]) -> Self
+	{
+		return Self {
+			mins: mins,
+			maxs: maxs,
+		};
+	}
+}
+
+/// Clipping hulls used by GoldSrc, after the point hull: standing player,
+/// crouching player and large monster.
+pub const GOLDSRC_HULLS: [HullSize; 3] = [
+	HullSize::new([-16.0, -16.0, -36.0], [16.0, 16.0, 36.0]),
+	HullSize::new([-16.0, -16.0, -18.0], [16.0, 16.0, 18.0]),
+	HullSize::new([-32.0, -32.0, -32.0], [32.0, 32.0, 32.0]),
+];
+
+/// Settings used when computing lightmaps.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LightingConfig
+{
+	/// Distance between lightmap samples, in texels.
+	pub luxel_size: f64,
+
+	/// Name of the file listing the light emitted by each material, which is
+	/// looked for in the toolchain root. Lights may also be listed in a .rad
+	/// file next to the map, which takes precedence.
+	pub texture_lights_file: String,
+
+	/// Number of times light is bounced between faces after direct lighting.
+	/// Zero disables bounced light.
+	pub bounces: usize,
+
+	/// Faces are divided into patches of roughly this size, in world units,
+	/// when bouncing light.
+	pub patch_size: f64,
+
+	/// Fraction of the light arriving at a face that is reflected, for
+	/// materials whose colour is not known.
+	pub default_reflectivity: f64,
+}
+
+impl Default for LightingConfig
+{
+	// Defaults are suitable for GoldSrc.
+	fn default() -> Self
+	{
+		return Self {
+			luxel_size: 16.0,
+			texture_lights_file: String::from("lights.rad"),
+			bounces: 8,
+			patch_size: 64.0,
+			default_reflectivity: 0.5,
+		};
+	}
+}
+
+/// Settings used when looking up the textures that the map uses.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TextureConfig
+{
+	/// Directories in which texture providers look for texture archives,
+	/// loose images and material definitions that are not found next to the
+	/// map. Relative directories are relative to the directory of the map
+	/// source file.
+	pub search_paths: Vec<String>,
+
+	/// If set, the pixels of each texture are stored in the BSP, so that the
+	/// game does not need the texture archives to load the map.
+	pub embed: bool,
+}
+
+/// Settings that vary depending on the game that the map is being compiled
+/// for. Any setting not present in a game's configuration file takes its
+/// default value.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct GameConfig
+{
+	pub brush_validation: BrushValidationConfig,
+	pub bsp: BspConfig,
+
+	/// Sizes of the clipping hulls built in addition to the point hull, in
+	/// the order in which they are written to the BSP.
+	pub hulls: Vec<HullSize>,
+
+	pub lighting: LightingConfig,
+	pub textures: TextureConfig,
+
+	/// Format of the BSP file that is written, which must be supported by one
+	/// of the loaded extensions.
+	pub bsp_format: String,
+
+	/// Limits of the BSP format that the game's engine changes, keyed by the
+	/// name that the format's writer reports them by, such as MAX_MAP_MODELS.
+	/// Limits that are not listed keep the format's own values.
+	pub limits: BTreeMap<String, usize>,
+
+	/// Names of the extensions that must be loaded to compile maps for the
+	/// game, such as goldsrcext.
+	pub required_extensions: Vec<String>,
+}
+
+impl Default for GameConfig
+{
+	fn default() -> Self
+	{
+		return Self {
+			brush_validation: BrushValidationConfig::default(),
+			bsp: BspConfig::default(),
+			hulls: GOLDSRC_HULLS.to_vec(),
+			lighting: LightingConfig::default(),
+			textures: TextureConfig::default(),
+			bsp_format: String::from("bsp30"),
+			limits: BTreeMap::new(),
+			required_extensions: Vec::new(),
+		};
+	}
+}
+
+impl GameConfig
+{
+	pub fn load(path: &PathBuf) -> Result<Self, CompilerError>
+	{
+		let text: String = std::fs::read_to_string(path).map_err(|err| {
+			CompilerError::new(
+				CompilerErrorCode::IoError,
+				format!(
+					"Could not read game configuration {}. {err}",
+					path.to_string_lossy()
+				),
+			)
+		})?;
+
+		return Self::from_toml(&text).map_err(|err| {
+			CompilerError::new(
+				err.code,
+				format!("{}: {}", path.to_string_lossy(), err.description),
+			)
+		});
+	}
+
+	pub fn from_toml(text: &str) -> Result<Self, CompilerError>
+	{
+		return toml::from_str(text).map_err(|err| {
+			CompilerError::new(
+				CompilerErrorCode::ConfigError,
+				format!("Invalid game configuration. {err}"),
+			)
+		});
+	}
+}
+
+#[cfg(test)]
+mod tests
+{
+	use super::*;
+
+	#[test]
+	fn load_partial_config()
+	{
+		let config: GameConfig = GameConfig::from_toml(
+			"bsp_format = \"bsp29\"\n\
+			required_extensions = [\"goldsrcext\"]\n\
+			[brush_validation]\n\
+			world_extent = 16384.0\n\
+			open_brushes = \"error\"\n\
+			[bsp]\n\
+			axial_weight = 0.0\n\
+			[[hulls]]\n\
+			mins = [-16.0, -16.0, -32.0]\n\
+			maxs = [16.0, 16.0, 32.0]\n\
+			[lighting]\n\
+			luxel_size = 8.0\n\
+			bounces = 2\n\
+			[textures]\n\
+			search_paths = [\"../wads\", \"/opt/valve\"]\n\
+			[limits]\n\
+			MAX_MAP_MODELS = 512\n",
+		)
+		.unwrap();
+
+		assert_eq!(config.brush_validation.world_extent, 16384.0);
+		assert_eq!(config.brush_validation.open_brushes, ProblemAction::Error);
+		assert_eq!(config.brush_validation.micro_brushes, ProblemAction::Warn);
+		assert_eq!(config.bsp.axial_weight, 0.0);
+		assert_eq!(config.bsp.detail_classnames, vec!["func_detail"]);
+		assert_eq!(
+			config.hulls,
+			vec![HullSize::new([-16.0, -16.0, -32.0], [16.0, 16.0, 32.0])]
+		);
+		assert_eq!(GameConfig::default().hulls, GOLDSRC_HULLS);
+		assert_eq!(config.lighting.luxel_size, 8.0);
+		assert_eq!(config.lighting.bounces, 2);
+		assert_eq!(config.lighting.patch_size, 64.0);
+		assert_eq!(config.lighting.texture_lights_file, "lights.rad");
+		assert_eq!(config.textures.search_paths, vec!["../wads", "/opt/valve"]);
+		assert!(!config.textures.embed);
+		assert_eq!(config.bsp_format, "bsp29");
+		assert_eq!(GameConfig::default().bsp_format, "bsp30");
+		assert_eq!(config.limits["MAX_MAP_MODELS"], 512);
+		assert_eq!(config.required_extensions, vec!["goldsrcext"]);
+
+		assert!(GameConfig::from_toml("[brush_validation]\nopen_brush = \"drop\"\n").is_err());
+
+		let path: PathBuf =
+			std::env::temp_dir().join(format!("bspsuite-game-config-{}.cfg", std::process::id()));
+		std::fs::write(&path, "bsp_format = 30\n").unwrap();
+
+		let err: CompilerError = GameConfig::load(&path).unwrap_err();
+		std::fs::remove_file(&path).unwrap();
+		assert!(err.description.starts_with(&format!(
+			"{}: Invalid game configuration.",
+			path.to_string_lossy()
+		)));
+	}
+}
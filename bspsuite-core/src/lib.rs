@@ -3,8 +3,10 @@ use constcat::concat;
 
 mod compiler_error;
 mod extensions;
+mod game_config;
 mod model;
 mod pipeline;
+mod source_diagnostic;
 mod toolchain;
 mod work_units;
 
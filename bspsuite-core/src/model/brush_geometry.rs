@@ -0,0 +1,243 @@
+use super::dplane3::DPlane3;
+use super::winding::{BASE_WINDING_EXTENT, ON_PLANE_EPSILON, Winding};
+use std::fmt;
+
+/// Maximum difference between normal components for two planes to be
+/// considered duplicates.
+const DUPLICATE_NORMAL_EPSILON: f64 = 1.0e-6;
+
+/// Maximum difference between distances for two planes to be considered
+/// duplicates.
+const DUPLICATE_DISTANCE_EPSILON: f64 = 0.01;
+
+/// Problems that make a brush's geometry degenerate.
+#[derive(Clone, PartialEq, Debug)]
+pub enum BrushDefect
+{
+	/// The face's plane is the same as that of an earlier face. The later face
+	/// is ignored.
+	DuplicatePlane
+	{
+		face: usize, duplicate_of: usize
+	},
+
+	/// The face's polygon was entirely clipped away by the other faces, so the
+	/// face does not contribute to the brush.
+	FaceClippedAway
+	{
+		face: usize
+	},
+
+	/// The face's polygon was left with fewer than three distinct points, so
+	/// the face does not contribute to the brush.
+	DegenerateFace
+	{
+		face: usize
+	},
+
+	/// The faces do not enclose a finite volume.
+	OpenBrush,
+}
+
+impl fmt::Display for BrushDefect
+{
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result
+	{
+		match self
+		{
+			BrushDefect::DuplicatePlane { face, duplicate_of } =>
+			{
+				write!(f, "face {face} has the same plane as face {duplicate_of}")
+			}
+			BrushDefect::FaceClippedAway { face } =>
+			{
+				write!(f, "face {face} was clipped away by the other faces")
+			}
+			BrushDefect::DegenerateFace { face } =>
+			{
+				write!(f, "face {face} has fewer than three distinct points")
+			}
+			BrushDefect::OpenBrush => write!(f, "brush does not enclose a volume"),
+		}
+	}
+}
+
+/// Polygons generated for each face of a brush. There is one entry in the
+/// windings list per face, which is [`None`] if the face produced no polygon.
+pub struct BrushGeometry
+{
+	pub windings: Vec<Option<Winding>>,
+	pub defects: Vec<BrushDefect>,
+}
+
+impl BrushGeometry
+{
+	/// Builds the polygon for each face by clipping a huge winding on the
+	/// face's plane against the planes of all the other faces. The brush's
+	/// volume is taken to be behind every plane.
+	pub fn from_planes(planes: &[DPlane3]) -> Self
+	{
+		let mut defects: Vec<BrushDefect> = Vec::new();
+		let mut duplicate: Vec<bool> = vec![false; planes.len()];
+
+		for face in 0..planes.len()
+		{
+			let earlier: Option<usize> =
+				(0..face).find(|other| planes_are_equal(&planes[*other], &planes[face]));
+
+			if let Some(duplicate_of) = earlier
+			{
+				duplicate[face] = true;
+				defects.push(BrushDefect::DuplicatePlane {
+					face: face,
+					duplicate_of: duplicate_of,
+				});
+			}
+		}
+
+		let mut windings: Vec<Option<Winding>> = Vec::with_capacity(planes.len());
+
+		for face in 0..planes.len()
+		{
+			if duplicate[face]
+			{
+				windings.push(None);
+				continue;
+			}
+
+			let mut winding: Option<Winding> = Some(Winding::from_plane(&planes[face]));
+
+			for other in 0..planes.len()
+			{
+				if other == face || duplicate[other]
+				{
+					continue;
+				}
+
+				winding = winding.and_then(|winding| winding.clip(&planes[other]));
+			}
+
+			let winding: Option<Winding> =
+				winding.map(|winding| winding.without_duplicate_points(ON_PLANE_EPSILON));
+
+			match &winding
+			{
+				None => defects.push(BrushDefect::FaceClippedAway { face: face }),
+				Some(winding) if winding.points.len() < 3 =>
+				{
+					defects.push(BrushDefect::DegenerateFace { face: face });
+					windings.push(None);
+					continue;
+				}
+				_ => (),
+			}
+
+			windings.push(winding);
+		}
+
+		let surviving_faces: usize = windings.iter().flatten().count();
+
+		// If any point of the base windings was not clipped off, the brush
+		// extends out towards infinity in that direction.
+		let unbounded: bool = windings.iter().flatten().any(|winding| {
+			winding
+				.points
+				.iter()
+				.any(|point| point.abs().max_element() >= BASE_WINDING_EXTENT * 0.5)
+		});
+
+		if surviving_faces < 4 || unbounded
+		{
+			defects.push(BrushDefect::OpenBrush);
+		}
+
+		return Self {
+			windings: windings,
+			defects: defects,
+		};
+	}
+
+	/// Returns whether the faces enclose a finite volume. Other defects only
+	/// cause individual faces to be ignored, so brushes with them can still be
+	/// used.
+	pub fn is_valid(&self) -> bool
+	{
+		return !self.defects.contains(&BrushDefect::OpenBrush);
+	}
+
+	/// Returns the minimum and maximum corners of the bounding box of all the
+	/// face polygons, or [`None`] if there are no polygons.
+	pub fn bounds(&self) -> Option<(glam::DVec3, glam::DVec3)>
+	{
+		return self
+			.windings
+			.iter()
+			.flatten()
+			.map(Winding::bounds)
+			.reduce(|(mins1, maxs1), (mins2, maxs2)| (mins1.min(mins2), maxs1.max(maxs2)));
+	}
+}
+
+fn planes_are_equal(a: &DPlane3, b: &DPlane3) -> bool
+{
+	return a.normal.abs_diff_eq(b.normal, DUPLICATE_NORMAL_EPSILON)
+		&& (a.distance - b.distance).abs() <= DUPLICATE_DISTANCE_EPSILON;
+}
+
+#[cfg(test)]
+mod tests
+{
+	use super::*;
+	use crate::work_units::test_fixtures::box_planes;
+	use glam::DVec3;
+
+	#[test]
+	fn build_box_geometry()
+	{
+		let geometry: BrushGeometry =
+			BrushGeometry::from_planes(&box_planes(DVec3::splat(-32.0), DVec3::splat(32.0)));
+
+		assert!(geometry.is_valid());
+		assert!(geometry.defects.is_empty());
+		assert_eq!(geometry.windings.len(), 6);
+
+		for winding in &geometry.windings
+		{
+			let winding: &Winding = winding.as_ref().unwrap();
+			assert_eq!(winding.points.len(), 4);
+			assert_eq!(winding.area(), 64.0 * 64.0);
+		}
+
+		assert_eq!(
+			geometry.bounds(),
+			Some((DVec3::splat(-32.0), DVec3::splat(32.0)))
+		);
+	}
+
+	#[test]
+	fn report_degenerate_brushes()
+	{
+		let mut planes: Vec<DPlane3> = box_planes(DVec3::splat(-32.0), DVec3::splat(32.0));
+		planes.push(DPlane3::new(DVec3::X, 32.001));
+		planes.push(DPlane3::new(DVec3::Z, 100.0));
+
+		let geometry: BrushGeometry = BrushGeometry::from_planes(&planes);
+
+		assert_eq!(
+			geometry.defects,
+			vec![
+				BrushDefect::DuplicatePlane {
+					face: 6,
+					duplicate_of: 0
+				},
+				BrushDefect::FaceClippedAway { face: 7 },
+			]
+		);
+		assert!(geometry.is_valid());
+
+		let open: BrushGeometry =
+			BrushGeometry::from_planes(&box_planes(DVec3::splat(-32.0), DVec3::splat(32.0))[..5]);
+		assert_eq!(open.defects, vec![BrushDefect::OpenBrush]);
+		assert!(!open.is_valid());
+	}
+}
@@ -0,0 +1,185 @@
+use super::contents::Contents;
+use super::dplane3::DPlane3;
+use super::plane_pool::PlaneIndex;
+use super::texture_projection::TextureAxis;
+use super::visibility::Visibility;
+use glam::DVec3;
+use std::ops::Range;
+
+/// Reference to a child of a node in [`BspData`]. Indices refer to the data's
+/// arrays of nodes and leaves. Children of BSP nodes are always nodes or
+/// leaves, and children of clipping nodes are always nodes or contents.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum BspDataChild
+{
+	Node(usize),
+	Leaf(usize),
+	Contents(Contents),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct BspDataTexture
+{
+	pub name: String,
+	pub size: [u32; 2],
+
+	/// Palette indices of the texture's pixels, row by row, if the texture is
+	/// embedded in the file. This is empty if the game loads the texture from
+	/// elsewhere.
+	pub pixels: Vec<u8>,
+
+	/// RGB colours used by the pixels. This is empty if the texture is not
+	/// embedded.
+	pub palette: Vec<u8>,
+}
+
+/// Projection of a texture onto faces. The axes are scaled so that they give
+/// texture coordinates in texels once the offsets are added.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BspDataTextureInfo
+{
+	pub u_axis: TextureAxis,
+	pub v_axis: TextureAxis,
+	pub texture: usize,
+
+	/// Set for faces that have no lightmap, such as sky and liquid surfaces.
+	pub special: bool,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct BspDataFace
+{
+	/// Plane that the face lies on and faces along.
+	pub plane: PlaneIndex,
+	pub points: Vec<DVec3>,
+	pub texture_info: usize,
+
+	/// Light styles of the face's lightmap, which is empty if the face has
+	/// none.
+	pub styles: Vec<u8>,
+
+	/// Byte offset of the face's lightmap within the lighting data.
+	pub lighting_offset: Option<usize>,
+
+	/// Position of the lightmap's first sample along each texture axis, and
+	/// its number of samples, in luxels. These are zero if the face has no
+	/// lightmap.
+	pub lightmap_mins: [i32; 2],
+	pub lightmap_size: [usize; 2],
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct BspDataNode
+{
+	/// Splitting plane, which always faces along the positive direction of its
+	/// major axis.
+	pub plane: PlaneIndex,
+
+	/// Children in front of and behind the plane, in that order.
+	pub children: [BspDataChild; 2],
+
+	pub mins: DVec3,
+	pub maxs: DVec3,
+
+	/// Faces that lie on the node's plane.
+	pub faces: Range<usize>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct BspDataLeaf
+{
+	pub contents: Contents,
+
+	/// Visibility cluster of the leaf. Only leaves of the world have
+	/// clusters.
+	pub cluster: Option<usize>,
+
+	pub mins: DVec3,
+	pub maxs: DVec3,
+
+	/// Faces that can be seen from within the leaf.
+	pub faces: Vec<usize>,
+
+	/// Brushes that overlap the leaf.
+	pub brushes: Vec<usize>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct BspDataClipNode
+{
+	pub plane: PlaneIndex,
+
+	/// Children in front of and behind the plane, in that order.
+	pub children: [BspDataChild; 2],
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct BspDataBrushSide
+{
+	/// Plane of the side, which faces out of the brush.
+	pub plane: PlaneIndex,
+
+	/// One of the faces produced by the side, if any are visible.
+	pub face: Option<usize>,
+}
+
+/// Brush that a model was built from, which some formats use for collision
+/// instead of clipping hulls.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BspDataBrush
+{
+	pub contents: Contents,
+	pub sides: Vec<BspDataBrushSide>,
+	pub mins: DVec3,
+	pub maxs: DVec3,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct BspDataModel
+{
+	pub mins: DVec3,
+	pub maxs: DVec3,
+	pub origin: DVec3,
+
+	/// Root of the model's BSP tree.
+	pub head_node: BspDataChild,
+
+	/// Roots of the model's clipping trees, beginning with hull 1.
+	pub clip_head_nodes: Vec<BspDataChild>,
+
+	pub faces: Range<usize>,
+	pub nodes: Range<usize>,
+	pub leaves: Range<usize>,
+	pub brushes: Range<usize>,
+}
+
+/// A compiled map, flattened into the arrays that BSP files of the Quake
+/// lineage are made of, so that it can be handed to a BSP writer. Models are
+/// listed in order, beginning with the world, and the nodes, leaves, faces
+/// and brushes of each model follow those of the model before it.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct BspData
+{
+	/// Text of the entities lump.
+	pub entities: String,
+
+	/// Every plane in the map's plane pool, in pairs as they are stored
+	/// there.
+	pub planes: Vec<DPlane3>,
+
+	pub textures: Vec<BspDataTexture>,
+	pub texture_infos: Vec<BspDataTextureInfo>,
+	pub faces: Vec<BspDataFace>,
+	pub nodes: Vec<BspDataNode>,
+	pub leaves: Vec<BspDataLeaf>,
+	pub clip_nodes: Vec<BspDataClipNode>,
+	pub brushes: Vec<BspDataBrush>,
+	pub models: Vec<BspDataModel>,
+	pub visibility: Visibility,
+
+	/// Lightmaps of every face, as RGB bytes.
+	pub lighting: Vec<u8>,
+
+	/// Size of each lightmap sample, in texels.
+	pub luxel_size: f64,
+}
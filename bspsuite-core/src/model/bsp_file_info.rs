@@ -0,0 +1,98 @@
+use serde::Serialize;
+
+/// Versions of the BSP format that can be inspected.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, strum::Display)]
+pub enum BspFormat
+{
+	/// Quake, version 29.
+	#[strum(to_string = "Quake BSP v29")]
+	Quake29,
+
+	/// GoldSrc, version 30.
+	#[strum(to_string = "GoldSrc BSP v30")]
+	GoldSrc30,
+
+	/// Nightfire, version 42.
+	#[strum(to_string = "Nightfire BSP v42")]
+	Nightfire42,
+}
+
+#[derive(Clone, PartialEq, Debug, Serialize)]
+pub struct LumpInfo
+{
+	pub name: &'static str,
+	pub offset: usize,
+	pub length: usize,
+
+	/// Number of records in the lump, for lumps made of fixed size records.
+	pub count: Option<usize>,
+}
+
+/// How much of one of the engine's limits a map uses.
+#[derive(Clone, PartialEq, Debug, Serialize)]
+pub struct LimitUsage
+{
+	/// Name of the limit, such as MAX_MAP_PLANES.
+	pub name: &'static str,
+
+	pub count: usize,
+	pub limit: usize,
+	pub percentage: f64,
+}
+
+impl LimitUsage
+{
+	pub fn new(name: &'static str, count: usize, limit: usize) -> Self
+	{
+		return Self {
+			name: name,
+			count: count,
+			limit: limit,
+			percentage: 100.0 * count as f64 / limit as f64,
+		};
+	}
+}
+
+#[derive(Clone, PartialEq, Debug, Serialize)]
+pub struct BspTextureInfo
+{
+	pub name: String,
+
+	/// Width and height in texels, if the format records them.
+	pub size: Option<[u32; 2]>,
+
+	/// Whether the texture's pixels are stored in the file, rather than being
+	/// loaded from elsewhere by the game.
+	pub embedded: bool,
+}
+
+/// Summary of a compiled map, for inspecting how much of the format's budget
+/// it uses.
+#[derive(Clone, PartialEq, Debug, Serialize)]
+pub struct BspFileInfo
+{
+	pub format: BspFormat,
+	pub file_size: usize,
+	pub lumps: Vec<LumpInfo>,
+	pub entity_count: usize,
+
+	/// Usage of each of the engine's limits. This is empty for formats whose
+	/// limits are not known.
+	pub limits: Vec<LimitUsage>,
+
+	pub textures: Vec<BspTextureInfo>,
+
+	/// Size of the lighting lump, and the number of lightmap samples that it
+	/// holds.
+	pub lighting_bytes: usize,
+	pub lightmap_samples: usize,
+
+	/// Size of the compressed visibility lump, and the size that the rows it
+	/// is used by would take up uncompressed.
+	pub vis_bytes: usize,
+	pub vis_uncompressed_bytes: usize,
+
+	/// Uncompressed size of the visibility rows divided by their compressed
+	/// size, or none if the map has no visibility.
+	pub vis_compression_ratio: Option<f64>,
+}
@@ -0,0 +1,210 @@
+use super::clip_tree::ClipTree;
+use super::contents::Contents;
+use super::dplane3::{DPlane3, PlaneSide};
+use super::plane_pool::{PlaneIndex, PlanePool};
+use super::winding::Winding;
+use glam::DVec3;
+
+/// Reference to a child of a BSP node.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum BspChild
+{
+	Node(usize),
+	Leaf(usize),
+}
+
+#[derive(Clone, Debug)]
+pub struct BspNode
+{
+	/// Splitting plane. This always faces along the positive direction of its
+	/// major axis, ie. it has an even index within the plane pool.
+	pub plane: PlaneIndex,
+
+	/// Children in front of and behind the plane, in that order.
+	pub children: [BspChild; 2],
+
+	/// Indices of the faces that lie on the node's plane.
+	pub faces: Vec<usize>,
+
+	pub mins: DVec3,
+	pub maxs: DVec3,
+}
+
+#[derive(Clone, Debug)]
+pub struct BspLeaf
+{
+	pub contents: Contents,
+
+	/// Leaves below the last structural split share a cluster. Visibility is
+	/// computed between clusters, rather than between individual leaves, so
+	/// that detail does not affect it.
+	pub cluster: usize,
+
+	/// Indices of the faces that can be seen from within the leaf.
+	pub faces: Vec<usize>,
+
+	pub mins: DVec3,
+	pub maxs: DVec3,
+}
+
+/// A visible face that has been placed on a node of the tree.
+#[derive(Clone, Debug)]
+pub struct BspFace
+{
+	pub plane: PlaneIndex,
+	pub winding: Winding,
+	pub contents: Contents,
+
+	/// Entity, brush within the entity, and face within the brush that this
+	/// face came from in the blueprint. The entity may differ from the one the
+	/// tree was built for if the face came from a detail brush.
+	pub entity: usize,
+	pub brush: usize,
+	pub face: usize,
+}
+
+#[derive(Clone, Debug)]
+pub struct BspTree
+{
+	pub root: BspChild,
+	pub nodes: Vec<BspNode>,
+	pub leaves: Vec<BspLeaf>,
+	pub faces: Vec<BspFace>,
+	pub cluster_count: usize,
+
+	/// Bounds of the volume that the tree partitions. Leaves touching the
+	/// edge of this volume are outside the map.
+	pub mins: DVec3,
+	pub maxs: DVec3,
+}
+
+impl BspTree
+{
+	/// Returns the outward facing planes of the volume partitioned by the
+	/// tree.
+	pub fn bounding_planes(&self) -> Vec<DPlane3>
+	{
+		return vec![
+			DPlane3::new(DVec3::X, self.maxs.x),
+			DPlane3::new(DVec3::NEG_X, -self.mins.x),
+			DPlane3::new(DVec3::Y, self.maxs.y),
+			DPlane3::new(DVec3::NEG_Y, -self.mins.y),
+			DPlane3::new(DVec3::Z, self.maxs.z),
+			DPlane3::new(DVec3::NEG_Z, -self.mins.z),
+		];
+	}
+
+	/// Returns the index of the leaf that contains the point. Points lying
+	/// exactly on a node's plane are considered to be in front of it.
+	pub fn leaf_for_point(&self, point: DVec3, planes: &PlanePool) -> usize
+	{
+		let mut child: BspChild = self.root;
+
+		loop
+		{
+			match child
+			{
+				BspChild::Leaf(index) => return index,
+				BspChild::Node(index) =>
+				{
+					let node: &BspNode = &self.nodes[index];
+					let side: PlaneSide = planes[node.plane].classify_point(point, 0.0);
+
+					child = if side == PlaneSide::Back
+					{
+						node.children[1]
+					}
+					else
+					{
+						node.children[0]
+					};
+				}
+			}
+		}
+	}
+
+	/// Follows the segment from start to end, and returns the contents of the
+	/// first leaf along it for which the stop function returns true. Returns
+	/// [`None`] if the segment reaches its end without stopping.
+	pub fn trace_segment(
+		&self,
+		start: DVec3,
+		end: DVec3,
+		planes: &PlanePool,
+		stop: impl Fn(Contents) -> bool,
+	) -> Option<Contents>
+	{
+		return self.trace_child(self.root, start, end, planes, &stop);
+	}
+
+	// Visits the children of each node in the order in which the segment
+	// passes through them.
+	fn trace_child(
+		&self,
+		child: BspChild,
+		start: DVec3,
+		end: DVec3,
+		planes: &PlanePool,
+		stop: &impl Fn(Contents) -> bool,
+	) -> Option<Contents>
+	{
+		let node: &BspNode = match child
+		{
+			BspChild::Leaf(index) =>
+			{
+				let contents: Contents = self.leaves[index].contents;
+				return if stop(contents) { Some(contents) } else { None };
+			}
+			BspChild::Node(index) => &self.nodes[index],
+		};
+
+		let plane: &DPlane3 = &planes[node.plane];
+		let start_distance: f64 = plane.distance_to(start);
+		let end_distance: f64 = plane.distance_to(end);
+
+		if start_distance >= 0.0 && end_distance >= 0.0
+		{
+			return self.trace_child(node.children[0], start, end, planes, stop);
+		}
+
+		if start_distance < 0.0 && end_distance < 0.0
+		{
+			return self.trace_child(node.children[1], start, end, planes, stop);
+		}
+
+		let middle: DVec3 =
+			start + ((end - start) * (start_distance / (start_distance - end_distance)));
+		let (near, far): (BspChild, BspChild) = if start_distance >= 0.0
+		{
+			(node.children[0], node.children[1])
+		}
+		else
+		{
+			(node.children[1], node.children[0])
+		};
+
+		return self
+			.trace_child(near, start, middle, planes, stop)
+			.or_else(|| self.trace_child(far, middle, end, planes, stop));
+	}
+}
+
+/// The BSP tree built for one entity. The world model comes from worldspawn,
+/// and every other brush entity gets a model of its own.
+#[derive(Clone, Debug)]
+pub struct BspModel
+{
+	pub entity: usize,
+	pub tree: BspTree,
+
+	/// Trees for each of the clipping hulls, beginning with hull 1. These are
+	/// empty until the clipping hulls have been built.
+	pub clip_trees: Vec<ClipTree>,
+}
+
+/// Output of the BSP stage. The world model is always first.
+#[derive(Clone, Debug, Default)]
+pub struct BspModels
+{
+	pub models: Vec<BspModel>,
+}
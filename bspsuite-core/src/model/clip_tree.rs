@@ -0,0 +1,69 @@
+use super::contents::Contents;
+#[cfg(test)]
+use super::dplane3::PlaneSide;
+use super::plane_pool::PlaneIndex;
+#[cfg(test)]
+use super::plane_pool::PlanePool;
+#[cfg(test)]
+use glam::DVec3;
+
+/// Reference to a child of a clipping node. Clipping trees have no leaves, as
+/// only the contents of the space below a node are needed for collision.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ClipChild
+{
+	Node(usize),
+	Contents(Contents),
+}
+
+#[derive(Clone, Debug)]
+pub struct ClipNode
+{
+	pub plane: PlaneIndex,
+
+	/// Children in front of and behind the plane, in that order.
+	pub children: [ClipChild; 2],
+}
+
+/// Tree used to collide a box of a particular size with a model. The brushes
+/// of the model are expanded by the size of the box, so that the box can be
+/// treated as a point at its origin.
+#[derive(Clone, Debug)]
+pub struct ClipTree
+{
+	pub root: ClipChild,
+	pub nodes: Vec<ClipNode>,
+}
+
+#[cfg(test)]
+impl ClipTree
+{
+	/// Returns the contents at the point. Points lying exactly on a node's
+	/// plane are considered to be in front of it.
+	pub fn contents_at(&self, point: DVec3, planes: &PlanePool) -> Contents
+	{
+		let mut child: ClipChild = self.root;
+
+		loop
+		{
+			match child
+			{
+				ClipChild::Contents(contents) => return contents,
+				ClipChild::Node(index) =>
+				{
+					let node: &ClipNode = &self.nodes[index];
+					let side: PlaneSide = planes[node.plane].classify_point(point, 0.0);
+
+					child = if side == PlaneSide::Back
+					{
+						node.children[1]
+					}
+					else
+					{
+						node.children[0]
+					};
+				}
+			}
+		}
+	}
+}
@@ -0,0 +1,114 @@
+/// What occupies a volume of the map. Variants are listed in priority order
+/// (see [`Contents::priority`]), apart from [`Contents::Empty`].
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, strum::Display)]
+pub enum Contents
+{
+	Empty,
+	Clip,
+	Solid,
+	Water,
+	Slime,
+	Lava,
+	Sky,
+}
+
+impl Contents
+{
+	/// Determines the contents of a brush from the name of the material on its
+	/// faces, following the Quake and GoldSrc naming conventions.
+	pub fn from_material(material: &str) -> Self
+	{
+		let name: String = material.to_ascii_lowercase();
+
+		// Hint and skip brushes are only used to guide the BSP, and do not
+		// occupy any volume.
+		if name == "hint" || name == "skip"
+		{
+			return Contents::Empty;
+		}
+
+		// Clip brushes are invisible, and only block movement.
+		if name == "clip"
+		{
+			return Contents::Clip;
+		}
+
+		if name.starts_with("sky")
+		{
+			return Contents::Sky;
+		}
+
+		if name.starts_with("!lava") || name.starts_with("*lava")
+		{
+			return Contents::Lava;
+		}
+
+		if name.starts_with("!slime") || name.starts_with("*slime")
+		{
+			return Contents::Slime;
+		}
+
+		if name.starts_with('!') || name.starts_with('*') || name.starts_with("water")
+		{
+			return Contents::Water;
+		}
+
+		return Contents::Solid;
+	}
+
+	/// When brushes with different contents overlap, the contents with the
+	/// higher priority occupy the overlapping volume. Clip brushes are only
+	/// present in the expanded clipping hulls, so a brush with any clip face
+	/// is treated entirely as a clip brush.
+	pub fn priority(&self) -> u32
+	{
+		return match self
+		{
+			Contents::Empty => 0,
+			Contents::Sky => 1,
+			Contents::Lava => 2,
+			Contents::Slime => 3,
+			Contents::Water => 4,
+			Contents::Solid => 5,
+			Contents::Clip => 6,
+		};
+	}
+
+	/// Whether the contents seal the map, so that the void outside of it
+	/// cannot be reached through them.
+	pub fn seals_map(&self) -> bool
+	{
+		return matches!(self, Contents::Solid | Contents::Sky);
+	}
+}
+
+#[cfg(test)]
+mod tests
+{
+	use super::*;
+
+	#[test]
+	fn contents_from_material()
+	{
+		assert_eq!(Contents::from_material("AAATRIGGER"), Contents::Solid);
+		assert_eq!(Contents::from_material("+0~LIGHT"), Contents::Solid);
+		assert_eq!(Contents::from_material("SKY"), Contents::Sky);
+		assert_eq!(Contents::from_material("!WATERBLUE"), Contents::Water);
+		assert_eq!(Contents::from_material("*04water1"), Contents::Water);
+		assert_eq!(Contents::from_material("!lava1"), Contents::Lava);
+		assert_eq!(Contents::from_material("*SLIME0"), Contents::Slime);
+		assert_eq!(Contents::from_material("HINT"), Contents::Empty);
+		assert_eq!(Contents::from_material("CLIP"), Contents::Clip);
+		assert_eq!(Contents::from_material("CLIPWALL"), Contents::Solid);
+	}
+
+	#[test]
+	fn sealing_contents()
+	{
+		assert!(Contents::Solid.seals_map());
+		assert!(Contents::Sky.seals_map());
+		assert!(!Contents::Water.seals_map());
+		assert!(!Contents::Clip.seals_map());
+		assert!(!Contents::Empty.seals_map());
+	}
+}
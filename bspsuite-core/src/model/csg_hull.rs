@@ -0,0 +1,44 @@
+use super::contents::Contents;
+use super::plane_pool::PlaneIndex;
+use super::winding::Winding;
+
+/// A visible fragment of a brush face, left over after overlapping brushes
+/// have been clipped against each other.
+#[derive(Clone, Debug)]
+pub struct HullFace
+{
+	pub plane: PlaneIndex,
+	pub winding: Winding,
+
+	/// Contents of the brush that the face belongs to. These lie behind the
+	/// face.
+	pub contents: Contents,
+
+	/// Index of the brush within its entity, and of the face within the
+	/// brush, that this fragment came from.
+	pub brush: usize,
+	pub face: usize,
+}
+
+/// Visible faces belonging to a single entity.
+#[derive(Clone, Debug, Default)]
+pub struct EntityHull
+{
+	pub faces: Vec<HullFace>,
+}
+
+/// Output of the CSG stage. There is one entity hull for each entity in the
+/// blueprint, in the same order.
+#[derive(Clone, Debug, Default)]
+pub struct CsgHull
+{
+	pub entities: Vec<EntityHull>,
+}
+
+impl CsgHull
+{
+	pub fn face_count(&self) -> usize
+	{
+		return self.entities.iter().map(|entity| entity.faces.len()).sum();
+	}
+}
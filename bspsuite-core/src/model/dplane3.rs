@@ -1,5 +1,43 @@
+use super::winding::Winding;
 use glam::DVec3;
 
+/// Normal components within this distance of 0 or ±1 are snapped to those
+/// values by [`DPlane3::snapped`].
+pub const SNAP_NORMAL_EPSILON: f64 = 1.0e-5;
+
+/// Plane distances within this distance of a whole number are snapped to it by
+/// [`DPlane3::snapped`].
+pub const SNAP_DISTANCE_EPSILON: f64 = 0.01;
+
+/// Which side of a plane a point or shape lies on.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum PlaneSide
+{
+	Front,
+	Back,
+	On,
+
+	/// The shape has parts both in front of and behind the plane.
+	Spanning,
+}
+
+/// Classification of a plane's orientation, matching the `type` field of a
+/// BSP file's plane structure.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[repr(i32)]
+pub enum PlaneType
+{
+	/// The normal points exactly along the X axis.
+	X = 0,
+	Y = 1,
+	Z = 2,
+
+	/// The normal is not axial, but is closest to the X axis.
+	AnyX = 3,
+	AnyY = 4,
+	AnyZ = 5,
+}
+
 #[derive(Clone, Copy, PartialEq, Debug)]
 pub struct DPlane3
 {
@@ -30,6 +68,233 @@ impl DPlane3
 	{
 		return self == &DPlane3::NULL;
 	}
+
+	/// Creates a plane from three points that lie on it. Following the .map
+	/// file convention, the points are wound clockwise when viewed from the
+	/// front of the plane. If the points are colinear, a null plane is
+	/// returned.
+	#[must_use]
+	pub fn from_points(points: &[DVec3; 3]) -> Self
+	{
+		let normal: DVec3 = (points[0] - points[1])
+			.cross(points[2] - points[1])
+			.normalize_or_zero();
+
+		if normal == DVec3::ZERO
+		{
+			return Self::NULL;
+		}
+
+		return Self::new(normal, normal.dot(points[0]));
+	}
+
+	/// Returns the plane facing in the opposite direction.
+	#[inline]
+	#[must_use]
+	pub fn flipped(&self) -> Self
+	{
+		return Self::new(-self.normal, -self.distance);
+	}
+
+	/// Returns the plane with a unit length normal. A null plane is returned
+	/// unchanged.
+	#[must_use]
+	pub fn normalized(&self) -> Self
+	{
+		let length: f64 = self.normal.length();
+
+		if length == 0.0
+		{
+			return *self;
+		}
+
+		return Self::new(self.normal / length, self.distance / length);
+	}
+
+	/// Returns the plane with normal components that are very close to 0 or ±1
+	/// set exactly to those values, and the distance snapped to a whole number
+	/// if it is very close to one. This avoids precision problems when
+	/// comparing nearly axial planes.
+	#[must_use]
+	pub fn snapped(&self) -> Self
+	{
+		let mut normal: DVec3 = self.normal;
+
+		for axis in 0..3
+		{
+			if (normal[axis].abs() - 1.0).abs() < SNAP_NORMAL_EPSILON
+			{
+				normal = DVec3::ZERO;
+				normal[axis] = self.normal[axis].signum();
+				break;
+			}
+
+			if normal[axis].abs() < SNAP_NORMAL_EPSILON
+			{
+				normal[axis] = 0.0;
+			}
+		}
+
+		// Only renormalise if something was snapped, so that planes which
+		// needed no snapping are returned exactly as they were.
+		if normal != self.normal
+		{
+			normal = normal.normalize_or_zero();
+		}
+
+		let rounded_distance: f64 = self.distance.round();
+
+		let distance: f64 = if (self.distance - rounded_distance).abs() < SNAP_DISTANCE_EPSILON
+		{
+			rounded_distance
+		}
+		else
+		{
+			self.distance
+		};
+
+		return Self::new(normal, distance);
+	}
+
+	pub fn plane_type(&self) -> PlaneType
+	{
+		if self.normal.x.abs() == 1.0
+		{
+			return PlaneType::X;
+		}
+
+		if self.normal.y.abs() == 1.0
+		{
+			return PlaneType::Y;
+		}
+
+		if self.normal.z.abs() == 1.0
+		{
+			return PlaneType::Z;
+		}
+
+		let abs: DVec3 = self.normal.abs();
+
+		if abs.x >= abs.y && abs.x >= abs.z
+		{
+			return PlaneType::AnyX;
+		}
+
+		if abs.y >= abs.z
+		{
+			return PlaneType::AnyY;
+		}
+
+		return PlaneType::AnyZ;
+	}
+
+	/// Returns the signed distance from the plane to the point. This is
+	/// positive if the point lies in front of the plane.
+	#[inline]
+	pub fn distance_to(&self, point: DVec3) -> f64
+	{
+		return self.normal.dot(point) - self.distance;
+	}
+
+	/// Classifies a point against the plane. Points within the epsilon of the
+	/// plane are considered to lie on it.
+	pub fn classify_point(&self, point: DVec3, epsilon: f64) -> PlaneSide
+	{
+		let distance: f64 = self.distance_to(point);
+
+		if distance > epsilon
+		{
+			return PlaneSide::Front;
+		}
+
+		if distance < -epsilon
+		{
+			return PlaneSide::Back;
+		}
+
+		return PlaneSide::On;
+	}
+
+	/// Classifies an axis-aligned box, given by its minimum and maximum
+	/// corners, against the plane.
+	pub fn classify_box(&self, mins: DVec3, maxs: DVec3, epsilon: f64) -> PlaneSide
+	{
+		// Find the corners furthest along and furthest against the normal.
+		let positive: glam::BVec3 = self.normal.cmpge(DVec3::ZERO);
+		let nearest: DVec3 = DVec3::select(positive, mins, maxs);
+		let furthest: DVec3 = DVec3::select(positive, maxs, mins);
+
+		return combine_sides(
+			self.classify_point(nearest, epsilon),
+			self.classify_point(furthest, epsilon),
+		);
+	}
+
+	/// Classifies all the points of a winding against the plane.
+	pub fn classify_winding(&self, winding: &Winding, epsilon: f64) -> PlaneSide
+	{
+		return winding
+			.points
+			.iter()
+			.map(|point| self.classify_point(*point, epsilon))
+			.fold(PlaneSide::On, combine_sides);
+	}
+
+	/// Returns the distance along the ray at which it intersects the plane,
+	/// measured in multiples of the direction vector. Returns [`None`] if the
+	/// ray is parallel to the plane, or the plane lies behind the ray's origin.
+	pub fn intersect_ray(&self, origin: DVec3, direction: DVec3) -> Option<f64>
+	{
+		let denominator: f64 = self.normal.dot(direction);
+
+		if denominator == 0.0
+		{
+			return None;
+		}
+
+		let fraction: f64 = -self.distance_to(origin) / denominator;
+
+		if fraction < 0.0
+		{
+			return None;
+		}
+
+		return Some(fraction);
+	}
+
+	/// Returns the point at which the line segment crosses the plane, or
+	/// [`None`] if both ends lie strictly on the same side of it.
+	pub fn intersect_segment(&self, start: DVec3, end: DVec3) -> Option<DVec3>
+	{
+		let start_distance: f64 = self.distance_to(start);
+		let end_distance: f64 = self.distance_to(end);
+
+		if (start_distance > 0.0 && end_distance > 0.0)
+			|| (start_distance < 0.0 && end_distance < 0.0)
+		{
+			return None;
+		}
+
+		if start_distance == end_distance
+		{
+			// The whole segment lies on the plane.
+			return Some(start);
+		}
+
+		let fraction: f64 = start_distance / (start_distance - end_distance);
+		return Some(start + ((end - start) * fraction));
+	}
+}
+
+fn combine_sides(a: PlaneSide, b: PlaneSide) -> PlaneSide
+{
+	return match (a, b)
+	{
+		(PlaneSide::On, side) | (side, PlaneSide::On) => side,
+		(PlaneSide::Front, PlaneSide::Front) => PlaneSide::Front,
+		(PlaneSide::Back, PlaneSide::Back) => PlaneSide::Back,
+		_ => PlaneSide::Spanning,
+	};
 }
 
 #[cfg(test)]
@@ -62,4 +327,153 @@ mod tests
 		assert_eq!(&null3, &DPlane3::NULL);
 		assert_eq!(&null4, &DPlane3::NULL);
 	}
+
+	#[test]
+	fn construct_from_points()
+	{
+		let plane: DPlane3 = DPlane3::from_points(&[
+			DVec3::new(0.0, 0.0, 16.0),
+			DVec3::new(0.0, 1.0, 16.0),
+			DVec3::new(1.0, 1.0, 16.0),
+		]);
+
+		assert_eq!(plane, DPlane3::new(DVec3::Z, 16.0));
+
+		let colinear: DPlane3 = DPlane3::from_points(&[DVec3::ZERO, DVec3::X, DVec3::X * 2.0]);
+		assert!(colinear.is_null());
+	}
+
+	#[test]
+	fn flip_plane()
+	{
+		let plane: DPlane3 = DPlane3::new_xyzd(0.0, 2.0, 0.0, 8.0);
+
+		assert_eq!(plane.flipped(), DPlane3::new_xyzd(0.0, -2.0, 0.0, -8.0));
+		assert_eq!(plane.flipped().flipped(), plane);
+	}
+
+	#[test]
+	fn normalize_plane()
+	{
+		let plane: DPlane3 = DPlane3::new_xyzd(0.0, 2.0, 0.0, 8.0);
+
+		assert_eq!(plane.normalized(), DPlane3::new(DVec3::Y, 4.0));
+		assert!(DPlane3::NULL.normalized().is_null());
+	}
+
+	#[test]
+	fn snap_near_axial_planes()
+	{
+		let plane: DPlane3 = DPlane3::new_xyzd(0.000001, -0.9999999, 0.0, 31.995).snapped();
+		assert_eq!(plane, DPlane3::new(DVec3::NEG_Y, 32.0));
+
+		let diagonal: DPlane3 = DPlane3::new(DVec3::new(1.0, 1.0, 0.0).normalize(), 10.5);
+		assert_eq!(diagonal.snapped(), diagonal);
+	}
+
+	#[test]
+	fn compute_plane_type()
+	{
+		assert_eq!(DPlane3::new(DVec3::NEG_X, 0.0).plane_type(), PlaneType::X);
+		assert_eq!(DPlane3::new(DVec3::Y, 0.0).plane_type(), PlaneType::Y);
+		assert_eq!(DPlane3::new(DVec3::Z, 0.0).plane_type(), PlaneType::Z);
+
+		let any_x: DPlane3 = DPlane3::new(DVec3::new(-0.8, 0.6, 0.0), 0.0);
+		assert_eq!(any_x.plane_type(), PlaneType::AnyX);
+
+		let any_y: DPlane3 = DPlane3::new(DVec3::new(0.0, 0.6, -0.6).normalize(), 0.0);
+		assert_eq!(any_y.plane_type(), PlaneType::AnyY);
+
+		let any_z: DPlane3 = DPlane3::new(DVec3::new(0.0, 0.6, 0.8), 0.0);
+		assert_eq!(any_z.plane_type(), PlaneType::AnyZ);
+		assert_eq!(PlaneType::AnyZ as i32, 5);
+	}
+
+	#[test]
+	fn compute_distance_to_point()
+	{
+		let plane: DPlane3 = DPlane3::new(DVec3::X, 16.0);
+
+		assert_eq!(plane.distance_to(DVec3::new(20.0, 5.0, 5.0)), 4.0);
+		assert_eq!(plane.distance_to(DVec3::new(10.0, 0.0, -5.0)), -6.0);
+		assert_eq!(plane.distance_to(DVec3::new(16.0, 8.0, 8.0)), 0.0);
+	}
+
+	#[test]
+	fn classify_point()
+	{
+		let plane: DPlane3 = DPlane3::new(DVec3::X, 16.0);
+
+		assert_eq!(
+			plane.classify_point(DVec3::new(20.0, 0.0, 0.0), 0.1),
+			PlaneSide::Front
+		);
+		assert_eq!(
+			plane.classify_point(DVec3::new(10.0, 0.0, 0.0), 0.1),
+			PlaneSide::Back
+		);
+		assert_eq!(
+			plane.classify_point(DVec3::new(16.05, 0.0, 0.0), 0.1),
+			PlaneSide::On
+		);
+	}
+
+	#[test]
+	fn classify_box()
+	{
+		let plane: DPlane3 = DPlane3::new(DVec3::X, 16.0);
+
+		assert_eq!(
+			plane.classify_box(DVec3::splat(-8.0), DVec3::splat(8.0), 0.1),
+			PlaneSide::Back
+		);
+		assert_eq!(
+			plane.classify_box(DVec3::splat(8.0), DVec3::splat(24.0), 0.1),
+			PlaneSide::Spanning
+		);
+		assert_eq!(
+			plane.classify_box(DVec3::splat(16.0), DVec3::splat(24.0), 0.1),
+			PlaneSide::Front
+		);
+	}
+
+	#[test]
+	fn classify_winding()
+	{
+		let plane: DPlane3 = DPlane3::new(DVec3::X, 16.0);
+
+		let on_plane: Winding = Winding::from_plane(&DPlane3::new(DVec3::NEG_X, -16.0));
+		assert_eq!(plane.classify_winding(&on_plane, 0.1), PlaneSide::On);
+
+		let crossing: Winding = Winding::from_plane(&DPlane3::new(DVec3::Z, 0.0));
+		assert_eq!(plane.classify_winding(&crossing, 0.1), PlaneSide::Spanning);
+	}
+
+	#[test]
+	fn intersect_ray()
+	{
+		let plane: DPlane3 = DPlane3::new(DVec3::Z, 8.0);
+
+		assert_eq!(plane.intersect_ray(DVec3::ZERO, DVec3::Z * 2.0), Some(4.0));
+		assert_eq!(plane.intersect_ray(DVec3::ZERO, DVec3::NEG_Z), None);
+		assert_eq!(plane.intersect_ray(DVec3::ZERO, DVec3::X), None);
+	}
+
+	#[test]
+	fn intersect_segment()
+	{
+		let plane: DPlane3 = DPlane3::new(DVec3::Z, 8.0);
+
+		assert_eq!(
+			plane.intersect_segment(DVec3::new(4.0, 0.0, 0.0), DVec3::new(4.0, 0.0, 16.0)),
+			Some(DVec3::new(4.0, 0.0, 8.0))
+		);
+		assert_eq!(plane.intersect_segment(DVec3::ZERO, DVec3::Z), None);
+
+		let on_plane: DVec3 = DVec3::new(0.0, 0.0, 8.0);
+		assert_eq!(
+			plane.intersect_segment(on_plane, on_plane + DVec3::X),
+			Some(on_plane)
+		);
+	}
 }
@@ -0,0 +1,162 @@
+use std::slice::Iter;
+
+/// Ordered collection of keyvalues on an entity. Keys may appear more than
+/// once, and the order in which keyvalues were added is preserved. Both of
+/// these properties are relied upon by game code: for example, multi_manager
+/// entities use repeated keys to fire the same target multiple times.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct EntityKeyValues
+{
+	pairs: Vec<(String, String)>,
+}
+
+impl EntityKeyValues
+{
+	pub fn new() -> Self
+	{
+		return Self { pairs: Vec::new() };
+	}
+
+	/// Appends a keyvalue. Any existing keyvalues with the same key are kept.
+	pub fn push(&mut self, key: String, value: String)
+	{
+		self.pairs.push((key, value));
+	}
+
+	/// Replaces the value of the last keyvalue with the given key, which is the
+	/// one that the engine would use, or appends the keyvalue if there is no
+	/// such key.
+	pub fn set(&mut self, key: String, value: String)
+	{
+		match self
+			.pairs
+			.iter_mut()
+			.rev()
+			.find(|(pair_key, _)| *pair_key == key)
+		{
+			Some((_, pair_value)) => *pair_value = value,
+			None => self.pairs.push((key, value)),
+		}
+	}
+
+	/// Removes every keyvalue with the given key, and returns how many there
+	/// were.
+	pub fn remove(&mut self, key: &str) -> usize
+	{
+		let count: usize = self.pairs.len();
+		self.pairs.retain(|(pair_key, _)| pair_key != key);
+		return count - self.pairs.len();
+	}
+
+	/// Returns the value of the first keyvalue with the given key.
+	pub fn first(&self, key: &str) -> Option<&str>
+	{
+		return self.all(key).next();
+	}
+
+	/// Returns the value of the last keyvalue with the given key. This is the
+	/// value that the engine would use if it only expects one instance of the
+	/// key.
+	pub fn last(&self, key: &str) -> Option<&str>
+	{
+		return self.all(key).next_back();
+	}
+
+	/// Returns the values of all keyvalues with the given key, in order.
+	pub fn all<'l>(&'l self, key: &str) -> impl DoubleEndedIterator<Item = &'l str>
+	{
+		return self
+			.pairs
+			.iter()
+			.filter(move |(pair_key, _)| pair_key == key)
+			.map(|(_, value)| value.as_str());
+	}
+
+	pub fn contains_key(&self, key: &str) -> bool
+	{
+		return self.first(key).is_some();
+	}
+
+	pub fn iter(&self) -> Iter<'_, (String, String)>
+	{
+		return self.pairs.iter();
+	}
+
+	pub fn len(&self) -> usize
+	{
+		return self.pairs.len();
+	}
+
+	pub fn is_empty(&self) -> bool
+	{
+		return self.pairs.is_empty();
+	}
+}
+
+#[cfg(test)]
+mod tests
+{
+	use super::*;
+
+	fn multi_manager_keyvalues() -> EntityKeyValues
+	{
+		let mut keyvalues: EntityKeyValues = EntityKeyValues::new();
+
+		for (key, value) in [
+			("classname", "multi_manager"),
+			("targetname", "mm"),
+			("door", "0"),
+			("light", "1.5"),
+			("door", "3"),
+		]
+		{
+			keyvalues.push(String::from(key), String::from(value));
+		}
+
+		return keyvalues;
+	}
+
+	#[test]
+	fn look_up_duplicate_keys()
+	{
+		let keyvalues: EntityKeyValues = multi_manager_keyvalues();
+
+		assert_eq!(keyvalues.len(), 5);
+		assert_eq!(keyvalues.first("door"), Some("0"));
+		assert_eq!(keyvalues.last("door"), Some("3"));
+		assert_eq!(keyvalues.all("door").collect::<Vec<&str>>(), vec!["0", "3"]);
+		assert_eq!(keyvalues.first("light"), keyvalues.last("light"));
+		assert_eq!(keyvalues.first("target"), None);
+		assert!(!keyvalues.contains_key("target"));
+	}
+
+	#[test]
+	fn preserve_insertion_order()
+	{
+		let keyvalues: EntityKeyValues = multi_manager_keyvalues();
+		let keys: Vec<&str> = keyvalues.iter().map(|(key, _)| key.as_str()).collect();
+
+		assert_eq!(
+			keys,
+			vec!["classname", "targetname", "door", "light", "door"]
+		);
+	}
+
+	#[test]
+	fn set_and_remove_keys()
+	{
+		let mut keyvalues: EntityKeyValues = multi_manager_keyvalues();
+
+		keyvalues.set(String::from("door"), String::from("5"));
+		keyvalues.set(String::from("wait"), String::from("1"));
+		assert_eq!(keyvalues.all("door").collect::<Vec<&str>>(), vec!["0", "5"]);
+		assert_eq!(keyvalues.last("wait"), Some("1"));
+		assert_eq!(keyvalues.len(), 6);
+
+		assert_eq!(keyvalues.remove("door"), 2);
+		assert_eq!(keyvalues.remove("door"), 0);
+		assert_eq!(keyvalues.len(), 4);
+		assert!(!keyvalues.is_empty());
+		assert!(EntityKeyValues::new().is_empty());
+	}
+}
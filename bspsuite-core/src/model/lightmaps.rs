@@ -0,0 +1,123 @@
+use glam::DVec3;
+
+/// Maximum number of light styles that may affect a single face.
+pub const MAX_FACE_LIGHT_STYLES: usize = 4;
+
+/// The normal, unchanging light style.
+pub const DEFAULT_LIGHT_STYLE: u8 = 0;
+
+/// Light received by a face, sampled on a grid aligned with the face's texture
+/// axes.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FaceLightmap
+{
+	/// Position of the first sample along each texture axis, in luxels.
+	pub mins: [i32; 2],
+
+	/// Number of samples along each texture axis.
+	pub size: [usize; 2],
+
+	/// Light styles affecting the face. The default style always comes first,
+	/// and there are never more than [`MAX_FACE_LIGHT_STYLES`].
+	pub styles: Vec<u8>,
+
+	/// Samples for each of the styles, row by row. Each sample is an RGB
+	/// colour in which 255 is full brightness.
+	pub samples: Vec<Vec<DVec3>>,
+}
+
+impl FaceLightmap
+{
+	/// Returns the samples of every style in turn, as RGB bytes.
+	pub fn rgb_bytes(&self) -> Vec<u8>
+	{
+		return self
+			.samples
+			.iter()
+			.flatten()
+			.flat_map(|sample| {
+				sample
+					.to_array()
+					.map(|channel| channel.round().clamp(0.0, 255.0) as u8)
+			})
+			.collect();
+	}
+}
+
+/// Lightmaps for the faces of every BSP model. Models and faces are in the
+/// same order as in [`BspModels`](super::BspModels). Faces that are not lit,
+/// such as sky and liquid surfaces, have no lightmap.
+#[derive(Clone, Debug, Default)]
+pub struct Lightmaps
+{
+	pub models: Vec<Vec<Option<FaceLightmap>>>,
+}
+
+/// Contents of a lighting lump, along with where each face's lightmap was
+/// placed in it.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct LightingLump
+{
+	pub data: Vec<u8>,
+
+	/// Byte offset of each face's lightmap within the data, indexed by model
+	/// and then by face.
+	pub offsets: Vec<Vec<Option<usize>>>,
+}
+
+impl Lightmaps
+{
+	/// Packs every lightmap into a lighting lump, one after the other.
+	pub fn lighting_lump(&self) -> LightingLump
+	{
+		let mut lump: LightingLump = LightingLump::default();
+
+		for faces in &self.models
+		{
+			let offsets: Vec<Option<usize>> = faces
+				.iter()
+				.map(|face| {
+					let lightmap: &FaceLightmap = face.as_ref()?;
+					let offset: usize = lump.data.len();
+
+					lump.data.extend(lightmap.rgb_bytes());
+					Some(offset)
+				})
+				.collect();
+
+			lump.offsets.push(offsets);
+		}
+
+		return lump;
+	}
+}
+
+#[cfg(test)]
+mod tests
+{
+	use super::*;
+
+	#[test]
+	fn pack_lighting_lump()
+	{
+		let lightmap: FaceLightmap = FaceLightmap {
+			mins: [-1, 0],
+			size: [2, 1],
+			styles: vec![DEFAULT_LIGHT_STYLE, 32],
+			samples: vec![
+				vec![DVec3::new(255.0, 300.0, -4.0), DVec3::splat(10.4)],
+				vec![DVec3::ZERO, DVec3::new(1.0, 2.0, 3.0)],
+			],
+		};
+
+		let lightmaps: Lightmaps = Lightmaps {
+			models: vec![vec![Some(lightmap.clone()), None], vec![Some(lightmap)]],
+		};
+
+		let lump: LightingLump = lightmaps.lighting_lump();
+		let face_bytes: Vec<u8> = vec![255, 255, 0, 10, 10, 10, 0, 0, 0, 1, 2, 3];
+
+		assert_eq!(lump.data, [face_bytes.clone(), face_bytes].concat());
+		assert_eq!(lump.offsets, vec![vec![Some(0), None], vec![Some(12)]]);
+	}
+}
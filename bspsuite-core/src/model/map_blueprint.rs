@@ -1,25 +1,224 @@
-use std::collections::HashMap;
-
+use super::brush_geometry::BrushGeometry;
+use super::bsp_tree::BspModels;
 use super::dplane3::DPlane3;
+use super::entity_keyvalues::EntityKeyValues;
+use super::plane_pool::{PlaneIndex, PlanePool};
+use super::texture_projection::TextureProjection;
+use glam::DVec3;
+use std::collections::HashMap;
 
 pub struct BlueprintBrushFace
 {
-	pub plane: DPlane3,
+	/// Index of the face's plane within the blueprint's plane pool.
+	pub plane: PlaneIndex,
 	pub material: String,
+	pub projection: TextureProjection,
 }
 
 pub struct BlueprintBrush
 {
 	pub faces: Vec<BlueprintBrushFace>,
+
+	/// 1-based line in the map source file on which the brush begins, or 0 if
+	/// this is not known.
+	pub source_line: u32,
+
+	/// Number of faces that were left out of the brush when it was read,
+	/// because their plane points did not define a plane.
+	pub colinear_face_count: usize,
+}
+
+impl BlueprintBrush
+{
+	/// Builds the polygon for each of the brush's faces, reporting any defects
+	/// that make the brush degenerate.
+	pub fn geometry(&self, plane_pool: &PlanePool) -> BrushGeometry
+	{
+		let planes: Vec<DPlane3> = self
+			.faces
+			.iter()
+			.map(|face| plane_pool[face.plane])
+			.collect();
+		return BrushGeometry::from_planes(&planes);
+	}
 }
 
 pub struct BlueprintEntity
 {
 	pub brushes: Vec<BlueprintBrush>,
-	pub keyvalues: HashMap<String, String>,
+	pub keyvalues: EntityKeyValues,
+
+	/// 1-based line in the map source file on which the entity begins, or 0
+	/// if this is not known.
+	pub source_line: u32,
+}
+
+impl BlueprintEntity
+{
+	/// Parses the entity's origin keyvalue, which holds three numbers
+	/// separated by whitespace. Returns [`None`] if the keyvalue is missing or
+	/// malformed.
+	pub fn origin(&self) -> Option<DVec3>
+	{
+		let components: Vec<f64> = self
+			.keyvalues
+			.last("origin")?
+			.split_whitespace()
+			.map(|component| component.parse::<f64>().ok())
+			.collect::<Option<Vec<f64>>>()?;
+
+		return match components[..]
+		{
+			[x, y, z] => Some(DVec3::new(x, y, z)),
+			_ => None,
+		};
+	}
+}
+
+/// A texture used by the map, as found by the extension that read it.
+pub struct BlueprintTexture
+{
+	/// Width and height in texels.
+	pub size: [u32; 2],
+
+	/// Average colour of the texture, with components ranging from 0 to 255,
+	/// if it is known.
+	pub average_color: Option<DVec3>,
+
+	/// Palette indices of the texture's pixels, row by row, or empty if they
+	/// are not known.
+	pub pixels: Vec<u8>,
+
+	/// RGB colours of the palette used by the pixels, or empty if there is
+	/// none.
+	pub palette: Vec<u8>,
 }
 
 pub struct MapBlueprint
 {
 	pub entities: Vec<BlueprintEntity>,
+
+	/// Planes referred to by brush faces.
+	pub planes: PlanePool,
+
+	/// Textures that the texture providers were able to find, keyed by their
+	/// name in lower case. Materials that are missing from here have an unknown
+	/// size.
+	pub textures: HashMap<String, BlueprintTexture>,
+}
+
+impl MapBlueprint
+{
+	/// Produces the text of the BSP entities lump. Entities and their
+	/// keyvalues are written in the order in which they appear in the
+	/// blueprint, and duplicate keys are preserved. Entities other than the
+	/// world that have a model of their own are given a model keyvalue
+	/// referring to it, in place of any they already had. Entities whose
+	/// brushes were merged into another entity's model are left out.
+	pub fn entity_lump_text(&self, models: &BspModels) -> String
+	{
+		let mut text: String = String::new();
+
+		for (index, entity) in self.entities.iter().enumerate()
+		{
+			let model: Option<usize> = models.models.iter().position(|model| model.entity == index);
+
+			if model.is_none() && !entity.brushes.is_empty()
+			{
+				continue;
+			}
+
+			let submodel: Option<usize> = model.filter(|model| *model > 0);
+			text.push_str("{\n");
+
+			for (key, value) in entity.keyvalues.iter()
+			{
+				if submodel.is_none() || key != "model"
+				{
+					text.push_str(&format!("\"{key}\" \"{value}\"\n"));
+				}
+			}
+
+			if let Some(submodel) = submodel
+			{
+				text.push_str(&format!("\"model\" \"*{submodel}\"\n"));
+			}
+
+			text.push_str("}\n");
+		}
+
+		return text;
+	}
+}
+
+#[cfg(test)]
+mod tests
+{
+	use super::*;
+
+	#[test]
+	fn write_entity_lump_in_source_order()
+	{
+		let mut worldspawn: EntityKeyValues = EntityKeyValues::new();
+		worldspawn.push(String::from("classname"), String::from("worldspawn"));
+		worldspawn.push(String::from("wad"), String::from(r"\valve\halflife.wad"));
+
+		let mut manager: EntityKeyValues = EntityKeyValues::new();
+		manager.push(String::from("targetname"), String::from("mm"));
+		manager.push(String::from("door"), String::from("0"));
+		manager.push(String::from("classname"), String::from("multi_manager"));
+		manager.push(String::from("door"), String::from("3"));
+
+		let blueprint: MapBlueprint = MapBlueprint {
+			entities: vec![
+				BlueprintEntity {
+					brushes: Vec::new(),
+					keyvalues: worldspawn,
+					source_line: 1,
+				},
+				BlueprintEntity {
+					brushes: Vec::new(),
+					keyvalues: manager,
+					source_line: 5,
+				},
+			],
+			planes: PlanePool::new(),
+			textures: HashMap::new(),
+		};
+
+		assert_eq!(
+			blueprint.entity_lump_text(&BspModels::default()),
+			"{\n\
+			\"classname\" \"worldspawn\"\n\
+			\"wad\" \"\\valve\\halflife.wad\"\n\
+			}\n\
+			{\n\
+			\"targetname\" \"mm\"\n\
+			\"door\" \"0\"\n\
+			\"classname\" \"multi_manager\"\n\
+			\"door\" \"3\"\n\
+			}\n"
+		);
+	}
+
+	#[test]
+	fn parse_entity_origin()
+	{
+		let mut keyvalues: EntityKeyValues = EntityKeyValues::new();
+		keyvalues.push(String::from("origin"), String::from("1 2"));
+		keyvalues.push(String::from("origin"), String::from(" 16 -32.5  8 "));
+
+		let mut entity: BlueprintEntity = BlueprintEntity {
+			brushes: Vec::new(),
+			keyvalues: keyvalues,
+			source_line: 1,
+		};
+
+		assert_eq!(entity.origin(), Some(DVec3::new(16.0, -32.5, 8.0)));
+
+		entity
+			.keyvalues
+			.push(String::from("origin"), String::from("1 2 x"));
+		assert_eq!(entity.origin(), None);
+	}
 }
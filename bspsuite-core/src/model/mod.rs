@@ -1,5 +1,42 @@
+mod brush_geometry;
+mod bsp_data;
+mod bsp_file_info;
+mod bsp_tree;
+mod clip_tree;
+mod contents;
+mod csg_hull;
 mod dplane3;
+mod entity_keyvalues;
+mod lightmaps;
 mod map_blueprint;
+mod plane_pool;
+mod portals;
+mod texture_lights;
+mod texture_projection;
+mod visibility;
+mod winding;
 
-pub use dplane3::DPlane3;
-pub use map_blueprint::MapBlueprint;
+pub use brush_geometry::{BrushDefect, BrushGeometry};
+pub use bsp_data::{
+	BspData, BspDataBrush, BspDataBrushSide, BspDataChild, BspDataClipNode, BspDataFace,
+	BspDataLeaf, BspDataModel, BspDataNode, BspDataTexture, BspDataTextureInfo,
+};
+pub use bsp_file_info::{BspFileInfo, BspFormat, BspTextureInfo, LimitUsage, LumpInfo};
+pub use bsp_tree::{BspChild, BspFace, BspLeaf, BspModel, BspModels, BspNode, BspTree};
+pub use clip_tree::{ClipChild, ClipNode, ClipTree};
+pub use contents::Contents;
+pub use csg_hull::{CsgHull, EntityHull, HullFace};
+pub use dplane3::{DPlane3, PlaneSide, PlaneType};
+pub use entity_keyvalues::EntityKeyValues;
+pub use lightmaps::{
+	DEFAULT_LIGHT_STYLE, FaceLightmap, LightingLump, Lightmaps, MAX_FACE_LIGHT_STYLES,
+};
+pub use map_blueprint::{
+	BlueprintBrush, BlueprintBrushFace, BlueprintEntity, BlueprintTexture, MapBlueprint,
+};
+pub use plane_pool::{PlaneIndex, PlanePool};
+pub use portals::{Portal, PortalGraph, VisPortal, VisPortals};
+pub use texture_lights::{TextureLights, light_from_values};
+pub use texture_projection::{TextureAxis, TextureProjection};
+pub use visibility::{Visibility, compress_vis_row};
+pub use winding::{ON_PLANE_EPSILON, Winding};
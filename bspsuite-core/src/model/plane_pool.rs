@@ -0,0 +1,208 @@
+use super::dplane3::DPlane3;
+use std::collections::HashMap;
+use std::ops::Index;
+
+/// Maximum difference between normal components for two planes to be
+/// considered the same.
+pub const PLANE_NORMAL_EPSILON: f64 = 1.0e-5;
+
+/// Maximum difference between distances for two planes to be considered the
+/// same.
+pub const PLANE_DISTANCE_EPSILON: f64 = 0.01;
+
+// Planes are hashed by the magnitude of their distance, rounded down to a
+// multiple of this. A plane and its opposite therefore share a bucket.
+const HASH_BUCKET_SIZE: f64 = 8.0;
+
+/// Index of a plane within a [`PlanePool`].
+pub type PlaneIndex = usize;
+
+/// Shared collection of unique planes, as stored in the planes lump of a
+/// Quake-lineage BSP file. Planes are always added in pairs, so that every
+/// plane sits next to its opposite: the plane at an even index faces along the
+/// positive direction of its major axis, and the plane at the following odd
+/// index is its flipped twin. Indices are stable once handed out.
+#[derive(Clone, Debug, Default)]
+pub struct PlanePool
+{
+	planes: Vec<DPlane3>,
+	buckets: HashMap<i64, Vec<PlaneIndex>>,
+}
+
+impl PlanePool
+{
+	pub fn new() -> Self
+	{
+		return Self::default();
+	}
+
+	/// Returns the index of a plane matching the one provided, adding it and
+	/// its opposite to the pool if no such plane exists. The plane is snapped
+	/// before it is looked up.
+	pub fn find_or_insert(&mut self, plane: &DPlane3) -> PlaneIndex
+	{
+		let plane: DPlane3 = plane.normalized().snapped();
+
+		if let Some(index) = self.find_snapped(&plane)
+		{
+			return index;
+		}
+
+		let flipped: DPlane3 = plane.flipped();
+		let major_axis: usize = plane.normal.abs().max_position();
+		let faces_positive: bool = plane.normal[major_axis] > 0.0;
+
+		let index: PlaneIndex = self.planes.len();
+
+		if faces_positive
+		{
+			self.planes.push(plane);
+			self.planes.push(flipped);
+		}
+		else
+		{
+			self.planes.push(flipped);
+			self.planes.push(plane);
+		}
+
+		self.buckets
+			.entry(bucket_key(plane.distance))
+			.or_default()
+			.extend([index, index + 1]);
+
+		return if faces_positive { index } else { index + 1 };
+	}
+
+	/// Returns the index of the plane facing the opposite way to the plane at
+	/// the given index.
+	#[inline]
+	pub fn opposite(index: PlaneIndex) -> PlaneIndex
+	{
+		return index ^ 1;
+	}
+
+	/// Returns the index of whichever plane in the pair faces along the
+	/// positive direction of its major axis.
+	#[inline]
+	pub fn positive_facing(index: PlaneIndex) -> PlaneIndex
+	{
+		return index & !1;
+	}
+
+	pub fn len(&self) -> usize
+	{
+		return self.planes.len();
+	}
+
+	pub fn is_empty(&self) -> bool
+	{
+		return self.planes.is_empty();
+	}
+
+	pub fn iter(&self) -> impl Iterator<Item = &DPlane3>
+	{
+		return self.planes.iter();
+	}
+
+	fn find_snapped(&self, plane: &DPlane3) -> Option<PlaneIndex>
+	{
+		let key: i64 = bucket_key(plane.distance);
+
+		// A matching plane may have been hashed into a neighbouring bucket if
+		// its distance is close to a bucket boundary.
+		for bucket_key in (key - 1)..=(key + 1)
+		{
+			let Some(bucket) = self.buckets.get(&bucket_key)
+			else
+			{
+				continue;
+			};
+
+			for index in bucket
+			{
+				if planes_match(&self.planes[*index], plane)
+				{
+					return Some(*index);
+				}
+			}
+		}
+
+		return None;
+	}
+}
+
+impl Index<PlaneIndex> for PlanePool
+{
+	type Output = DPlane3;
+
+	fn index(&self, index: PlaneIndex) -> &Self::Output
+	{
+		return &self.planes[index];
+	}
+}
+
+fn bucket_key(distance: f64) -> i64
+{
+	return (distance.abs() / HASH_BUCKET_SIZE).floor() as i64;
+}
+
+fn planes_match(a: &DPlane3, b: &DPlane3) -> bool
+{
+	return a.normal.abs_diff_eq(b.normal, PLANE_NORMAL_EPSILON)
+		&& (a.distance - b.distance).abs() <= PLANE_DISTANCE_EPSILON;
+}
+
+#[cfg(test)]
+mod tests
+{
+	use super::*;
+	use glam::DVec3;
+
+	#[test]
+	fn pair_planes_with_opposites()
+	{
+		let mut pool: PlanePool = PlanePool::new();
+		assert!(pool.is_empty());
+
+		let floor: PlaneIndex = pool.find_or_insert(&DPlane3::new(DVec3::NEG_Z, 0.0));
+		assert_eq!(floor, 1);
+		assert_eq!(pool[0], DPlane3::new(DVec3::Z, 0.0));
+		assert_eq!(pool[1], DPlane3::new(DVec3::NEG_Z, 0.0));
+
+		let wall: PlaneIndex = pool.find_or_insert(&DPlane3::new(DVec3::X, 64.0));
+		assert_eq!(wall, 2);
+		assert_eq!(
+			pool[PlanePool::opposite(wall)],
+			DPlane3::new(DVec3::NEG_X, -64.0)
+		);
+
+		// Looking up either plane of an existing pair does not add anything.
+		assert_eq!(pool.find_or_insert(&DPlane3::new(DVec3::Z, 0.0)), 0);
+		assert_eq!(pool.find_or_insert(&DPlane3::new(DVec3::NEG_X, -64.0)), 3);
+		assert_eq!(pool.len(), 4);
+	}
+
+	#[test]
+	fn deduplicate_within_tolerance()
+	{
+		let mut pool: PlanePool = PlanePool::new();
+
+		// Distances this close to a whole number are snapped onto it.
+		let index: PlaneIndex = pool.find_or_insert(&DPlane3::new(DVec3::Y, 15.995));
+		assert_eq!(pool[index], DPlane3::new(DVec3::Y, 16.0));
+
+		let near: DPlane3 = DPlane3::new(DVec3::new(0.000001, 1.0, 0.0), 16.004);
+		assert_eq!(pool.find_or_insert(&near), index);
+
+		// A plane just below the bucket boundary at 16 is hashed into the
+		// bucket before the existing plane's, but still matches it.
+		let below: DPlane3 = DPlane3::new(DVec3::Y, 15.995);
+		assert_ne!(bucket_key(below.distance), bucket_key(pool[index].distance));
+		assert_eq!(pool.find_snapped(&below), Some(index));
+
+		let diagonal: DPlane3 = DPlane3::new(DVec3::new(1.0, 1.0, 0.0).normalize(), 15.9);
+		assert_eq!(pool.find_snapped(&diagonal.normalized().snapped()), None);
+		assert_eq!(pool.find_or_insert(&diagonal), 2);
+		assert_eq!(pool.len(), 4);
+	}
+}
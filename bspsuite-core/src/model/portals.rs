@@ -0,0 +1,94 @@
+use super::dplane3::DPlane3;
+use super::winding::Winding;
+
+/// An opening between two adjacent leaves of a BSP tree, through which one
+/// leaf may be seen or reached from the other.
+#[derive(Clone, Debug)]
+pub struct Portal
+{
+	pub plane: DPlane3,
+	pub winding: Winding,
+
+	/// Leaves in front of and behind the plane, in that order. Either may be
+	/// the graph's outside leaf.
+	pub leaves: [usize; 2],
+}
+
+impl Portal
+{
+	/// Returns the leaf on the other side of the portal from the given one.
+	pub fn neighbour(&self, leaf: usize) -> usize
+	{
+		return if self.leaves[0] == leaf
+		{
+			self.leaves[1]
+		}
+		else
+		{
+			self.leaves[0]
+		};
+	}
+}
+
+/// The portals between the non-solid leaves of a BSP tree.
+#[derive(Clone, Debug)]
+pub struct PortalGraph
+{
+	pub portals: Vec<Portal>,
+
+	/// Indices of the portals bordering each leaf. This has one more entry
+	/// than the tree has leaves, for the outside leaf.
+	pub leaf_portals: Vec<Vec<usize>>,
+
+	/// Index used for the void beyond the volume partitioned by the tree.
+	/// This is always one past the index of the tree's last leaf.
+	pub outside_leaf: usize,
+}
+
+impl PortalGraph
+{
+	pub fn new(leaf_count: usize) -> Self
+	{
+		return Self {
+			portals: Vec::new(),
+			leaf_portals: vec![Vec::new(); leaf_count + 1],
+			outside_leaf: leaf_count,
+		};
+	}
+
+	pub fn add_portal(&mut self, portal: Portal)
+	{
+		let index: usize = self.portals.len();
+
+		self.leaf_portals[portal.leaves[0]].push(index);
+		self.leaf_portals[portal.leaves[1]].push(index);
+		self.portals.push(portal);
+	}
+}
+
+/// A portal between two visibility clusters.
+#[derive(Clone, Debug)]
+pub struct VisPortal
+{
+	pub plane: DPlane3,
+
+	/// Faces the same way as the plane.
+	pub winding: Winding,
+
+	/// Clusters in front of and behind the plane, in that order.
+	pub clusters: [usize; 2],
+}
+
+/// The portals through which one visibility cluster may see another. Only
+/// clusters made up of leaves that do not seal the map are included.
+#[derive(Clone, Debug, Default)]
+pub struct VisPortals
+{
+	pub cluster_count: usize,
+
+	/// Cluster of each leaf of the tree, numbered consecutively from zero.
+	/// Leaves that seal the map do not have a cluster.
+	pub leaf_clusters: Vec<Option<usize>>,
+
+	pub portals: Vec<VisPortal>,
+}
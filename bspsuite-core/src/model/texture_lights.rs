@@ -0,0 +1,167 @@
+use glam::DVec3;
+use std::collections::HashMap;
+
+/// Converts the numbers given for a light into the light's colour, scaled by
+/// its brightness. A single number is a white light of that brightness, three
+/// numbers are the colour at full brightness, and four numbers are a colour
+/// whose components range from 0 to 255, followed by a brightness. This is the
+/// form used both by the `_light` keyvalue and by lights.rad files. Returns
+/// [`None`] for any other number of values.
+pub fn light_from_values(values: &[f64]) -> Option<DVec3>
+{
+	return match values
+	{
+		[brightness] => Some(DVec3::splat(*brightness)),
+		[red, green, blue] => Some(DVec3::new(*red, *green, *blue)),
+		[red, green, blue, brightness] =>
+		{
+			Some(DVec3::new(*red, *green, *blue) * (brightness / 255.0))
+		}
+		_ => None,
+	};
+}
+
+/// Light emitted by faces with particular materials, in the style of the
+/// lights.rad file used by the original GoldSrc tools, along with the fraction
+/// of the light arriving at them that they reflect. Material names are not
+/// case sensitive.
+#[derive(Clone, Debug, Default)]
+pub struct TextureLights
+{
+	lights: HashMap<String, DVec3>,
+	reflectivity: HashMap<String, DVec3>,
+}
+
+impl TextureLights
+{
+	pub fn new() -> Self
+	{
+		return Self::default();
+	}
+
+	/// Sets the light emitted by each unit of area of faces with the material,
+	/// replacing any light previously set for it.
+	pub fn insert(&mut self, material: &str, light: DVec3)
+	{
+		self.lights.insert(material.to_ascii_lowercase(), light);
+	}
+
+	pub fn get(&self, material: &str) -> Option<DVec3>
+	{
+		return self.lights.get(&material.to_ascii_lowercase()).copied();
+	}
+
+	/// Sets the reflectivity of the material from its average colour, whose
+	/// components range from 0 to 255.
+	pub fn set_average_color(&mut self, material: &str, color: DVec3)
+	{
+		self.reflectivity
+			.insert(material.to_ascii_lowercase(), color / 255.0);
+	}
+
+	/// Fraction of each colour of the light arriving at faces with the
+	/// material that they reflect, if the material's colour is known.
+	pub fn reflectivity(&self, material: &str) -> Option<DVec3>
+	{
+		return self
+			.reflectivity
+			.get(&material.to_ascii_lowercase())
+			.copied();
+	}
+
+	pub fn len(&self) -> usize
+	{
+		return self.lights.len();
+	}
+
+	pub fn is_empty(&self) -> bool
+	{
+		return self.lights.is_empty();
+	}
+
+	/// Adds the lights listed in the text of a lights.rad file. Each line holds
+	/// a material name followed by the numbers described by
+	/// [`light_from_values`]. Blank lines and anything following `//` are
+	/// ignored. On failure, returns the 1-based number of the offending line
+	/// along with a description of the problem.
+	pub fn add_from_rad(&mut self, text: &str) -> Result<(), (usize, String)>
+	{
+		for (index, line) in text.lines().enumerate()
+		{
+			let line: &str = line.split("//").next().unwrap_or_default();
+			let mut tokens = line.split_whitespace();
+
+			let Some(material) = tokens.next()
+			else
+			{
+				continue;
+			};
+
+			let values: Vec<f64> = tokens
+				.map(|token| token.parse::<f64>())
+				.collect::<Result<Vec<f64>, _>>()
+				.map_err(|err| {
+					(
+						index + 1,
+						format!("Invalid light value for {material}. {err}"),
+					)
+				})?;
+
+			let light: DVec3 = light_from_values(&values).ok_or_else(|| {
+				(
+					index + 1,
+					format!(
+						"Expected 1, 3 or 4 light values for {material}, but found {}",
+						values.len()
+					),
+				)
+			})?;
+
+			self.insert(material, light);
+		}
+
+		return Ok(());
+	}
+}
+
+#[cfg(test)]
+mod tests
+{
+	use super::*;
+
+	#[test]
+	fn parse_rad_text()
+	{
+		let mut lights: TextureLights = TextureLights::new();
+		lights.insert("~LIGHT3A", DVec3::ONE);
+
+		lights
+			.add_from_rad(
+				"// Standard lights\n\
+				\n\
+				~light3a 255 255 255 2000\n\
+				+0~FIFTS_LGHT01  160 170 220 4000 // Cool blue\n\
+				white 100\n",
+			)
+			.unwrap();
+
+		assert_eq!(lights.len(), 3);
+		assert_eq!(lights.get("~Light3A"), Some(DVec3::splat(2000.0)));
+		assert_eq!(
+			lights.get("+0~fifts_lght01"),
+			Some(DVec3::new(160.0, 170.0, 220.0) * (4000.0 / 255.0))
+		);
+		assert_eq!(lights.get("white"), Some(DVec3::splat(100.0)));
+		assert_eq!(lights.get("black"), None);
+
+		lights.set_average_color("White", DVec3::new(255.0, 51.0, 0.0));
+		assert_eq!(
+			lights.reflectivity("WHITE"),
+			Some(DVec3::new(1.0, 0.2, 0.0))
+		);
+		assert_eq!(lights.reflectivity("~light3a"), None);
+
+		assert_eq!(lights.add_from_rad("ok 1\nbad 1 2\n").unwrap_err().0, 2);
+		assert_eq!(lights.add_from_rad("bad one\n").unwrap_err().0, 1);
+	}
+}
@@ -0,0 +1,270 @@
+use glam::{DVec2, DVec3};
+
+// Classic Quake texture projection axes. Each entry is a face normal, followed
+// by the U and V axes to use for faces whose normal is closest to it.
+const QUAKE_BASE_AXES: [[DVec3; 3]; 6] = [
+	// Floor
+	[DVec3::Z, DVec3::X, DVec3::NEG_Y],
+	// Ceiling
+	[DVec3::NEG_Z, DVec3::X, DVec3::NEG_Y],
+	// West wall
+	[DVec3::X, DVec3::Y, DVec3::NEG_Z],
+	// East wall
+	[DVec3::NEG_X, DVec3::Y, DVec3::NEG_Z],
+	// South wall
+	[DVec3::Y, DVec3::X, DVec3::NEG_Z],
+	// North wall
+	[DVec3::NEG_Y, DVec3::X, DVec3::NEG_Z],
+];
+
+/// One texture axis of a brush face. The axis is in world space, and the offset
+/// is in texels.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct TextureAxis
+{
+	pub axis: DVec3,
+	pub offset: f64,
+}
+
+/// Describes how a texture is projected onto a brush face, in the same form as
+/// the Valve 220 map format. The axes already incorporate the rotation, which
+/// is kept only so that it can be reported back to editors.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct TextureProjection
+{
+	pub u_axis: TextureAxis,
+	pub v_axis: TextureAxis,
+	pub rotation: f64,
+	pub scale: DVec2,
+}
+
+impl TextureProjection
+{
+	/// Creates a projection from standard Quake alignment values, where the
+	/// texture axes are not specified explicitly. The texture is projected
+	/// along the world axis closest to the face's normal, and is then rotated
+	/// by the given number of degrees.
+	pub fn from_quake_alignment(normal: DVec3, offset: DVec2, rotation: f64, scale: DVec2) -> Self
+	{
+		let mut best_index: usize = 0;
+		let mut best_dot: f64 = 0.0;
+
+		// Ties are resolved in favour of the earliest entry in the table, to
+		// match the original tools.
+		for (index, axes) in QUAKE_BASE_AXES.iter().enumerate()
+		{
+			let dot: f64 = normal.dot(axes[0]);
+
+			if dot > best_dot
+			{
+				best_dot = dot;
+				best_index = index;
+			}
+		}
+
+		let [_, mut u_axis, mut v_axis] = QUAKE_BASE_AXES[best_index];
+
+		// Multiples of 90 degrees are special-cased so that the results are
+		// exact.
+		let (sin, cos): (f64, f64) = match rotation
+		{
+			0.0 => (0.0, 1.0),
+			90.0 => (1.0, 0.0),
+			180.0 => (0.0, -1.0),
+			270.0 => (-1.0, 0.0),
+			_ => rotation.to_radians().sin_cos(),
+		};
+
+		// The rotation is applied within the plane formed by the two world
+		// axes that the texture axes lie along.
+		let s_index: usize = u_axis.abs().max_position();
+		let t_index: usize = v_axis.abs().max_position();
+
+		for axis in [&mut u_axis, &mut v_axis]
+		{
+			let s: f64 = axis[s_index];
+			let t: f64 = axis[t_index];
+
+			axis[s_index] = (cos * s) - (sin * t);
+			axis[t_index] = (sin * s) + (cos * t);
+		}
+
+		return Self {
+			u_axis: TextureAxis {
+				axis: u_axis,
+				offset: offset.x,
+			},
+			v_axis: TextureAxis {
+				axis: v_axis,
+				offset: offset.y,
+			},
+			rotation: rotation,
+			scale: scale,
+		};
+	}
+
+	/// Returns the scale that should actually be applied to the texture. A
+	/// scale of zero is treated as 1, as it is by the original tools.
+	pub fn effective_scale(&self) -> DVec2
+	{
+		return DVec2::new(
+			if self.scale.x == 0.0
+			{
+				1.0
+			}
+			else
+			{
+				self.scale.x
+			},
+			if self.scale.y == 0.0
+			{
+				1.0
+			}
+			else
+			{
+				self.scale.y
+			},
+		);
+	}
+
+	/// Returns the world space axes that give texture coordinates in texels,
+	/// once the offsets are added.
+	pub fn texel_axes(&self) -> (DVec3, DVec3)
+	{
+		let scale: DVec2 = self.effective_scale();
+		return (self.u_axis.axis / scale.x, self.v_axis.axis / scale.y);
+	}
+
+	/// Returns the projection to use on a face with the given normal. Axes
+	/// that lie along the normal cannot be used to find positions on the face,
+	/// so the standard Quake projection is used for such faces instead,
+	/// keeping the offsets and scale.
+	pub fn for_face(&self, normal: DVec3) -> Self
+	{
+		let (u_axis, v_axis): (DVec3, DVec3) = self.texel_axes();
+
+		if u_axis.cross(v_axis).dot(normal).abs() >= 1.0e-3 * u_axis.length() * v_axis.length()
+		{
+			return *self;
+		}
+
+		return Self::from_quake_alignment(
+			normal,
+			DVec2::new(self.u_axis.offset, self.v_axis.offset),
+			0.0,
+			self.scale,
+		);
+	}
+
+	/// Computes the texture coordinates, in texels, of a point in world space.
+	pub fn texture_coordinates(&self, point: DVec3) -> DVec2
+	{
+		let scale: DVec2 = self.effective_scale();
+
+		return DVec2::new(
+			(point.dot(self.u_axis.axis) / scale.x) + self.u_axis.offset,
+			(point.dot(self.v_axis.axis) / scale.y) + self.v_axis.offset,
+		);
+	}
+}
+
+#[cfg(test)]
+mod tests
+{
+	use super::*;
+
+	#[test]
+	fn convert_from_quake_alignment()
+	{
+		let floor: TextureProjection =
+			TextureProjection::from_quake_alignment(DVec3::Z, DVec2::ZERO, 0.0, DVec2::ONE);
+
+		assert_eq!(floor.u_axis.axis, DVec3::X);
+		assert_eq!(floor.v_axis.axis, DVec3::NEG_Y);
+
+		let wall: TextureProjection = TextureProjection::from_quake_alignment(
+			DVec3::NEG_Y,
+			DVec2::new(16.0, 8.0),
+			0.0,
+			DVec2::ONE,
+		);
+
+		assert_eq!(wall.u_axis.axis, DVec3::X);
+		assert_eq!(wall.v_axis.axis, DVec3::NEG_Z);
+		assert_eq!(wall.u_axis.offset, 16.0);
+		assert_eq!(wall.v_axis.offset, 8.0);
+
+		let rotated: TextureProjection =
+			TextureProjection::from_quake_alignment(DVec3::Z, DVec2::ZERO, 90.0, DVec2::ONE);
+
+		assert_eq!(rotated.u_axis.axis, DVec3::Y);
+		assert_eq!(rotated.v_axis.axis, DVec3::X);
+		assert_eq!(rotated.rotation, 90.0);
+
+		// Exactly diagonal between a floor and a wall, so the floor wins.
+		let diagonal: TextureProjection = TextureProjection::from_quake_alignment(
+			DVec3::new(1.0, 0.0, 1.0),
+			DVec2::ZERO,
+			0.0,
+			DVec2::ONE,
+		);
+
+		assert_eq!(diagonal.u_axis.axis, DVec3::X);
+		assert_eq!(diagonal.v_axis.axis, DVec3::NEG_Y);
+
+		let tilted: TextureProjection =
+			TextureProjection::from_quake_alignment(DVec3::Z, DVec2::ZERO, 45.0, DVec2::ONE);
+		let half_root_2: f64 = 0.5_f64.sqrt();
+
+		assert!(
+			tilted
+				.u_axis
+				.axis
+				.abs_diff_eq(DVec3::new(half_root_2, half_root_2, 0.0), 1e-9)
+		);
+		assert!(
+			tilted
+				.v_axis
+				.axis
+				.abs_diff_eq(DVec3::new(half_root_2, -half_root_2, 0.0), 1e-9)
+		);
+	}
+
+	#[test]
+	fn compute_texture_coordinates()
+	{
+		let projection: TextureProjection = TextureProjection::from_quake_alignment(
+			DVec3::Z,
+			DVec2::new(4.0, -2.0),
+			0.0,
+			DVec2::new(2.0, 0.0),
+		);
+
+		// X scale is halved, Y scale of zero is treated as 1, and V runs along -Y.
+		assert_eq!(
+			projection.texture_coordinates(DVec3::new(64.0, 32.0, 100.0)),
+			DVec2::new(36.0, -34.0)
+		);
+	}
+
+	#[test]
+	fn fall_back_for_face_along_axes()
+	{
+		let projection: TextureProjection = TextureProjection::from_quake_alignment(
+			DVec3::Z,
+			DVec2::new(4.0, -2.0),
+			0.0,
+			DVec2::new(2.0, 2.0),
+		);
+
+		// The floor's axes can be used on a sloped face, but not on a wall.
+		let slope: DVec3 = DVec3::new(1.0, 0.0, 1.0).normalize();
+		assert_eq!(projection.for_face(slope), projection);
+
+		let wall: TextureProjection = projection.for_face(DVec3::X);
+		assert_eq!(wall.u_axis.axis, DVec3::Y);
+		assert_eq!(wall.v_axis.axis, DVec3::NEG_Z);
+		assert_eq!(wall.v_axis.offset, -2.0);
+		assert_eq!(wall.texel_axes(), (DVec3::Y * 0.5, DVec3::NEG_Z * 0.5));
+	}
+}
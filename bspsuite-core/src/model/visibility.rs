@@ -0,0 +1,138 @@
+/// Potentially visible set of each visibility cluster. Each row holds one bit
+/// per cluster, least significant bit first, and is padded to a whole number
+/// of bytes.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Visibility
+{
+	pub cluster_count: usize,
+	pub rows: Vec<Vec<u8>>,
+}
+
+impl Visibility
+{
+	/// Creates a set in which no cluster can see any other.
+	pub fn new(cluster_count: usize) -> Self
+	{
+		return Self {
+			cluster_count: cluster_count,
+			rows: vec![vec![0; cluster_count.div_ceil(8)]; cluster_count],
+		};
+	}
+
+	pub fn set_visible(&mut self, from: usize, to: usize)
+	{
+		self.rows[from][to / 8] |= 1 << (to % 8);
+	}
+
+	pub fn is_visible(&self, from: usize, to: usize) -> bool
+	{
+		return self.rows[from][to / 8] & (1 << (to % 8)) != 0;
+	}
+
+	/// Number of clusters that can be seen from the cluster, including
+	/// itself.
+	pub fn visible_count(&self, from: usize) -> usize
+	{
+		return self.rows[from]
+			.iter()
+			.map(|byte| byte.count_ones() as usize)
+			.sum();
+	}
+
+	/// Expands the set into one row per leaf, for formats that store
+	/// visibility between leaves rather than clusters. Leaves are given in the
+	/// order in which they appear in the output, along with their cluster, and
+	/// bit N of each row refers to the Nth leaf given. Leaves without a
+	/// cluster can see nothing, and cannot be seen.
+	pub fn leaf_rows(&self, leaf_clusters: &[Option<usize>]) -> Vec<Vec<u8>>
+	{
+		let row_size: usize = leaf_clusters.len().div_ceil(8);
+
+		return leaf_clusters
+			.iter()
+			.map(|from| {
+				let mut row: Vec<u8> = vec![0; row_size];
+
+				if let Some(from) = from
+				{
+					for (index, to) in leaf_clusters.iter().enumerate()
+					{
+						if to.is_some_and(|to| self.is_visible(*from, to))
+						{
+							row[index / 8] |= 1 << (index % 8);
+						}
+					}
+				}
+
+				row
+			})
+			.collect();
+	}
+}
+
+/// Compresses a visibility row using Quake's run length encoding, in which
+/// each run of zero bytes is written as a zero followed by the length of the
+/// run. Runs are limited to 255 bytes.
+pub fn compress_vis_row(row: &[u8]) -> Vec<u8>
+{
+	let mut compressed: Vec<u8> = Vec::with_capacity(row.len());
+	let mut index: usize = 0;
+
+	while index < row.len()
+	{
+		compressed.push(row[index]);
+
+		if row[index] != 0
+		{
+			index += 1;
+			continue;
+		}
+
+		let run: usize = row[index..]
+			.iter()
+			.take(255)
+			.take_while(|byte| **byte == 0)
+			.count();
+
+		compressed.push(run as u8);
+		index += run;
+	}
+
+	return compressed;
+}
+
+#[cfg(test)]
+mod tests
+{
+	use super::*;
+
+	#[test]
+	fn compress_zero_runs()
+	{
+		let row: Vec<u8> = vec![0xFF, 0, 0, 0, 0x01, 0];
+
+		assert_eq!(compress_vis_row(&row), vec![0xFF, 0, 3, 0x01, 0, 1]);
+
+		// Runs longer than a byte can describe are split.
+		assert_eq!(compress_vis_row(&[0; 300]), vec![0, 255, 0, 45]);
+	}
+
+	#[test]
+	fn expand_clusters_to_leaves()
+	{
+		let mut visibility: Visibility = Visibility::new(2);
+		visibility.set_visible(0, 0);
+		visibility.set_visible(1, 1);
+		visibility.set_visible(1, 0);
+
+		assert_eq!(visibility.visible_count(1), 2);
+		assert!(!visibility.is_visible(0, 1));
+
+		// Leaves 0 and 2 are in cluster 0, and leaf 1 is solid.
+		let rows: Vec<Vec<u8>> = visibility.leaf_rows(&[Some(0), None, Some(0), Some(1)]);
+		assert_eq!(
+			rows,
+			vec![vec![0b0101], vec![0], vec![0b0101], vec![0b1101]]
+		);
+	}
+}
@@ -0,0 +1,348 @@
+use super::dplane3::{DPlane3, PlaneSide};
+use glam::DVec3;
+
+/// Half the side length of the square winding created for a plane by
+/// [`Winding::from_plane`]. This comfortably exceeds the size of any map.
+pub const BASE_WINDING_EXTENT: f64 = 1.0e6;
+
+/// Distance within which a point is considered to lie on a plane when clipping.
+pub const ON_PLANE_EPSILON: f64 = 0.01;
+
+/// A convex polygon in 3D space. Points are wound clockwise when viewed from
+/// the front of the polygon, matching the .map file convention for plane
+/// points.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Winding
+{
+	pub points: Vec<DVec3>,
+}
+
+impl Winding
+{
+	/// Creates a huge square winding lying on the given plane, facing the same
+	/// way as the plane. Clipping this against other planes is used to produce
+	/// the actual polygon for a brush face.
+	pub fn from_plane(plane: &DPlane3) -> Self
+	{
+		// Choose an up vector that is not close to parallel to the normal.
+		let major_axis: usize = plane.normal.abs().max_position();
+		let up_seed: DVec3 = if major_axis == 2 { DVec3::X } else { DVec3::Z };
+
+		let up: DVec3 = (up_seed - (plane.normal * up_seed.dot(plane.normal))).normalize()
+			* BASE_WINDING_EXTENT;
+		let right: DVec3 = up.cross(plane.normal);
+		let origin: DVec3 = plane.normal * plane.distance;
+
+		return Self {
+			points: vec![
+				origin - right + up,
+				origin + right + up,
+				origin + right - up,
+				origin - right - up,
+			],
+		};
+	}
+
+	/// Clips the winding against the plane, keeping the part that lies behind
+	/// it. Points within [`ON_PLANE_EPSILON`] of the plane are treated as lying
+	/// on it. Returns [`None`] if nothing of the winding remains.
+	pub fn clip(&self, plane: &DPlane3) -> Option<Winding>
+	{
+		let distances: Vec<f64> = self
+			.points
+			.iter()
+			.map(|point| plane.distance_to(*point))
+			.collect();
+
+		let sides: Vec<PlaneSide> = self
+			.points
+			.iter()
+			.map(|point| plane.classify_point(*point, ON_PLANE_EPSILON))
+			.collect();
+
+		if !sides.contains(&PlaneSide::Front)
+		{
+			return Some(self.clone());
+		}
+
+		if !sides.contains(&PlaneSide::Back)
+		{
+			return None;
+		}
+
+		let mut points: Vec<DVec3> = Vec::with_capacity(self.points.len() + 1);
+
+		for index in 0..self.points.len()
+		{
+			let next_index: usize = (index + 1) % self.points.len();
+			let point: DVec3 = self.points[index];
+
+			if sides[index] != PlaneSide::Front
+			{
+				points.push(point);
+			}
+
+			// Only generate a split point if the edge crosses the plane.
+			if sides[index] == PlaneSide::On
+				|| sides[next_index] == PlaneSide::On
+				|| sides[index] == sides[next_index]
+			{
+				continue;
+			}
+
+			let fraction: f64 = distances[index] / (distances[index] - distances[next_index]);
+			let mut split: DVec3 = point + ((self.points[next_index] - point) * fraction);
+
+			// Avoid accumulating rounding errors for axial planes.
+			for axis in 0..3
+			{
+				if plane.normal[axis] == 1.0
+				{
+					split[axis] = plane.distance;
+				}
+				else if plane.normal[axis] == -1.0
+				{
+					split[axis] = -plane.distance;
+				}
+			}
+
+			points.push(split);
+		}
+
+		if points.len() < 3
+		{
+			return None;
+		}
+
+		return Some(Winding { points: points });
+	}
+
+	/// Returns a copy of the winding with any point that lies within the
+	/// epsilon of the previous point removed.
+	pub fn without_duplicate_points(&self, epsilon: f64) -> Winding
+	{
+		let mut points: Vec<DVec3> = Vec::with_capacity(self.points.len());
+
+		for point in &self.points
+		{
+			let is_duplicate: bool = points
+				.last()
+				.is_some_and(|previous| previous.distance(*point) <= epsilon);
+
+			if !is_duplicate
+			{
+				points.push(*point);
+			}
+		}
+
+		// The last point may also duplicate the first.
+		while points.len() > 1 && points[0].distance(points[points.len() - 1]) <= epsilon
+		{
+			points.pop();
+		}
+
+		return Winding { points: points };
+	}
+
+	pub fn area(&self) -> f64
+	{
+		let mut area: f64 = 0.0;
+
+		for index in 2..self.points.len()
+		{
+			let edge1: DVec3 = self.points[index - 1] - self.points[0];
+			let edge2: DVec3 = self.points[index] - self.points[0];
+
+			area += edge1.cross(edge2).length() * 0.5;
+		}
+
+		return area;
+	}
+
+	/// Returns the average of all the points in the winding.
+	pub fn center(&self) -> DVec3
+	{
+		if self.points.is_empty()
+		{
+			return DVec3::ZERO;
+		}
+
+		return self.points.iter().sum::<DVec3>() / (self.points.len() as f64);
+	}
+
+	/// Returns the minimum and maximum corners of the winding's bounding box.
+	pub fn bounds(&self) -> (DVec3, DVec3)
+	{
+		return self.points.iter().fold(
+			(DVec3::INFINITY, DVec3::NEG_INFINITY),
+			|(mins, maxs), point| (mins.min(*point), maxs.max(*point)),
+		);
+	}
+
+	/// Splits the winding along the planes at every multiple of the size on
+	/// each world axis, so that no piece spans more than the size along any
+	/// axis.
+	pub fn subdivide(&self, size: f64) -> Vec<Winding>
+	{
+		let mut pieces: Vec<Winding> = vec![self.clone()];
+
+		for axis in 0..3
+		{
+			let mut normal: DVec3 = DVec3::ZERO;
+			normal[axis] = 1.0;
+
+			pieces = pieces
+				.into_iter()
+				.flat_map(|piece| {
+					let (mins, maxs): (DVec3, DVec3) = piece.bounds();
+					let mut split: Vec<Winding> = Vec::new();
+					let mut remaining: Option<Winding> = Some(piece);
+					let mut cut: f64 = ((mins[axis] / size).floor() + 1.0) * size;
+
+					while cut < maxs[axis]
+						&& let Some(rest) = remaining.take()
+					{
+						let plane: DPlane3 = DPlane3::new(normal, cut);
+						split.extend(rest.clip(&plane));
+						remaining = rest.clip(&plane.flipped());
+						cut += size;
+					}
+
+					split.extend(remaining);
+					split
+				})
+				.collect();
+		}
+
+		return pieces;
+	}
+
+	/// Splits the winding so that no piece spans more than the size, measured
+	/// along the axis. The axis does not need to be normalised, so the size
+	/// may be measured in texels by using a texture axis. Each cut is made at
+	/// the size from the lowest point of what remains.
+	pub fn split_by_extent(&self, axis: DVec3, size: f64) -> Vec<Winding>
+	{
+		let length: f64 = axis.length();
+		let mut pieces: Vec<Winding> = Vec::new();
+		let mut remaining: Option<Winding> = Some(self.clone());
+
+		while let Some(rest) = remaining.take()
+		{
+			let (min, max): (f64, f64) = rest
+				.points
+				.iter()
+				.map(|point| point.dot(axis))
+				.fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), value| {
+					(min.min(value), max.max(value))
+				});
+
+			if max - min <= size
+			{
+				pieces.push(rest);
+				break;
+			}
+
+			let plane: DPlane3 = DPlane3::new(axis / length, (min + size) / length);
+			pieces.extend(rest.clip(&plane));
+			remaining = rest.clip(&plane.flipped());
+		}
+
+		return pieces;
+	}
+}
+
+#[cfg(test)]
+mod tests
+{
+	use super::*;
+
+	#[test]
+	fn subdivide_into_grid()
+	{
+		let wall: Winding = Winding {
+			points: vec![
+				DVec3::new(0.0, -40.0, 64.0),
+				DVec3::new(0.0, 100.0, 64.0),
+				DVec3::new(0.0, 100.0, 0.0),
+				DVec3::new(0.0, -40.0, 0.0),
+			],
+		};
+
+		// Y is cut at 0 and 64, and Z does not need to be cut at all.
+		let pieces: Vec<Winding> = wall.subdivide(64.0);
+		assert_eq!(pieces.len(), 3);
+		assert!((pieces.iter().map(Winding::area).sum::<f64>() - wall.area()).abs() < 1.0e-6);
+		assert!(pieces.iter().all(|piece| {
+			let (mins, maxs): (DVec3, DVec3) = piece.bounds();
+			return (maxs - mins).max_element() <= 64.0;
+		}));
+	}
+
+	#[test]
+	fn split_by_texel_extent()
+	{
+		let floor: Winding = Winding {
+			points: vec![
+				DVec3::new(0.0, 0.0, 0.0),
+				DVec3::new(0.0, 300.0, 0.0),
+				DVec3::new(500.0, 300.0, 0.0),
+				DVec3::new(500.0, 0.0, 0.0),
+			],
+		};
+
+		// At a scale of 2, the floor spans 1000 texels along X.
+		let axis: DVec3 = DVec3::new(2.0, 0.0, 0.0);
+		let pieces: Vec<Winding> = floor.split_by_extent(axis, 240.0);
+
+		assert_eq!(pieces.len(), 5);
+		assert!((pieces.iter().map(Winding::area).sum::<f64>() - floor.area()).abs() < 1.0e-6);
+		assert!(pieces.iter().all(|piece| {
+			let (mins, maxs): (DVec3, DVec3) = piece.bounds();
+			return (maxs.x - mins.x) * 2.0 <= 240.0;
+		}));
+
+		assert_eq!(floor.split_by_extent(DVec3::Y, 300.0), vec![floor.clone()]);
+	}
+
+	#[test]
+	fn clip_base_winding()
+	{
+		let floor: Winding = Winding::from_plane(&DPlane3::new(DVec3::Z, 0.0));
+
+		// Points are wound clockwise when viewed from the front.
+		let normal: DVec3 =
+			(floor.points[0] - floor.points[1]).cross(floor.points[2] - floor.points[1]);
+		assert!(normal.normalize().abs_diff_eq(DVec3::Z, 1.0e-9));
+
+		let clipped: Winding = floor
+			.clip(&DPlane3::new(DVec3::X, 32.0))
+			.and_then(|winding| winding.clip(&DPlane3::new(DVec3::NEG_X, 32.0)))
+			.and_then(|winding| winding.clip(&DPlane3::new(DVec3::Y, 16.0)))
+			.and_then(|winding| winding.clip(&DPlane3::new(DVec3::NEG_Y, 16.0)))
+			.unwrap();
+
+		assert_eq!(clipped.points.len(), 4);
+		assert_eq!(clipped.area(), 64.0 * 32.0);
+		assert_eq!(clipped.center(), DVec3::ZERO);
+		assert_eq!(
+			clipped.bounds(),
+			(DVec3::new(-32.0, -16.0, 0.0), DVec3::new(32.0, 16.0, 0.0))
+		);
+
+		// Clipping against a plane that the whole winding lies in front of
+		// leaves nothing.
+		assert!(clipped.clip(&DPlane3::new(DVec3::X, -64.0)).is_none());
+
+		// Clipping against a plane that the whole winding lies behind, or
+		// within the epsilon of, leaves the winding unchanged.
+		assert_eq!(
+			clipped.clip(&DPlane3::new(DVec3::X, 64.0)),
+			Some(clipped.clone())
+		);
+		assert_eq!(
+			clipped.clip(&DPlane3::new(DVec3::Z, 0.005)),
+			Some(clipped.clone())
+		);
+	}
+}
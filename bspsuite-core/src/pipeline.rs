@@ -1,20 +1,50 @@
-use crate::extensions::dummy_api::call_dummy_api;
+use crate::commands::CompileStage;
+use crate::compiler_error::{CompilerError, CompilerErrorCode};
+use crate::extensions::hull_api::call_hull_api;
+use crate::extensions::stage_hook_api::{StageData, call_stage_hook_api};
+use crate::game_config::{GameConfig, HullSize};
+use crate::model::{
+	BspData, BspModels, BspTree, CsgHull, Lightmaps, MapBlueprint, PortalGraph, TextureLights,
+	VisPortals, Visibility, compress_vis_row,
+};
 use crate::toolchain::Toolchain;
+use crate::work_units::{
+	assemble_bsp_data, build_bsp_trees, build_clip_hulls, build_csg_hull, build_portals,
+	build_vis_portals, compute_lighting, compute_visibility, create_map_blueprint, fill_outside,
+	find_textures, load_texture_lights, validate_brushes, write_bsp, write_debug_portal_file,
+	write_portal_file,
+};
+use bspextifc::stage_hook_api::{HookPoint, StageHook};
+use log::{debug, info};
 use std::path::PathBuf;
 
 pub enum ExtensionFeature
 {
-	DummyFeature,
+	/// Run the stage hooks that extensions register at points in the pipeline.
+	StageHooks,
 }
 
 pub struct PipelineBuilder
 {
 	toolchain: Toolchain,
+	game_config: GameConfig,
+	final_stage: Option<CompileStage>,
+	debug_portals: bool,
+	fast_vis: bool,
+	stage_hooks: Vec<(String, StageHook)>,
 }
 
 pub struct Pipeline
 {
 	toolchain: Toolchain,
+	game_config: GameConfig,
+	final_stage: Option<CompileStage>,
+	debug_portals: bool,
+	fast_vis: bool,
+
+	// Hooks to run, along with the name of the extension that registered each
+	// of them.
+	stage_hooks: Vec<(String, StageHook)>,
 }
 
 impl PipelineBuilder
@@ -23,6 +53,11 @@ impl PipelineBuilder
 	{
 		return Self {
 			toolchain: Toolchain::new(toolchain_root),
+			game_config: GameConfig::default(),
+			final_stage: None,
+			debug_portals: false,
+			fast_vis: false,
+			stage_hooks: Vec::new(),
 		};
 	}
 
@@ -30,36 +65,385 @@ impl PipelineBuilder
 	{
 		return Pipeline {
 			toolchain: self.toolchain,
+			game_config: self.game_config,
+			final_stage: self.final_stage,
+			debug_portals: self.debug_portals,
+			fast_vis: self.fast_vis,
+			stage_hooks: self.stage_hooks,
 		};
 	}
 
+	pub fn game_config(mut self, game_config: GameConfig) -> Self
+	{
+		self.game_config = game_config;
+		return self;
+	}
+
+	/// Uses the config of the named game from the toolchain's games
+	/// directory.
+	pub fn game(self, name: &str) -> Result<Self, CompilerError>
+	{
+		let game_config: GameConfig = self.toolchain.load_game_config(name)?;
+		info!("Compiling for game {name}");
+
+		return Ok(self.game_config(game_config));
+	}
+
+	pub fn final_stage(mut self, final_stage: Option<CompileStage>) -> Self
+	{
+		self.final_stage = final_stage;
+		return self;
+	}
+
+	pub fn debug_portals(mut self, debug_portals: bool) -> Self
+	{
+		self.debug_portals = debug_portals;
+		return self;
+	}
+
+	pub fn fast_vis(mut self, fast_vis: bool) -> Self
+	{
+		self.fast_vis = fast_vis;
+		return self;
+	}
+
 	pub fn require_feature(self, feature: ExtensionFeature) -> Self
 	{
 		return match feature
 		{
-			ExtensionFeature::DummyFeature => self.set_up_dummy_feature(),
+			ExtensionFeature::StageHooks => self.set_up_stage_hooks(),
 		};
 	}
 
-	fn set_up_dummy_feature(self) -> Self
+	// Hooks are run in the order in which their extensions were loaded, then in
+	// the order in which each extension lists them.
+	fn set_up_stage_hooks(mut self) -> Self
 	{
-		self.toolchain.extensions().iter().for_each(|extension| {
-			if let Some(callbacks) = &extension.get_api_callbacks().dummy_api_callbacks
-			{
-				call_dummy_api(callbacks.entry_point);
-			}
-		});
+		let stage_hooks: Vec<(String, StageHook)> = self
+			.toolchain
+			.extensions()
+			.iter()
+			.filter_map(|extension| {
+				extension
+					.get_api_callbacks()
+					.stage_hook_api_callbacks
+					.as_ref()
+					.map(|callbacks| (extension.get_name(), callbacks))
+			})
+			.flat_map(|(extension_name, callbacks)| {
+				callbacks
+					.hooks
+					.as_slice()
+					.iter()
+					.map(move |hook| (String::from(extension_name), hook.clone()))
+			})
+			.collect();
+
+		for (extension_name, hook) in &stage_hooks
+		{
+			debug!(
+				"Extension {extension_name} registered stage hook {} at {:?}",
+				hook.name.as_str(),
+				hook.point
+			);
+		}
 
+		self.stage_hooks = stage_hooks;
 		return self;
 	}
 }
 
 impl Pipeline
 {
-	fn new(toolchain: Toolchain) -> Self
+	fn new(toolchain: Toolchain, game_config: GameConfig) -> Self
 	{
 		return Self {
 			toolchain: toolchain,
+			game_config: game_config,
+			final_stage: None,
+			debug_portals: false,
+			fast_vis: false,
+			stage_hooks: Vec::new(),
 		};
 	}
+
+	pub fn run(&self, input_file: &PathBuf) -> Result<(), CompilerError>
+	{
+		let mut blueprint: MapBlueprint =
+			create_map_blueprint(self.toolchain.extensions(), input_file)?;
+
+		info!(
+			"Read {} entities from {}",
+			blueprint.entities.len(),
+			input_file.to_string_lossy()
+		);
+
+		validate_brushes(
+			&mut blueprint,
+			&self.game_config.brush_validation,
+			input_file,
+		)?;
+
+		self.run_stage_hooks(HookPoint::PreCsg, &mut blueprint, StageData::default())?;
+
+		find_textures(
+			self.toolchain.extensions(),
+			&mut blueprint,
+			input_file,
+			&self.game_config.textures,
+		)?;
+
+		let hull: CsgHull = build_csg_hull(&blueprint);
+		info!("CSG produced {} visible faces", hull.face_count());
+
+		if self.is_final_stage(CompileStage::Csg)
+		{
+			return Ok(());
+		}
+
+		let mut models: BspModels = build_bsp_trees(&blueprint, &hull, &self.game_config.bsp);
+		info!(
+			"Built {} BSP models with {} nodes and {} leaves",
+			models.models.len(),
+			models
+				.models
+				.iter()
+				.map(|model| model.tree.nodes.len())
+				.sum::<usize>(),
+			models
+				.models
+				.iter()
+				.map(|model| model.tree.leaves.len())
+				.sum::<usize>()
+		);
+
+		let vis_portals: VisPortals = match models.models.first_mut()
+		{
+			Some(world) => self.build_world_portals(&blueprint, &mut world.tree, input_file)?,
+			None => VisPortals::default(),
+		};
+
+		self.run_stage_hooks(
+			HookPoint::PostBsp,
+			&mut blueprint,
+			StageData {
+				models: Some(&models),
+				vis_portals: Some(&vis_portals),
+				..StageData::default()
+			},
+		)?;
+
+		if self.is_final_stage(CompileStage::Bsp)
+		{
+			return Ok(());
+		}
+
+		let hulls: Vec<HullSize> = self.hull_sizes();
+		build_clip_hulls(&mut blueprint, &mut models, &hulls, &self.game_config.bsp);
+		info!(
+			"Built {} clipping hulls with {} clipnodes",
+			hulls.len(),
+			models
+				.models
+				.iter()
+				.flat_map(|model| model.clip_trees.iter())
+				.map(|tree| tree.nodes.len())
+				.sum::<usize>()
+		);
+
+		if self.is_final_stage(CompileStage::Hulls)
+		{
+			return Ok(());
+		}
+
+		let visibility: Visibility = compute_visibility(&vis_portals, self.fast_vis);
+		let visdata_size: usize = visibility
+			.leaf_rows(&vis_portals.leaf_clusters)
+			.iter()
+			.map(|row| compress_vis_row(row).len())
+			.sum();
+
+		info!(
+			"Computed {} visibility for {} clusters, which see {:.1} clusters on average, with {} bytes of compressed visdata",
+			if self.fast_vis { "fast" } else { "full" },
+			visibility.cluster_count,
+			(0..visibility.cluster_count)
+				.map(|cluster| visibility.visible_count(cluster))
+				.sum::<usize>() as f64
+				/ visibility.cluster_count.max(1) as f64,
+			visdata_size
+		);
+
+		if self.is_final_stage(CompileStage::Vis)
+		{
+			return Ok(());
+		}
+
+		self.run_stage_hooks(
+			HookPoint::PreLighting,
+			&mut blueprint,
+			StageData {
+				models: Some(&models),
+				vis_portals: Some(&vis_portals),
+				visibility: Some(&visibility),
+				lightmaps: None,
+			},
+		)?;
+
+		let texture_lights: TextureLights = load_texture_lights(
+			&blueprint,
+			self.toolchain.root(),
+			input_file,
+			&self.game_config.lighting,
+		)?;
+
+		let lightmaps: Lightmaps = compute_lighting(
+			&mut blueprint,
+			&models,
+			&vis_portals,
+			&visibility,
+			&texture_lights,
+			&self.game_config.lighting,
+			input_file,
+		)?;
+
+		info!(
+			"Computed lightmaps for {} faces, with {} bytes of lighting data",
+			lightmaps.models.iter().flatten().flatten().count(),
+			lightmaps.lighting_lump().data.len()
+		);
+
+		if self.is_final_stage(CompileStage::Rad)
+		{
+			return Ok(());
+		}
+
+		self.run_stage_hooks(
+			HookPoint::PreWrite,
+			&mut blueprint,
+			StageData {
+				models: Some(&models),
+				vis_portals: Some(&vis_portals),
+				visibility: Some(&visibility),
+				lightmaps: Some(&lightmaps),
+			},
+		)?;
+
+		let data: BspData = assemble_bsp_data(
+			&blueprint,
+			&models,
+			&vis_portals,
+			&visibility,
+			&lightmaps,
+			self.game_config.lighting.luxel_size,
+			self.game_config.textures.embed,
+		);
+		let output_file: PathBuf = write_bsp(
+			self.toolchain.extensions(),
+			&self.game_config.bsp_format,
+			&self.game_config.limits,
+			&data,
+			input_file,
+		)?;
+
+		info!(
+			"Wrote {} with {} planes, {} faces, {} nodes, {} leaves and {} clipnodes",
+			output_file.to_string_lossy(),
+			data.planes.len(),
+			data.faces.len(),
+			data.nodes.len(),
+			data.leaves.len(),
+			data.clip_nodes.len()
+		);
+
+		return Ok(());
+	}
+
+	// Checks the world for leaks, then writes the portals that VIS will use.
+	// Portals are built again after filling the outside of the world, so that
+	// none lead into the filled leaves.
+	fn build_world_portals(
+		&self,
+		blueprint: &MapBlueprint,
+		tree: &mut BspTree,
+		input_file: &PathBuf,
+	) -> Result<VisPortals, CompilerError>
+	{
+		let portals: PortalGraph = build_portals(tree, &blueprint.planes);
+		fill_outside(blueprint, tree, &portals, input_file)?;
+
+		let portals: PortalGraph = build_portals(tree, &blueprint.planes);
+		let vis_portals: VisPortals = build_vis_portals(tree, &portals);
+
+		write_portal_file(input_file, &vis_portals)?;
+
+		if self.debug_portals
+		{
+			write_debug_portal_file(input_file, tree.leaves.len(), &portals)?;
+		}
+
+		info!(
+			"Found {} portals between {} visibility clusters",
+			vis_portals.portals.len(),
+			vis_portals.cluster_count
+		);
+
+		return Ok(vis_portals);
+	}
+
+	fn run_stage_hooks(
+		&self,
+		point: HookPoint,
+		blueprint: &mut MapBlueprint,
+		data: StageData,
+	) -> Result<(), CompilerError>
+	{
+		for (extension_name, hook) in self
+			.stage_hooks
+			.iter()
+			.filter(|(_, hook)| hook.point == point)
+		{
+			debug!(
+				"Running stage hook {} of extension {extension_name} at {point:?}",
+				hook.name.as_str()
+			);
+
+			call_stage_hook_api(
+				hook.hook_fn,
+				point,
+				&self.game_config.bsp_format,
+				blueprint,
+				data,
+			)
+			.map_err(|message| {
+				CompilerError::new(
+					CompilerErrorCode::ExtensionError,
+					format!(
+						"Stage hook {} of extension {extension_name} failed. {message}",
+						hook.name.as_str()
+					),
+				)
+			})?;
+		}
+
+		return Ok(());
+	}
+
+	// Hull sizes come from the game config, but may be changed by extensions.
+	fn hull_sizes(&self) -> Vec<HullSize>
+	{
+		return self
+			.toolchain
+			.extensions()
+			.iter()
+			.filter_map(|extension| extension.get_api_callbacks().hull_api_callbacks.as_ref())
+			.fold(self.game_config.hulls.clone(), |hulls, callbacks| {
+				call_hull_api(callbacks.define_hulls_fn, hulls)
+			});
+	}
+
+	fn is_final_stage(&self, stage: CompileStage) -> bool
+	{
+		return self.final_stage == Some(stage);
+	}
 }
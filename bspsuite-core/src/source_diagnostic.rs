@@ -0,0 +1,80 @@
+use std::fmt;
+use std::ops::Range;
+use std::path::PathBuf;
+
+/// Describes a problem at a particular location within a source file, such as
+/// a syntax error in a map file.
+#[derive(Debug, Clone)]
+pub struct SourceDiagnostic
+{
+	pub file: PathBuf,
+
+	/// 1-based line number.
+	pub line: u32,
+
+	/// 1-based column, counted in characters.
+	pub column: u32,
+
+	/// Byte range of the offending text within the file. This may be empty if
+	/// the problem does not refer to any particular text.
+	pub span: Range<usize>,
+
+	pub message: String,
+
+	/// Full text of the line referred to by the diagnostic.
+	pub line_text: String,
+}
+
+impl SourceDiagnostic
+{
+	/// Returns the line number gutter, the line of source text, and a marker
+	/// line that underlines the offending text. These are returned separately
+	/// so that the caller may format them as appropriate.
+	pub fn snippet(&self) -> (String, String, String)
+	{
+		let gutter: String = format!("{} | ", self.line);
+		let blank_gutter: String = format!("{} | ", " ".repeat(self.line.to_string().len()));
+
+		// Tabs are kept as they are in the indentation, so that the marker
+		// lines up with the text regardless of how wide the terminal
+		// renders them.
+		let indentation: String = self
+			.line_text
+			.chars()
+			.take(self.column.saturating_sub(1) as usize)
+			.map(|ch| if ch == '\t' { '\t' } else { ' ' })
+			.collect();
+
+		// The span is in bytes, but is only used to choose the marker's width.
+		// Clamp the marker so that it doesn't extend past the end of the line.
+		let remaining_chars: usize = self
+			.line_text
+			.chars()
+			.count()
+			.saturating_sub(self.column.saturating_sub(1) as usize);
+
+		let marker_width: usize = self.span.len().min(remaining_chars).max(1);
+		let marker: String = format!("{indentation}{}", "^".repeat(marker_width));
+
+		return (
+			gutter,
+			self.line_text.clone(),
+			format!("{blank_gutter}{marker}"),
+		);
+	}
+}
+
+impl fmt::Display for SourceDiagnostic
+{
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result
+	{
+		write!(
+			f,
+			"{}:{}:{}: {}",
+			self.file.to_string_lossy(),
+			self.line,
+			self.column,
+			self.message
+		)
+	}
+}
@@ -1,10 +1,19 @@
+use super::compiler_error::{CompilerError, CompilerErrorCode};
 use super::extensions::ExtensionList;
+use super::game_config::GameConfig;
+use log::{debug, warn};
+use std::collections::BTreeMap;
+use std::fs;
 use std::path::PathBuf;
 
 pub struct Toolchain
 {
 	root: PathBuf,
 	extensions: ExtensionList,
+
+	// Paths to the config file of each game, keyed by the name of the game's
+	// directory.
+	games: BTreeMap<String, PathBuf>,
 }
 
 impl Toolchain
@@ -23,14 +32,177 @@ impl Toolchain
 		return Self {
 			root: root_path.clone(),
 			extensions: ExtensionList::new(&root_path),
+			games: Toolchain::find_games(&root_path.join("games")),
 		};
 	}
 
+	pub fn root(&self) -> &PathBuf
+	{
+		return &self.root;
+	}
+
 	pub fn extensions(&self) -> &ExtensionList
 	{
 		return &self.extensions;
 	}
 
+	/// Reads the config file of the named game, and checks that the
+	/// extensions it needs have been loaded.
+	pub fn load_game_config(&self, name: &str) -> Result<GameConfig, CompilerError>
+	{
+		let Some(path) = self.games.get(name)
+		else
+		{
+			let available: String = if self.games.is_empty()
+			{
+				String::from("No games were found")
+			}
+			else
+			{
+				format!(
+					"Available games are: {}",
+					self.games
+						.keys()
+						.cloned()
+						.collect::<Vec<String>>()
+						.join(", ")
+				)
+			};
+
+			return Err(CompilerError::new(
+				CompilerErrorCode::ConfigError,
+				format!(
+					"Game {name} was not found in {}. {available}",
+					self.root.join("games").to_string_lossy()
+				),
+			));
+		};
+
+		debug!(
+			"Loading config for game {name} from {}",
+			path.to_string_lossy()
+		);
+
+		let config: GameConfig = GameConfig::load(path)?;
+		self.check_game_extensions(name, &config)?;
+
+		return Ok(config);
+	}
+
+	// Extensions are compared by name without regard to case, since library
+	// names are not case-sensitive on every platform.
+	fn check_game_extensions(&self, name: &str, config: &GameConfig) -> Result<(), CompilerError>
+	{
+		let missing: Vec<&str> = config
+			.required_extensions
+			.iter()
+			.filter(|required| {
+				!self
+					.extensions
+					.iter()
+					.any(|extension| extension.get_name().eq_ignore_ascii_case(required))
+			})
+			.map(String::as_str)
+			.collect();
+
+		if !missing.is_empty()
+		{
+			return Err(CompilerError::new(
+				CompilerErrorCode::ConfigError,
+				format!(
+					"Game {name} requires extension(s) that are not loaded: {}. Extensions are looked for in {}",
+					missing.join(", "),
+					self.root.join("extensions").to_string_lossy()
+				),
+			));
+		}
+
+		let writes_format: bool = self.extensions.iter().any(|extension| {
+			extension
+				.get_api_callbacks()
+				.bsp_writer_api_callbacks
+				.as_ref()
+				.is_some_and(|callbacks| {
+					callbacks
+						.format
+						.as_str()
+						.eq_ignore_ascii_case(&config.bsp_format)
+				})
+		});
+
+		if !writes_format
+		{
+			return Err(CompilerError::new(
+				CompilerErrorCode::ConfigError,
+				format!(
+					"Game {name} writes {} files, but no loaded extension supports this format",
+					config.bsp_format
+				),
+			));
+		}
+
+		return Ok(());
+	}
+
+	// Each game has its own directory, containing a config file with the same
+	// name as the directory.
+	fn find_games(games_dir: &PathBuf) -> BTreeMap<String, PathBuf>
+	{
+		let entries: fs::ReadDir = match fs::read_dir(games_dir)
+		{
+			Ok(entries) => entries,
+			Err(err) =>
+			{
+				debug!(
+					"Could not read games from directory {}. {err}",
+					games_dir.to_string_lossy()
+				);
+
+				return BTreeMap::new();
+			}
+		};
+
+		let mut games: BTreeMap<String, PathBuf> = BTreeMap::new();
+
+		for path in entries
+			.filter_map(|entry| entry.ok())
+			.map(|entry| entry.path())
+		{
+			let Some(name) = path.file_name().and_then(|name| name.to_str())
+			else
+			{
+				continue;
+			};
+
+			if !path.is_dir()
+			{
+				continue;
+			}
+
+			let config_path: PathBuf = path.join(format!("{name}.cfg"));
+
+			if !config_path.is_file()
+			{
+				warn!(
+					"Game directory {} does not contain a {name}.cfg file, so will be ignored",
+					path.to_string_lossy()
+				);
+
+				continue;
+			}
+
+			games.insert(String::from(name), config_path);
+		}
+
+		debug!(
+			"Found {} games in {}",
+			games.len(),
+			games_dir.to_string_lossy()
+		);
+
+		return games;
+	}
+
 	fn infer_toolchain_root() -> PathBuf
 	{
 		let exe_path: PathBuf =
@@ -39,3 +211,45 @@ impl Toolchain
 		return exe_path.parent().unwrap().to_path_buf();
 	}
 }
+
+#[cfg(test)]
+mod tests
+{
+	use super::*;
+
+	#[test]
+	fn find_game_directories()
+	{
+		let root: PathBuf =
+			std::env::temp_dir().join(format!("bspsuite-games-{}", std::process::id()));
+		let games_dir: PathBuf = root.join("games");
+
+		fs::create_dir_all(games_dir.join("valve")).unwrap();
+		fs::create_dir_all(games_dir.join("empty")).unwrap();
+		fs::write(
+			games_dir.join("valve").join("valve.cfg"),
+			"bsp_format = \"bsp30\"\n",
+		)
+		.unwrap();
+		fs::write(games_dir.join("loose.cfg"), "").unwrap();
+
+		let games: BTreeMap<String, PathBuf> = Toolchain::find_games(&games_dir);
+		let toolchain: Toolchain = Toolchain {
+			root: root.clone(),
+			extensions: ExtensionList::new(&root),
+			games: games.clone(),
+		};
+
+		assert_eq!(games.keys().collect::<Vec<&String>>(), vec!["valve"]);
+		assert!(Toolchain::find_games(&root.join("missing")).is_empty());
+
+		// No extensions are loaded, so nothing can write the game's format.
+		let err: CompilerError = toolchain.load_game_config("valve").unwrap_err();
+		assert!(err.description.contains("no loaded extension supports"));
+
+		let err: CompilerError = toolchain.load_game_config("cstrike").unwrap_err();
+		assert!(err.description.ends_with("Available games are: valve"));
+
+		fs::remove_dir_all(&root).unwrap();
+	}
+}
@@ -0,0 +1,561 @@
+use crate::model::{
+	BlueprintBrush, BlueprintBrushFace, BlueprintTexture, BrushGeometry, BspChild, BspData,
+	BspDataBrush, BspDataBrushSide, BspDataChild, BspDataClipNode, BspDataFace, BspDataLeaf,
+	BspDataModel, BspDataNode, BspDataTexture, BspDataTextureInfo, BspFace, BspLeaf, BspModel,
+	BspModels, BspNode, BspTree, ClipChild, ClipTree, Contents, FaceLightmap, LightingLump,
+	Lightmaps, MapBlueprint, TextureAxis, TextureProjection, VisPortals, Visibility,
+};
+use glam::DVec3;
+use std::collections::{BTreeSet, HashMap};
+
+// Textures are given this size when their real size is not known.
+const UNKNOWN_TEXTURE_SIZE: [u32; 2] = [64, 64];
+
+/// Flattens the compiled models into the arrays that BSP files are made of.
+/// The nodes of each tree are numbered depth first, with children in front of
+/// their node's plane coming first, so the root of each tree comes before the
+/// rest of its nodes. The faces on each node are kept together, in the order
+/// in which the nodes are numbered. Each model also keeps the brushes that it
+/// was built from, for formats that use them for collision. Textures found by
+/// the map reader are given their real size, and if embedding is enabled,
+/// their pixels are stored with them.
+pub fn assemble_bsp_data(
+	blueprint: &MapBlueprint,
+	models: &BspModels,
+	vis_portals: &VisPortals,
+	visibility: &Visibility,
+	lightmaps: &Lightmaps,
+	luxel_size: f64,
+	embed_textures: bool,
+) -> BspData
+{
+	let lump: LightingLump = lightmaps.lighting_lump();
+	let mut assembler: Assembler = Assembler {
+		blueprint: blueprint,
+		data: BspData {
+			entities: blueprint.entity_lump_text(models),
+			planes: blueprint.planes.iter().copied().collect(),
+			visibility: visibility.clone(),
+			luxel_size: luxel_size,
+			..BspData::default()
+		},
+		textures: HashMap::new(),
+		texture_infos: HashMap::new(),
+		embed_textures: embed_textures,
+	};
+
+	for (index, model) in models.models.iter().enumerate()
+	{
+		let lighting: ModelLighting = ModelLighting {
+			lightmaps: lightmaps
+				.models
+				.get(index)
+				.map(Vec::as_slice)
+				.unwrap_or_default(),
+			offsets: lump
+				.offsets
+				.get(index)
+				.map(Vec::as_slice)
+				.unwrap_or_default(),
+		};
+
+		// Only leaves of the world are in visibility clusters.
+		let leaf_clusters: &[Option<usize>] = if index == 0
+		{
+			&vis_portals.leaf_clusters
+		}
+		else
+		{
+			&[]
+		};
+
+		assembler.add_model(model, &lighting, leaf_clusters);
+	}
+
+	assembler.data.lighting = lump.data;
+	return assembler.data;
+}
+
+// Lightmaps of a model's faces, and where they were placed in the lighting
+// data.
+struct ModelLighting<'a>
+{
+	lightmaps: &'a [Option<FaceLightmap>],
+	offsets: &'a [Option<usize>],
+}
+
+struct Assembler<'a>
+{
+	blueprint: &'a MapBlueprint,
+	data: BspData,
+
+	// Indices of textures by lowercase name, and of texture infos by the bits
+	// of their values.
+	textures: HashMap<String, usize>,
+	texture_infos: HashMap<[u64; 10], usize>,
+	embed_textures: bool,
+}
+
+impl Assembler<'_>
+{
+	fn add_model(
+		&mut self,
+		model: &BspModel,
+		lighting: &ModelLighting,
+		leaf_clusters: &[Option<usize>],
+	)
+	{
+		let tree: &BspTree = &model.tree;
+		let first_face: usize = self.data.faces.len();
+		let first_node: usize = self.data.nodes.len();
+		let first_leaf: usize = self.data.leaves.len();
+
+		// Leaves are given their faces once every face has been placed.
+		let mut face_indices: Vec<Option<usize>> = vec![None; tree.faces.len()];
+		let mut leaf_sources: Vec<usize> = Vec::new();
+		let head_node: BspDataChild = self.add_child(
+			tree,
+			tree.root,
+			lighting,
+			&mut face_indices,
+			&mut leaf_sources,
+		);
+
+		for (offset, source) in leaf_sources.into_iter().enumerate()
+		{
+			let leaf: &mut BspDataLeaf = &mut self.data.leaves[first_leaf + offset];
+			leaf.faces = tree.leaves[source]
+				.faces
+				.iter()
+				.filter_map(|face| face_indices[*face])
+				.collect();
+			leaf.cluster = leaf_clusters.get(source).copied().flatten();
+		}
+
+		let first_brush: usize = self.data.brushes.len();
+		self.add_brushes(model, &face_indices);
+		self.add_leaf_brushes(first_leaf, first_brush);
+
+		let clip_head_nodes: Vec<BspDataChild> = model
+			.clip_trees
+			.iter()
+			.map(|clip_tree| self.add_clip_child(clip_tree, clip_tree.root))
+			.collect();
+
+		let faces: &[BspDataFace] = &self.data.faces[first_face..];
+		let (mins, maxs): (DVec3, DVec3) = if faces.is_empty()
+		{
+			(DVec3::ZERO, DVec3::ZERO)
+		}
+		else
+		{
+			faces.iter().flat_map(|face| &face.points).fold(
+				(DVec3::INFINITY, DVec3::NEG_INFINITY),
+				|(mins, maxs), point| (mins.min(*point), maxs.max(*point)),
+			)
+		};
+
+		self.data.models.push(BspDataModel {
+			mins: mins,
+			maxs: maxs,
+			origin: DVec3::ZERO,
+			head_node: head_node,
+			clip_head_nodes: clip_head_nodes,
+			faces: first_face..self.data.faces.len(),
+			nodes: first_node..self.data.nodes.len(),
+			leaves: first_leaf..self.data.leaves.len(),
+			brushes: first_brush..self.data.brushes.len(),
+		});
+	}
+
+	// Adds the brushes of the model's entity, along with those of any detail
+	// entities that were merged into it. Brushes that do not enclose a volume,
+	// or that have empty contents, are left out, as they were when building
+	// the tree.
+	fn add_brushes(&mut self, model: &BspModel, face_indices: &[Option<usize>])
+	{
+		let mut entities: BTreeSet<usize> = BTreeSet::from([model.entity]);
+		entities.extend(model.tree.faces.iter().map(|face| face.entity));
+
+		// A visible face produced by each brush side, if there is one.
+		let side_faces: HashMap<(usize, usize, usize), usize> = model
+			.tree
+			.faces
+			.iter()
+			.zip(face_indices)
+			.filter_map(|(face, index)| Some(((face.entity, face.brush, face.face), (*index)?)))
+			.collect();
+
+		for entity in entities
+		{
+			for (brush_index, brush) in self.blueprint.entities[entity].brushes.iter().enumerate()
+			{
+				let Some(brush) = self.brush(brush, |side| {
+					side_faces.get(&(entity, brush_index, side)).copied()
+				})
+				else
+				{
+					continue;
+				};
+
+				self.data.brushes.push(brush);
+			}
+		}
+	}
+
+	fn brush(
+		&self,
+		brush: &BlueprintBrush,
+		side_face: impl Fn(usize) -> Option<usize>,
+	) -> Option<BspDataBrush>
+	{
+		let contents: Contents = brush
+			.faces
+			.iter()
+			.map(|face| Contents::from_material(&face.material))
+			.max_by_key(Contents::priority)?;
+
+		let geometry: BrushGeometry = brush.geometry(&self.blueprint.planes);
+
+		// Clip brushes are not part of the visible hull, and only block
+		// movement through the clipping hulls.
+		if matches!(contents, Contents::Empty | Contents::Clip) || !geometry.is_valid()
+		{
+			return None;
+		}
+
+		let (mins, maxs): (DVec3, DVec3) = geometry.bounds()?;
+
+		return Some(BspDataBrush {
+			contents: contents,
+			sides: brush
+				.faces
+				.iter()
+				.enumerate()
+				.map(|(index, face)| BspDataBrushSide {
+					plane: face.plane,
+					face: side_face(index),
+				})
+				.collect(),
+			mins: mins,
+			maxs: maxs,
+		});
+	}
+
+	// Each leaf lists the model's brushes whose bounds overlap its own.
+	fn add_leaf_brushes(&mut self, first_leaf: usize, first_brush: usize)
+	{
+		let brushes: &[BspDataBrush] = &self.data.brushes[first_brush..];
+
+		for leaf in &mut self.data.leaves[first_leaf..]
+		{
+			leaf.brushes = brushes
+				.iter()
+				.enumerate()
+				.filter(|(_, brush)| {
+					brush.mins.cmplt(leaf.maxs).all() && brush.maxs.cmpgt(leaf.mins).all()
+				})
+				.map(|(index, _)| first_brush + index)
+				.collect();
+		}
+	}
+
+	fn add_child(
+		&mut self,
+		tree: &BspTree,
+		child: BspChild,
+		lighting: &ModelLighting,
+		face_indices: &mut [Option<usize>],
+		leaf_sources: &mut Vec<usize>,
+	) -> BspDataChild
+	{
+		let node_index: usize = match child
+		{
+			BspChild::Leaf(index) =>
+			{
+				let leaf: &BspLeaf = &tree.leaves[index];
+				leaf_sources.push(index);
+				self.data.leaves.push(BspDataLeaf {
+					contents: leaf.contents,
+					cluster: None,
+					mins: leaf.mins,
+					maxs: leaf.maxs,
+					faces: Vec::new(),
+					brushes: Vec::new(),
+				});
+
+				return BspDataChild::Leaf(self.data.leaves.len() - 1);
+			}
+			BspChild::Node(index) => index,
+		};
+
+		let node: &BspNode = &tree.nodes[node_index];
+		let first_face: usize = self.data.faces.len();
+
+		for face in &node.faces
+		{
+			face_indices[*face] = Some(self.data.faces.len());
+			let data_face: BspDataFace = self.face(&tree.faces[*face], *face, lighting);
+			self.data.faces.push(data_face);
+		}
+
+		// The node's children are filled in once they have been added.
+		let index: usize = self.data.nodes.len();
+		self.data.nodes.push(BspDataNode {
+			plane: node.plane,
+			children: [BspDataChild::Leaf(0); 2],
+			mins: node.mins,
+			maxs: node.maxs,
+			faces: first_face..self.data.faces.len(),
+		});
+
+		for side in 0..2
+		{
+			let child: BspDataChild = self.add_child(
+				tree,
+				node.children[side],
+				lighting,
+				face_indices,
+				leaf_sources,
+			);
+			self.data.nodes[index].children[side] = child;
+		}
+
+		return BspDataChild::Node(index);
+	}
+
+	fn add_clip_child(&mut self, tree: &ClipTree, child: ClipChild) -> BspDataChild
+	{
+		let node_index: usize = match child
+		{
+			ClipChild::Contents(contents) => return BspDataChild::Contents(contents),
+			ClipChild::Node(index) => index,
+		};
+
+		let index: usize = self.data.clip_nodes.len();
+		self.data.clip_nodes.push(BspDataClipNode {
+			plane: tree.nodes[node_index].plane,
+			children: [BspDataChild::Leaf(0); 2],
+		});
+
+		for side in 0..2
+		{
+			let child: BspDataChild =
+				self.add_clip_child(tree, tree.nodes[node_index].children[side]);
+			self.data.clip_nodes[index].children[side] = child;
+		}
+
+		return BspDataChild::Node(index);
+	}
+
+	fn face(&mut self, face: &BspFace, index: usize, lighting: &ModelLighting) -> BspDataFace
+	{
+		let lightmap: Option<&FaceLightmap> =
+			lighting.lightmaps.get(index).and_then(Option::as_ref);
+		let brush_face: &BlueprintBrushFace =
+			&self.blueprint.entities[face.entity].brushes[face.brush].faces[face.face];
+		let projection: TextureProjection = brush_face
+			.projection
+			.for_face(self.blueprint.planes[face.plane].normal);
+
+		let texture: usize = self.texture(&brush_face.material);
+		let texture_info: usize = self.texture_info(&projection, texture, lightmap.is_none());
+
+		return BspDataFace {
+			plane: face.plane,
+			points: face.winding.points.clone(),
+			texture_info: texture_info,
+			styles: lightmap
+				.map(|lightmap| lightmap.styles.clone())
+				.unwrap_or_default(),
+			lighting_offset: lighting.offsets.get(index).copied().flatten(),
+			lightmap_mins: lightmap.map(|lightmap| lightmap.mins).unwrap_or_default(),
+			lightmap_size: lightmap.map(|lightmap| lightmap.size).unwrap_or_default(),
+		};
+	}
+
+	fn texture(&mut self, material: &str) -> usize
+	{
+		if let Some(index) = self.textures.get(&material.to_ascii_lowercase())
+		{
+			return *index;
+		}
+
+		let found: Option<&BlueprintTexture> =
+			self.blueprint.textures.get(&material.to_ascii_lowercase());
+		let embedded: Option<&BlueprintTexture> = found.filter(|texture| {
+			self.embed_textures && !texture.pixels.is_empty() && !texture.palette.is_empty()
+		});
+
+		let index: usize = self.data.textures.len();
+		self.textures.insert(material.to_ascii_lowercase(), index);
+		self.data.textures.push(BspDataTexture {
+			name: material.to_owned(),
+			size: found.map_or(UNKNOWN_TEXTURE_SIZE, |texture| texture.size),
+			pixels: embedded.map_or_else(Vec::new, |texture| texture.pixels.clone()),
+			palette: embedded.map_or_else(Vec::new, |texture| texture.palette.clone()),
+		});
+
+		return index;
+	}
+
+	fn texture_info(
+		&mut self,
+		projection: &TextureProjection,
+		texture: usize,
+		special: bool,
+	) -> usize
+	{
+		let (u_axis, v_axis): (DVec3, DVec3) = projection.texel_axes();
+		let texture_info: BspDataTextureInfo = BspDataTextureInfo {
+			u_axis: TextureAxis {
+				axis: u_axis,
+				offset: projection.u_axis.offset,
+			},
+			v_axis: TextureAxis {
+				axis: v_axis,
+				offset: projection.v_axis.offset,
+			},
+			texture: texture,
+			special: special,
+		};
+
+		let key: [u64; 10] = [
+			u_axis.x.to_bits(),
+			u_axis.y.to_bits(),
+			u_axis.z.to_bits(),
+			projection.u_axis.offset.to_bits(),
+			v_axis.x.to_bits(),
+			v_axis.y.to_bits(),
+			v_axis.z.to_bits(),
+			projection.v_axis.offset.to_bits(),
+			texture as u64,
+			special as u64,
+		];
+
+		return *self.texture_infos.entry(key).or_insert_with(|| {
+			self.data.texture_infos.push(texture_info);
+			self.data.texture_infos.len() - 1
+		});
+	}
+}
+
+#[cfg(test)]
+mod tests
+{
+	use super::*;
+	use crate::game_config::BspConfig;
+	use crate::model::{BlueprintEntity, CsgHull, DPlane3};
+	use crate::work_units::test_fixtures::{add_entity, box_planes, make_blueprint, room_brushes};
+	use crate::work_units::{build_bsp_trees, build_csg_hull};
+
+	#[test]
+	fn flatten_world_and_entity_models()
+	{
+		let mut brushes: Vec<(Vec<DPlane3>, &str)> =
+			room_brushes(DVec3::splat(-64.0), DVec3::splat(64.0), 16.0)
+				.into_iter()
+				.map(|planes| (planes, "WALL"))
+				.collect();
+		brushes[0].1 = "OTHER";
+
+		let mut blueprint: MapBlueprint = make_blueprint(&brushes);
+		let door: &mut BlueprintEntity = add_entity(
+			&mut blueprint,
+			"func_door",
+			&[(box_planes(DVec3::splat(-8.0), DVec3::splat(8.0)), "DOOR")],
+		);
+		door.keyvalues
+			.push(String::from("model"), String::from("old"));
+		blueprint.textures.insert(
+			String::from("wall"),
+			BlueprintTexture {
+				size: [32, 16],
+				average_color: None,
+				pixels: vec![1; 32 * 16],
+				palette: vec![2; 768],
+			},
+		);
+
+		let hull: CsgHull = build_csg_hull(&blueprint);
+		let models: BspModels = build_bsp_trees(&blueprint, &hull, &BspConfig::default());
+		let data: BspData = assemble_bsp_data(
+			&blueprint,
+			&models,
+			&VisPortals::default(),
+			&Visibility::default(),
+			&Lightmaps::default(),
+			16.0,
+			true,
+		);
+
+		assert_eq!(data.models.len(), 2);
+		assert!(
+			data.entities
+				.ends_with("\"classname\" \"func_door\"\n\"model\" \"*1\"\n}\n")
+		);
+		assert_eq!(data.planes.len(), blueprint.planes.len());
+
+		// Each model's nodes, leaves and faces follow on from the previous
+		// model's, and each tree's root is its first node.
+		assert_eq!(data.models[0].head_node, BspDataChild::Node(0));
+		assert_eq!(data.models[0].nodes.end, data.models[1].nodes.start);
+		assert_eq!(data.models[0].leaves.end, data.models[1].leaves.start);
+		assert_eq!(data.models[0].faces.end, data.models[1].faces.start);
+		assert_eq!(data.models[1].faces.len(), 6);
+		assert_eq!(data.models[1].mins, DVec3::splat(-8.0));
+		assert_eq!(data.models[1].maxs, DVec3::splat(8.0));
+		assert_eq!(
+			data.models[1].head_node,
+			BspDataChild::Node(data.models[1].nodes.start)
+		);
+
+		// Every face is on exactly one node.
+		let mut node_faces: Vec<usize> = data
+			.nodes
+			.iter()
+			.flat_map(|node| node.faces.clone())
+			.collect();
+		node_faces.sort();
+		assert_eq!(node_faces, (0..data.faces.len()).collect::<Vec<usize>>());
+
+		// Without lightmaps, every face is special, and faces with the same
+		// material and alignment share a texture info.
+		assert_eq!(data.textures.len(), 3);
+
+		// Textures that were found keep their size, and are embedded.
+		for texture in &data.textures
+		{
+			let found: bool = texture.name == "WALL";
+			assert_eq!(
+				texture.size,
+				if found
+				{
+					[32, 16]
+				}
+				else
+				{
+					UNKNOWN_TEXTURE_SIZE
+				}
+			);
+			assert_eq!(texture.pixels.len(), if found { 32 * 16 } else { 0 });
+			assert_eq!(texture.palette.len(), if found { 768 } else { 0 });
+		}
+
+		assert!(data.texture_infos.iter().all(|info| info.special));
+		assert!(data.texture_infos.len() < data.faces.len());
+		assert!(data.faces.iter().all(|face| face.lighting_offset.is_none()));
+
+		// Each model keeps its own brushes, and every side of the door is
+		// visible. The door's solid leaf lies within its brush.
+		assert_eq!(data.models[0].brushes, 0..6);
+		assert_eq!(data.models[1].brushes, 6..7);
+		assert!(data.brushes[6].sides.iter().all(|side| side.face.is_some()));
+		assert!(
+			data.leaves[data.models[1].leaves.clone()]
+				.iter()
+				.filter(|leaf| leaf.contents == Contents::Solid)
+				.all(|leaf| leaf.brushes == vec![6])
+		);
+	}
+}
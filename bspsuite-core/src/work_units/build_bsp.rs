@@ -0,0 +1,772 @@
+use crate::game_config::BspConfig;
+use crate::model::{
+	BlueprintEntity, BrushGeometry, BspChild, BspFace, BspLeaf, BspModel, BspModels, BspNode,
+	BspTree, Contents, CsgHull, DPlane3, MapBlueprint, ON_PLANE_EPSILON, PlaneIndex, PlanePool,
+	PlaneSide, PlaneType, TextureProjection, Winding,
+};
+use glam::DVec3;
+use log::debug;
+
+// Space left between the faces of the map and the edge of the volume that the
+// tree partitions.
+const VOLUME_MARGIN: f64 = 32.0;
+
+// Kinds of face, in the order in which they are used to split the tree.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+enum FaceKind
+{
+	Hint,
+	Structural,
+	Detail,
+}
+
+struct BuildFace
+{
+	plane: PlaneIndex,
+	winding: Winding,
+	kind: FaceKind,
+	contents: Contents,
+	entity: usize,
+	brush: usize,
+	face: usize,
+}
+
+// Brush volume used to decide the contents of leaves.
+struct ContentsBrush
+{
+	planes: Vec<DPlane3>,
+	contents: Contents,
+	mins: DVec3,
+	maxs: DVec3,
+}
+
+impl ContentsBrush
+{
+	fn contains(&self, point: DVec3) -> bool
+	{
+		return point.cmpge(self.mins).all()
+			&& point.cmple(self.maxs).all()
+			&& self
+				.planes
+				.iter()
+				.all(|plane| plane.distance_to(point) < 0.0);
+	}
+}
+
+/// Builds a BSP tree for the world, and for every other entity that has
+/// visible faces. Brushes of detail entities are merged into the world.
+pub fn build_bsp_trees(blueprint: &MapBlueprint, hull: &CsgHull, config: &BspConfig) -> BspModels
+{
+	let is_detail = |entity: &BlueprintEntity| -> bool {
+		return entity
+			.keyvalues
+			.first("classname")
+			.is_some_and(|classname| {
+				config
+					.detail_classnames
+					.iter()
+					.any(|name| name == classname)
+			});
+	};
+
+	let world_index: usize = blueprint
+		.entities
+		.iter()
+		.position(|entity| entity.keyvalues.first("classname") == Some("worldspawn"))
+		.unwrap_or(0);
+
+	let detail_indices: Vec<usize> = (0..blueprint.entities.len())
+		.filter(|index| *index != world_index && is_detail(&blueprint.entities[*index]))
+		.collect();
+
+	let mut models: BspModels = BspModels::default();
+
+	if blueprint.entities.is_empty()
+	{
+		return models;
+	}
+
+	let mut world_sources: Vec<(usize, bool)> = vec![(world_index, false)];
+	world_sources.extend(detail_indices.iter().map(|index| (*index, true)));
+
+	models.models.push(BspModel {
+		entity: world_index,
+		tree: build_tree(blueprint, hull, &world_sources, config),
+		clip_trees: Vec::new(),
+	});
+
+	for index in 0..blueprint.entities.len()
+	{
+		if index == world_index
+			|| detail_indices.contains(&index)
+			|| hull.entities[index].faces.is_empty()
+		{
+			continue;
+		}
+
+		models.models.push(BspModel {
+			entity: index,
+			tree: build_tree(blueprint, hull, &[(index, false)], config),
+			clip_trees: Vec::new(),
+		});
+	}
+
+	for model in &models.models
+	{
+		debug!(
+			"Built BSP tree for entity {}: {} nodes, {} leaves, {} faces",
+			model.entity,
+			model.tree.nodes.len(),
+			model.tree.leaves.len(),
+			model.tree.faces.len()
+		);
+	}
+
+	return models;
+}
+
+// Builds a single tree from the given entities. Each entity is paired with
+// whether its brushes are detail.
+fn build_tree(
+	blueprint: &MapBlueprint,
+	hull: &CsgHull,
+	sources: &[(usize, bool)],
+	config: &BspConfig,
+) -> BspTree
+{
+	let mut faces: Vec<BuildFace> = Vec::new();
+	let mut brushes: Vec<ContentsBrush> = Vec::new();
+
+	for (entity_index, is_detail) in sources.iter().copied()
+	{
+		let entity: &BlueprintEntity = &blueprint.entities[entity_index];
+
+		for hull_face in &hull.entities[entity_index].faces
+		{
+			let material: &str = &entity.brushes[hull_face.brush].faces[hull_face.face].material;
+
+			let kind: FaceKind = if hull_face.contents == Contents::Empty
+			{
+				// Faces of brushes without contents are not visible. They are
+				// only kept if they are hints.
+				if !config
+					.hint_materials
+					.iter()
+					.any(|name| name.eq_ignore_ascii_case(material))
+				{
+					continue;
+				}
+
+				FaceKind::Hint
+			}
+			else if is_detail
+			{
+				FaceKind::Detail
+			}
+			else
+			{
+				FaceKind::Structural
+			};
+
+			// Sky and liquid faces have no lightmap, so do not need to be
+			// subdivided.
+			let windings: Vec<Winding> = if kind == FaceKind::Hint
+				|| hull_face.contents != Contents::Solid
+			{
+				vec![hull_face.winding.clone()]
+			}
+			else
+			{
+				subdivide_face(
+					&hull_face.winding,
+					&entity.brushes[hull_face.brush].faces[hull_face.face].projection,
+					blueprint.planes[hull_face.plane].normal,
+					config.subdivide_size,
+				)
+			};
+
+			faces.extend(windings.into_iter().map(|winding| BuildFace {
+				plane: hull_face.plane,
+				winding: winding,
+				kind: kind,
+				contents: hull_face.contents,
+				entity: entity_index,
+				brush: hull_face.brush,
+				face: hull_face.face,
+			}));
+		}
+
+		brushes.extend(entity.brushes.iter().filter_map(|brush| {
+			let contents: Contents = brush
+				.faces
+				.iter()
+				.map(|face| Contents::from_material(&face.material))
+				.max_by_key(Contents::priority)?;
+
+			let geometry: BrushGeometry = brush.geometry(&blueprint.planes);
+
+			if matches!(contents, Contents::Empty | Contents::Clip) || !geometry.is_valid()
+			{
+				return None;
+			}
+
+			let (mins, maxs) = geometry.bounds()?;
+
+			return Some(ContentsBrush {
+				planes: brush
+					.faces
+					.iter()
+					.map(|face| blueprint.planes[face.plane])
+					.collect(),
+				contents: contents,
+				mins: mins,
+				maxs: maxs,
+			});
+		}));
+	}
+
+	let (mins, maxs) = faces
+		.iter()
+		.map(|face| face.winding.bounds())
+		.reduce(|(mins1, maxs1), (mins2, maxs2)| (mins1.min(mins2), maxs1.max(maxs2)))
+		.unwrap_or((DVec3::ZERO, DVec3::ZERO));
+
+	let mins: DVec3 = mins - DVec3::splat(VOLUME_MARGIN);
+	let maxs: DVec3 = maxs + DVec3::splat(VOLUME_MARGIN);
+
+	let mut builder: TreeBuilder = TreeBuilder {
+		planes: &blueprint.planes,
+		config: config,
+		brushes: brushes,
+		volume: Vec::new(),
+		tree: BspTree {
+			root: BspChild::Leaf(0),
+			nodes: Vec::new(),
+			leaves: Vec::new(),
+			faces: Vec::new(),
+			cluster_count: 0,
+			mins: mins,
+			maxs: maxs,
+		},
+	};
+
+	builder.volume = builder.tree.bounding_planes();
+	builder.tree.root = builder.build_child(faces, None);
+	builder.mark_leaf_faces();
+
+	return builder.tree;
+}
+
+// Splits a face along its texture axes, so that each piece's lightmap stays
+// within the size that the engine supports.
+fn subdivide_face(
+	winding: &Winding,
+	projection: &TextureProjection,
+	normal: DVec3,
+	size: f64,
+) -> Vec<Winding>
+{
+	let (u_axis, v_axis): (DVec3, DVec3) = projection.for_face(normal).texel_axes();
+
+	return winding
+		.split_by_extent(u_axis, size)
+		.iter()
+		.flat_map(|piece| piece.split_by_extent(v_axis, size))
+		.collect();
+}
+
+struct TreeBuilder<'l>
+{
+	planes: &'l PlanePool,
+	config: &'l BspConfig,
+	brushes: Vec<ContentsBrush>,
+
+	// Planes bounding the region of space covered by the node currently being
+	// built. The region lies behind all of them.
+	volume: Vec<DPlane3>,
+
+	tree: BspTree,
+}
+
+impl<'l> TreeBuilder<'l>
+{
+	// Builds the subtree for the given faces. If the cluster is set, all
+	// leaves of the subtree belong to it.
+	fn build_child(&mut self, faces: Vec<BuildFace>, cluster: Option<usize>) -> BspChild
+	{
+		let Some((plane_index, kind)) = self.choose_splitter(&faces)
+		else
+		{
+			return BspChild::Leaf(self.make_leaf(cluster));
+		};
+
+		// Everything below the first detail split belongs to a single cluster.
+		let cluster: Option<usize> = match (kind, cluster)
+		{
+			(FaceKind::Detail, None) => Some(self.allocate_cluster()),
+			_ => cluster,
+		};
+
+		let plane: DPlane3 = self.planes[plane_index];
+		let node_index: usize = self.tree.nodes.len();
+
+		self.tree.nodes.push(BspNode {
+			plane: plane_index,
+			children: [BspChild::Leaf(0); 2],
+			faces: Vec::new(),
+			mins: DVec3::ZERO,
+			maxs: DVec3::ZERO,
+		});
+
+		let mut front: Vec<BuildFace> = Vec::new();
+		let mut back: Vec<BuildFace> = Vec::new();
+
+		for face in faces
+		{
+			let side: PlaneSide = if PlanePool::positive_facing(face.plane) == plane_index
+			{
+				PlaneSide::On
+			}
+			else
+			{
+				plane.classify_winding(&face.winding, ON_PLANE_EPSILON)
+			};
+
+			match side
+			{
+				PlaneSide::Front => front.push(face),
+				PlaneSide::Back => back.push(face),
+				PlaneSide::On =>
+				{
+					// Hints are not visible, so are discarded once used.
+					if face.kind != FaceKind::Hint
+					{
+						let face_index: usize = self.tree.faces.len();
+						self.tree.nodes[node_index].faces.push(face_index);
+						self.tree.faces.push(BspFace {
+							plane: face.plane,
+							winding: face.winding,
+							contents: face.contents,
+							entity: face.entity,
+							brush: face.brush,
+							face: face.face,
+						});
+					}
+				}
+				PlaneSide::Spanning =>
+				{
+					if let Some(winding) = face.winding.clip(&plane.flipped())
+					{
+						front.push(BuildFace {
+							winding: winding,
+							..face.clone_without_winding()
+						});
+					}
+
+					if let Some(winding) = face.winding.clip(&plane)
+					{
+						back.push(BuildFace {
+							winding: winding,
+							..face.clone_without_winding()
+						});
+					}
+				}
+			}
+		}
+
+		self.volume.push(plane.flipped());
+		let front_child: BspChild = self.build_child(front, cluster);
+		self.volume.pop();
+
+		self.volume.push(plane);
+		let back_child: BspChild = self.build_child(back, cluster);
+		self.volume.pop();
+
+		let (front_mins, front_maxs) = self.child_bounds(front_child);
+		let (back_mins, back_maxs) = self.child_bounds(back_child);
+
+		let node: &mut BspNode = &mut self.tree.nodes[node_index];
+		node.children = [front_child, back_child];
+		node.mins = front_mins.min(back_mins);
+		node.maxs = front_maxs.max(back_maxs);
+
+		return BspChild::Node(node_index);
+	}
+
+	// Chooses the plane that scores best according to the configured
+	// weights, from the faces of the kind that should be used first. The
+	// returned plane is always the positive facing one of its pair.
+	fn choose_splitter(&self, faces: &[BuildFace]) -> Option<(PlaneIndex, FaceKind)>
+	{
+		let kind: FaceKind = faces.iter().map(|face| face.kind).min()?;
+
+		let mut candidates: Vec<PlaneIndex> = faces
+			.iter()
+			.filter(|face| face.kind == kind)
+			.map(|face| PlanePool::positive_facing(face.plane))
+			.collect();
+
+		candidates.sort_unstable();
+		candidates.dedup();
+
+		let mut best: Option<(PlaneIndex, f64)> = None;
+
+		for candidate in candidates
+		{
+			let score: f64 = self.score_splitter(candidate, faces);
+
+			if best.is_none_or(|(_, best_score)| score < best_score)
+			{
+				best = Some((candidate, score));
+			}
+		}
+
+		return best.map(|(plane, _)| (plane, kind));
+	}
+
+	// Lower scores are better.
+	fn score_splitter(&self, candidate: PlaneIndex, faces: &[BuildFace]) -> f64
+	{
+		let plane: &DPlane3 = &self.planes[candidate];
+		let mut front: usize = 0;
+		let mut back: usize = 0;
+		let mut splits: usize = 0;
+
+		for face in faces
+		{
+			if PlanePool::positive_facing(face.plane) == candidate
+			{
+				continue;
+			}
+
+			match plane.classify_winding(&face.winding, ON_PLANE_EPSILON)
+			{
+				PlaneSide::Front => front += 1,
+				PlaneSide::Back => back += 1,
+				PlaneSide::Spanning => splits += 1,
+				PlaneSide::On => (),
+			}
+		}
+
+		let mut score: f64 = (splits as f64 * self.config.split_weight)
+			+ (front.abs_diff(back) as f64 * self.config.balance_weight);
+
+		if matches!(
+			plane.plane_type(),
+			PlaneType::X | PlaneType::Y | PlaneType::Z
+		)
+		{
+			score -= self.config.axial_weight;
+		}
+
+		return score;
+	}
+
+	fn make_leaf(&mut self, cluster: Option<usize>) -> usize
+	{
+		let geometry: BrushGeometry = BrushGeometry::from_planes(&self.volume);
+		let points: Vec<DVec3> = geometry
+			.windings
+			.iter()
+			.flatten()
+			.flat_map(|winding| winding.points.iter().copied())
+			.collect();
+
+		let (mins, maxs) = geometry.bounds().unwrap_or((DVec3::ZERO, DVec3::ZERO));
+
+		// The average of the points of a convex volume always lies within it.
+		let center: DVec3 = if points.is_empty()
+		{
+			(mins + maxs) * 0.5
+		}
+		else
+		{
+			points.iter().sum::<DVec3>() / (points.len() as f64)
+		};
+
+		let contents: Contents = self
+			.brushes
+			.iter()
+			.filter(|brush| brush.contains(center))
+			.map(|brush| brush.contents)
+			.max_by_key(Contents::priority)
+			.unwrap_or(Contents::Empty);
+
+		let cluster: usize = match cluster
+		{
+			Some(cluster) => cluster,
+			None => self.allocate_cluster(),
+		};
+
+		self.tree.leaves.push(BspLeaf {
+			contents: contents,
+			cluster: cluster,
+			faces: Vec::new(),
+			mins: mins,
+			maxs: maxs,
+		});
+
+		return self.tree.leaves.len() - 1;
+	}
+
+	fn allocate_cluster(&mut self) -> usize
+	{
+		self.tree.cluster_count += 1;
+		return self.tree.cluster_count - 1;
+	}
+
+	fn child_bounds(&self, child: BspChild) -> (DVec3, DVec3)
+	{
+		return match child
+		{
+			BspChild::Node(index) => (self.tree.nodes[index].mins, self.tree.nodes[index].maxs),
+			BspChild::Leaf(index) => (self.tree.leaves[index].mins, self.tree.leaves[index].maxs),
+		};
+	}
+
+	// Records, for each non-solid leaf, the faces that can be seen from
+	// within it. A face can be seen from the leaves that it touches on its
+	// front side.
+	fn mark_leaf_faces(&mut self)
+	{
+		for node_index in 0..self.tree.nodes.len()
+		{
+			for face_index in self.tree.nodes[node_index].faces.clone()
+			{
+				let node: &BspNode = &self.tree.nodes[node_index];
+				let face: &BspFace = &self.tree.faces[face_index];
+
+				let front_child: BspChild = if face.plane == node.plane
+				{
+					node.children[0]
+				}
+				else
+				{
+					node.children[1]
+				};
+
+				let winding: Winding = face.winding.clone();
+				self.mark_face(front_child, face_index, winding);
+			}
+		}
+	}
+
+	fn mark_face(&mut self, child: BspChild, face_index: usize, winding: Winding)
+	{
+		let node_index: usize = match child
+		{
+			BspChild::Leaf(leaf_index) =>
+			{
+				let leaf: &mut BspLeaf = &mut self.tree.leaves[leaf_index];
+
+				if leaf.contents != Contents::Solid && !leaf.faces.contains(&face_index)
+				{
+					leaf.faces.push(face_index);
+				}
+
+				return;
+			}
+			BspChild::Node(node_index) => node_index,
+		};
+
+		let node: &BspNode = &self.tree.nodes[node_index];
+		let plane: DPlane3 = self.planes[node.plane];
+		let [front, back] = node.children;
+
+		match plane.classify_winding(&winding, ON_PLANE_EPSILON)
+		{
+			PlaneSide::Front => self.mark_face(front, face_index, winding),
+			PlaneSide::Back => self.mark_face(back, face_index, winding),
+			PlaneSide::On =>
+			{
+				let face_normal: DVec3 = self.planes[self.tree.faces[face_index].plane].normal;
+				let child: BspChild = if face_normal.dot(plane.normal) > 0.0
+				{
+					front
+				}
+				else
+				{
+					back
+				};
+
+				self.mark_face(child, face_index, winding);
+			}
+			PlaneSide::Spanning =>
+			{
+				if let Some(front_winding) = winding.clip(&plane.flipped())
+				{
+					self.mark_face(front, face_index, front_winding);
+				}
+
+				if let Some(back_winding) = winding.clip(&plane)
+				{
+					self.mark_face(back, face_index, back_winding);
+				}
+			}
+		}
+	}
+}
+
+impl BuildFace
+{
+	// Winding is left empty, and is expected to be replaced.
+	fn clone_without_winding(&self) -> BuildFace
+	{
+		return BuildFace {
+			plane: self.plane,
+			winding: Winding { points: Vec::new() },
+			kind: self.kind,
+			contents: self.contents,
+			entity: self.entity,
+			brush: self.brush,
+			face: self.face,
+		};
+	}
+}
+
+#[cfg(test)]
+mod tests
+{
+	use super::*;
+	use crate::work_units::build_csg_hull;
+	use crate::work_units::test_fixtures::{add_entity, box_planes, make_blueprint, room_brushes};
+
+	fn build_world(blueprint: &MapBlueprint, config: &BspConfig) -> BspTree
+	{
+		let hull: CsgHull = build_csg_hull(blueprint);
+		return build_bsp_trees(blueprint, &hull, config)
+			.models
+			.remove(0)
+			.tree;
+	}
+
+	fn room_blueprint() -> MapBlueprint
+	{
+		let brushes: Vec<(Vec<DPlane3>, &str)> =
+			room_brushes(DVec3::splat(-64.0), DVec3::splat(64.0), 16.0)
+				.into_iter()
+				.map(|planes| (planes, "AAATRIGGER"))
+				.collect();
+
+		return make_blueprint(&brushes);
+	}
+
+	#[test]
+	fn build_tree_for_room()
+	{
+		let blueprint: MapBlueprint = room_blueprint();
+		let tree: BspTree = build_world(&blueprint, &BspConfig::default());
+
+		let inside: &BspLeaf = &tree.leaves[tree.leaf_for_point(DVec3::ZERO, &blueprint.planes)];
+		assert_eq!(inside.contents, Contents::Empty);
+
+		// The six inner faces of the room are visible from inside it.
+		assert_eq!(inside.faces.len(), 6);
+
+		let wall_point: DVec3 = DVec3::new(72.0, 0.0, 0.0);
+		let wall: &BspLeaf = &tree.leaves[tree.leaf_for_point(wall_point, &blueprint.planes)];
+		assert_eq!(wall.contents, Contents::Solid);
+		assert!(wall.faces.is_empty());
+
+		let outside: &BspLeaf =
+			&tree.leaves[tree.leaf_for_point(DVec3::splat(90.0), &blueprint.planes)];
+		assert_eq!(outside.contents, Contents::Empty);
+
+		// Every node plane faces the positive direction.
+		assert!(tree.nodes.iter().all(|node| node.plane % 2 == 0));
+		assert_eq!(tree.mins, DVec3::splat(-80.0 - VOLUME_MARGIN));
+	}
+
+	#[test]
+	fn merge_detail_into_single_cluster()
+	{
+		let mut blueprint: MapBlueprint = room_blueprint();
+		add_entity(
+			&mut blueprint,
+			"func_detail",
+			&[(
+				box_planes(
+					DVec3::new(-16.0, -16.0, -64.0),
+					DVec3::new(16.0, 16.0, -32.0),
+				),
+				"CRATE",
+			)],
+		);
+
+		let tree: BspTree = build_world(&blueprint, &BspConfig::default());
+
+		let crate_leaf: &BspLeaf =
+			&tree.leaves[tree.leaf_for_point(DVec3::new(0.0, 0.0, -48.0), &blueprint.planes)];
+		let room_leaf: &BspLeaf =
+			&tree.leaves[tree.leaf_for_point(DVec3::new(40.0, 40.0, 40.0), &blueprint.planes)];
+
+		assert_eq!(crate_leaf.contents, Contents::Solid);
+		assert_eq!(room_leaf.contents, Contents::Empty);
+
+		// The detail brush splits the room's leaf, but the pieces all belong
+		// to the same cluster.
+		assert_eq!(crate_leaf.cluster, room_leaf.cluster);
+		assert!(tree.faces.iter().any(|face| face.entity == 1));
+	}
+
+	#[test]
+	fn split_on_hints_first_and_discard_them()
+	{
+		let mut brushes: Vec<(Vec<DPlane3>, &str)> =
+			room_brushes(DVec3::splat(-64.0), DVec3::splat(64.0), 16.0)
+				.into_iter()
+				.map(|planes| (planes, "AAATRIGGER"))
+				.collect();
+
+		let hint_planes: Vec<DPlane3> =
+			box_planes(DVec3::new(-64.0, -64.0, -64.0), DVec3::new(0.0, 64.0, 64.0));
+		brushes.push((hint_planes, "HINT"));
+
+		let blueprint: MapBlueprint = make_blueprint(&brushes);
+		let tree: BspTree = build_world(&blueprint, &BspConfig::default());
+
+		// The hint plane at X = 0 is the first split, and no hint faces are
+		// kept.
+		assert_eq!(
+			blueprint.planes[tree.nodes[0].plane],
+			DPlane3::new(DVec3::X, 0.0)
+		);
+		assert!(
+			tree.faces
+				.iter()
+				.all(|face| face.contents == Contents::Solid)
+		);
+		assert_ne!(
+			tree.leaf_for_point(DVec3::new(-32.0, 0.0, 0.0), &blueprint.planes),
+			tree.leaf_for_point(DVec3::new(32.0, 0.0, 0.0), &blueprint.planes)
+		);
+	}
+
+	#[test]
+	fn subdivide_large_faces()
+	{
+		let brushes: Vec<(Vec<DPlane3>, &str)> =
+			room_brushes(DVec3::splat(-256.0), DVec3::splat(256.0), 16.0)
+				.into_iter()
+				.map(|planes| (planes, "WALL"))
+				.collect();
+
+		let blueprint: MapBlueprint = make_blueprint(&brushes);
+		let tree: BspTree = build_world(&blueprint, &BspConfig::default());
+
+		// Each inner face is 512 texels across, so is split into three along
+		// both of its texture axes.
+		let inside: &BspLeaf = &tree.leaves[tree.leaf_for_point(DVec3::ZERO, &blueprint.planes)];
+		assert_eq!(inside.faces.len(), 6 * 9);
+
+		for face in &tree.faces
+		{
+			let (mins, maxs): (DVec3, DVec3) = face.winding.bounds();
+			let extent: DVec3 = maxs - mins;
+			let normal: DVec3 = blueprint.planes[face.plane].normal;
+
+			// Axes lying along the normal are not used for texture coordinates.
+			assert!((extent * (DVec3::ONE - normal.abs())).max_element() <= 240.0);
+		}
+	}
+}
@@ -0,0 +1,521 @@
+use crate::game_config::{BspConfig, HullSize};
+use crate::model::{
+	BlueprintBrush, BlueprintBrushFace, BlueprintEntity, BrushGeometry, BspChild, BspModels,
+	BspTree, ClipChild, ClipNode, ClipTree, Contents, CsgHull, DPlane3, MapBlueprint,
+	ON_PLANE_EPSILON, PortalGraph,
+};
+use crate::work_units::{build_bsp_trees, build_csg_hull, build_portals};
+use glam::DVec3;
+use log::debug;
+use std::collections::{HashMap, VecDeque};
+
+// Normals whose dot product is greater than this are considered the same.
+const SAME_NORMAL_DOT: f64 = 1.0 - 1.0e-5;
+
+// Material given to the faces of expanded clip brushes. Clip brushes are left
+// out of the visible hull, but block movement in every clipping hull, so are
+// expanded as if they were solid.
+const EXPANDED_CLIP_MATERIAL: &str = "solid";
+
+/// Builds a clipping tree for each hull size, for every model. Each tree is
+/// built from the model's brushes, expanded by the hull's box so that the
+/// box can be collided with the tree as if it were a point. Planes used by
+/// the trees are added to the blueprint's plane pool.
+pub fn build_clip_hulls(
+	blueprint: &mut MapBlueprint,
+	models: &mut BspModels,
+	hulls: &[HullSize],
+	config: &BspConfig,
+)
+{
+	for (hull_index, hull) in hulls.iter().enumerate()
+	{
+		let expanded: MapBlueprint = expand_blueprint(blueprint, hull);
+		let csg_hull: CsgHull = build_csg_hull(&expanded);
+		let mut expanded_models: BspModels = build_bsp_trees(&expanded, &csg_hull, config);
+
+		// The world is the first model. Everything outside it is solid, so that
+		// nothing can escape the map.
+		if let Some(world) = expanded_models.models.first_mut()
+		{
+			let portals: PortalGraph = build_portals(&world.tree, &expanded.planes);
+			fill_from_void(&mut world.tree, &portals);
+		}
+
+		for model in models.models.iter_mut()
+		{
+			let clip_tree: ClipTree = match expanded_models
+				.models
+				.iter()
+				.find(|expanded_model| expanded_model.entity == model.entity)
+			{
+				Some(expanded_model) => clip_tree_from_bsp(&expanded_model.tree),
+				None => ClipTree {
+					root: ClipChild::Contents(Contents::Empty),
+					nodes: Vec::new(),
+				},
+			};
+
+			debug!(
+				"Built hull {} for entity {} with {} clipnodes",
+				hull_index + 1,
+				model.entity,
+				clip_tree.nodes.len()
+			);
+
+			model.clip_trees.push(clip_tree);
+		}
+
+		// The expanded blueprint's pool began as a copy of the original, so
+		// only differs by the planes added for this hull.
+		blueprint.planes = expanded.planes;
+	}
+}
+
+// Produces a copy of the blueprint in which each solid brush is expanded by
+// the size of the hull. Brushes that do not block movement are left out.
+fn expand_blueprint(blueprint: &MapBlueprint, hull: &HullSize) -> MapBlueprint
+{
+	let mut expanded: MapBlueprint = MapBlueprint {
+		entities: Vec::with_capacity(blueprint.entities.len()),
+		planes: blueprint.planes.clone(),
+		textures: HashMap::new(),
+	};
+
+	for entity in &blueprint.entities
+	{
+		let mut brushes: Vec<BlueprintBrush> = Vec::new();
+
+		for brush in &entity.brushes
+		{
+			let Some(contents_face) = brush
+				.faces
+				.iter()
+				.max_by_key(|face| Contents::from_material(&face.material).priority())
+			else
+			{
+				continue;
+			};
+
+			let contents: Contents = Contents::from_material(&contents_face.material);
+			let geometry: BrushGeometry = brush.geometry(&blueprint.planes);
+
+			if !(contents.seals_map() || contents == Contents::Clip) || !geometry.is_valid()
+			{
+				continue;
+			}
+
+			let material: &str = if contents == Contents::Clip
+			{
+				EXPANDED_CLIP_MATERIAL
+			}
+			else
+			{
+				&contents_face.material
+			};
+
+			let mut planes: Vec<DPlane3> = brush
+				.faces
+				.iter()
+				.map(|face| blueprint.planes[face.plane])
+				.collect();
+
+			planes.extend(bevel_planes(&planes, &geometry));
+
+			// All faces take the material that gives the brush its contents,
+			// as only the contents of the expanded brush matter.
+			let faces: Vec<BlueprintBrushFace> = planes
+				.iter()
+				.map(|plane| BlueprintBrushFace {
+					plane: expanded.planes.find_or_insert(&expand_plane(plane, hull)),
+					material: String::from(material),
+					projection: contents_face.projection,
+				})
+				.collect();
+
+			brushes.push(BlueprintBrush {
+				faces: faces,
+				source_line: brush.source_line,
+				colinear_face_count: 0,
+			});
+		}
+
+		expanded.entities.push(BlueprintEntity {
+			brushes: brushes,
+			keyvalues: entity.keyvalues.clone(),
+			source_line: entity.source_line,
+		});
+	}
+
+	return expanded;
+}
+
+// Returns extra planes which bound the brush without changing its shape.
+// Without these, expanding a brush with sharp edges or corners pushes its
+// faces out much further than the hull's box reaches, and a box sliding
+// along the brush catches on the empty space beyond the edge. The brush is
+// bevelled with axial planes at its bounds, and with planes along each edge
+// that are parallel to one of the axes.
+fn bevel_planes(planes: &[DPlane3], geometry: &BrushGeometry) -> Vec<DPlane3>
+{
+	let points: Vec<DVec3> = geometry
+		.windings
+		.iter()
+		.flatten()
+		.flat_map(|winding| winding.points.iter().copied())
+		.collect();
+
+	let Some((mins, maxs)) = geometry.bounds()
+	else
+	{
+		return Vec::new();
+	};
+
+	let mut bevels: Vec<DPlane3> = Vec::new();
+
+	let has_normal = |bevels: &[DPlane3], normal: DVec3| -> bool {
+		return planes
+			.iter()
+			.chain(bevels.iter())
+			.any(|plane| plane.normal.dot(normal) > SAME_NORMAL_DOT);
+	};
+
+	for axis in 0..3
+	{
+		let mut normal: DVec3 = DVec3::ZERO;
+
+		normal[axis] = 1.0;
+		if !has_normal(&bevels, normal)
+		{
+			bevels.push(DPlane3::new(normal, maxs[axis]));
+		}
+
+		normal[axis] = -1.0;
+		if !has_normal(&bevels, normal)
+		{
+			bevels.push(DPlane3::new(normal, -mins[axis]));
+		}
+	}
+
+	for winding in geometry.windings.iter().flatten()
+	{
+		for (index, start) in winding.points.iter().enumerate()
+		{
+			let end: DVec3 = winding.points[(index + 1) % winding.points.len()];
+			let Some(edge) = (end - *start).try_normalize()
+			else
+			{
+				continue;
+			};
+
+			for axis in [
+				DVec3::X,
+				DVec3::NEG_X,
+				DVec3::Y,
+				DVec3::NEG_Y,
+				DVec3::Z,
+				DVec3::NEG_Z,
+			]
+			{
+				let Some(normal) = edge.cross(axis).try_normalize()
+				else
+				{
+					continue;
+				};
+
+				// Axial bevels have already been added.
+				if normal.abs().max_element() > SAME_NORMAL_DOT || has_normal(&bevels, normal)
+				{
+					continue;
+				}
+
+				let plane: DPlane3 = DPlane3::new(normal, normal.dot(*start));
+
+				// The plane only bevels the edge if the whole brush lies
+				// behind it.
+				if points
+					.iter()
+					.all(|point| plane.distance_to(*point) <= ON_PLANE_EPSILON)
+				{
+					bevels.push(plane);
+				}
+			}
+		}
+	}
+
+	return bevels;
+}
+
+// Moves the plane outwards so that, for any point behind the moved plane, a
+// box of the hull's size placed at that point would touch the original
+// plane.
+fn expand_plane(plane: &DPlane3, hull: &HullSize) -> DPlane3
+{
+	let mut offset: f64 = 0.0;
+
+	for axis in 0..3
+	{
+		let corner: f64 = if plane.normal[axis] > 0.0
+		{
+			hull.mins[axis]
+		}
+		else
+		{
+			hull.maxs[axis]
+		};
+
+		offset += plane.normal[axis] * corner;
+	}
+
+	return DPlane3::new(plane.normal, plane.distance - offset);
+}
+
+// Fills every leaf that can be reached from outside the tree's volume.
+fn fill_from_void(tree: &mut BspTree, portals: &PortalGraph)
+{
+	let mut reached: Vec<bool> = vec![false; portals.outside_leaf + 1];
+	let mut queue: VecDeque<usize> = VecDeque::from([portals.outside_leaf]);
+	reached[portals.outside_leaf] = true;
+
+	while let Some(leaf) = queue.pop_front()
+	{
+		for portal in &portals.leaf_portals[leaf]
+		{
+			let neighbour: usize = portals.portals[*portal].neighbour(leaf);
+
+			if !reached[neighbour] && !tree.leaves[neighbour].contents.seals_map()
+			{
+				reached[neighbour] = true;
+				queue.push_back(neighbour);
+			}
+		}
+	}
+
+	for (leaf, reached) in tree.leaves.iter_mut().zip(reached)
+	{
+		if reached
+		{
+			leaf.contents = Contents::Solid;
+		}
+	}
+}
+
+fn clip_tree_from_bsp(tree: &BspTree) -> ClipTree
+{
+	let mut clip_tree: ClipTree = ClipTree {
+		root: ClipChild::Contents(Contents::Empty),
+		nodes: Vec::new(),
+	};
+
+	clip_tree.root = add_clip_child(&mut clip_tree, tree, tree.root);
+	return clip_tree;
+}
+
+// Adds the subtree to the clipping tree. Nodes whose children both have the
+// same contents are replaced by those contents.
+fn add_clip_child(clip_tree: &mut ClipTree, tree: &BspTree, child: BspChild) -> ClipChild
+{
+	let node_index: usize = match child
+	{
+		BspChild::Leaf(leaf) =>
+		{
+			// Anything that blocks movement is solid for the purposes of
+			// collision.
+			return ClipChild::Contents(
+				if tree.leaves[leaf].contents.seals_map()
+				{
+					Contents::Solid
+				}
+				else
+				{
+					Contents::Empty
+				},
+			);
+		}
+		BspChild::Node(node_index) => node_index,
+	};
+
+	let clip_index: usize = clip_tree.nodes.len();
+	clip_tree.nodes.push(ClipNode {
+		plane: tree.nodes[node_index].plane,
+		children: [ClipChild::Contents(Contents::Empty); 2],
+	});
+
+	let [front, back] = tree.nodes[node_index].children;
+	let front: ClipChild = add_clip_child(clip_tree, tree, front);
+	let back: ClipChild = add_clip_child(clip_tree, tree, back);
+
+	if let (ClipChild::Contents(front_contents), ClipChild::Contents(back_contents)) = (front, back)
+		&& front_contents == back_contents
+	{
+		// The node was the last one added, as both of its children are
+		// contents, so it can simply be removed.
+		clip_tree.nodes.pop();
+		return front;
+	}
+
+	clip_tree.nodes[clip_index].children = [front, back];
+	return ClipChild::Node(clip_index);
+}
+
+#[cfg(test)]
+mod tests
+{
+	use super::*;
+	use crate::game_config::GOLDSRC_HULLS;
+	use crate::work_units::test_fixtures::{box_planes, make_blueprint, room_brushes};
+
+	#[test]
+	fn expand_plane_by_hull()
+	{
+		let standing: HullSize = GOLDSRC_HULLS[0];
+
+		assert_eq!(
+			expand_plane(&DPlane3::new(DVec3::Z, 64.0), &standing),
+			DPlane3::new(DVec3::Z, 100.0)
+		);
+		assert_eq!(
+			expand_plane(&DPlane3::new(DVec3::NEG_X, 32.0), &standing),
+			DPlane3::new(DVec3::NEG_X, 48.0)
+		);
+
+		let diagonal: DVec3 = DVec3::new(1.0, 1.0, 0.0).normalize();
+		let expanded: DPlane3 = expand_plane(&DPlane3::new(diagonal, 0.0), &standing);
+		assert!((expanded.distance - (32.0 / 2.0_f64.sqrt())).abs() < 1.0e-9);
+	}
+
+	#[test]
+	fn bevel_cut_corner()
+	{
+		// A cube with a slice cut diagonally from one corner, leaving a
+		// hexagonal face.
+		let mut planes: Vec<DPlane3> = box_planes(DVec3::splat(-32.0), DVec3::splat(32.0));
+		planes.push(DPlane3::new(DVec3::ONE.normalize(), 16.0));
+
+		let bevels: Vec<DPlane3> = bevel_planes(&planes, &BrushGeometry::from_planes(&planes));
+
+		// The box's faces already cover the axial bevels, so only the edges of
+		// the cut need bevelling.
+		assert!(
+			bevels
+				.iter()
+				.all(|plane| plane.normal.abs().max_element() < SAME_NORMAL_DOT)
+		);
+		assert_eq!(bevels.len(), 3);
+
+		let expected: DVec3 = DVec3::new(1.0, 1.0, 0.0).normalize();
+		assert!(
+			bevels
+				.iter()
+				.any(|plane| plane.normal.dot(expected) > SAME_NORMAL_DOT)
+		);
+	}
+
+	#[test]
+	fn bevel_tetrahedron_at_bounds()
+	{
+		let planes: Vec<DPlane3> = [
+			DVec3::new(1.0, 1.0, 1.0),
+			DVec3::new(-1.0, 1.0, -1.0),
+			DVec3::new(1.0, -1.0, -1.0),
+			DVec3::new(-1.0, -1.0, 1.0),
+		]
+		.iter()
+		.map(|normal| DPlane3::new(normal.normalize(), 16.0))
+		.collect();
+
+		let geometry: BrushGeometry = BrushGeometry::from_planes(&planes);
+		let (mins, maxs) = geometry.bounds().unwrap();
+		let bevels: Vec<DPlane3> = bevel_planes(&planes, &geometry);
+
+		// Each corner of the tetrahedron touches two faces of its bounding box.
+		assert_eq!(bevels.len(), 6);
+		assert!(bevels.contains(&DPlane3::new(DVec3::Z, maxs.z)));
+		assert!(bevels.contains(&DPlane3::new(DVec3::NEG_Z, -mins.z)));
+	}
+
+	#[test]
+	fn clip_room_with_standing_hull()
+	{
+		let brushes: Vec<(Vec<DPlane3>, &str)> =
+			room_brushes(DVec3::splat(-64.0), DVec3::splat(64.0), 16.0)
+				.into_iter()
+				.map(|planes| (planes, "AAATRIGGER"))
+				.collect();
+
+		let mut blueprint: MapBlueprint = make_blueprint(&brushes);
+		let csg_hull: CsgHull = build_csg_hull(&blueprint);
+		let mut models: BspModels = build_bsp_trees(&blueprint, &csg_hull, &BspConfig::default());
+
+		build_clip_hulls(
+			&mut blueprint,
+			&mut models,
+			&GOLDSRC_HULLS,
+			&BspConfig::default(),
+		);
+
+		let clip_trees: &[ClipTree] = &models.models[0].clip_trees;
+		assert_eq!(clip_trees.len(), GOLDSRC_HULLS.len());
+
+		let standing: &ClipTree = &clip_trees[0];
+		let contents_at = |point: DVec3| standing.contents_at(point, &blueprint.planes);
+
+		assert_eq!(contents_at(DVec3::ZERO), Contents::Empty);
+		assert_eq!(contents_at(DVec3::new(0.0, 0.0, 27.0)), Contents::Empty);
+		assert_eq!(contents_at(DVec3::new(0.0, 0.0, 29.0)), Contents::Solid);
+		assert_eq!(contents_at(DVec3::new(47.0, 0.0, 0.0)), Contents::Empty);
+		assert_eq!(contents_at(DVec3::new(49.0, 0.0, 0.0)), Contents::Solid);
+		assert_eq!(contents_at(DVec3::splat(200.0)), Contents::Solid);
+
+		// Nodes separating space with the same contents are collapsed.
+		for node in &standing.nodes
+		{
+			if let [ClipChild::Contents(front), ClipChild::Contents(back)] = node.children
+			{
+				assert_ne!(front, back);
+			}
+		}
+	}
+
+	#[test]
+	fn block_only_clipping_hulls_with_clip_brushes()
+	{
+		let mut brushes: Vec<(Vec<DPlane3>, &str)> =
+			room_brushes(DVec3::splat(-64.0), DVec3::splat(64.0), 16.0)
+				.into_iter()
+				.map(|planes| (planes, "AAATRIGGER"))
+				.collect();
+
+		brushes.push((
+			box_planes(DVec3::new(-8.0, -64.0, -64.0), DVec3::new(8.0, 64.0, 64.0)),
+			"CLIP",
+		));
+
+		let mut blueprint: MapBlueprint = make_blueprint(&brushes);
+		let csg_hull: CsgHull = build_csg_hull(&blueprint);
+		let mut models: BspModels = build_bsp_trees(&blueprint, &csg_hull, &BspConfig::default());
+
+		// The clip brush is neither visible nor solid in the visible hull.
+		let tree: &BspTree = &models.models[0].tree;
+		let leaf: usize = tree.leaf_for_point(DVec3::ZERO, &blueprint.planes);
+		assert_eq!(tree.leaves[leaf].contents, Contents::Empty);
+		assert!(tree.faces.iter().all(|face| face.brush < brushes.len() - 1));
+
+		build_clip_hulls(
+			&mut blueprint,
+			&mut models,
+			&GOLDSRC_HULLS,
+			&BspConfig::default(),
+		);
+
+		let standing: &ClipTree = &models.models[0].clip_trees[0];
+		assert_eq!(
+			standing.contents_at(DVec3::ZERO, &blueprint.planes),
+			Contents::Solid
+		);
+		assert_eq!(
+			standing.contents_at(DVec3::new(-40.0, 0.0, 0.0), &blueprint.planes),
+			Contents::Empty
+		);
+	}
+}
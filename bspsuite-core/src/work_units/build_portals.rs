@@ -0,0 +1,323 @@
+use crate::model::{
+	BspChild, BspNode, BspTree, Contents, DPlane3, ON_PLANE_EPSILON, PlanePool, PlaneSide, Portal,
+	PortalGraph, VisPortal, VisPortals, Winding,
+};
+use log::debug;
+
+/// Builds the portals between adjacent leaves of the tree. Leaves touching
+/// the edge of the tree's volume are given portals to the outside leaf.
+/// Portals into solid leaves are left out, as nothing can pass through them.
+pub fn build_portals(tree: &BspTree, planes: &PlanePool) -> PortalGraph
+{
+	let mut builder: PortalBuilder = PortalBuilder {
+		tree: tree,
+		planes: planes,
+		graph: PortalGraph::new(tree.leaves.len()),
+	};
+
+	let bounds: Vec<DPlane3> = tree.bounding_planes();
+
+	// Each face of the bounding volume separates the leaves behind it from
+	// the void in front of it.
+	for (index, plane) in bounds.iter().enumerate()
+	{
+		let mut winding: Option<Winding> = Some(Winding::from_plane(plane));
+
+		for (other_index, other) in bounds.iter().enumerate()
+		{
+			if other_index != index
+			{
+				winding = winding.and_then(|winding| winding.clip(other));
+			}
+		}
+
+		if let Some(winding) = winding
+		{
+			let outside_leaf: usize = builder.graph.outside_leaf;
+			builder.add_portals(tree.root, *plane, winding, &mut |leaf| [outside_leaf, leaf]);
+		}
+	}
+
+	if let BspChild::Node(root) = tree.root
+	{
+		builder.add_node_portals(root, &mut bounds.clone());
+	}
+
+	debug!(
+		"Built {} portals between {} leaves",
+		builder.graph.portals.len(),
+		tree.leaves.len()
+	);
+
+	return builder.graph;
+}
+
+/// Numbers the clusters of the leaves that do not seal the map, and collects
+/// the portals between different clusters. Portals within a cluster are left
+/// out, as visibility is not computed between the leaves of a cluster.
+pub fn build_vis_portals(tree: &BspTree, graph: &PortalGraph) -> VisPortals
+{
+	let mut vis_portals: VisPortals = VisPortals {
+		cluster_count: 0,
+		leaf_clusters: vec![None; tree.leaves.len()],
+		portals: Vec::new(),
+	};
+
+	let mut cluster_numbers: Vec<Option<usize>> = vec![None; tree.cluster_count];
+
+	for (leaf_index, leaf) in tree.leaves.iter().enumerate()
+	{
+		if leaf.contents.seals_map()
+		{
+			continue;
+		}
+
+		let number: usize = *cluster_numbers[leaf.cluster].get_or_insert_with(|| {
+			vis_portals.cluster_count += 1;
+			vis_portals.cluster_count - 1
+		});
+
+		vis_portals.leaf_clusters[leaf_index] = Some(number);
+	}
+
+	for portal in &graph.portals
+	{
+		let clusters: [Option<usize>; 2] = portal
+			.leaves
+			.map(|leaf| vis_portals.leaf_clusters.get(leaf).copied().flatten());
+
+		if let [Some(front), Some(back)] = clusters
+			&& front != back
+		{
+			vis_portals.portals.push(VisPortal {
+				plane: portal.plane,
+				winding: portal.winding.clone(),
+				clusters: [front, back],
+			});
+		}
+	}
+
+	debug!(
+		"Found {} portals between {} visibility clusters",
+		vis_portals.portals.len(),
+		vis_portals.cluster_count
+	);
+
+	return vis_portals;
+}
+
+struct PortalBuilder<'l>
+{
+	tree: &'l BspTree,
+	planes: &'l PlanePool,
+	graph: PortalGraph,
+}
+
+impl<'l> PortalBuilder<'l>
+{
+	// Adds the portals lying on the node's plane, then those of the nodes
+	// below it. The volume is the set of planes bounding the node, which lies
+	// behind all of them.
+	fn add_node_portals(&mut self, node_index: usize, volume: &mut Vec<DPlane3>)
+	{
+		let node: &BspNode = &self.tree.nodes[node_index];
+		let plane: DPlane3 = self.planes[node.plane];
+		let [front, back] = node.children;
+
+		let winding: Option<Winding> = volume
+			.iter()
+			.try_fold(Winding::from_plane(&plane), |winding, bound| {
+				winding.clip(bound)
+			});
+
+		if let Some(winding) = winding
+		{
+			// Split the winding among the leaves in front of the plane, then
+			// split each of those pieces among the leaves behind it.
+			let mut front_pieces: Vec<(usize, Winding)> = Vec::new();
+			self.split_to_leaves(front, winding, &mut front_pieces);
+
+			for (front_leaf, piece) in front_pieces
+			{
+				self.add_portals(back, plane, piece, &mut |back_leaf| [front_leaf, back_leaf]);
+			}
+		}
+
+		volume.push(plane.flipped());
+		if let BspChild::Node(front_node) = front
+		{
+			self.add_node_portals(front_node, volume);
+		}
+		volume.pop();
+
+		volume.push(plane);
+		if let BspChild::Node(back_node) = back
+		{
+			self.add_node_portals(back_node, volume);
+		}
+		volume.pop();
+	}
+
+	// Splits the winding among the leaves of the subtree, and adds a portal
+	// for each piece. The leaves of each portal are produced from the leaf
+	// that the piece ended up in.
+	fn add_portals(
+		&mut self,
+		child: BspChild,
+		plane: DPlane3,
+		winding: Winding,
+		leaves_for: &mut dyn FnMut(usize) -> [usize; 2],
+	)
+	{
+		let mut pieces: Vec<(usize, Winding)> = Vec::new();
+		self.split_to_leaves(child, winding, &mut pieces);
+
+		for (leaf, piece) in pieces
+		{
+			let leaves: [usize; 2] = leaves_for(leaf);
+
+			if leaves.iter().any(|leaf| self.is_solid(*leaf))
+			{
+				continue;
+			}
+
+			self.graph.add_portal(Portal {
+				plane: plane,
+				winding: piece,
+				leaves: leaves,
+			});
+		}
+	}
+
+	fn split_to_leaves(&self, child: BspChild, winding: Winding, out: &mut Vec<(usize, Winding)>)
+	{
+		let node_index: usize = match child
+		{
+			BspChild::Leaf(leaf) =>
+			{
+				out.push((leaf, winding));
+				return;
+			}
+			BspChild::Node(node_index) => node_index,
+		};
+
+		let node: &BspNode = &self.tree.nodes[node_index];
+		let plane: DPlane3 = self.planes[node.plane];
+		let [front, back] = node.children;
+
+		match plane.classify_winding(&winding, ON_PLANE_EPSILON)
+		{
+			PlaneSide::Front | PlaneSide::On => self.split_to_leaves(front, winding, out),
+			PlaneSide::Back => self.split_to_leaves(back, winding, out),
+			PlaneSide::Spanning =>
+			{
+				if let Some(front_winding) = winding.clip(&plane.flipped())
+				{
+					self.split_to_leaves(front, front_winding, out);
+				}
+
+				if let Some(back_winding) = winding.clip(&plane)
+				{
+					self.split_to_leaves(back, back_winding, out);
+				}
+			}
+		}
+	}
+
+	fn is_solid(&self, leaf: usize) -> bool
+	{
+		return leaf != self.graph.outside_leaf
+			&& self.tree.leaves[leaf].contents == Contents::Solid;
+	}
+}
+
+#[cfg(test)]
+mod tests
+{
+	use super::*;
+	use crate::game_config::BspConfig;
+	use crate::model::{CsgHull, MapBlueprint};
+	use crate::work_units::test_fixtures::{box_planes, make_blueprint, room_brushes};
+	use crate::work_units::{build_bsp_trees, build_csg_hull};
+	use glam::DVec3;
+
+	#[test]
+	fn seal_closed_room()
+	{
+		let brushes: Vec<(Vec<DPlane3>, &str)> =
+			room_brushes(DVec3::splat(-64.0), DVec3::splat(64.0), 16.0)
+				.into_iter()
+				.map(|planes| (planes, "AAATRIGGER"))
+				.collect();
+
+		let blueprint: MapBlueprint = make_blueprint(&brushes);
+		let hull: CsgHull = build_csg_hull(&blueprint);
+		let tree: BspTree = build_bsp_trees(&blueprint, &hull, &BspConfig::default())
+			.models
+			.remove(0)
+			.tree;
+
+		let graph: PortalGraph = build_portals(&tree, &blueprint.planes);
+		let inside: usize = tree.leaf_for_point(DVec3::ZERO, &blueprint.planes);
+
+		// The space around the room touches the outside, but the walls of the
+		// room seal its interior off from everything else.
+		assert!(!graph.leaf_portals[graph.outside_leaf].is_empty());
+		assert!(graph.leaf_portals[inside].is_empty());
+
+		for portal in &graph.portals
+		{
+			assert_ne!(portal.leaves[0], portal.leaves[1]);
+			assert!(portal.leaves.iter().all(|leaf| {
+				*leaf == graph.outside_leaf || tree.leaves[*leaf].contents != Contents::Solid
+			}));
+		}
+	}
+
+	#[test]
+	fn split_room_into_clusters_with_hint()
+	{
+		let mut brushes: Vec<(Vec<DPlane3>, &str)> =
+			room_brushes(DVec3::splat(-64.0), DVec3::splat(64.0), 16.0)
+				.into_iter()
+				.map(|planes| (planes, "AAATRIGGER"))
+				.collect();
+
+		brushes.push((
+			box_planes(DVec3::splat(-64.0), DVec3::new(0.0, 64.0, 64.0)),
+			"HINT",
+		));
+
+		let blueprint: MapBlueprint = make_blueprint(&brushes);
+		let hull: CsgHull = build_csg_hull(&blueprint);
+		let tree: BspTree = build_bsp_trees(&blueprint, &hull, &BspConfig::default())
+			.models
+			.remove(0)
+			.tree;
+
+		let vis_portals: VisPortals =
+			build_vis_portals(&tree, &build_portals(&tree, &blueprint.planes));
+
+		let cluster_at = |point: DVec3| -> Option<usize> {
+			return vis_portals.leaf_clusters[tree.leaf_for_point(point, &blueprint.planes)];
+		};
+
+		let left: usize = cluster_at(DVec3::new(-32.0, 0.0, 0.0)).unwrap();
+		let right: usize = cluster_at(DVec3::new(32.0, 0.0, 0.0)).unwrap();
+		assert_ne!(left, right);
+		assert_eq!(cluster_at(DVec3::new(72.0, 0.0, 0.0)), None);
+
+		// The hint plane divides the room into two clusters, which see each
+		// other through a single portal.
+		let between: Vec<&VisPortal> = vis_portals
+			.portals
+			.iter()
+			.filter(|portal| portal.clusters.contains(&left) && portal.clusters.contains(&right))
+			.collect();
+
+		assert_eq!(between.len(), 1);
+		assert_eq!(between[0].plane, DPlane3::new(DVec3::X, 0.0));
+		assert_eq!(between[0].clusters, [right, left]);
+		assert!((between[0].winding.area() - (128.0 * 128.0)).abs() < 1.0e-6);
+	}
+}
@@ -0,0 +1,536 @@
+use crate::model::{DPlane3, ON_PLANE_EPSILON, VisPortals, Visibility, Winding};
+use crate::work_units::run_on_threads;
+use glam::DVec3;
+use std::sync::OnceLock;
+
+/// Computes which visibility clusters can see each other through the portals
+/// between them. In fast mode, a cluster is treated as seeing everything that
+/// can be reached by passing forwards through portals, which is quick but
+/// generous. Otherwise, each chain of portals is clipped to the region that can
+/// actually be seen through all of them, which is far slower but much more
+/// precise. Either way, the work is spread across all available threads.
+pub fn compute_visibility(vis_portals: &VisPortals, fast: bool) -> Visibility
+{
+	let portals: Vec<FlowPortal> = one_way_portals(vis_portals);
+	let mut cluster_portals: Vec<Vec<usize>> = vec![Vec::new(); vis_portals.cluster_count];
+
+	for (index, portal) in portals.iter().enumerate()
+	{
+		cluster_portals[portal.source].push(index);
+	}
+
+	let graph: FlowGraph = FlowGraph {
+		portals: portals,
+		cluster_portals: cluster_portals,
+	};
+
+	let all_portals: Vec<usize> = (0..graph.portals.len()).collect();
+	let flood: Vec<PortalSet> = run_on_threads("Base visibility", &all_portals, |index, _| {
+		return graph.base_vis(index);
+	});
+
+	let portal_vis: Vec<PortalSet> = if fast
+	{
+		flood
+	}
+	else
+	{
+		// Portals that can see the least finish first, and their results then
+		// limit the work done by portals that look through them.
+		let mut order: Vec<usize> = all_portals;
+		order.sort_by_key(|index| flood[*index].count());
+
+		run_on_threads("Full visibility", &order, |index, finished| {
+			return graph.full_vis(index, &flood, finished);
+		})
+	};
+
+	return graph.cluster_visibility(&portal_vis);
+}
+
+// A portal that is looked through in one direction only, from its source
+// cluster into its target cluster. Its plane faces the target.
+struct FlowPortal
+{
+	plane: DPlane3,
+	winding: Winding,
+	source: usize,
+	target: usize,
+}
+
+struct FlowGraph
+{
+	portals: Vec<FlowPortal>,
+
+	// Indices of the portals leading out of each cluster.
+	cluster_portals: Vec<Vec<usize>>,
+}
+
+// One step along a chain of portals during portal flow.
+struct FlowStep
+{
+	// Part of the first portal in the chain from which the rest of the chain
+	// may be visible.
+	source: Winding,
+
+	// Part of the last portal in the chain that may be seen through the rest
+	// of it. This is unset for the first portal, which is seen in full.
+	pass: Option<Winding>,
+
+	// Plane of the last portal in the chain.
+	plane: DPlane3,
+
+	// Portals that might still be visible through the chain.
+	might_see: PortalSet,
+}
+
+impl FlowGraph
+{
+	// Finds the portals that could be seen through the given portal by any
+	// path leading forwards from it, ignoring whether the path is straight.
+	fn base_vis(&self, index: usize) -> PortalSet
+	{
+		let portal: &FlowPortal = &self.portals[index];
+		let mut in_front: PortalSet = PortalSet::new(self.portals.len());
+
+		for (other_index, other) in self.portals.iter().enumerate()
+		{
+			// The other portal must lie at least partly in front of this one,
+			// and this one at least partly behind the other.
+			if other_index == index
+				|| !other
+					.winding
+					.points
+					.iter()
+					.any(|point| portal.plane.distance_to(*point) > ON_PLANE_EPSILON)
+				|| !portal
+					.winding
+					.points
+					.iter()
+					.any(|point| other.plane.distance_to(*point) < -ON_PLANE_EPSILON)
+			{
+				continue;
+			}
+
+			in_front.insert(other_index);
+		}
+
+		let mut flood: PortalSet = PortalSet::new(self.portals.len());
+		let mut clusters: Vec<usize> = vec![portal.target];
+
+		while let Some(cluster) = clusters.pop()
+		{
+			for next in &self.cluster_portals[cluster]
+			{
+				if in_front.contains(*next) && !flood.contains(*next)
+				{
+					flood.insert(*next);
+					clusters.push(self.portals[*next].target);
+				}
+			}
+		}
+
+		return flood;
+	}
+
+	// Finds the portals that can be seen through the given portal along a
+	// straight line. The results of portals that have already finished are
+	// used to rule out anything they cannot see.
+	fn full_vis(
+		&self,
+		index: usize,
+		flood: &[PortalSet],
+		finished: &[OnceLock<PortalSet>],
+	) -> PortalSet
+	{
+		let portal: &FlowPortal = &self.portals[index];
+		let mut flow: PortalFlow = PortalFlow {
+			graph: self,
+			flood: flood,
+			finished: finished,
+			base_plane: portal.plane,
+			visible: PortalSet::new(self.portals.len()),
+		};
+
+		let first: FlowStep = FlowStep {
+			source: portal.winding.clone(),
+			pass: None,
+			plane: portal.plane,
+			might_see: flood[index].clone(),
+		};
+
+		flow.recurse(portal.target, &first);
+		return flow.visible;
+	}
+
+	fn cluster_visibility(&self, portal_vis: &[PortalSet]) -> Visibility
+	{
+		let mut visibility: Visibility = Visibility::new(self.cluster_portals.len());
+
+		for (cluster, portals) in self.cluster_portals.iter().enumerate()
+		{
+			visibility.set_visible(cluster, cluster);
+
+			for index in portals
+			{
+				visibility.set_visible(cluster, self.portals[*index].target);
+
+				for visible in portal_vis[*index].iter()
+				{
+					visibility.set_visible(cluster, self.portals[visible].target);
+				}
+			}
+		}
+
+		return visibility;
+	}
+}
+
+struct PortalFlow<'a>
+{
+	graph: &'a FlowGraph,
+	flood: &'a [PortalSet],
+	finished: &'a [OnceLock<PortalSet>],
+	base_plane: DPlane3,
+	visible: PortalSet,
+}
+
+impl PortalFlow<'_>
+{
+	fn recurse(&mut self, cluster: usize, previous: &FlowStep)
+	{
+		for index in &self.graph.cluster_portals[cluster]
+		{
+			let index: usize = *index;
+
+			if !previous.might_see.contains(index)
+			{
+				continue;
+			}
+
+			// Once a portal has finished, what it can see is all that could
+			// ever be seen through it.
+			let portal: &FlowPortal = &self.graph.portals[index];
+			let limit: &PortalSet = self.finished[index].get().unwrap_or(&self.flood[index]);
+
+			let might_see: PortalSet = previous.might_see.intersection(limit);
+
+			if self.visible.contains(index) && !might_see.has_any_outside(&self.visible)
+			{
+				continue;
+			}
+
+			// Turning back through a portal facing the other way cannot lead
+			// anywhere new.
+			if portal
+				.plane
+				.normal
+				.abs_diff_eq(-previous.plane.normal, 1.0e-6)
+			{
+				continue;
+			}
+
+			let Some(source) = keep_front(&previous.source, &portal.plane.flipped())
+			else
+			{
+				continue;
+			};
+
+			let Some(mut pass) = keep_front(&portal.winding, &self.base_plane)
+			else
+			{
+				continue;
+			};
+
+			// Beyond the first portal, the region that can be seen is bounded
+			// by planes separating the source from the previous pass.
+			if let Some(previous_pass) = &previous.pass
+			{
+				let Some(clipped) = keep_front(&pass, &previous.plane)
+					.and_then(|pass| clip_to_separators(&source, previous_pass, pass, false))
+					.and_then(|pass| clip_to_separators(previous_pass, &source, pass, true))
+				else
+				{
+					continue;
+				};
+
+				pass = clipped;
+			}
+
+			self.visible.insert(index);
+
+			let step: FlowStep = FlowStep {
+				source: source,
+				pass: Some(pass),
+				plane: portal.plane,
+				might_see: might_see,
+			};
+
+			self.recurse(portal.target, &step);
+		}
+	}
+}
+
+// Each portal between clusters becomes two one way portals, one for each
+// direction in which it can be looked through.
+fn one_way_portals(vis_portals: &VisPortals) -> Vec<FlowPortal>
+{
+	return vis_portals
+		.portals
+		.iter()
+		.flat_map(|portal| {
+			let [front, back] = portal.clusters;
+
+			return [
+				FlowPortal {
+					plane: portal.plane,
+					winding: portal.winding.clone(),
+					source: back,
+					target: front,
+				},
+				FlowPortal {
+					plane: portal.plane.flipped(),
+					winding: portal.winding.clone(),
+					source: front,
+					target: back,
+				},
+			];
+		})
+		.collect();
+}
+
+// Clips the target to the planes that pass along an edge of the source and a
+// point of the pass winding, and which have the source entirely behind them
+// and the pass entirely in front. Anything outside these planes cannot be seen
+// from the source through the pass. If flipped, the planes are reversed, for
+// use when the source and pass have been swapped.
+fn clip_to_separators(
+	source: &Winding,
+	pass: &Winding,
+	mut target: Winding,
+	flip: bool,
+) -> Option<Winding>
+{
+	let count: usize = source.points.len();
+
+	for edge_start in 0..count
+	{
+		let edge_end: usize = (edge_start + 1) % count;
+		let edge: DVec3 = source.points[edge_end] - source.points[edge_start];
+
+		for (pass_index, pass_point) in pass.points.iter().enumerate()
+		{
+			let normal: DVec3 = edge.cross(*pass_point - source.points[edge_start]);
+			let length: f64 = normal.length();
+
+			if length < ON_PLANE_EPSILON
+			{
+				continue;
+			}
+
+			let normal: DVec3 = normal / length;
+			let mut plane: DPlane3 = DPlane3::new(normal, normal.dot(*pass_point));
+
+			// Points of the source not on the edge decide which way the plane
+			// faces. If there are none, the source lies on the plane.
+			let Some(source_distance) = source
+				.points
+				.iter()
+				.enumerate()
+				.filter(|(index, _)| *index != edge_start && *index != edge_end)
+				.map(|(_, point)| plane.distance_to(*point))
+				.find(|distance| distance.abs() > ON_PLANE_EPSILON)
+			else
+			{
+				continue;
+			};
+
+			if source_distance > 0.0
+			{
+				plane = plane.flipped();
+			}
+
+			let pass_distances: Vec<f64> = pass
+				.points
+				.iter()
+				.enumerate()
+				.filter(|(index, _)| *index != pass_index)
+				.map(|(_, point)| plane.distance_to(*point))
+				.collect();
+
+			if pass_distances
+				.iter()
+				.any(|distance| *distance < -ON_PLANE_EPSILON)
+				|| !pass_distances
+					.iter()
+					.any(|distance| *distance > ON_PLANE_EPSILON)
+			{
+				continue;
+			}
+
+			if flip
+			{
+				plane = plane.flipped();
+			}
+
+			target = keep_front(&target, &plane)?;
+		}
+	}
+
+	return Some(target);
+}
+
+fn keep_front(winding: &Winding, plane: &DPlane3) -> Option<Winding>
+{
+	return winding.clip(&plane.flipped());
+}
+
+// A set of portal indices.
+#[derive(Clone, Debug, Default)]
+struct PortalSet
+{
+	words: Vec<u64>,
+}
+
+impl PortalSet
+{
+	fn new(len: usize) -> Self
+	{
+		return Self {
+			words: vec![0; len.div_ceil(64)],
+		};
+	}
+
+	fn insert(&mut self, index: usize)
+	{
+		self.words[index / 64] |= 1 << (index % 64);
+	}
+
+	fn contains(&self, index: usize) -> bool
+	{
+		return self.words[index / 64] & (1 << (index % 64)) != 0;
+	}
+
+	fn count(&self) -> usize
+	{
+		return self
+			.words
+			.iter()
+			.map(|word| word.count_ones() as usize)
+			.sum();
+	}
+
+	fn intersection(&self, other: &PortalSet) -> PortalSet
+	{
+		return PortalSet {
+			words: self
+				.words
+				.iter()
+				.zip(&other.words)
+				.map(|(word, other)| word & other)
+				.collect(),
+		};
+	}
+
+	fn has_any_outside(&self, other: &PortalSet) -> bool
+	{
+		return self
+			.words
+			.iter()
+			.zip(&other.words)
+			.any(|(word, other)| word & !other != 0);
+	}
+
+	fn iter(&self) -> impl Iterator<Item = usize> + '_
+	{
+		return self
+			.words
+			.iter()
+			.enumerate()
+			.flat_map(|(word_index, word)| {
+				(0..64)
+					.filter(move |bit| word & (1 << bit) != 0)
+					.map(move |bit| (word_index * 64) + bit)
+			});
+	}
+}
+
+#[cfg(test)]
+mod tests
+{
+	use super::*;
+	use crate::model::VisPortal;
+
+	// A portal on a plane of constant X, spanning the given range of Y and Z
+	// from 0 to 16.
+	fn x_portal(x: f64, min_y: f64, max_y: f64, clusters: [usize; 2]) -> VisPortal
+	{
+		return VisPortal {
+			plane: DPlane3::new(DVec3::X, x),
+			winding: Winding {
+				points: vec![
+					DVec3::new(x, min_y, 16.0),
+					DVec3::new(x, max_y, 16.0),
+					DVec3::new(x, max_y, 0.0),
+					DVec3::new(x, min_y, 0.0),
+				],
+			},
+			clusters: clusters,
+		};
+	}
+
+	// Four clusters in a row, joined by openings that zig-zag from one side
+	// to the other. Nothing in the first cluster lines up with the opening
+	// into the last.
+	fn zig_zag() -> VisPortals
+	{
+		return VisPortals {
+			cluster_count: 4,
+			leaf_clusters: vec![Some(0), Some(1), Some(2), Some(3)],
+			portals: vec![
+				x_portal(0.0, 0.0, 10.0, [1, 0]),
+				x_portal(100.0, 90.0, 100.0, [2, 1]),
+				x_portal(200.0, 0.0, 10.0, [3, 2]),
+			],
+		};
+	}
+
+	#[test]
+	fn fast_vis_floods_through_portals()
+	{
+		let visibility: Visibility = compute_visibility(&zig_zag(), true);
+
+		for from in 0..4
+		{
+			assert_eq!(visibility.visible_count(from), 4);
+		}
+	}
+
+	#[test]
+	fn full_vis_clips_to_line_of_sight()
+	{
+		let visibility: Visibility = compute_visibility(&zig_zag(), false);
+
+		// Each cluster can see its neighbours' neighbours, but no line passes
+		// through all three openings.
+		assert!(visibility.is_visible(0, 2));
+		assert!(visibility.is_visible(1, 3));
+		assert!(!visibility.is_visible(0, 3));
+		assert!(!visibility.is_visible(3, 0));
+		assert_eq!(visibility.visible_count(1), 4);
+	}
+
+	#[test]
+	fn full_vis_sees_along_straight_corridor()
+	{
+		let mut portals: VisPortals = zig_zag();
+		portals.portals[1] = x_portal(100.0, 0.0, 10.0, [2, 1]);
+
+		let visibility: Visibility = compute_visibility(&portals, false);
+
+		for from in 0..4
+		{
+			assert_eq!(visibility.visible_count(from), 4);
+		}
+	}
+}
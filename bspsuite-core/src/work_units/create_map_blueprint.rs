@@ -1,13 +1,67 @@
 use crate::compiler_error::{CompilerError, CompilerErrorCode};
+use crate::extensions::ExtensionList;
+use crate::extensions::map_reader_api::{MapReaderError, call_map_reader_api};
 use crate::model::MapBlueprint;
 use anyhow::Result;
+use bspextifc::map_reader_api::MapReaderCallbacks;
+use log::debug;
 use std::path::PathBuf;
 
-pub fn create_map_blueprint(input_file: std::path::PathBuf) -> Result<MapBlueprint, CompilerError>
+/// Reads the map source file into a blueprint, using whichever extension has
+/// registered itself as being able to read files of this type.
+pub fn create_map_blueprint(
+	extensions: &ExtensionList,
+	input_file: &PathBuf,
+) -> Result<MapBlueprint, CompilerError>
 {
-	// TODO
-	return Err(CompilerError::new(
-		CompilerErrorCode::InternalError,
-		String::from("Not yet implemented"),
-	));
+	let path_string: String = input_file.to_string_lossy().to_string();
+
+	let file_extension: &str = input_file
+		.extension()
+		.and_then(|ext| ext.to_str())
+		.ok_or_else(|| {
+			CompilerError::new(
+				CompilerErrorCode::ArgumentError,
+				format!(
+					"Could not determine the format of {path_string}, as it has no file extension"
+				),
+			)
+		})?;
+
+	let (extension_name, callbacks): (&str, &MapReaderCallbacks) = extensions
+		.iter()
+		.find_map(|extension| {
+			extension
+				.get_api_callbacks()
+				.map_reader_api_callbacks
+				.as_ref()
+				.filter(|callbacks| {
+					callbacks
+						.file_extensions
+						.as_slice()
+						.iter()
+						.any(|ext| ext.as_str().eq_ignore_ascii_case(file_extension))
+				})
+				.map(|callbacks| (extension.get_name(), callbacks))
+		})
+		.ok_or_else(|| {
+			CompilerError::new(
+				CompilerErrorCode::ArgumentError,
+				format!("No loaded extension supports reading .{file_extension} files"),
+			)
+		})?;
+
+	debug!("Reading {path_string} using extension {extension_name}");
+
+	return call_map_reader_api(callbacks.read_map_fn, input_file).map_err(|err| match err
+	{
+		MapReaderError::Failed(message) => CompilerError::new(
+			CompilerErrorCode::IoError,
+			format!("Failed to read {path_string}. {message}"),
+		),
+		MapReaderError::SyntaxError(diagnostic) =>
+		{
+			CompilerError::from_diagnostic(CompilerErrorCode::SyntaxError, diagnostic)
+		}
+	});
 }
@@ -0,0 +1,331 @@
+use crate::model::{
+	BlueprintEntity, BrushGeometry, Contents, CsgHull, DPlane3, EntityHull, HullFace, MapBlueprint,
+	ON_PLANE_EPSILON, PlanePool, PlaneSide, Winding,
+};
+use glam::DVec3;
+use log::debug;
+
+// Everything about a brush that is needed to clip other brushes against it.
+struct CsgBrush
+{
+	planes: Vec<DPlane3>,
+	windings: Vec<Option<Winding>>,
+	contents: Contents,
+	mins: DVec3,
+	maxs: DVec3,
+}
+
+impl CsgBrush
+{
+	fn overlaps(&self, other: &CsgBrush) -> bool
+	{
+		let epsilon: DVec3 = DVec3::splat(ON_PLANE_EPSILON);
+
+		return (self.mins - epsilon).cmple(other.maxs).all()
+			&& (other.mins - epsilon).cmple(self.maxs).all();
+	}
+}
+
+/// Clips the brushes of each entity against each other, discarding the parts
+/// of faces that are hidden inside other brushes. Where brushes of different
+/// contents overlap, the contents with the higher priority occupy the
+/// overlapping volume, so faces of lower priority brushes are hidden inside
+/// it. Faces of higher priority brushes remain visible from within lower
+/// priority brushes (eg. a solid wall that extends into water). Where faces
+/// of brushes with the same contents coincide, only one of them is kept.
+pub fn build_csg_hull(blueprint: &MapBlueprint) -> CsgHull
+{
+	let hull: CsgHull = CsgHull {
+		entities: blueprint
+			.entities
+			.iter()
+			.map(|entity| build_entity_hull(entity, &blueprint.planes))
+			.collect(),
+	};
+
+	debug!(
+		"CSG produced {} visible face fragment(s) across {} entities",
+		hull.face_count(),
+		hull.entities.len()
+	);
+
+	return hull;
+}
+
+fn build_entity_hull(entity: &BlueprintEntity, planes: &PlanePool) -> EntityHull
+{
+	let brushes: Vec<Option<CsgBrush>> = entity
+		.brushes
+		.iter()
+		.map(|brush| {
+			// If a brush's faces have different materials, the contents with
+			// the highest priority are used.
+			let contents: Contents = brush
+				.faces
+				.iter()
+				.map(|face| Contents::from_material(&face.material))
+				.max_by_key(Contents::priority)?;
+
+			// Clip brushes only block movement, so are left out of the visible
+			// hull and only appear in the expanded clipping hulls.
+			if contents == Contents::Clip
+			{
+				return None;
+			}
+
+			let geometry: BrushGeometry = brush.geometry(planes);
+
+			// Open brushes can't be clipped against meaningfully. Validation
+			// will already have reported these.
+			if !geometry.is_valid()
+			{
+				return None;
+			}
+
+			let (mins, maxs) = geometry.bounds()?;
+
+			return Some(CsgBrush {
+				planes: brush.faces.iter().map(|face| planes[face.plane]).collect(),
+				windings: geometry.windings,
+				contents: contents,
+				mins: mins,
+				maxs: maxs,
+			});
+		})
+		.collect();
+
+	let mut hull: EntityHull = EntityHull::default();
+
+	for (brush_index, brush) in brushes.iter().enumerate()
+	{
+		let Some(brush) = brush
+		else
+		{
+			continue;
+		};
+
+		for (face_index, winding) in brush.windings.iter().enumerate()
+		{
+			let Some(winding) = winding
+			else
+			{
+				continue;
+			};
+
+			let face_plane: &DPlane3 = &brush.planes[face_index];
+			let mut fragments: Vec<Winding> = vec![winding.clone()];
+
+			for (other_index, other) in brushes.iter().enumerate()
+			{
+				let Some(other) = other
+				else
+				{
+					continue;
+				};
+
+				if other_index == brush_index || !brush.overlaps(other)
+				{
+					continue;
+				}
+
+				fragments = clip_fragments_against_brush(
+					fragments,
+					face_plane,
+					brush.contents,
+					other,
+					other_index > brush_index,
+				);
+
+				if fragments.is_empty()
+				{
+					break;
+				}
+			}
+
+			hull.faces
+				.extend(fragments.into_iter().map(|fragment| HullFace {
+					plane: entity.brushes[brush_index].faces[face_index].plane,
+					winding: fragment,
+					contents: brush.contents,
+					brush: brush_index,
+					face: face_index,
+				}));
+		}
+	}
+
+	return hull;
+}
+
+// Returns the fragments that remain visible once the other brush has been
+// taken into account. If the other brush comes after the face's own brush, it
+// takes precedence when faces coincide.
+fn clip_fragments_against_brush(
+	fragments: Vec<Winding>,
+	face_plane: &DPlane3,
+	contents: Contents,
+	other: &CsgBrush,
+	other_is_later: bool,
+) -> Vec<Winding>
+{
+	let other_wins: bool = other.contents.priority() > contents.priority()
+		|| (other.contents == contents && other_is_later);
+
+	let inside_is_hidden: bool = other.contents.priority() >= contents.priority();
+	let mut output: Vec<Winding> = Vec::with_capacity(fragments.len());
+
+	for fragment in fragments
+	{
+		let (outside, inside) = split_by_brush(fragment, face_plane, other, other_wins);
+		output.extend(outside);
+
+		if let Some(inside) = inside
+			&& !inside_is_hidden
+		{
+			output.push(inside);
+		}
+	}
+
+	return output;
+}
+
+// Splits the winding into the parts outside the brush, and the part inside it
+// (if any). A winding that lies on one of the brush's faces and faces the same
+// way is considered inside only if coplanar_is_inside is set. A winding that
+// lies on one of the brush's faces but faces the opposite way (ie. into the
+// brush) is always considered inside.
+fn split_by_brush(
+	winding: Winding,
+	face_plane: &DPlane3,
+	brush: &CsgBrush,
+	coplanar_is_inside: bool,
+) -> (Vec<Winding>, Option<Winding>)
+{
+	let mut outside: Vec<Winding> = Vec::new();
+	let mut remaining: Winding = winding;
+
+	for plane in &brush.planes
+	{
+		match plane.classify_winding(&remaining, ON_PLANE_EPSILON)
+		{
+			PlaneSide::Back => (),
+			PlaneSide::Front =>
+			{
+				outside.push(remaining);
+				return (outside, None);
+			}
+			PlaneSide::On =>
+			{
+				if face_plane.normal.dot(plane.normal) > 0.0 && !coplanar_is_inside
+				{
+					outside.push(remaining);
+					return (outside, None);
+				}
+			}
+			PlaneSide::Spanning =>
+			{
+				outside.extend(remaining.clip(&plane.flipped()));
+
+				match remaining.clip(plane)
+				{
+					Some(back) => remaining = back,
+					None => return (outside, None),
+				}
+			}
+		}
+	}
+
+	return (outside, Some(remaining));
+}
+
+#[cfg(test)]
+mod tests
+{
+	use super::*;
+	use crate::work_units::test_fixtures::{box_planes, make_blueprint};
+
+	fn area_with_contents(hull: &CsgHull, contents: Contents) -> f64
+	{
+		return hull.entities[0]
+			.faces
+			.iter()
+			.filter(|face| face.contents == contents)
+			.map(|face| face.winding.area())
+			.sum();
+	}
+
+	#[test]
+	fn merge_overlapping_solid_brushes()
+	{
+		let blueprint: MapBlueprint = make_blueprint(&[
+			(
+				box_planes(DVec3::splat(-32.0), DVec3::splat(32.0)),
+				"AAATRIGGER",
+			),
+			(
+				box_planes(DVec3::new(0.0, -32.0, -32.0), DVec3::new(64.0, 32.0, 32.0)),
+				"AAATRIGGER",
+			),
+		]);
+
+		let hull: CsgHull = build_csg_hull(&blueprint);
+
+		// The visible faces form the surface of a single 96x64x64 box.
+		let expected_area: f64 = (4.0 * 96.0 * 64.0) + (2.0 * 64.0 * 64.0);
+		assert_eq!(area_with_contents(&hull, Contents::Solid), expected_area);
+	}
+
+	#[test]
+	fn remove_faces_between_touching_brushes()
+	{
+		let blueprint: MapBlueprint = make_blueprint(&[
+			(
+				box_planes(DVec3::splat(-32.0), DVec3::splat(32.0)),
+				"AAATRIGGER",
+			),
+			(
+				box_planes(DVec3::new(32.0, -32.0, -32.0), DVec3::new(96.0, 32.0, 32.0)),
+				"AAATRIGGER",
+			),
+		]);
+
+		let hull: CsgHull = build_csg_hull(&blueprint);
+
+		// Each brush loses the face that touches the other brush.
+		assert_eq!(hull.entities[0].faces.len(), 10);
+		assert!(
+			hull.entities[0]
+				.faces
+				.iter()
+				.all(|face| { face.winding.points.iter().any(|point| point.x != 32.0) })
+		);
+	}
+
+	#[test]
+	fn clip_water_by_content_priority()
+	{
+		let blueprint: MapBlueprint = make_blueprint(&[
+			(
+				box_planes(DVec3::new(-64.0, -64.0, -64.0), DVec3::new(64.0, 64.0, 0.0)),
+				"!WATERBLUE",
+			),
+			(
+				box_planes(
+					DVec3::new(-16.0, -16.0, -32.0),
+					DVec3::new(16.0, 16.0, 32.0),
+				),
+				"AAATRIGGER",
+			),
+		]);
+
+		let hull: CsgHull = build_csg_hull(&blueprint);
+
+		// All of the solid brush remains visible, including the parts within
+		// the water.
+		let solid_area: f64 = (2.0 * 32.0 * 32.0) + (4.0 * 32.0 * 64.0);
+		assert_eq!(area_with_contents(&hull, Contents::Solid), solid_area);
+
+		// The part of the water's surface inside the solid brush is hidden.
+		let water_area: f64 = (2.0 * 128.0 * 128.0) + (4.0 * 128.0 * 64.0) - (32.0 * 32.0);
+		assert_eq!(area_with_contents(&hull, Contents::Water), water_area);
+	}
+}
@@ -0,0 +1,400 @@
+use crate::compiler_error::{CompilerError, CompilerErrorCode};
+use crate::model::{BspNode, BspTree, Contents, MapBlueprint, PlanePool, PortalGraph};
+use glam::DVec3;
+use log::{debug, info, warn};
+use std::collections::VecDeque;
+use std::path::PathBuf;
+use std::{fs, io};
+
+// Distance between the points written to a .pts file.
+const POINTFILE_STEP: f64 = 2.0;
+
+// An entity from which the flood fill starts.
+struct FloodSource
+{
+	entity: usize,
+	origin: DVec3,
+	leaf: usize,
+}
+
+// How the flood fill reached a leaf.
+#[derive(Clone, Copy)]
+struct FloodStep
+{
+	source: usize,
+
+	// Portal through which the leaf was entered, or none if the leaf contains
+	// the source.
+	portal: Option<usize>,
+}
+
+/// Flood fills the leaves of the world tree from the origins of point
+/// entities. If the fill reaches the void outside the map, the map leaks:
+/// the shortest path from an entity to the void is written next to the map
+/// as a .pts and a .lin file, and an error is returned. Otherwise, every
+/// leaf that was not reached is filled in as solid, along with the faces
+/// that could only be seen from such leaves.
+pub fn fill_outside(
+	blueprint: &MapBlueprint,
+	tree: &mut BspTree,
+	portals: &PortalGraph,
+	source_file: &PathBuf,
+) -> Result<(), CompilerError>
+{
+	let sources: Vec<FloodSource> = find_flood_sources(blueprint, tree, &blueprint.planes);
+
+	if sources.is_empty()
+	{
+		warn!("No entities are in empty space, so the map cannot be checked for leaks");
+		return Ok(());
+	}
+
+	let steps: Vec<Option<FloodStep>> = flood(tree, portals, &sources);
+
+	if let Some(outside_step) = steps[portals.outside_leaf]
+	{
+		let source: &FloodSource = &sources[outside_step.source];
+		let path: Vec<DVec3> = leak_path(portals, &steps, source);
+
+		write_pointfiles(source_file, &path)?;
+
+		let classname: &str = blueprint.entities[source.entity]
+			.keyvalues
+			.first("classname")
+			.unwrap_or("<no classname>");
+
+		return Err(CompilerError::new(
+			CompilerErrorCode::LeakError,
+			format!(
+				"Map leaks: entity {} ({classname}) at ({} {} {}) can reach the void. The leak path was written to {}",
+				source.entity,
+				source.origin.x,
+				source.origin.y,
+				source.origin.z,
+				source_file.with_extension("pts").to_string_lossy()
+			),
+		));
+	}
+
+	remove_pointfiles(source_file)?;
+
+	let mut filled_count: usize = 0;
+
+	for (leaf, step) in tree.leaves.iter_mut().zip(steps.iter())
+	{
+		if step.is_none() && leaf.contents != Contents::Solid
+		{
+			leaf.contents = Contents::Solid;
+			leaf.faces.clear();
+			filled_count += 1;
+		}
+	}
+
+	// Faces that are no longer visible from any leaf are removed from their
+	// nodes, so that they are not written out.
+	let mut visible: Vec<bool> = vec![false; tree.faces.len()];
+	tree.leaves
+		.iter()
+		.flat_map(|leaf| leaf.faces.iter())
+		.for_each(|face| visible[*face] = true);
+
+	let face_count: usize = tree.nodes.iter().map(|node| node.faces.len()).sum();
+	tree.nodes
+		.iter_mut()
+		.for_each(|node: &mut BspNode| node.faces.retain(|face| visible[*face]));
+	let removed_count: usize = face_count
+		- tree
+			.nodes
+			.iter()
+			.map(|node| node.faces.len())
+			.sum::<usize>();
+
+	info!("Filled {filled_count} leaves outside the map, removing {removed_count} faces");
+	return Ok(());
+}
+
+fn find_flood_sources(
+	blueprint: &MapBlueprint,
+	tree: &BspTree,
+	planes: &PlanePool,
+) -> Vec<FloodSource>
+{
+	let mut sources: Vec<FloodSource> = Vec::new();
+
+	for (index, entity) in blueprint.entities.iter().enumerate()
+	{
+		// Brush entities are part of the map's structure, rather than things
+		// placed within it.
+		if !entity.brushes.is_empty()
+		{
+			continue;
+		}
+
+		let Some(origin) = entity.origin()
+		else
+		{
+			continue;
+		};
+
+		let leaf: usize = tree.leaf_for_point(origin, planes);
+
+		if tree.leaves[leaf].contents.seals_map()
+		{
+			debug!("Entity {index} is inside solid, so will not be used to check for leaks");
+			continue;
+		}
+
+		sources.push(FloodSource {
+			entity: index,
+			origin: origin,
+			leaf: leaf,
+		});
+	}
+
+	return sources;
+}
+
+// Breadth first search outwards from all sources at once, so that the first
+// path found to any leaf is the shortest one from any source. The outside
+// leaf is never passed through.
+fn flood(tree: &BspTree, portals: &PortalGraph, sources: &[FloodSource]) -> Vec<Option<FloodStep>>
+{
+	let mut steps: Vec<Option<FloodStep>> = vec![None; tree.leaves.len() + 1];
+	let mut queue: VecDeque<usize> = VecDeque::new();
+
+	for (index, source) in sources.iter().enumerate()
+	{
+		if steps[source.leaf].is_none()
+		{
+			steps[source.leaf] = Some(FloodStep {
+				source: index,
+				portal: None,
+			});
+			queue.push_back(source.leaf);
+		}
+	}
+
+	while let Some(leaf) = queue.pop_front()
+	{
+		let source: usize = steps[leaf].map(|step| step.source).unwrap_or_default();
+
+		for portal in &portals.leaf_portals[leaf]
+		{
+			let neighbour: usize = portals.portals[*portal].neighbour(leaf);
+
+			if steps[neighbour].is_some()
+			{
+				continue;
+			}
+
+			steps[neighbour] = Some(FloodStep {
+				source: source,
+				portal: Some(*portal),
+			});
+
+			if neighbour == portals.outside_leaf
+			{
+				return steps;
+			}
+
+			if !tree.leaves[neighbour].contents.seals_map()
+			{
+				queue.push_back(neighbour);
+			}
+		}
+	}
+
+	return steps;
+}
+
+// Returns the points along the path from the source to the void, passing
+// through the centre of each portal on the way.
+fn leak_path(portals: &PortalGraph, steps: &[Option<FloodStep>], source: &FloodSource)
+-> Vec<DVec3>
+{
+	let mut path: Vec<DVec3> = Vec::new();
+	let mut leaf: usize = portals.outside_leaf;
+
+	while let Some(FloodStep {
+		portal: Some(portal),
+		..
+	}) = steps[leaf]
+	{
+		path.push(portals.portals[portal].winding.center());
+		leaf = portals.portals[portal].neighbour(leaf);
+	}
+
+	path.push(source.origin);
+	path.reverse();
+	return path;
+}
+
+fn write_pointfiles(source_file: &PathBuf, path: &[DVec3]) -> Result<(), CompilerError>
+{
+	let format_point = |point: &DVec3| -> String {
+		return format!("{} {} {}\n", point.x, point.y, point.z);
+	};
+
+	// Quake-derived editors draw the leak as a trail of points, spaced evenly
+	// along the path.
+	let mut pts_text: String = String::new();
+
+	for segment in path.windows(2)
+	{
+		let direction: DVec3 = segment[1] - segment[0];
+		let steps: usize = (direction.length() / POINTFILE_STEP).ceil().max(1.0) as usize;
+
+		for step in 0..steps
+		{
+			pts_text.push_str(&format_point(
+				&(segment[0] + (direction * (step as f64 / steps as f64))),
+			));
+		}
+	}
+
+	pts_text.extend(path.last().map(format_point));
+
+	// Half-Life editors draw a line through each point on the path.
+	let lin_text: String = path.iter().map(format_point).collect();
+
+	write_pointfile(&source_file.with_extension("pts"), &pts_text)?;
+	write_pointfile(&source_file.with_extension("lin"), &lin_text)?;
+	return Ok(());
+}
+
+fn write_pointfile(path: &PathBuf, text: &str) -> Result<(), CompilerError>
+{
+	return fs::write(path, text).map_err(|err| {
+		CompilerError::new(
+			CompilerErrorCode::IoError,
+			format!("Failed to write {}. {err}", path.to_string_lossy()),
+		)
+	});
+}
+
+// Removes any pointfiles left behind by a previous compile, so that they are
+// not mistaken for a leak in the current one.
+fn remove_pointfiles(source_file: &PathBuf) -> Result<(), CompilerError>
+{
+	for extension in ["pts", "lin"]
+	{
+		let path: PathBuf = source_file.with_extension(extension);
+
+		match fs::remove_file(&path)
+		{
+			Ok(()) => debug!("Removed stale pointfile {}", path.to_string_lossy()),
+			Err(err) if err.kind() == io::ErrorKind::NotFound => (),
+			Err(err) =>
+			{
+				return Err(CompilerError::new(
+					CompilerErrorCode::IoError,
+					format!("Failed to remove {}. {err}", path.to_string_lossy()),
+				));
+			}
+		}
+	}
+
+	return Ok(());
+}
+
+#[cfg(test)]
+mod tests
+{
+	use super::*;
+	use crate::game_config::BspConfig;
+	use crate::model::{CsgHull, DPlane3};
+	use crate::work_units::test_fixtures::{add_entity, make_blueprint, room_brushes};
+	use crate::work_units::{build_bsp_trees, build_csg_hull, build_portals};
+
+	fn room_blueprint(wall_count: usize) -> MapBlueprint
+	{
+		let brushes: Vec<(Vec<DPlane3>, &str)> =
+			room_brushes(DVec3::splat(-64.0), DVec3::splat(64.0), 16.0)
+				.into_iter()
+				.take(wall_count)
+				.map(|planes| (planes, "AAATRIGGER"))
+				.collect();
+
+		let mut blueprint: MapBlueprint = make_blueprint(&brushes);
+		add_entity(&mut blueprint, "info_player_start", &[])
+			.keyvalues
+			.push(String::from("origin"), String::from("0 0 0"));
+
+		return blueprint;
+	}
+
+	fn fill(blueprint: &MapBlueprint, source_file: &PathBuf)
+	-> (BspTree, Result<(), CompilerError>)
+	{
+		let hull: CsgHull = build_csg_hull(blueprint);
+		let mut tree: BspTree = build_bsp_trees(blueprint, &hull, &BspConfig::default())
+			.models
+			.remove(0)
+			.tree;
+
+		let portals: PortalGraph = build_portals(&tree, &blueprint.planes);
+		let result: Result<(), CompilerError> =
+			fill_outside(blueprint, &mut tree, &portals, source_file);
+
+		return (tree, result);
+	}
+
+	fn temp_map_path(name: &str) -> PathBuf
+	{
+		let directory: PathBuf =
+			std::env::temp_dir().join(format!("bspsuite-fill-outside-{}", std::process::id()));
+		fs::create_dir_all(&directory).unwrap();
+		return directory.join(name);
+	}
+
+	#[test]
+	fn fill_outside_of_sealed_room()
+	{
+		let blueprint: MapBlueprint = room_blueprint(6);
+		let source_file: PathBuf = temp_map_path("sealed.map");
+		fs::write(source_file.with_extension("pts"), "stale").unwrap();
+
+		let (tree, result) = fill(&blueprint, &source_file);
+		assert!(result.is_ok());
+		assert!(!source_file.with_extension("pts").exists());
+
+		let inside: usize = tree.leaf_for_point(DVec3::ZERO, &blueprint.planes);
+		let outside: usize = tree.leaf_for_point(DVec3::splat(90.0), &blueprint.planes);
+
+		assert_eq!(tree.leaves[inside].contents, Contents::Empty);
+		assert_eq!(tree.leaves[outside].contents, Contents::Solid);
+
+		// Only the six faces on the inside of the room remain.
+		assert_eq!(
+			tree.nodes
+				.iter()
+				.map(|node| node.faces.len())
+				.sum::<usize>(),
+			6
+		);
+	}
+
+	#[test]
+	fn report_leak_from_open_room()
+	{
+		// Leave off the ceiling of the room.
+		let blueprint: MapBlueprint = room_blueprint(5);
+		let source_file: PathBuf = temp_map_path("leaky.map");
+
+		let (_, result) = fill(&blueprint, &source_file);
+		let err: CompilerError = result.unwrap_err();
+
+		assert!(matches!(err.code, CompilerErrorCode::LeakError));
+		assert!(
+			err.description
+				.contains("entity 1 (info_player_start) at (0 0 0)")
+		);
+
+		let lin_text: String = fs::read_to_string(source_file.with_extension("lin")).unwrap();
+		let pts_text: String = fs::read_to_string(source_file.with_extension("pts")).unwrap();
+
+		assert_eq!(lin_text.lines().next(), Some("0 0 0"));
+		assert!(lin_text.lines().count() >= 2);
+		assert!(pts_text.lines().count() > lin_text.lines().count());
+	}
+}
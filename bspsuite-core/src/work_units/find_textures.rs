@@ -0,0 +1,304 @@
+use crate::compiler_error::{CompilerError, CompilerErrorCode};
+use crate::extensions::ExtensionList;
+use crate::extensions::texture_api::call_texture_api;
+use crate::game_config::TextureConfig;
+use crate::model::{BlueprintTexture, EntityKeyValues, MapBlueprint};
+use bspextifc::texture_api::TextureProvider;
+use log::{debug, warn};
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::path::PathBuf;
+
+// Number of brush lines listed when reporting a missing texture.
+const MAX_LISTED_BRUSHES: usize = 8;
+
+// Where a material is used in the map.
+#[derive(Default)]
+struct MaterialUsage
+{
+	// Name as it is first written in the map.
+	name: String,
+	face_count: usize,
+	brush_lines: BTreeSet<u32>,
+}
+
+/// Asks the texture providers registered by extensions for the textures of
+/// the materials used by the map, and stores those that are found in the
+/// blueprint. Providers are asked in order of descending priority, and each is
+/// only asked for the materials that no provider before it found. Materials
+/// that are not found are reported along with the brushes that use them.
+pub fn find_textures(
+	extensions: &ExtensionList,
+	blueprint: &mut MapBlueprint,
+	input_file: &PathBuf,
+	config: &TextureConfig,
+) -> Result<(), CompilerError>
+{
+	let providers: Vec<(&str, &TextureProvider)> = extensions
+		.iter()
+		.flat_map(|extension| {
+			extension
+				.get_api_callbacks()
+				.texture_api_callbacks
+				.iter()
+				.flat_map(|callbacks| callbacks.providers.as_slice())
+				.map(|provider| (extension.get_name(), provider))
+		})
+		.collect();
+
+	let textures: HashMap<String, BlueprintTexture> =
+		find_with_providers(&providers, blueprint, input_file, &config.search_paths)?;
+
+	blueprint.textures.extend(textures);
+	return Ok(());
+}
+
+fn find_with_providers(
+	providers: &[(&str, &TextureProvider)],
+	blueprint: &MapBlueprint,
+	input_file: &PathBuf,
+	search_paths: &[String],
+) -> Result<HashMap<String, BlueprintTexture>, CompilerError>
+{
+	let usages: BTreeMap<String, MaterialUsage> = material_usages(blueprint);
+	let no_keyvalues: EntityKeyValues = EntityKeyValues::new();
+	let world_keyvalues: &EntityKeyValues = blueprint
+		.entities
+		.iter()
+		.map(|entity| &entity.keyvalues)
+		.find(|keyvalues| keyvalues.last("classname") == Some("worldspawn"))
+		.unwrap_or(&no_keyvalues);
+
+	let mut providers: Vec<(&str, &TextureProvider)> = providers.to_vec();
+	providers.sort_by_key(|(_, provider)| Reverse(provider.priority));
+
+	let mut textures: HashMap<String, BlueprintTexture> = HashMap::new();
+
+	for (extension_name, provider) in providers
+	{
+		let remaining: Vec<String> = usages
+			.iter()
+			.filter(|(material, _)| !textures.contains_key(*material))
+			.map(|(_, usage)| usage.name.clone())
+			.collect();
+
+		if remaining.is_empty()
+		{
+			break;
+		}
+
+		let found: HashMap<String, BlueprintTexture> = call_texture_api(
+			provider.find_textures_fn,
+			input_file,
+			world_keyvalues,
+			search_paths,
+			&remaining,
+		)
+		.map_err(|message| {
+			CompilerError::new(
+				CompilerErrorCode::IoError,
+				format!(
+					"Texture provider {} of extension {extension_name} failed. {message}",
+					provider.name.as_str()
+				),
+			)
+		})?;
+
+		debug!(
+			"Texture provider {} of extension {extension_name} found {} of {} texture(s)",
+			provider.name.as_str(),
+			found.len(),
+			remaining.len()
+		);
+
+		textures.extend(found);
+	}
+
+	for (material, usage) in &usages
+	{
+		if !textures.contains_key(material)
+		{
+			warn!("{}", describe_missing(usage));
+		}
+	}
+
+	return Ok(textures);
+}
+
+// Materials used by the map's brush faces, keyed by their name in lower case.
+fn material_usages(blueprint: &MapBlueprint) -> BTreeMap<String, MaterialUsage>
+{
+	let mut usages: BTreeMap<String, MaterialUsage> = BTreeMap::new();
+
+	for brush in blueprint.entities.iter().flat_map(|entity| &entity.brushes)
+	{
+		for face in &brush.faces
+		{
+			let usage: &mut MaterialUsage = usages
+				.entry(face.material.to_ascii_lowercase())
+				.or_default();
+
+			if usage.name.is_empty()
+			{
+				usage.name = face.material.clone();
+			}
+
+			usage.face_count += 1;
+			usage.brush_lines.insert(brush.source_line);
+		}
+	}
+
+	return usages;
+}
+
+fn describe_missing(usage: &MaterialUsage) -> String
+{
+	let mut lines: Vec<String> = usage
+		.brush_lines
+		.iter()
+		.take(MAX_LISTED_BRUSHES)
+		.map(u32::to_string)
+		.collect();
+
+	if usage.brush_lines.len() > MAX_LISTED_BRUSHES
+	{
+		lines.push(format!(
+			"and {} more",
+			usage.brush_lines.len() - MAX_LISTED_BRUSHES
+		));
+	}
+
+	return format!(
+		"No texture was found for material {}, which is used by {} face(s) on the brush(es) at line(s) {}",
+		usage.name,
+		usage.face_count,
+		lines.join(", ")
+	);
+}
+
+#[cfg(test)]
+mod tests
+{
+	use super::*;
+	use crate::model::DPlane3;
+	use crate::work_units::test_fixtures::{add_entity, box_planes, make_blueprint};
+	use bspextifc::texture_api::{FindTexturesArgs, FindTexturesResult, TextureApi, TextureArgs};
+	use bspextifc::{SliceRef, StringRef};
+	use glam::DVec3;
+
+	// Provides a square texture of the given size for each of the named
+	// materials, whether or not it was asked for.
+	fn provide(api: &mut TextureApi, names: &[&str], size: u32)
+	{
+		for name in names
+		{
+			api.add_texture(&TextureArgs {
+				name: StringRef::from(*name),
+				width: size,
+				height: size,
+				average_color: [0.0; 3],
+				has_average_color: false,
+				pixels: SliceRef::from(&[][..]),
+				palette: SliceRef::from(&[][..]),
+			});
+		}
+	}
+
+	extern "C" fn find_walls(api: &mut TextureApi, _: &FindTexturesArgs) -> FindTexturesResult
+	{
+		provide(api, &["wall"], 32);
+		return FindTexturesResult::Success;
+	}
+
+	extern "C" fn find_everything(
+		api: &mut TextureApi,
+		args: &FindTexturesArgs,
+	) -> FindTexturesResult
+	{
+		let is_world: bool = args
+			.world_keyvalues
+			.as_slice()
+			.iter()
+			.any(|keyvalue| keyvalue.value.to_string() == "worldspawn");
+
+		if !is_world
+		{
+			api.set_error("Expected the world's keyvalues");
+			return FindTexturesResult::Failure;
+		}
+
+		provide(api, &["WALL", "Door", "Unused"], 16);
+		return FindTexturesResult::Success;
+	}
+
+	extern "C" fn fail(api: &mut TextureApi, _: &FindTexturesArgs) -> FindTexturesResult
+	{
+		api.set_error("Archive is corrupt");
+		return FindTexturesResult::Failure;
+	}
+
+	#[test]
+	fn chain_providers_by_priority()
+	{
+		let brushes: Vec<(Vec<DPlane3>, &str)> = vec![
+			(box_planes(DVec3::splat(-64.0), DVec3::splat(-32.0)), "WALL"),
+			(box_planes(DVec3::splat(32.0), DVec3::splat(64.0)), "SKY"),
+		];
+		let mut blueprint: MapBlueprint = make_blueprint(&brushes);
+		add_entity(
+			&mut blueprint,
+			"func_door",
+			&[(box_planes(DVec3::splat(-8.0), DVec3::splat(8.0)), "DOOR")],
+		);
+
+		let walls: TextureProvider = TextureProvider {
+			name: StringRef::new("walls"),
+			priority: 10,
+			find_textures_fn: find_walls,
+		};
+		let everything: TextureProvider = TextureProvider {
+			name: StringRef::new("everything"),
+			priority: 0,
+			find_textures_fn: find_everything,
+		};
+		let failing: TextureProvider = TextureProvider {
+			name: StringRef::new("failing"),
+			priority: -1,
+			find_textures_fn: fail,
+		};
+
+		// The lower priority provider is listed first, but is asked second,
+		// and the wall texture that it provides again is ignored.
+		let textures: HashMap<String, BlueprintTexture> = find_with_providers(
+			&[("ext1", &everything), ("ext2", &walls)],
+			&blueprint,
+			&PathBuf::from("test.map"),
+			&[],
+		)
+		.unwrap();
+
+		let mut names: Vec<&str> = textures.keys().map(String::as_str).collect();
+		names.sort();
+		assert_eq!(names, vec!["door", "wall"]);
+		assert_eq!(textures["wall"].size, [32, 32]);
+		assert_eq!(textures["door"].size, [16, 16]);
+
+		// A provider that fails stops the search.
+		assert!(
+			find_with_providers(
+				&[("ext1", &walls), ("ext2", &failing)],
+				&blueprint,
+				&PathBuf::from("test.map"),
+				&[],
+			)
+			.is_err()
+		);
+
+		let usages: BTreeMap<String, MaterialUsage> = material_usages(&blueprint);
+		assert_eq!(usages["door"].face_count, 6);
+		assert!(
+			describe_missing(&usages["sky"])
+				.starts_with("No texture was found for material SKY, which is used by 6 face(s)")
+		);
+	}
+}
@@ -0,0 +1,1184 @@
+use crate::compiler_error::{CompilerError, CompilerErrorCode};
+use crate::game_config::LightingConfig;
+use crate::model::{
+	BlueprintBrushFace, BlueprintEntity, BspFace, BspModels, BspTree, Contents,
+	DEFAULT_LIGHT_STYLE, DPlane3, FaceLightmap, Lightmaps, MAX_FACE_LIGHT_STYLES, MapBlueprint,
+	TextureLights, TextureProjection, VisPortals, Visibility, Winding, light_from_values,
+};
+use crate::work_units::{Patch, Transfers, bounce_light, cached_transfers, run_on_threads};
+use glam::{DMat3, DVec2, DVec3};
+use log::{debug, warn};
+use std::collections::HashMap;
+use std::f64::consts::PI;
+use std::path::PathBuf;
+
+// Samples are moved this far off their face, so that tracing from them does
+// not immediately hit the brush behind the face.
+const SAMPLE_OFFSET: f64 = 1.0;
+
+// Samples that fall outside their face, or within the sample offset of its
+// edges, are moved towards its centre in steps of this size until they are
+// inside it.
+const SAMPLE_NUDGE_DISTANCE: f64 = 2.0;
+
+// A point light's brightness is received in full by a face directly facing it
+// at this distance, and falls off with the square of the distance beyond it.
+const POINT_LIGHT_REFERENCE_DISTANCE: f64 = 128.0;
+
+// Brightness of a light entity that does not specify one.
+const DEFAULT_LIGHT_BRIGHTNESS: f64 = 300.0;
+
+// Angles in degrees from the axis of a spotlight within which it is at full
+// brightness, and beyond which it gives no light, if not specified.
+const DEFAULT_SPOT_INNER_CONE: f64 = 10.0;
+const DEFAULT_SPOT_OUTER_CONE: f64 = 20.0;
+
+// Rays towards the sky are traced this far before giving up.
+const SKY_TRACE_DISTANCE: f64 = 65536.0;
+
+// Number of directions in which rays are traced to find how much of the sky a
+// sample can see.
+const SKY_SAMPLE_DIRECTIONS: usize = 64;
+
+// Faces that emit light are divided into squares of roughly this size, each of
+// which is treated as a separate light.
+const TEXTURE_LIGHT_PATCH_SIZE: f64 = 64.0;
+
+// Light entities that can be switched on and off by name are given styles
+// from this one upwards, matching the original tools.
+const FIRST_SWITCHABLE_STYLE: u8 = 32;
+
+// Style 255 marks an unused style slot of a face, so cannot be given to a
+// light.
+const LAST_SWITCHABLE_STYLE: u8 = 254;
+
+// Style samples whose channels are all below this round to black, so the style
+// is not kept.
+const MIN_STYLE_BRIGHTNESS: f64 = 0.5;
+
+/// Computes a lightmap for each lit face of every model, from the light
+/// entities in the map and from faces whose materials emit light. Light
+/// entities that have a targetname but no style are given a style of their
+/// own, so that the game can switch them, and the style is added to their
+/// keyvalues. This fails if there are more targetnames than styles. Only the
+/// world casts shadows.
+///
+/// Light of the default style is then bounced between the lit faces, as set
+/// by the configuration. The transfers of light between parts of faces are
+/// cached in a .transfers file next to the map source file, so that they do
+/// not need to be computed again while the map's geometry is unchanged.
+pub fn compute_lighting(
+	blueprint: &mut MapBlueprint,
+	models: &BspModels,
+	vis_portals: &VisPortals,
+	visibility: &Visibility,
+	texture_lights: &TextureLights,
+	config: &LightingConfig,
+	source_file: &PathBuf,
+) -> Result<Lightmaps, CompilerError>
+{
+	assign_light_styles(blueprint)?;
+
+	let mut lights: Vec<Light> = entity_lights(blueprint);
+	let entity_light_count: usize = lights.len();
+	lights.extend(surface_lights(blueprint, models, texture_lights));
+
+	debug!(
+		"Found {entity_light_count} light entities and {} texture light patches",
+		lights.len() - entity_light_count
+	);
+
+	let Some(world) = models.models.first()
+	else
+	{
+		return Ok(Lightmaps::default());
+	};
+
+	let mut scene: Scene = Scene {
+		blueprint: blueprint,
+		world: &world.tree,
+		lights: lights,
+		sky_directions: sky_directions(),
+		luxel_size: config.luxel_size,
+		bounce: None,
+	};
+
+	let faces: Vec<(usize, &BspFace)> = models
+		.models
+		.iter()
+		.enumerate()
+		.flat_map(|(model, bsp_model)| bsp_model.tree.faces.iter().map(move |face| (model, face)))
+		.collect();
+
+	if config.bounces > 0
+	{
+		let bsp_faces: Vec<&BspFace> = faces.iter().map(|(_, face)| *face).collect();
+		scene.bounce = Some(bounced_light(
+			&scene,
+			&bsp_faces,
+			vis_portals,
+			visibility,
+			texture_lights,
+			config,
+			source_file,
+		));
+	}
+
+	let order: Vec<usize> = (0..faces.len()).collect();
+	let results: Vec<(Option<FaceLightmap>, bool)> =
+		run_on_threads("Lighting", &order, |index, _| {
+			return scene.light_face(index, faces[index].1);
+		});
+
+	let dropped_count: usize = results.iter().filter(|(_, dropped)| *dropped).count();
+
+	if dropped_count > 0
+	{
+		warn!(
+			"{dropped_count} faces are affected by more than {MAX_FACE_LIGHT_STYLES} light styles, so the dimmest styles were left out"
+		);
+	}
+
+	let mut lightmaps: Lightmaps = Lightmaps {
+		models: models
+			.models
+			.iter()
+			.map(|model| Vec::with_capacity(model.tree.faces.len()))
+			.collect(),
+	};
+
+	for ((model, _), (lightmap, _)) in faces.iter().zip(results)
+	{
+		lightmaps.models[*model].push(lightmap);
+	}
+
+	return Ok(lightmaps);
+}
+
+// Bounces the light arriving directly at patches of the lit faces between
+// them. Faces are indexed as in the given list.
+fn bounced_light(
+	scene: &Scene,
+	faces: &[&BspFace],
+	vis_portals: &VisPortals,
+	visibility: &Visibility,
+	texture_lights: &TextureLights,
+	config: &LightingConfig,
+	source_file: &PathBuf,
+) -> BouncedLight
+{
+	let patches: Vec<Patch> = faces
+		.iter()
+		.enumerate()
+		.filter(|(_, face)| scene.is_lit(face))
+		.flat_map(|(index, face)| {
+			let normal: DVec3 = scene.blueprint.planes[face.plane].normal;
+
+			face.winding
+				.subdivide(config.patch_size)
+				.into_iter()
+				.map(move |piece| Patch {
+					face: index,
+					center: piece.center(),
+					normal: normal,
+					area: piece.area(),
+				})
+		})
+		.collect();
+
+	let transfers: Transfers = cached_transfers(
+		&patches,
+		scene.world,
+		&scene.blueprint.planes,
+		visibility,
+		&vis_portals.leaf_clusters,
+		&source_file.with_extension("transfers"),
+	);
+
+	let order: Vec<usize> = (0..patches.len()).collect();
+	let direct: Vec<DVec3> = run_on_threads("Patch lighting", &order, |index, _| {
+		let patch: &Patch = &patches[index];
+		return scene.direct_light(patch.center + (patch.normal * SAMPLE_OFFSET), patch.normal);
+	});
+
+	let reflectivity: Vec<DVec3> = patches
+		.iter()
+		.map(|patch| {
+			texture_lights
+				.reflectivity(scene.material(faces[patch.face]))
+				.unwrap_or(DVec3::splat(config.default_reflectivity))
+		})
+		.collect();
+
+	let light: Vec<DVec3> = bounce_light(&transfers, &direct, &reflectivity, config.bounces);
+	let mut face_patches: Vec<Vec<usize>> = vec![Vec::new(); faces.len()];
+
+	for (index, patch) in patches.iter().enumerate()
+	{
+		face_patches[patch.face].push(index);
+	}
+
+	return BouncedLight {
+		face_patches: face_patches,
+		patches: patches,
+		light: light,
+	};
+}
+
+// Light received by each patch from bounces.
+struct BouncedLight
+{
+	face_patches: Vec<Vec<usize>>,
+	patches: Vec<Patch>,
+	light: Vec<DVec3>,
+}
+
+impl BouncedLight
+{
+	// Blends the light of the face's patches, weighted by the inverse square of
+	// their distance from the point.
+	fn light_at(&self, face: usize, point: DVec3) -> DVec3
+	{
+		let mut light: DVec3 = DVec3::ZERO;
+		let mut total_weight: f64 = 0.0;
+
+		for index in &self.face_patches[face]
+		{
+			let weight: f64 = 1.0 / self.patches[*index].center.distance_squared(point).max(1.0);
+			light += self.light[*index] * weight;
+			total_weight += weight;
+		}
+
+		if total_weight == 0.0
+		{
+			return DVec3::ZERO;
+		}
+
+		return light / total_weight;
+	}
+}
+
+#[derive(Clone, Copy, Debug)]
+enum LightKind
+{
+	Point,
+
+	// Direction is that in which the light shines, and the cones are the
+	// cosines of the angles from it.
+	Spot
+	{
+		direction: DVec3,
+		inner_cone: f64,
+		outer_cone: f64,
+	},
+
+	// Light arriving from the sky in a single direction.
+	Sun
+	{
+		direction: DVec3,
+	},
+
+	// Light arriving from every part of the sky that can be seen.
+	SkyAmbient,
+
+	// Part of a face that emits light, facing along the normal.
+	Surface
+	{
+		normal: DVec3,
+		area: f64,
+	},
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Light
+{
+	kind: LightKind,
+	position: DVec3,
+	color: DVec3,
+	style: u8,
+}
+
+struct Scene<'a>
+{
+	blueprint: &'a MapBlueprint,
+	world: &'a BspTree,
+	lights: Vec<Light>,
+	sky_directions: Vec<DVec3>,
+	luxel_size: f64,
+	bounce: Option<BouncedLight>,
+}
+
+impl Scene<'_>
+{
+	fn brush_face(&self, face: &BspFace) -> &BlueprintBrushFace
+	{
+		return &self.blueprint.entities[face.entity].brushes[face.brush].faces[face.face];
+	}
+
+	fn material(&self, face: &BspFace) -> &str
+	{
+		return &self.brush_face(face).material;
+	}
+
+	// Sky and liquid surfaces, and faces left visible by triggers, have no
+	// lightmap.
+	fn is_lit(&self, face: &BspFace) -> bool
+	{
+		return face.contents == Contents::Solid
+			&& !self.material(face).eq_ignore_ascii_case("aaatrigger")
+			&& face.winding.points.len() >= 3;
+	}
+
+	// Returns the lightmap of the face with the given index, if it has one,
+	// and whether any of its light styles had to be left out.
+	fn light_face(&self, index: usize, face: &BspFace) -> (Option<FaceLightmap>, bool)
+	{
+		if !self.is_lit(face)
+		{
+			return (None, false);
+		}
+
+		let brush_face: &BlueprintBrushFace = self.brush_face(face);
+
+		let plane: &DPlane3 = &self.blueprint.planes[face.plane];
+		let grid: LuxelGrid = LuxelGrid::new(
+			&face.winding,
+			plane,
+			&brush_face.projection,
+			self.luxel_size,
+		);
+
+		let center: DVec3 = face.winding.center();
+		let mut styles: Vec<u8> = vec![DEFAULT_LIGHT_STYLE];
+		let mut samples: Vec<Vec<DVec3>> = vec![Vec::with_capacity(grid.sample_count())];
+
+		for t in 0..grid.size[1]
+		{
+			for s in 0..grid.size[0]
+			{
+				let point: DVec3 =
+					nudge_into_winding(grid.point(s, t), &face.winding, plane, center)
+						+ (plane.normal * SAMPLE_OFFSET);
+
+				for style_samples in &mut samples
+				{
+					style_samples.push(DVec3::ZERO);
+				}
+
+				if let Some(bounce) = &self.bounce
+				{
+					*samples[0].last_mut().unwrap() += bounce.light_at(index, point);
+				}
+
+				for light in &self.lights
+				{
+					let received: DVec3 = self.received_light(light, point, plane.normal);
+
+					if received == DVec3::ZERO
+					{
+						continue;
+					}
+
+					let slot: usize = match styles.iter().position(|style| *style == light.style)
+					{
+						Some(slot) => slot,
+						None =>
+						{
+							styles.push(light.style);
+							samples.push(vec![DVec3::ZERO; samples[0].len()]);
+							styles.len() - 1
+						}
+					};
+
+					*samples[slot].last_mut().unwrap() += received;
+				}
+			}
+		}
+
+		let (styles, samples, dropped): (Vec<u8>, Vec<Vec<DVec3>>, bool) =
+			limit_styles(styles, samples);
+
+		let lightmap: FaceLightmap = FaceLightmap {
+			mins: grid.mins,
+			size: grid.size,
+			styles: styles,
+			samples: samples,
+		};
+
+		return (Some(lightmap), dropped);
+	}
+
+	// Light of the default style received at the point, on a face with the
+	// given normal.
+	fn direct_light(&self, point: DVec3, normal: DVec3) -> DVec3
+	{
+		return self
+			.lights
+			.iter()
+			.filter(|light| light.style == DEFAULT_LIGHT_STYLE)
+			.map(|light| self.received_light(light, point, normal))
+			.sum();
+	}
+
+	// Light received from the light by a sample at the point, on a face with
+	// the given normal.
+	fn received_light(&self, light: &Light, point: DVec3, normal: DVec3) -> DVec3
+	{
+		let scale: f64 = match light.kind
+		{
+			LightKind::Point | LightKind::Spot { .. } =>
+			{
+				let (direction, distance) = direction_and_distance(point, light.position);
+				let mut scale: f64 = normal.dot(direction)
+					* (POINT_LIGHT_REFERENCE_DISTANCE / distance.max(1.0)).powi(2);
+
+				if let LightKind::Spot {
+					direction: spot_direction,
+					inner_cone,
+					outer_cone,
+				} = light.kind
+				{
+					scale *= spot_falloff(-direction.dot(spot_direction), inner_cone, outer_cone);
+				}
+
+				if scale <= 0.0 || !self.is_clear(point, light.position)
+				{
+					return DVec3::ZERO;
+				}
+
+				scale
+			}
+			LightKind::Sun { direction } =>
+			{
+				let scale: f64 = -normal.dot(direction);
+
+				if scale <= 0.0 || !self.sees_sky(point, -direction)
+				{
+					return DVec3::ZERO;
+				}
+
+				scale
+			}
+			LightKind::SkyAmbient =>
+			{
+				let visible: f64 = self
+					.sky_directions
+					.iter()
+					.map(|direction| (direction, normal.dot(*direction)))
+					.filter(|(direction, dot)| *dot > 0.0 && self.sees_sky(point, **direction))
+					.map(|(_, dot)| dot)
+					.sum();
+
+				visible * sky_direction_weight(&self.sky_directions)
+			}
+			LightKind::Surface {
+				normal: emitter_normal,
+				area,
+			} =>
+			{
+				let (direction, distance) = direction_and_distance(point, light.position);
+				let receiver_dot: f64 = normal.dot(direction);
+				let emitter_dot: f64 = -emitter_normal.dot(direction);
+
+				if receiver_dot <= 0.0
+					|| emitter_dot <= 0.0
+					|| !self.is_clear(point, light.position)
+				{
+					return DVec3::ZERO;
+				}
+
+				receiver_dot * emitter_dot * area / (PI * distance.max(1.0).powi(2))
+			}
+		};
+
+		return light.color * scale;
+	}
+
+	fn is_clear(&self, start: DVec3, end: DVec3) -> bool
+	{
+		return self
+			.world
+			.trace_segment(start, end, &self.blueprint.planes, |contents| {
+				contents.seals_map()
+			})
+			.is_none();
+	}
+
+	fn sees_sky(&self, point: DVec3, direction: DVec3) -> bool
+	{
+		return self.world.trace_segment(
+			point,
+			point + (direction * SKY_TRACE_DISTANCE),
+			&self.blueprint.planes,
+			|contents| contents.seals_map(),
+		) == Some(Contents::Sky);
+	}
+}
+
+// Positions of a face's lightmap samples. Samples lie at whole multiples of
+// the luxel size along the texture axes, and cover the whole face.
+struct LuxelGrid
+{
+	mins: [i32; 2],
+	size: [usize; 2],
+	luxel_size: f64,
+
+	// Maps texture coordinates, with the offsets removed, and distance along
+	// the plane's normal to a point in world space.
+	to_world: DMat3,
+	offsets: DVec2,
+	plane_distance: f64,
+}
+
+impl LuxelGrid
+{
+	fn new(
+		winding: &Winding,
+		plane: &DPlane3,
+		projection: &TextureProjection,
+		luxel_size: f64,
+	) -> Self
+	{
+		let projection: TextureProjection = projection.for_face(plane.normal);
+		let (u_axis, v_axis): (DVec3, DVec3) = projection.texel_axes();
+
+		let offsets: DVec2 = DVec2::new(projection.u_axis.offset, projection.v_axis.offset);
+		let (mut min, mut max): (DVec2, DVec2) = (DVec2::INFINITY, DVec2::NEG_INFINITY);
+
+		for point in &winding.points
+		{
+			let coordinates: DVec2 = DVec2::new(point.dot(u_axis), point.dot(v_axis)) + offsets;
+			min = min.min(coordinates);
+			max = max.max(coordinates);
+		}
+
+		let mins: DVec2 = (min / luxel_size).floor();
+		let maxs: DVec2 = (max / luxel_size).ceil();
+
+		return Self {
+			mins: [mins.x as i32, mins.y as i32],
+			size: [
+				(maxs.x - mins.x) as usize + 1,
+				(maxs.y - mins.y) as usize + 1,
+			],
+			luxel_size: luxel_size,
+			to_world: DMat3::from_cols(u_axis, v_axis, plane.normal)
+				.transpose()
+				.inverse(),
+			offsets: offsets,
+			plane_distance: plane.distance,
+		};
+	}
+
+	fn sample_count(&self) -> usize
+	{
+		return self.size[0] * self.size[1];
+	}
+
+	fn point(&self, s: usize, t: usize) -> DVec3
+	{
+		let coordinates: DVec2 = (DVec2::new(
+			(self.mins[0] + s as i32) as f64,
+			(self.mins[1] + t as i32) as f64,
+		) * self.luxel_size)
+			- self.offsets;
+
+		return self.to_world * coordinates.extend(self.plane_distance);
+	}
+}
+
+// Samples at the edges of the grid may fall outside the face, where they could
+// be inside a neighbouring brush. These are moved back towards the centre.
+fn nudge_into_winding(point: DVec3, winding: &Winding, plane: &DPlane3, center: DVec3) -> DVec3
+{
+	let mut point: DVec3 = point;
+
+	while !is_inside_winding(point, winding, plane, center)
+	{
+		if point.distance(center) <= SAMPLE_NUDGE_DISTANCE
+		{
+			return center;
+		}
+
+		point += (center - point).normalize() * SAMPLE_NUDGE_DISTANCE;
+	}
+
+	return point;
+}
+
+fn is_inside_winding(point: DVec3, winding: &Winding, plane: &DPlane3, center: DVec3) -> bool
+{
+	let count: usize = winding.points.len();
+
+	return (0..count).all(|index| {
+		let start: DVec3 = winding.points[index];
+		let mut edge_normal: DVec3 = (winding.points[(index + 1) % count] - start)
+			.cross(plane.normal)
+			.normalize_or_zero();
+
+		// Point the edge's normal into the face.
+		if edge_normal.dot(center - start) < 0.0
+		{
+			edge_normal = -edge_normal;
+		}
+
+		return edge_normal.dot(point - start) >= SAMPLE_OFFSET;
+	});
+}
+
+// Keeps the default style and the brightest of the others, up to the limit on
+// styles per face. Styles that give no visible light are dropped. Returns
+// whether any visible styles had to be left out.
+fn limit_styles(styles: Vec<u8>, samples: Vec<Vec<DVec3>>) -> (Vec<u8>, Vec<Vec<DVec3>>, bool)
+{
+	let mut others: Vec<(u8, Vec<DVec3>, f64)> = styles
+		.into_iter()
+		.zip(samples)
+		.filter_map(|(style, samples)| {
+			let brightest: f64 = samples
+				.iter()
+				.map(|sample| sample.max_element())
+				.fold(0.0, f64::max);
+
+			return (style == DEFAULT_LIGHT_STYLE || brightest >= MIN_STYLE_BRIGHTNESS)
+				.then_some((style, samples, brightest));
+		})
+		.collect();
+
+	// The default style is always first, and is always kept.
+	others[1..].sort_by(|(_, _, a), (_, _, b)| b.total_cmp(a));
+	let dropped: bool = others.len() > MAX_FACE_LIGHT_STYLES;
+	others.truncate(MAX_FACE_LIGHT_STYLES);
+
+	let (styles, samples): (Vec<u8>, Vec<Vec<DVec3>>) = others
+		.into_iter()
+		.map(|(style, samples, _)| (style, samples))
+		.unzip();
+
+	return (styles, samples, dropped);
+}
+
+fn direction_and_distance(from: DVec3, to: DVec3) -> (DVec3, f64)
+{
+	let offset: DVec3 = to - from;
+	let distance: f64 = offset.length();
+
+	return (offset / distance.max(f64::EPSILON), distance);
+}
+
+// Fraction of a spotlight's brightness given at an angle from its axis, given
+// as a cosine. Brightness falls off linearly between the inner and outer cones.
+fn spot_falloff(cosine: f64, inner_cone: f64, outer_cone: f64) -> f64
+{
+	if cosine >= inner_cone
+	{
+		return 1.0;
+	}
+
+	if cosine <= outer_cone
+	{
+		return 0.0;
+	}
+
+	return (cosine - outer_cone) / (inner_cone - outer_cone);
+}
+
+// Directions spread evenly over the sphere, along a spiral.
+fn sky_directions() -> Vec<DVec3>
+{
+	let golden_angle: f64 = PI * (3.0 - 5.0_f64.sqrt());
+
+	return (0..SKY_SAMPLE_DIRECTIONS)
+		.map(|index| {
+			let z: f64 = 1.0 - (((index as f64) + 0.5) * 2.0 / SKY_SAMPLE_DIRECTIONS as f64);
+			let radius: f64 = (1.0 - (z * z)).sqrt();
+			let (sin, cos): (f64, f64) = (golden_angle * index as f64).sin_cos();
+
+			DVec3::new(cos * radius, sin * radius, z)
+		})
+		.collect();
+}
+
+// Weight given to each sky direction, such that an upward facing sample that
+// can see the whole sky receives the sky's light in full.
+fn sky_direction_weight(directions: &[DVec3]) -> f64
+{
+	return 1.0
+		/ directions
+			.iter()
+			.map(|direction| direction.z.max(0.0))
+			.sum::<f64>();
+}
+
+// Named lights without an explicit style share a style with other lights of
+// the same name. Fails if there are more names than there are styles.
+fn assign_light_styles(blueprint: &mut MapBlueprint) -> Result<(), CompilerError>
+{
+	let max_names: usize = (LAST_SWITCHABLE_STYLE - FIRST_SWITCHABLE_STYLE) as usize + 1;
+	let mut styles: HashMap<String, u8> = HashMap::new();
+
+	for entity in &mut blueprint.entities
+	{
+		let classname: &str = entity.keyvalues.first("classname").unwrap_or_default();
+
+		if !matches!(classname, "light" | "light_spot") || entity.keyvalues.contains_key("style")
+		{
+			continue;
+		}
+
+		let Some(targetname) = entity.keyvalues.last("targetname").map(str::to_owned)
+		else
+		{
+			continue;
+		};
+
+		if !styles.contains_key(&targetname) && styles.len() == max_names
+		{
+			return Err(CompilerError::new(
+				CompilerErrorCode::LimitError,
+				format!(
+					"Switchable lights use more than {max_names} different targetnames, so cannot all be given a light style"
+				),
+			));
+		}
+
+		let next_style: u8 = FIRST_SWITCHABLE_STYLE + styles.len() as u8;
+		let style: u8 = *styles.entry(targetname).or_insert(next_style);
+		entity
+			.keyvalues
+			.push(String::from("style"), style.to_string());
+	}
+
+	return Ok(());
+}
+
+fn entity_lights(blueprint: &MapBlueprint) -> Vec<Light>
+{
+	let mut lights: Vec<Light> = Vec::new();
+
+	for entity in &blueprint.entities
+	{
+		let classname: &str = entity.keyvalues.first("classname").unwrap_or_default();
+		let color: DVec3 = entity_light_color(entity, "_light");
+		let style: u8 = entity_number(entity, "style").unwrap_or_default() as u8;
+
+		let kind: LightKind = match classname
+		{
+			"light" => LightKind::Point,
+			"light_spot" => LightKind::Spot {
+				direction: entity_direction(entity),
+				inner_cone: entity_number(entity, "_cone")
+					.unwrap_or(DEFAULT_SPOT_INNER_CONE)
+					.to_radians()
+					.cos(),
+				outer_cone: entity_number(entity, "_cone2")
+					.unwrap_or(DEFAULT_SPOT_OUTER_CONE)
+					.to_radians()
+					.cos(),
+			},
+			"light_environment" =>
+			{
+				let ambient: DVec3 = if entity.keyvalues.contains_key("_diffuse_light")
+				{
+					entity_light_color(entity, "_diffuse_light")
+				}
+				else
+				{
+					color
+				};
+
+				lights.push(Light {
+					kind: LightKind::SkyAmbient,
+					position: DVec3::ZERO,
+					color: ambient,
+					style: DEFAULT_LIGHT_STYLE,
+				});
+
+				LightKind::Sun {
+					direction: entity_direction(entity),
+				}
+			}
+			_ => continue,
+		};
+
+		// Only the direction of the sun matters, so it does not need an origin.
+		let position: Option<DVec3> = entity.origin();
+
+		if position.is_none() && !matches!(kind, LightKind::Sun { .. })
+		{
+			continue;
+		}
+
+		lights.push(Light {
+			kind: kind,
+			position: position.unwrap_or_default(),
+			color: color,
+			style: style,
+		});
+	}
+
+	return lights;
+}
+
+// Each face whose material emits light is split into patches, and a light is
+// placed just in front of the centre of each.
+fn surface_lights(
+	blueprint: &MapBlueprint,
+	models: &BspModels,
+	texture_lights: &TextureLights,
+) -> Vec<Light>
+{
+	let mut lights: Vec<Light> = Vec::new();
+
+	if texture_lights.is_empty()
+	{
+		return lights;
+	}
+
+	for face in models
+		.models
+		.iter()
+		.flat_map(|model| model.tree.faces.iter())
+	{
+		let material: &str =
+			&blueprint.entities[face.entity].brushes[face.brush].faces[face.face].material;
+
+		let Some(color) = texture_lights.get(material)
+		else
+		{
+			continue;
+		};
+
+		let normal: DVec3 = blueprint.planes[face.plane].normal;
+
+		for patch in face.winding.subdivide(TEXTURE_LIGHT_PATCH_SIZE)
+		{
+			lights.push(Light {
+				kind: LightKind::Surface {
+					normal: normal,
+					area: patch.area(),
+				},
+				position: patch.center() + (normal * SAMPLE_OFFSET),
+				color: color,
+				style: DEFAULT_LIGHT_STYLE,
+			});
+		}
+	}
+
+	return lights;
+}
+
+fn entity_number(entity: &BlueprintEntity, key: &str) -> Option<f64>
+{
+	return entity.keyvalues.last(key)?.trim().parse::<f64>().ok();
+}
+
+fn entity_light_color(entity: &BlueprintEntity, key: &str) -> DVec3
+{
+	let values: Option<Vec<f64>> = entity.keyvalues.last(key).and_then(|value| {
+		value
+			.split_whitespace()
+			.map(|component| component.parse::<f64>().ok())
+			.collect()
+	});
+
+	return values
+		.and_then(|values| light_from_values(&values))
+		.or_else(|| entity_number(entity, "light").map(DVec3::splat))
+		.unwrap_or(DVec3::splat(DEFAULT_LIGHT_BRIGHTNESS));
+}
+
+// Direction in which a light entity points. The yaw comes from the angle key
+// or the angles key, and the pitch from the pitch key or the angles key, with
+// negative pitches pointing downwards. Angles of -1 and -2 point straight up
+// and down respectively.
+fn entity_direction(entity: &BlueprintEntity) -> DVec3
+{
+	let angles: Vec<f64> = entity
+		.keyvalues
+		.last("angles")
+		.map(|value| {
+			value
+				.split_whitespace()
+				.filter_map(|component| component.parse::<f64>().ok())
+				.collect()
+		})
+		.unwrap_or_default();
+
+	let yaw: f64 =
+		entity_number(entity, "angle").unwrap_or(angles.get(1).copied().unwrap_or_default());
+	let pitch: f64 =
+		entity_number(entity, "pitch").unwrap_or(angles.first().copied().unwrap_or_default());
+
+	if yaw == -1.0
+	{
+		return DVec3::Z;
+	}
+
+	if yaw == -2.0
+	{
+		return DVec3::NEG_Z;
+	}
+
+	let (yaw_sin, yaw_cos): (f64, f64) = yaw.to_radians().sin_cos();
+	let (pitch_sin, pitch_cos): (f64, f64) = pitch.to_radians().sin_cos();
+
+	return DVec3::new(yaw_cos * pitch_cos, yaw_sin * pitch_cos, pitch_sin);
+}
+
+#[cfg(test)]
+mod tests
+{
+	use super::*;
+	use crate::game_config::BspConfig;
+	use crate::model::CsgHull;
+	use crate::work_units::test_fixtures::{add_entity, box_planes, make_blueprint, room_brushes};
+	use crate::work_units::{build_bsp_trees, build_csg_hull};
+
+	// A closed room spanning -64 to 64 along each axis. Its ceiling uses the
+	// given material.
+	fn room_blueprint(ceiling: &str, extra_brushes: Vec<(Vec<DPlane3>, &str)>) -> MapBlueprint
+	{
+		let mut brushes: Vec<(Vec<DPlane3>, &str)> =
+			room_brushes(DVec3::splat(-64.0), DVec3::splat(64.0), 16.0)
+				.into_iter()
+				.map(|planes| (planes, "WALL"))
+				.collect();
+
+		brushes[5].1 = ceiling;
+		brushes.extend(extra_brushes);
+		return make_blueprint(&brushes);
+	}
+
+	fn add_light(blueprint: &mut MapBlueprint, classname: &str, keyvalues: &[(&str, &str)])
+	{
+		let entity: &mut BlueprintEntity = add_entity(blueprint, classname, &[]);
+
+		for (key, value) in keyvalues
+		{
+			entity
+				.keyvalues
+				.push(String::from(*key), String::from(*value));
+		}
+	}
+
+	// Lights the world with the given number of bounces, and returns the
+	// lightmaps of the floor's faces along with the centre of each face.
+	fn light_floor(
+		blueprint: &mut MapBlueprint,
+		texture_lights: &TextureLights,
+		bounces: usize,
+	) -> Vec<(DVec3, FaceLightmap)>
+	{
+		let hull: CsgHull = build_csg_hull(blueprint);
+		let models: BspModels = build_bsp_trees(blueprint, &hull, &BspConfig::default());
+		let source_file: PathBuf =
+			std::env::temp_dir().join(format!("bspsuite-light-faces-{}.map", std::process::id()));
+
+		let lightmaps: Lightmaps = compute_lighting(
+			blueprint,
+			&models,
+			&VisPortals::default(),
+			&Visibility::default(),
+			texture_lights,
+			&LightingConfig {
+				bounces: bounces,
+				..LightingConfig::default()
+			},
+			&source_file,
+		)
+		.unwrap();
+
+		let tree: &BspTree = &models.models[0].tree;
+		assert_eq!(lightmaps.models[0].len(), tree.faces.len());
+
+		return tree
+			.faces
+			.iter()
+			.zip(&lightmaps.models[0])
+			.filter(|(face, _)| blueprint.planes[face.plane] == DPlane3::new(DVec3::Z, -64.0))
+			.map(|(face, lightmap)| (face.winding.center(), lightmap.clone().unwrap()))
+			.collect();
+	}
+
+	fn brightest(lightmap: &FaceLightmap, style: usize) -> f64
+	{
+		return lightmap.samples[style]
+			.iter()
+			.map(|sample| sample.max_element())
+			.fold(0.0, f64::max);
+	}
+
+	#[test]
+	fn cast_shadows_from_light_entities()
+	{
+		// A wall divides the room in two, and the lights are on one side of it.
+		let mut blueprint: MapBlueprint = room_blueprint(
+			"WALL",
+			vec![(
+				box_planes(DVec3::new(-8.0, -64.0, -64.0), DVec3::new(8.0, 64.0, 64.0)),
+				"WALL",
+			)],
+		);
+
+		add_light(
+			&mut blueprint,
+			"light",
+			&[("origin", "-32 0 0"), ("_light", "255 128 0 300")],
+		);
+		add_light(
+			&mut blueprint,
+			"light",
+			&[("origin", "-32 32 32"), ("targetname", "lamp")],
+		);
+
+		let floor: Vec<(DVec3, FaceLightmap)> =
+			light_floor(&mut blueprint, &TextureLights::new(), 0);
+		assert_eq!(floor.len(), 2);
+
+		// The switchable light is given a style of its own.
+		assert_eq!(blueprint.entities[2].keyvalues.last("style"), Some("32"));
+
+		for (center, lightmap) in &floor
+		{
+			if center.x < 0.0
+			{
+				// Texture coordinates run from -64 to -8 along U and from -64
+				// to 64 along V, so there are 5 by 9 samples.
+				assert_eq!(lightmap.mins, [-4, -4]);
+				assert_eq!(lightmap.size, [5, 9]);
+				assert_eq!(
+					lightmap.styles,
+					vec![DEFAULT_LIGHT_STYLE, FIRST_SWITCHABLE_STYLE]
+				);
+				assert!(brightest(lightmap, 0) > 255.0);
+				assert!(brightest(lightmap, 1) > 0.0);
+
+				// The first light has no blue in it.
+				assert!(lightmap.samples[0].iter().all(|sample| sample.z == 0.0));
+			}
+			else
+			{
+				assert_eq!(lightmap.styles, vec![DEFAULT_LIGHT_STYLE]);
+				assert_eq!(brightest(lightmap, 0), 0.0);
+			}
+		}
+	}
+
+	#[test]
+	fn light_through_sky()
+	{
+		let mut blueprint: MapBlueprint = room_blueprint("sky", Vec::new());
+		add_light(
+			&mut blueprint,
+			"light_environment",
+			&[
+				("pitch", "-90"),
+				("_light", "255 255 255 100"),
+				("_diffuse_light", "0 0 50"),
+			],
+		);
+
+		let floor: Vec<(DVec3, FaceLightmap)> =
+			light_floor(&mut blueprint, &TextureLights::new(), 0);
+		let (_, lightmap) = &floor[0];
+
+		// Sunlight falls straight onto the floor, and some of the blue sky can
+		// be seen from every part of it.
+		for sample in &lightmap.samples[0]
+		{
+			assert!((sample.x - 100.0).abs() < 1.0e-6);
+			assert!(sample.z > 100.0 && sample.z < 150.0);
+		}
+	}
+
+	#[test]
+	fn light_from_texture()
+	{
+		let mut blueprint: MapBlueprint = room_blueprint("LAMP", Vec::new());
+		let mut texture_lights: TextureLights = TextureLights::new();
+		texture_lights.insert("lamp", DVec3::splat(10.0));
+
+		let floor: Vec<(DVec3, FaceLightmap)> = light_floor(&mut blueprint, &texture_lights, 0);
+		let (_, lightmap) = &floor[0];
+
+		// The middle of the floor sees more of the ceiling than the corners.
+		let corner: f64 = lightmap.samples[0][0].x;
+		let middle: f64 = lightmap.samples[0][lightmap.samples[0].len() / 2].x;
+		assert!(corner > 0.0);
+		assert!(middle > corner);
+	}
+
+	#[test]
+	fn bounce_light_over_wall()
+	{
+		// A wall rises from the floor part of the way to the ceiling, and the
+		// light is on one side of it, below its top. The floor on the other
+		// side can only be reached by light reflected from the ceiling.
+		let mut blueprint: MapBlueprint = room_blueprint(
+			"WALL",
+			vec![(
+				box_planes(DVec3::new(-8.0, -64.0, -64.0), DVec3::new(8.0, 64.0, 32.0)),
+				"WALL",
+			)],
+		);
+		add_light(
+			&mut blueprint,
+			"light",
+			&[("origin", "-32 0 -40"), ("_light", "255 255 255 1000")],
+		);
+
+		let shadowed = |floor: &[(DVec3, FaceLightmap)]| -> f64 {
+			let (_, lightmap) = floor.iter().find(|(center, _)| center.x > 0.0).unwrap();
+			return brightest(lightmap, 0);
+		};
+
+		let direct: Vec<(DVec3, FaceLightmap)> =
+			light_floor(&mut blueprint, &TextureLights::new(), 0);
+		assert_eq!(shadowed(&direct), 0.0);
+
+		let mut texture_lights: TextureLights = TextureLights::new();
+		let bounced: Vec<(DVec3, FaceLightmap)> = light_floor(&mut blueprint, &texture_lights, 2);
+		assert!(shadowed(&bounced) > 0.0);
+
+		// The transfers are read back from the cache, and are unaffected by
+		// how reflective the faces are.
+		texture_lights.set_average_color("wall", DVec3::splat(255.0));
+		let brighter: Vec<(DVec3, FaceLightmap)> = light_floor(&mut blueprint, &texture_lights, 2);
+		assert!(shadowed(&brighter) > shadowed(&bounced));
+
+		let _ = std::fs::remove_file(std::env::temp_dir().join(format!(
+			"bspsuite-light-faces-{}.transfers",
+			std::process::id()
+		)));
+	}
+
+	#[test]
+	fn limit_switchable_light_styles()
+	{
+		// Lights with a name that is already in use share its style.
+		let named_lights = |count: usize| -> MapBlueprint {
+			let mut blueprint: MapBlueprint = room_blueprint("WALL", Vec::new());
+
+			for index in 0..count
+			{
+				add_light(
+					&mut blueprint,
+					"light",
+					&[("targetname", &format!("lamp{index}"))],
+				);
+			}
+
+			add_light(&mut blueprint, "light", &[("targetname", "lamp0")]);
+			return blueprint;
+		};
+
+		let mut blueprint: MapBlueprint = named_lights(223);
+		assign_light_styles(&mut blueprint).unwrap();
+		assert_eq!(blueprint.entities[223].keyvalues.last("style"), Some("254"));
+		assert_eq!(blueprint.entities[224].keyvalues.last("style"), Some("32"));
+
+		let err: CompilerError = assign_light_styles(&mut named_lights(224)).unwrap_err();
+		assert!(matches!(err.code, CompilerErrorCode::LimitError));
+	}
+}
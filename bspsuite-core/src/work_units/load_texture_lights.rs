@@ -0,0 +1,110 @@
+use crate::compiler_error::{CompilerError, CompilerErrorCode};
+use crate::game_config::LightingConfig;
+use crate::model::{MapBlueprint, TextureLights};
+use log::debug;
+use std::fs;
+use std::path::PathBuf;
+
+/// Reads the texture lights file from the toolchain root, followed by the .rad
+/// file next to the map source file. Lights in the map's file replace those
+/// for the same material in the toolchain's file. Neither file has to exist.
+/// The average colours of the textures found for the map are used for the
+/// reflectivity of their materials, which decides how much light bounces off
+/// faces that use them.
+pub fn load_texture_lights(
+	blueprint: &MapBlueprint,
+	toolchain_root: &PathBuf,
+	source_file: &PathBuf,
+	config: &LightingConfig,
+) -> Result<TextureLights, CompilerError>
+{
+	let mut lights: TextureLights = TextureLights::new();
+
+	for path in [
+		toolchain_root.join(&config.texture_lights_file),
+		source_file.with_extension("rad"),
+	]
+	{
+		if !path.is_file()
+		{
+			continue;
+		}
+
+		let text: String = fs::read_to_string(&path).map_err(|err| {
+			CompilerError::new(
+				CompilerErrorCode::IoError,
+				format!("Failed to read {}. {err}", path.to_string_lossy()),
+			)
+		})?;
+
+		lights.add_from_rad(&text).map_err(|(line, message)| {
+			CompilerError::new(
+				CompilerErrorCode::ConfigError,
+				format!("{}:{line}: {message}", path.to_string_lossy()),
+			)
+		})?;
+
+		debug!("Read texture lights from {}", path.to_string_lossy());
+	}
+
+	for (name, texture) in &blueprint.textures
+	{
+		if let Some(color) = texture.average_color
+		{
+			lights.set_average_color(name, color);
+		}
+	}
+
+	if !lights.is_empty()
+	{
+		debug!("{} materials emit light", lights.len());
+	}
+
+	return Ok(lights);
+}
+
+#[cfg(test)]
+mod tests
+{
+	use super::*;
+	use crate::model::BlueprintTexture;
+	use crate::work_units::test_fixtures::make_blueprint;
+	use glam::DVec3;
+
+	#[test]
+	fn reflect_average_texture_colors()
+	{
+		let root: PathBuf =
+			std::env::temp_dir().join(format!("bspsuite-texture-lights-{}", std::process::id()));
+		fs::create_dir_all(&root).unwrap();
+
+		let source_file: PathBuf = root.join("test.map");
+		fs::write(
+			source_file.with_extension("rad"),
+			"~LIGHT 255 255 255 100
+",
+		)
+		.unwrap();
+
+		let mut blueprint: MapBlueprint = make_blueprint(&[]);
+		blueprint.textures.insert(
+			String::from("RED"),
+			BlueprintTexture {
+				size: [16, 16],
+				average_color: Some(DVec3::new(255.0, 0.0, 0.0)),
+				pixels: Vec::new(),
+				palette: Vec::new(),
+			},
+		);
+
+		let lights: TextureLights =
+			load_texture_lights(&blueprint, &root, &source_file, &LightingConfig::default())
+				.unwrap();
+
+		assert!(lights.get("~light").is_some());
+		assert_eq!(lights.reflectivity("red"), Some(DVec3::X));
+		assert_eq!(lights.reflectivity("~light"), None);
+
+		fs::remove_dir_all(&root).unwrap();
+	}
+}
@@ -1,3 +1,38 @@
+mod assemble_bsp_data;
+mod build_bsp;
+mod build_clip_hulls;
+mod build_portals;
+mod compute_vis;
 mod create_map_blueprint;
+mod csg;
+mod fill_outside;
+mod find_textures;
+mod light_faces;
+mod load_texture_lights;
+mod radiosity;
+mod read_bsp_info;
+mod run_on_threads;
+mod validate_brushes;
+mod write_bsp;
+mod write_portal_file;
 
+#[cfg(test)]
+pub(crate) mod test_fixtures;
+
+pub use assemble_bsp_data::assemble_bsp_data;
+pub use build_bsp::build_bsp_trees;
+pub use build_clip_hulls::build_clip_hulls;
+pub use build_portals::{build_portals, build_vis_portals};
+pub use compute_vis::compute_visibility;
 pub use create_map_blueprint::create_map_blueprint;
+pub use csg::build_csg_hull;
+pub use fill_outside::fill_outside;
+pub use find_textures::find_textures;
+pub use light_faces::compute_lighting;
+pub use load_texture_lights::load_texture_lights;
+pub use radiosity::{Patch, Transfers, bounce_light, cached_transfers};
+pub use read_bsp_info::read_bsp_info;
+pub use run_on_threads::run_on_threads;
+pub use validate_brushes::validate_brushes;
+pub use write_bsp::write_bsp;
+pub use write_portal_file::{write_debug_portal_file, write_portal_file};
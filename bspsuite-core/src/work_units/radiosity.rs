@@ -0,0 +1,433 @@
+use crate::model::{BspChild, BspTree, PlanePool, Visibility};
+use crate::work_units::run_on_threads;
+use glam::DVec3;
+use log::{debug, warn};
+use std::f64::consts::PI;
+use std::fs;
+use std::path::PathBuf;
+
+// Patches are moved this far off their face before tracing between them, so
+// that the traces do not start inside the brush behind the face.
+const PATCH_OFFSET: f64 = 1.0;
+
+// Identifies a transfers file, and the version of its layout.
+const TRANSFERS_MAGIC: &[u8; 8] = b"BSPTRN01";
+
+/// Part of a face that receives and reflects light as a whole when light is
+/// bounced.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Patch
+{
+	/// Index of the face that the patch lies on, counting the faces of every
+	/// model in turn.
+	pub face: usize,
+
+	pub center: DVec3,
+	pub normal: DVec3,
+	pub area: f64,
+}
+
+/// For each patch, the other patches that it can see, along with the fraction
+/// of the light leaving them that it receives. The fractions for each patch
+/// add up to no more than one.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Transfers
+{
+	pub patches: Vec<Vec<(u32, f32)>>,
+}
+
+/// Reads the transfers between the patches from the file if it was written
+/// for the same patches and world, or computes them otherwise and writes them
+/// to the file for next time. Problems with the file are only warned about,
+/// as the transfers can always be computed again.
+pub fn cached_transfers(
+	patches: &[Patch],
+	world: &BspTree,
+	planes: &PlanePool,
+	visibility: &Visibility,
+	leaf_clusters: &[Option<usize>],
+	path: &PathBuf,
+) -> Transfers
+{
+	let key: u64 = transfers_key(patches, world, planes, visibility, leaf_clusters);
+
+	if path.is_file()
+	{
+		match read_transfers(path, key, patches.len())
+		{
+			Ok(Some(transfers)) =>
+			{
+				debug!("Read patch transfers from {}", path.to_string_lossy());
+				return transfers;
+			}
+			Ok(None) => debug!("{} is out of date", path.to_string_lossy()),
+			Err(err) => warn!("Ignoring {}. {err}", path.to_string_lossy()),
+		}
+	}
+
+	let transfers: Transfers = compute_transfers(patches, world, planes, visibility, leaf_clusters);
+
+	match fs::write(path, transfers_bytes(&transfers, key))
+	{
+		Ok(()) => debug!("Wrote patch transfers to {}", path.to_string_lossy()),
+		Err(err) => warn!("Failed to write {}. {err}", path.to_string_lossy()),
+	}
+
+	return transfers;
+}
+
+// Finds the fraction of the light leaving each patch that arrives at each of
+// the others, tracing through the world to find which patches can see each
+// other. Patches in clusters that cannot see each other are not traced
+// between.
+fn compute_transfers(
+	patches: &[Patch],
+	world: &BspTree,
+	planes: &PlanePool,
+	visibility: &Visibility,
+	leaf_clusters: &[Option<usize>],
+) -> Transfers
+{
+	let origins: Vec<DVec3> = patches
+		.iter()
+		.map(|patch| patch.center + (patch.normal * PATCH_OFFSET))
+		.collect();
+
+	let clusters: Vec<Option<usize>> = origins
+		.iter()
+		.map(|origin| {
+			leaf_clusters
+				.get(world.leaf_for_point(*origin, planes))
+				.copied()
+				.flatten()
+				.filter(|cluster| *cluster < visibility.cluster_count)
+		})
+		.collect();
+
+	let order: Vec<usize> = (0..patches.len()).collect();
+	let transfers: Vec<Vec<(u32, f32)>> = run_on_threads("Transfers", &order, |index, _| {
+		let patch: &Patch = &patches[index];
+		let mut factors: Vec<(u32, f64)> = Vec::new();
+
+		for (other_index, other) in patches.iter().enumerate()
+		{
+			if other_index == index
+			{
+				continue;
+			}
+
+			if let (Some(cluster), Some(other_cluster)) = (clusters[index], clusters[other_index])
+				&& !visibility.is_visible(cluster, other_cluster)
+			{
+				continue;
+			}
+
+			let offset: DVec3 = origins[other_index] - origins[index];
+			let distance: f64 = offset.length().max(1.0);
+			let direction: DVec3 = offset / distance;
+			let cosine: f64 = patch.normal.dot(direction);
+			let other_cosine: f64 = -other.normal.dot(direction);
+
+			if cosine <= 0.0 || other_cosine <= 0.0
+			{
+				continue;
+			}
+
+			let clear: bool = world
+				.trace_segment(origins[index], origins[other_index], planes, |contents| {
+					contents.seals_map()
+				})
+				.is_none();
+
+			if clear
+			{
+				factors.push((
+					other_index as u32,
+					cosine * other_cosine * other.area / (PI * distance * distance),
+				));
+			}
+		}
+
+		// Patches very close to each other would otherwise receive more light
+		// than is sent their way.
+		let total: f64 = factors.iter().map(|(_, factor)| factor).sum();
+		let scale: f64 = if total > 1.0 { 1.0 / total } else { 1.0 };
+
+		return factors
+			.into_iter()
+			.map(|(other, factor)| (other, (factor * scale) as f32))
+			.collect();
+	});
+
+	debug!(
+		"Found {} transfers between {} patches",
+		transfers.iter().map(Vec::len).sum::<usize>(),
+		patches.len()
+	);
+
+	return Transfers { patches: transfers };
+}
+
+/// Bounces the light arriving directly at each patch between the patches the
+/// given number of times, and returns the light that each receives from the
+/// bounces. Reflectivity is the fraction of each colour of the light arriving
+/// at a patch that it reflects.
+pub fn bounce_light(
+	transfers: &Transfers,
+	direct: &[DVec3],
+	reflectivity: &[DVec3],
+	bounces: usize,
+) -> Vec<DVec3>
+{
+	let mut emitted: Vec<DVec3> = direct
+		.iter()
+		.zip(reflectivity)
+		.map(|(light, reflectivity)| *light * *reflectivity)
+		.collect();
+	let mut bounced: Vec<DVec3> = vec![DVec3::ZERO; direct.len()];
+
+	for _ in 0..bounces
+	{
+		let received: Vec<DVec3> = transfers
+			.patches
+			.iter()
+			.map(|sources| {
+				sources
+					.iter()
+					.map(|(source, factor)| emitted[*source as usize] * (*factor as f64))
+					.sum::<DVec3>()
+			})
+			.collect();
+
+		for (index, light) in received.into_iter().enumerate()
+		{
+			bounced[index] += light;
+			emitted[index] = light * reflectivity[index];
+		}
+	}
+
+	return bounced;
+}
+
+// Identifies the inputs that the transfers depend on, so that a stale
+// transfers file can be detected.
+fn transfers_key(
+	patches: &[Patch],
+	world: &BspTree,
+	planes: &PlanePool,
+	visibility: &Visibility,
+	leaf_clusters: &[Option<usize>],
+) -> u64
+{
+	let mut key: KeyHasher = KeyHasher::new();
+
+	key.add(patches.len() as u64);
+
+	for patch in patches
+	{
+		key.add(patch.face as u64);
+		key.add_vector(patch.center);
+		key.add_vector(patch.normal);
+		key.add(patch.area.to_bits());
+	}
+
+	key.add(world.nodes.len() as u64);
+
+	for node in &world.nodes
+	{
+		key.add_vector(planes[node.plane].normal);
+		key.add(planes[node.plane].distance.to_bits());
+
+		for child in node.children
+		{
+			key.add(match child
+			{
+				BspChild::Node(index) => index as u64 * 2,
+				BspChild::Leaf(index) => (index as u64 * 2) + 1,
+			});
+		}
+	}
+
+	key.add(world.leaves.len() as u64);
+
+	for leaf in &world.leaves
+	{
+		key.add(leaf.contents as u64);
+	}
+
+	for cluster in leaf_clusters
+	{
+		key.add(cluster.map_or(u64::MAX, |cluster| cluster as u64));
+	}
+
+	key.add(visibility.cluster_count as u64);
+
+	for byte in visibility.rows.iter().flatten()
+	{
+		key.add(*byte as u64);
+	}
+
+	return key.hash;
+}
+
+// Hashes values with FNV-1a, one little endian byte at a time.
+struct KeyHasher
+{
+	hash: u64,
+}
+
+impl KeyHasher
+{
+	fn new() -> Self
+	{
+		return Self {
+			hash: 0xcbf2_9ce4_8422_2325,
+		};
+	}
+
+	fn add(&mut self, value: u64)
+	{
+		for byte in value.to_le_bytes()
+		{
+			self.hash = (self.hash ^ byte as u64).wrapping_mul(0x0000_0100_0000_01b3);
+		}
+	}
+
+	fn add_vector(&mut self, vector: DVec3)
+	{
+		for component in vector.to_array()
+		{
+			self.add(component.to_bits());
+		}
+	}
+}
+
+// Transfers files begin with the magic and the key, followed by the number of
+// patches. Each patch then has the number of its transfers, followed by the
+// index and factor of each. All values are little endian.
+fn transfers_bytes(transfers: &Transfers, key: u64) -> Vec<u8>
+{
+	let mut bytes: Vec<u8> = Vec::new();
+	bytes.extend(TRANSFERS_MAGIC);
+	bytes.extend(key.to_le_bytes());
+	bytes.extend((transfers.patches.len() as u32).to_le_bytes());
+
+	for sources in &transfers.patches
+	{
+		bytes.extend((sources.len() as u32).to_le_bytes());
+
+		for (source, factor) in sources
+		{
+			bytes.extend(source.to_le_bytes());
+			bytes.extend(factor.to_le_bytes());
+		}
+	}
+
+	return bytes;
+}
+
+// Returns None if the file was written for different inputs.
+fn read_transfers(path: &PathBuf, key: u64, patch_count: usize)
+-> Result<Option<Transfers>, String>
+{
+	let bytes: Vec<u8> = fs::read(path).map_err(|err| err.to_string())?;
+	let mut offset: usize = 0;
+	let mut take = |length: usize| -> Result<&[u8], String> {
+		let taken: &[u8] = bytes
+			.get(offset..offset + length)
+			.ok_or_else(|| String::from("The file is truncated"))?;
+		offset += length;
+		return Ok(taken);
+	};
+
+	if take(TRANSFERS_MAGIC.len())? != TRANSFERS_MAGIC
+	{
+		return Err(String::from("The file is not a transfers file"));
+	}
+
+	let file_key: u64 = u64::from_le_bytes(take(8)?.try_into().unwrap());
+	let file_patch_count: usize = u32::from_le_bytes(take(4)?.try_into().unwrap()) as usize;
+
+	if file_key != key || file_patch_count != patch_count
+	{
+		return Ok(None);
+	}
+
+	let mut transfers: Transfers = Transfers::default();
+
+	for _ in 0..patch_count
+	{
+		let count: usize = u32::from_le_bytes(take(4)?.try_into().unwrap()) as usize;
+		let mut sources: Vec<(u32, f32)> = Vec::with_capacity(count.min(patch_count));
+
+		for _ in 0..count
+		{
+			let source: u32 = u32::from_le_bytes(take(4)?.try_into().unwrap());
+			let factor: f32 = f32::from_le_bytes(take(4)?.try_into().unwrap());
+
+			if source as usize >= patch_count
+			{
+				return Err(format!("Transfer from patch {source} is out of range"));
+			}
+
+			sources.push((source, factor));
+		}
+
+		transfers.patches.push(sources);
+	}
+
+	return Ok(Some(transfers));
+}
+
+#[cfg(test)]
+mod tests
+{
+	use super::*;
+
+	#[test]
+	fn round_trip_transfers()
+	{
+		let transfers: Transfers = Transfers {
+			patches: vec![vec![(1, 0.25), (2, 0.5)], Vec::new(), vec![(0, 1.0)]],
+		};
+
+		let path: PathBuf =
+			std::env::temp_dir().join(format!("bspsuite-transfers-{}", std::process::id()));
+		fs::write(&path, transfers_bytes(&transfers, 42)).unwrap();
+
+		assert_eq!(read_transfers(&path, 42, 3), Ok(Some(transfers.clone())));
+		assert_eq!(read_transfers(&path, 43, 3), Ok(None));
+		assert_eq!(read_transfers(&path, 42, 2), Ok(None));
+
+		let bytes: Vec<u8> = transfers_bytes(&transfers, 42);
+		fs::write(&path, &bytes[..bytes.len() - 1]).unwrap();
+		assert!(read_transfers(&path, 42, 3).is_err());
+
+		fs::remove_file(&path).unwrap();
+	}
+
+	#[test]
+	fn bounce_between_two_patches()
+	{
+		// Each patch receives half the light leaving the other, and reflects
+		// half of what it receives.
+		let transfers: Transfers = Transfers {
+			patches: vec![vec![(1, 0.5)], vec![(0, 0.5)]],
+		};
+		let reflectivity: Vec<DVec3> = vec![DVec3::splat(0.5); 2];
+		let direct: Vec<DVec3> = vec![DVec3::splat(100.0), DVec3::ZERO];
+
+		assert_eq!(
+			bounce_light(&transfers, &direct, &reflectivity, 0),
+			vec![DVec3::ZERO; 2]
+		);
+		assert_eq!(
+			bounce_light(&transfers, &direct, &reflectivity, 1),
+			vec![DVec3::ZERO, DVec3::splat(25.0)]
+		);
+		assert_eq!(
+			bounce_light(&transfers, &direct, &reflectivity, 2),
+			vec![DVec3::splat(6.25), DVec3::splat(25.0)]
+		);
+	}
+}
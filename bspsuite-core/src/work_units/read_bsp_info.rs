@@ -0,0 +1,614 @@
+use crate::compiler_error::{CompilerError, CompilerErrorCode};
+use crate::extensions::ExtensionList;
+use crate::model::{BspFileInfo, BspFormat, BspTextureInfo, LimitUsage, LumpInfo};
+use log::debug;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+
+// What a limit of the engine counts.
+enum LimitCount
+{
+	Records(&'static str),
+	Bytes(&'static str),
+	Entities,
+	Textures,
+}
+
+enum TextureLump
+{
+	// Mip textures, each with its name, size and optionally its pixels.
+	MipTextures,
+
+	// Fixed length names only.
+	Names(usize),
+}
+
+// Where each part of the map is found within a version of the format.
+struct FormatLayout
+{
+	format: BspFormat,
+
+	// Name of the format, as registered by the extension that writes it.
+	writer_format: &'static str,
+
+	// Name of each lump in the order of the header, with the size of its
+	// records if it is made of them.
+	lumps: &'static [(&'static str, Option<usize>)],
+
+	// How to count what each of the format's limits applies to. The values of
+	// the limits come from the format's writer.
+	limit_counts: &'static [(&'static str, LimitCount)],
+
+	textures: TextureLump,
+	lighting_sample_bytes: usize,
+
+	// Offset of the number of leaves with visibility within the model record.
+	model_vis_leaves_offset: usize,
+}
+
+const QUAKE_LUMPS: [(&str, Option<usize>); 15] = [
+	("ENTITIES", None),
+	("PLANES", Some(20)),
+	("TEXTURES", None),
+	("VERTEXES", Some(12)),
+	("VISIBILITY", None),
+	("NODES", Some(24)),
+	("TEXINFO", Some(40)),
+	("FACES", Some(20)),
+	("LIGHTING", None),
+	("CLIPNODES", Some(8)),
+	("LEAFS", Some(28)),
+	("MARKSURFACES", Some(2)),
+	("EDGES", Some(4)),
+	("SURFEDGES", Some(4)),
+	("MODELS", Some(64)),
+];
+
+const QUAKE_LIMIT_COUNTS: [(&str, LimitCount); 17] = [
+	("MAX_MAP_MODELS", LimitCount::Records("MODELS")),
+	("MAX_MAP_ENTITIES", LimitCount::Entities),
+	("MAX_MAP_ENTSTRING", LimitCount::Bytes("ENTITIES")),
+	("MAX_MAP_PLANES", LimitCount::Records("PLANES")),
+	("MAX_MAP_NODES", LimitCount::Records("NODES")),
+	("MAX_MAP_CLIPNODES", LimitCount::Records("CLIPNODES")),
+	("MAX_MAP_LEAFS", LimitCount::Records("LEAFS")),
+	("MAX_MAP_VERTS", LimitCount::Records("VERTEXES")),
+	("MAX_MAP_FACES", LimitCount::Records("FACES")),
+	("MAX_MAP_MARKSURFACES", LimitCount::Records("MARKSURFACES")),
+	("MAX_MAP_TEXINFO", LimitCount::Records("TEXINFO")),
+	("MAX_MAP_EDGES", LimitCount::Records("EDGES")),
+	("MAX_MAP_SURFEDGES", LimitCount::Records("SURFEDGES")),
+	("MAX_MAP_TEXTURES", LimitCount::Textures),
+	("MAX_MAP_MIPTEX", LimitCount::Bytes("TEXTURES")),
+	("MAX_MAP_LIGHTING", LimitCount::Bytes("LIGHTING")),
+	("MAX_MAP_VISIBILITY", LimitCount::Bytes("VISIBILITY")),
+];
+
+const QUAKE29: FormatLayout = FormatLayout {
+	format: BspFormat::Quake29,
+	writer_format: "bsp29",
+	lumps: &QUAKE_LUMPS,
+	limit_counts: &QUAKE_LIMIT_COUNTS,
+	textures: TextureLump::MipTextures,
+	lighting_sample_bytes: 1,
+	model_vis_leaves_offset: 52,
+};
+
+const GOLDSRC30: FormatLayout = FormatLayout {
+	format: BspFormat::GoldSrc30,
+	writer_format: "bsp30",
+	lumps: &QUAKE_LUMPS,
+	limit_counts: &QUAKE_LIMIT_COUNTS,
+	textures: TextureLump::MipTextures,
+	lighting_sample_bytes: 3,
+	model_vis_leaves_offset: 52,
+};
+
+const NIGHTFIRE42: FormatLayout = FormatLayout {
+	format: BspFormat::Nightfire42,
+	writer_format: "bsp42",
+	lumps: &[
+		("ENTITIES", None),
+		("PLANES", Some(20)),
+		("TEXTURES", Some(64)),
+		("MATERIALS", Some(64)),
+		("VERTICES", Some(12)),
+		("NORMALS", Some(12)),
+		("INDICES", Some(4)),
+		("VISIBILITY", None),
+		("NODES", Some(36)),
+		("FACES", Some(48)),
+		("LIGHTING", None),
+		("LEAVES", Some(48)),
+		("LEAF_FACES", Some(4)),
+		("LEAF_BRUSHES", Some(4)),
+		("MODELS", Some(56)),
+		("BRUSHES", Some(12)),
+		("BRUSH_SIDES", Some(8)),
+		("TEXTURE_MATRICES", Some(32)),
+	],
+	// The Nightfire writer does not declare any limits, as the engine's are
+	// not documented.
+	limit_counts: &[],
+	textures: TextureLump::Names(64),
+	lighting_sample_bytes: 3,
+	model_vis_leaves_offset: 28,
+};
+
+// Length of a mip texture's name, including its terminating NUL.
+const MIPTEX_NAME_LENGTH: usize = 16;
+
+/// Reads a compiled map, and summarises how much of the format's budget it
+/// uses. The format's limits are those of the extension that writes it, with
+/// limits named in the overrides replaced.
+pub fn read_bsp_info(
+	path: &PathBuf,
+	extensions: &ExtensionList,
+	limit_overrides: &BTreeMap<String, usize>,
+) -> Result<BspFileInfo, CompilerError>
+{
+	let path_string: String = path.to_string_lossy().to_string();
+
+	let bytes: Vec<u8> = fs::read(path).map_err(|err| {
+		CompilerError::new(
+			CompilerErrorCode::IoError,
+			format!("Could not read {path_string}. {err}"),
+		)
+	})?;
+
+	return format_layout(&bytes)
+		.and_then(|layout| {
+			let limits: BTreeMap<String, usize> =
+				format_limits(extensions, layout.writer_format, limit_overrides);
+
+			return parse_bsp_info(&bytes, layout, &limits);
+		})
+		.map_err(|message| {
+			CompilerError::new(
+				CompilerErrorCode::ArgumentError,
+				format!("{path_string} is not a valid BSP file. {message}"),
+			)
+		});
+}
+
+fn format_layout(bytes: &[u8]) -> Result<&'static FormatLayout, String>
+{
+	let version: i32 = read_i32(bytes, 0).ok_or("File is too short to hold a version")?;
+
+	return match version
+	{
+		29 => Ok(&QUAKE29),
+		30 => Ok(&GOLDSRC30),
+		42 => Ok(&NIGHTFIRE42),
+		_ => Err(format!("Version {version} is not supported")),
+	};
+}
+
+// If no loaded extension writes the format, only the overridden limits are
+// known.
+fn format_limits(
+	extensions: &ExtensionList,
+	format: &str,
+	limit_overrides: &BTreeMap<String, usize>,
+) -> BTreeMap<String, usize>
+{
+	let mut limits: BTreeMap<String, usize> = match extensions.iter().find_map(|extension| {
+		extension
+			.get_api_callbacks()
+			.bsp_writer_api_callbacks
+			.as_ref()
+			.filter(|callbacks| callbacks.format.as_str().eq_ignore_ascii_case(format))
+	})
+	{
+		Some(callbacks) => callbacks
+			.limits
+			.as_slice()
+			.iter()
+			.map(|limit| (limit.name.to_string(), limit.value))
+			.collect(),
+		None =>
+		{
+			debug!(
+				"No loaded extension writes {format} files, so the format's limits are not known"
+			);
+			BTreeMap::new()
+		}
+	};
+
+	limits.extend(
+		limit_overrides
+			.iter()
+			.map(|(name, value)| (name.clone(), *value)),
+	);
+
+	return limits;
+}
+
+// Limits are only reported if both their value is known and the layout
+// describes how to count them.
+fn parse_bsp_info(
+	bytes: &[u8],
+	layout: &FormatLayout,
+	limits: &BTreeMap<String, usize>,
+) -> Result<BspFileInfo, String>
+{
+	let lumps: Vec<LumpInfo> = read_lumps(bytes, layout)?;
+	let lump = |name: &str| -> &[u8] {
+		let info: &LumpInfo = lumps.iter().find(|lump| lump.name == name).unwrap();
+		return &bytes[info.offset..info.offset + info.length];
+	};
+
+	let entity_text: &[u8] = lump("ENTITIES");
+	let entity_count: usize = count_entities(entity_text);
+
+	let textures: Vec<BspTextureInfo> = match layout.textures
+	{
+		TextureLump::MipTextures => read_mip_textures(lump("TEXTURES"))?,
+		TextureLump::Names(length) => lump("TEXTURES")
+			.chunks_exact(length)
+			.map(|name| BspTextureInfo {
+				name: read_name(name),
+				size: None,
+				embedded: false,
+			})
+			.collect(),
+	};
+
+	let limits: Vec<LimitUsage> = layout
+		.limit_counts
+		.iter()
+		.filter_map(|(name, count)| {
+			let limit: usize = *limits.get(*name)?;
+			let count: usize = match count
+			{
+				LimitCount::Records(lump_name) => lumps
+					.iter()
+					.find(|lump| lump.name == *lump_name)
+					.and_then(|lump| lump.count)
+					.unwrap_or_default(),
+				LimitCount::Bytes(lump_name) => lump(lump_name).len(),
+				LimitCount::Entities => entity_count,
+				LimitCount::Textures => textures.len(),
+			};
+
+			return Some(LimitUsage::new(name, count, limit));
+		})
+		.collect();
+
+	let vis_bytes: usize = lump("VISIBILITY").len();
+	let vis_uncompressed_bytes: usize = uncompressed_vis_size(&lumps, layout, bytes);
+	let lighting_bytes: usize = lump("LIGHTING").len();
+
+	return Ok(BspFileInfo {
+		format: layout.format,
+		file_size: bytes.len(),
+		lumps: lumps,
+		entity_count: entity_count,
+		limits: limits,
+		textures: textures,
+		lighting_bytes: lighting_bytes,
+		lightmap_samples: lighting_bytes / layout.lighting_sample_bytes,
+		vis_bytes: vis_bytes,
+		vis_uncompressed_bytes: vis_uncompressed_bytes,
+		vis_compression_ratio: (vis_bytes > 0)
+			.then(|| vis_uncompressed_bytes as f64 / vis_bytes as f64),
+	});
+}
+
+fn read_lumps(bytes: &[u8], layout: &FormatLayout) -> Result<Vec<LumpInfo>, String>
+{
+	let mut lumps: Vec<LumpInfo> = Vec::with_capacity(layout.lumps.len());
+
+	for (index, (name, record_size)) in layout.lumps.iter().enumerate()
+	{
+		let header_offset: usize = 4 + index * 8;
+		let (Some(offset), Some(length)) = (
+			read_i32(bytes, header_offset),
+			read_i32(bytes, header_offset + 4),
+		)
+		else
+		{
+			return Err(String::from("File is too short to hold its header"));
+		};
+
+		let (Ok(offset), Ok(length)) = (usize::try_from(offset), usize::try_from(length))
+		else
+		{
+			return Err(format!("{name} lump has a negative offset or length"));
+		};
+
+		if offset + length > bytes.len()
+		{
+			return Err(format!("{name} lump lies outside of the file"));
+		}
+
+		if record_size.is_some_and(|size| !length.is_multiple_of(size))
+		{
+			return Err(format!(
+				"{name} lump does not hold a whole number of records"
+			));
+		}
+
+		lumps.push(LumpInfo {
+			name: name,
+			offset: offset,
+			length: length,
+			count: record_size.map(|size| length / size),
+		});
+	}
+
+	return Ok(lumps);
+}
+
+// Each entity is a block in braces. Braces within quoted values do not count.
+fn count_entities(text: &[u8]) -> usize
+{
+	let mut count: usize = 0;
+	let mut depth: usize = 0;
+	let mut in_quotes: bool = false;
+
+	for byte in text
+	{
+		match byte
+		{
+			b'"' => in_quotes = !in_quotes,
+			b'{' if !in_quotes =>
+			{
+				if depth == 0
+				{
+					count += 1;
+				}
+
+				depth += 1;
+			}
+			b'}' if !in_quotes => depth = depth.saturating_sub(1),
+			_ => (),
+		}
+	}
+
+	return count;
+}
+
+// The lump begins with the number of textures, followed by the offset of each
+// from the start of the lump. Textures that the compiler could not find have
+// an offset of -1.
+fn read_mip_textures(lump: &[u8]) -> Result<Vec<BspTextureInfo>, String>
+{
+	if lump.is_empty()
+	{
+		return Ok(Vec::new());
+	}
+
+	// Each texture needs at least an offset, so the count can be checked
+	// before anything is allocated for it.
+	let count: usize = read_i32(lump, 0)
+		.and_then(|count| usize::try_from(count).ok())
+		.filter(|count| *count <= (lump.len() - 4) / 4)
+		.ok_or("Texture lump has an invalid texture count")?;
+
+	let mut textures: Vec<BspTextureInfo> = Vec::with_capacity(count);
+
+	for index in 0..count
+	{
+		let offset: i32 = read_i32(lump, 4 + index * 4).ok_or("Texture lump is truncated")?;
+
+		let Ok(offset) = usize::try_from(offset)
+		else
+		{
+			continue;
+		};
+
+		let (Some(name), Some(width), Some(height), Some(pixels)) = (
+			lump.get(offset..offset + MIPTEX_NAME_LENGTH),
+			read_i32(lump, offset + 16),
+			read_i32(lump, offset + 20),
+			read_i32(lump, offset + 24),
+		)
+		else
+		{
+			return Err(format!("Texture {index} lies outside of the texture lump"));
+		};
+
+		textures.push(BspTextureInfo {
+			name: read_name(name),
+			size: Some([width as u32, height as u32]),
+			embedded: pixels != 0,
+		});
+	}
+
+	return Ok(textures);
+}
+
+// Every leaf of the world after leaf 0 that has visibility would have a whole
+// row of one bit per leaf if the rows were not compressed.
+fn uncompressed_vis_size(lumps: &[LumpInfo], layout: &FormatLayout, bytes: &[u8]) -> usize
+{
+	let models: &LumpInfo = lumps.iter().find(|lump| lump.name == "MODELS").unwrap();
+	let leaves: &LumpInfo = lumps
+		.iter()
+		.find(|lump| lump.name == "LEAFS" || lump.name == "LEAVES")
+		.unwrap();
+
+	let Some(vis_leaves) = (models.count.unwrap_or_default() > 0)
+		.then(|| read_i32(bytes, models.offset + layout.model_vis_leaves_offset))
+		.flatten()
+		.and_then(|count| usize::try_from(count).ok())
+	else
+	{
+		return 0;
+	};
+
+	let leaf_size: usize = leaves.length / leaves.count.unwrap_or(1).max(1);
+	let rows: usize = (1..=vis_leaves.min(leaves.count.unwrap_or_default().saturating_sub(1)))
+		.filter(|leaf| {
+			read_i32(bytes, leaves.offset + leaf * leaf_size + 4).is_some_and(|offset| offset >= 0)
+		})
+		.count();
+
+	return rows * vis_leaves.div_ceil(8);
+}
+
+fn read_name(bytes: &[u8]) -> String
+{
+	let length: usize = bytes
+		.iter()
+		.position(|byte| *byte == 0)
+		.unwrap_or(bytes.len());
+
+	return String::from_utf8_lossy(&bytes[..length]).to_string();
+}
+
+fn read_i32(bytes: &[u8], offset: usize) -> Option<i32>
+{
+	return bytes
+		.get(offset..offset + 4)
+		.map(|value| i32::from_le_bytes(value.try_into().unwrap()));
+}
+
+#[cfg(test)]
+mod tests
+{
+	use super::*;
+
+	// Lays out lumps after a header, in the order given.
+	fn bsp_bytes(version: i32, lumps: &[Vec<u8>]) -> Vec<u8>
+	{
+		let mut header: Vec<u8> = version.to_le_bytes().to_vec();
+		let mut body: Vec<u8> = Vec::new();
+		let header_size: usize = 4 + lumps.len() * 8;
+
+		for lump in lumps
+		{
+			header.extend_from_slice(&((header_size + body.len()) as i32).to_le_bytes());
+			header.extend_from_slice(&(lump.len() as i32).to_le_bytes());
+			body.extend_from_slice(lump);
+		}
+
+		header.append(&mut body);
+		return header;
+	}
+
+	// Only some of the limits are given, as the writer's would be.
+	fn parse(bytes: &[u8]) -> Result<BspFileInfo, String>
+	{
+		let limits: BTreeMap<String, usize> = BTreeMap::from([
+			(String::from("MAX_MAP_PLANES"), 32767),
+			(String::from("MAX_MAP_TEXTURES"), 512),
+		]);
+
+		return parse_bsp_info(bytes, format_layout(bytes)?, &limits);
+	}
+
+	fn i32s(values: &[i32]) -> Vec<u8>
+	{
+		return values
+			.iter()
+			.flat_map(|value| value.to_le_bytes())
+			.collect();
+	}
+
+	#[test]
+	fn summarise_goldsrc_file()
+	{
+		let entities: &[u8] =
+			b"{\n\"classname\" \"worldspawn\"\n\"wad\" \"{odd}.wad\"\n}\n{\n\"classname\" \"info_player_start\"\n}\n\0";
+
+		// One missing texture, and one embedded texture whose pixels are left
+		// out, as only the offset to them is read.
+		let mut textures: Vec<u8> = i32s(&[2, -1, 12]);
+		textures.extend_from_slice(b"BRICK\0\0\0\0\0\0\0\0\0\0\0");
+		textures.extend_from_slice(&i32s(&[64, 32, 40, 0, 0, 0]));
+
+		// Leaf 0, then two leaves whose rows of two leaves compress to two bytes
+		// each.
+		let mut leaves: Vec<u8> = Vec::new();
+		[-1, 0, 2].iter().for_each(|offset| {
+			leaves.extend_from_slice(&i32s(&[-1, *offset]));
+			leaves.extend_from_slice(&[0; 20]);
+		});
+
+		let mut model: Vec<u8> = vec![0; 52];
+		model.extend_from_slice(&i32s(&[2, 0, 0]));
+
+		let mut lumps: Vec<Vec<u8>> = vec![Vec::new(); 15];
+		lumps[0] = entities.to_vec();
+		lumps[1] = vec![0; 3 * 20];
+		lumps[2] = textures;
+		lumps[4] = vec![3, 0, 1, 2];
+		lumps[8] = vec![0; 48];
+		lumps[10] = leaves;
+		lumps[14] = model;
+
+		let info: BspFileInfo = parse(&bsp_bytes(30, &lumps)).unwrap();
+		assert_eq!(info.format, BspFormat::GoldSrc30);
+		assert_eq!(info.lumps.len(), 15);
+		assert_eq!(info.lumps[1].count, Some(3));
+		assert_eq!(info.entity_count, 2);
+
+		assert_eq!(
+			info.textures,
+			vec![BspTextureInfo {
+				name: String::from("BRICK"),
+				size: Some([64, 32]),
+				embedded: true,
+			}]
+		);
+
+		assert_eq!((info.lighting_bytes, info.lightmap_samples), (48, 16));
+		assert_eq!((info.vis_bytes, info.vis_uncompressed_bytes), (4, 2));
+		assert_eq!(info.vis_compression_ratio, Some(0.5));
+
+		let planes: &LimitUsage = info
+			.limits
+			.iter()
+			.find(|limit| limit.name == "MAX_MAP_PLANES")
+			.unwrap();
+		assert_eq!((planes.count, planes.limit), (3, 32767));
+
+		let textures: &LimitUsage = info
+			.limits
+			.iter()
+			.find(|limit| limit.name == "MAX_MAP_TEXTURES")
+			.unwrap();
+		assert_eq!(textures.percentage, 100.0 / 512.0);
+
+		// Limits without a known value are left out.
+		assert_eq!(info.limits.len(), 2);
+	}
+
+	#[test]
+	fn reject_unknown_and_malformed_files()
+	{
+		assert!(parse(&[]).is_err());
+		assert!(parse(&bsp_bytes(38, &[])).is_err());
+
+		// Planes that do not fill a whole number of records.
+		let mut lumps: Vec<Vec<u8>> = vec![Vec::new(); 15];
+		lumps[1] = vec![0; 21];
+		assert!(parse(&bsp_bytes(29, &lumps)).is_err());
+
+		let mut bytes: Vec<u8> = bsp_bytes(29, &vec![Vec::new(); 15]);
+		bytes[4..8].copy_from_slice(&1000_i32.to_le_bytes());
+		assert!(parse(&bytes).is_err());
+
+		// More textures than the lump has room for.
+		let mut lumps: Vec<Vec<u8>> = vec![Vec::new(); 15];
+		lumps[2] = i32s(&[i32::MAX, 0]);
+		assert!(parse(&bsp_bytes(30, &lumps)).is_err());
+	}
+
+	#[test]
+	fn override_format_limits()
+	{
+		let missing: PathBuf = std::env::temp_dir().join("bspsuite-no-extensions");
+		let overrides: BTreeMap<String, usize> =
+			BTreeMap::from([(String::from("MAX_MAP_MODELS"), 512)]);
+
+		// No extension writes the format, so only the override is known.
+		assert_eq!(
+			format_limits(&ExtensionList::new(&missing), "bsp30", &overrides),
+			overrides
+		);
+	}
+}
@@ -0,0 +1,58 @@
+use log::info;
+use std::sync::OnceLock;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+
+// Work items such as VIS portal flow recurse deeply, so threads are given more
+// stack than the default.
+const WORKER_STACK_SIZE: usize = 64 * 1024 * 1024;
+
+/// Runs the work for each item in the given order, spread across all available
+/// threads, and returns the results indexed by item. The order must contain
+/// each item exactly once. The work is given the results of the items that
+/// have already finished, so that later items can make use of them. Progress
+/// is logged under the given label each time another tenth of the items
+/// finishes.
+pub fn run_on_threads<T, F>(label: &str, order: &[usize], work: F) -> Vec<T>
+where
+	T: Default + Send + Sync,
+	F: Fn(usize, &[OnceLock<T>]) -> T + Sync,
+{
+	let results: Vec<OnceLock<T>> = order.iter().map(|_| OnceLock::new()).collect();
+	let next: AtomicUsize = AtomicUsize::new(0);
+	let done: AtomicUsize = AtomicUsize::new(0);
+
+	let thread_count: usize = thread::available_parallelism()
+		.map(|count| count.get())
+		.unwrap_or(1)
+		.clamp(1, order.len().max(1));
+
+	thread::scope(|scope| {
+		for _ in 0..thread_count
+		{
+			thread::Builder::new()
+				.stack_size(WORKER_STACK_SIZE)
+				.spawn_scoped(scope, || {
+					while let Some(index) = order.get(next.fetch_add(1, Ordering::Relaxed))
+					{
+						let _ = results[*index].set(work(*index, &results));
+						log_progress(label, done.fetch_add(1, Ordering::Relaxed) + 1, order.len());
+					}
+				})
+				.expect("Failed to spawn worker thread");
+		}
+	});
+
+	return results
+		.into_iter()
+		.map(|result| result.into_inner().unwrap_or_default())
+		.collect();
+}
+
+fn log_progress(label: &str, done: usize, total: usize)
+{
+	if (done * 10) / total != ((done - 1) * 10) / total
+	{
+		info!("{label}: {}% ({done}/{total})", (done * 100) / total);
+	}
+}
@@ -0,0 +1,113 @@
+// Helpers for building blueprints in work unit tests.
+
+use crate::model::{
+	BlueprintBrush, BlueprintBrushFace, BlueprintEntity, DPlane3, EntityKeyValues, MapBlueprint,
+	PlanePool, TextureAxis, TextureProjection,
+};
+use glam::{DVec2, DVec3};
+use std::collections::HashMap;
+
+/// Returns the planes of an axis-aligned box brush.
+pub fn box_planes(mins: DVec3, maxs: DVec3) -> Vec<DPlane3>
+{
+	return vec![
+		DPlane3::new(DVec3::X, maxs.x),
+		DPlane3::new(DVec3::NEG_X, -mins.x),
+		DPlane3::new(DVec3::Y, maxs.y),
+		DPlane3::new(DVec3::NEG_Y, -mins.y),
+		DPlane3::new(DVec3::Z, maxs.z),
+		DPlane3::new(DVec3::NEG_Z, -mins.z),
+	];
+}
+
+/// Returns the brushes for the walls, floor and ceiling of a closed room.
+/// The bounds are those of the room's interior.
+pub fn room_brushes(mins: DVec3, maxs: DVec3, thickness: f64) -> Vec<Vec<DPlane3>>
+{
+	let outer_mins: DVec3 = mins - DVec3::splat(thickness);
+	let outer_maxs: DVec3 = maxs + DVec3::splat(thickness);
+	let mut brushes: Vec<Vec<DPlane3>> = Vec::new();
+
+	for axis in 0..3
+	{
+		let mut low_maxs: DVec3 = outer_maxs;
+		low_maxs[axis] = mins[axis];
+		brushes.push(box_planes(outer_mins, low_maxs));
+
+		let mut high_mins: DVec3 = outer_mins;
+		high_mins[axis] = maxs[axis];
+		brushes.push(box_planes(high_mins, outer_maxs));
+	}
+
+	return brushes;
+}
+
+/// Builds a blueprint containing a single worldspawn entity. Each brush is
+/// given as a list of face planes, and the material used for all its faces.
+pub fn make_blueprint(brushes: &[(Vec<DPlane3>, &str)]) -> MapBlueprint
+{
+	let mut blueprint: MapBlueprint = MapBlueprint {
+		entities: Vec::new(),
+		planes: PlanePool::new(),
+		textures: HashMap::new(),
+	};
+
+	add_entity(&mut blueprint, "worldspawn", brushes);
+	return blueprint;
+}
+
+/// Adds an entity with the given classname and brushes to the blueprint, and
+/// returns a reference to it.
+pub fn add_entity<'l>(
+	blueprint: &'l mut MapBlueprint,
+	classname: &str,
+	brushes: &[(Vec<DPlane3>, &str)],
+) -> &'l mut BlueprintEntity
+{
+	let brushes: Vec<BlueprintBrush> = brushes
+		.iter()
+		.map(|(face_planes, material)| make_brush(&mut blueprint.planes, face_planes, material))
+		.collect();
+
+	let mut keyvalues: EntityKeyValues = EntityKeyValues::new();
+	keyvalues.push(String::from("classname"), String::from(classname));
+
+	blueprint.entities.push(BlueprintEntity {
+		brushes: brushes,
+		keyvalues: keyvalues,
+		source_line: 1,
+	});
+
+	return blueprint.entities.last_mut().unwrap();
+}
+
+fn make_brush(planes: &mut PlanePool, face_planes: &[DPlane3], material: &str) -> BlueprintBrush
+{
+	let projection: TextureProjection = TextureProjection {
+		u_axis: TextureAxis {
+			axis: DVec3::X,
+			offset: 0.0,
+		},
+		v_axis: TextureAxis {
+			axis: DVec3::NEG_Y,
+			offset: 0.0,
+		},
+		rotation: 0.0,
+		scale: DVec2::ONE,
+	};
+
+	let faces: Vec<BlueprintBrushFace> = face_planes
+		.iter()
+		.map(|plane| BlueprintBrushFace {
+			plane: planes.find_or_insert(plane),
+			material: String::from(material),
+			projection: projection,
+		})
+		.collect();
+
+	return BlueprintBrush {
+		faces: faces,
+		source_line: 3,
+		colinear_face_count: 0,
+	};
+}
@@ -0,0 +1,300 @@
+use crate::compiler_error::{CompilerError, CompilerErrorCode};
+use crate::game_config::{BrushValidationConfig, ProblemAction};
+use crate::model::{BlueprintBrush, BrushDefect, BrushGeometry, MapBlueprint};
+use glam::DVec3;
+use log::{debug, warn};
+use std::fmt;
+use std::path::PathBuf;
+
+enum BrushProblem
+{
+	ColinearPlanePoints(usize),
+	Defect(BrushDefect),
+	MicroBrush(f64),
+	OutsideWorld,
+}
+
+impl BrushProblem
+{
+	fn action(&self, config: &BrushValidationConfig) -> ProblemAction
+	{
+		return match self
+		{
+			BrushProblem::ColinearPlanePoints(_) => config.colinear_plane_points,
+			BrushProblem::Defect(BrushDefect::DuplicatePlane { .. }) => config.duplicate_planes,
+			BrushProblem::Defect(BrushDefect::FaceClippedAway { .. }) => config.clipped_faces,
+			BrushProblem::Defect(BrushDefect::DegenerateFace { .. }) => config.degenerate_faces,
+			BrushProblem::Defect(BrushDefect::OpenBrush) => config.open_brushes,
+			BrushProblem::MicroBrush(_) => config.micro_brushes,
+			BrushProblem::OutsideWorld => config.outside_world,
+		};
+	}
+
+	// Returns the face that should be removed to repair the problem, if the
+	// problem can be repaired.
+	fn face_to_remove(&self) -> Option<usize>
+	{
+		return match self
+		{
+			BrushProblem::Defect(BrushDefect::DuplicatePlane { face, .. })
+			| BrushProblem::Defect(BrushDefect::FaceClippedAway { face })
+			| BrushProblem::Defect(BrushDefect::DegenerateFace { face }) => Some(*face),
+			_ => None,
+		};
+	}
+
+	fn can_be_repaired(&self) -> bool
+	{
+		// Faces with colinear plane points were already left out of the brush
+		// when the map was read, so there is nothing more to do.
+		return matches!(self, BrushProblem::ColinearPlanePoints(_))
+			|| self.face_to_remove().is_some();
+	}
+}
+
+impl fmt::Display for BrushProblem
+{
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result
+	{
+		match self
+		{
+			BrushProblem::ColinearPlanePoints(count) =>
+			{
+				write!(f, "{count} face(s) had colinear plane points")
+			}
+			BrushProblem::Defect(defect) => write!(f, "{defect}"),
+			BrushProblem::MicroBrush(size) =>
+			{
+				write!(f, "brush is only {size} units thick")
+			}
+			BrushProblem::OutsideWorld => write!(f, "brush extends beyond the world limits"),
+		}
+	}
+}
+
+// What happens to a brush once all of its problems have been considered.
+enum BrushOutcome
+{
+	Keep,
+	RemoveFaces(Vec<usize>),
+	Drop,
+}
+
+/// Checks every brush in the blueprint for problems, such as faces that do
+/// not contribute to the brush or brushes that do not enclose a volume. Each
+/// problem is handled according to the action specified in the config, which
+/// may involve repairing or dropping the brush.
+pub fn validate_brushes(
+	blueprint: &mut MapBlueprint,
+	config: &BrushValidationConfig,
+	source_file: &PathBuf,
+) -> Result<(), CompilerError>
+{
+	let mut problem_count: usize = 0;
+	let mut repaired_count: usize = 0;
+	let mut dropped_count: usize = 0;
+
+	for (entity_index, entity) in blueprint.entities.iter_mut().enumerate()
+	{
+		let classname: String = entity
+			.keyvalues
+			.first("classname")
+			.unwrap_or("<no classname>")
+			.to_owned();
+
+		let mut outcomes: Vec<BrushOutcome> = Vec::with_capacity(entity.brushes.len());
+
+		for (brush_index, brush) in entity.brushes.iter().enumerate()
+		{
+			let problems: Vec<BrushProblem> =
+				find_problems(brush, &brush.geometry(&blueprint.planes), config);
+
+			problem_count += problems.len();
+
+			let location: String = format!(
+				"{}:{}: entity {entity_index} ({classname}), brush {brush_index}",
+				source_file.to_string_lossy(),
+				brush.source_line,
+			);
+
+			outcomes.push(resolve_problems(&problems, config, &location)?);
+		}
+
+		let mut outcomes = outcomes.into_iter();
+
+		entity.brushes.retain_mut(|brush| match outcomes.next()
+		{
+			Some(BrushOutcome::Drop) =>
+			{
+				dropped_count += 1;
+				false
+			}
+			Some(BrushOutcome::RemoveFaces(faces)) =>
+			{
+				remove_faces(brush, &faces);
+				repaired_count += 1;
+				true
+			}
+			_ => true,
+		});
+	}
+
+	debug!(
+		"Brush validation found {problem_count} problem(s): {repaired_count} brush(es) repaired, {dropped_count} dropped"
+	);
+
+	return Ok(());
+}
+
+fn find_problems(
+	brush: &BlueprintBrush,
+	geometry: &BrushGeometry,
+	config: &BrushValidationConfig,
+) -> Vec<BrushProblem>
+{
+	let mut problems: Vec<BrushProblem> = Vec::new();
+
+	if brush.colinear_face_count > 0
+	{
+		problems.push(BrushProblem::ColinearPlanePoints(brush.colinear_face_count));
+	}
+
+	problems.extend(geometry.defects.iter().cloned().map(BrushProblem::Defect));
+
+	// The size of an open brush is meaningless.
+	if !geometry.is_valid()
+	{
+		return problems;
+	}
+
+	if let Some((mins, maxs)) = geometry.bounds()
+	{
+		let size: DVec3 = maxs - mins;
+
+		if size.min_element() < config.min_brush_size
+		{
+			problems.push(BrushProblem::MicroBrush(size.min_element()));
+		}
+
+		if mins.min_element() < -config.world_extent || maxs.max_element() > config.world_extent
+		{
+			problems.push(BrushProblem::OutsideWorld);
+		}
+	}
+
+	return problems;
+}
+
+fn resolve_problems(
+	problems: &[BrushProblem],
+	config: &BrushValidationConfig,
+	location: &str,
+) -> Result<BrushOutcome, CompilerError>
+{
+	let mut faces_to_remove: Vec<usize> = Vec::new();
+	let mut drop_brush: bool = false;
+
+	for problem in problems
+	{
+		match problem.action(config)
+		{
+			ProblemAction::Ignore => (),
+			ProblemAction::Warn => warn!("{location}: {problem}"),
+			ProblemAction::Repair if problem.can_be_repaired() =>
+			{
+				warn!("{location}: {problem}. The brush will be repaired.");
+				faces_to_remove.extend(problem.face_to_remove());
+			}
+			ProblemAction::Repair | ProblemAction::Drop =>
+			{
+				warn!("{location}: {problem}. The brush will be dropped.");
+				drop_brush = true;
+			}
+			ProblemAction::Error =>
+			{
+				return Err(CompilerError::new(
+					CompilerErrorCode::GeometryError,
+					format!("{location}: {problem}"),
+				));
+			}
+		}
+	}
+
+	if drop_brush
+	{
+		return Ok(BrushOutcome::Drop);
+	}
+
+	if !faces_to_remove.is_empty()
+	{
+		return Ok(BrushOutcome::RemoveFaces(faces_to_remove));
+	}
+
+	return Ok(BrushOutcome::Keep);
+}
+
+fn remove_faces(brush: &mut BlueprintBrush, faces: &[usize])
+{
+	let mut index: usize = 0;
+
+	brush.faces.retain(|_| {
+		let keep: bool = !faces.contains(&index);
+		index += 1;
+		keep
+	});
+}
+
+#[cfg(test)]
+mod tests
+{
+	use super::*;
+	use crate::model::DPlane3;
+	use crate::work_units::test_fixtures::{box_planes, make_blueprint};
+
+	#[test]
+	fn repair_and_drop_brushes()
+	{
+		let valid: Vec<DPlane3> = box_planes(DVec3::splat(-32.0), DVec3::splat(32.0));
+
+		let mut duplicate: Vec<DPlane3> = valid.clone();
+		duplicate.push(DPlane3::new(DVec3::X, 32.0));
+
+		let open: Vec<DPlane3> = valid[..5].to_vec();
+		let outside: Vec<DPlane3> = box_planes(DVec3::splat(4000.0), DVec3::splat(5000.0));
+
+		let mut blueprint: MapBlueprint = make_blueprint(&[
+			(valid, "AAATRIGGER"),
+			(duplicate, "AAATRIGGER"),
+			(open, "AAATRIGGER"),
+			(outside, "AAATRIGGER"),
+		]);
+		let config: BrushValidationConfig = BrushValidationConfig::default();
+
+		validate_brushes(&mut blueprint, &config, &PathBuf::from("test.map")).unwrap();
+
+		let brushes: &Vec<BlueprintBrush> = &blueprint.entities[0].brushes;
+		assert_eq!(brushes.len(), 2);
+		assert_eq!(brushes[0].faces.len(), 6);
+		assert_eq!(brushes[1].faces.len(), 6);
+	}
+
+	#[test]
+	fn fail_on_error_action()
+	{
+		let micro: Vec<DPlane3> = box_planes(DVec3::ZERO, DVec3::new(64.0, 64.0, 0.5));
+		let mut blueprint: MapBlueprint = make_blueprint(&[(micro, "AAATRIGGER")]);
+
+		let config: BrushValidationConfig = BrushValidationConfig {
+			micro_brushes: ProblemAction::Error,
+			..BrushValidationConfig::default()
+		};
+
+		let err: CompilerError =
+			validate_brushes(&mut blueprint, &config, &PathBuf::from("test.map")).unwrap_err();
+
+		assert_eq!(
+			err.description,
+			"test.map:3: entity 0 (worldspawn), brush 0: brush is only 0.5 units thick"
+		);
+	}
+}
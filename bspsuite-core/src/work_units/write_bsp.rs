@@ -0,0 +1,83 @@
+use crate::compiler_error::{CompilerError, CompilerErrorCode};
+use crate::extensions::ExtensionList;
+use crate::extensions::bsp_writer_api::{BspWriterError, ExceededLimit, call_bsp_writer_api};
+use crate::model::BspData;
+use bspextifc::bsp_writer_api::BspWriterCallbacks;
+use log::{debug, error};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+/// Writes the compiled map to a .bsp file next to the map source file, using
+/// whichever extension has registered itself as being able to write the
+/// requested format. Limits named in the overrides replace those of the format.
+pub fn write_bsp(
+	extensions: &ExtensionList,
+	format: &str,
+	limit_overrides: &BTreeMap<String, usize>,
+	data: &BspData,
+	source_file: &PathBuf,
+) -> Result<PathBuf, CompilerError>
+{
+	let (extension_name, callbacks): (&str, &BspWriterCallbacks) = extensions
+		.iter()
+		.find_map(|extension| {
+			extension
+				.get_api_callbacks()
+				.bsp_writer_api_callbacks
+				.as_ref()
+				.filter(|callbacks| callbacks.format.as_str().eq_ignore_ascii_case(format))
+				.map(|callbacks| (extension.get_name(), callbacks))
+		})
+		.ok_or_else(|| {
+			CompilerError::new(
+				CompilerErrorCode::ConfigError,
+				format!("No loaded extension supports writing {format} files"),
+			)
+		})?;
+
+	let path: PathBuf = source_file.with_extension("bsp");
+	let path_string: String = path.to_string_lossy().to_string();
+	debug!("Writing {path_string} as {format} using extension {extension_name}");
+
+	call_bsp_writer_api(callbacks.write_bsp_fn, &path, data, limit_overrides).map_err(|err| {
+		match err
+		{
+			BspWriterError::Failed(message) => CompilerError::new(
+				CompilerErrorCode::IoError,
+				format!("Failed to write {path_string}. {message}"),
+			),
+			BspWriterError::LimitsExceeded(limits) =>
+			{
+				limits
+					.iter()
+					.for_each(|limit| error!("{}", describe_limit(limit)));
+
+				CompilerError::new(
+					CompilerErrorCode::LimitError,
+					format!(
+						"Map exceeded {} limit(s) of the {format} format: {}",
+						limits.len(),
+						limits
+							.iter()
+							.map(|limit| limit.name.as_str())
+							.collect::<Vec<&str>>()
+							.join(", ")
+					),
+				)
+			}
+		}
+	})?;
+
+	return Ok(path);
+}
+
+fn describe_limit(limit: &ExceededLimit) -> String
+{
+	return format!(
+		"{}: {} of {} ({} over)",
+		limit.name,
+		limit.count,
+		limit.limit,
+		limit.count.saturating_sub(limit.limit)
+	);
+}
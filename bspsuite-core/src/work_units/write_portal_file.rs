@@ -0,0 +1,120 @@
+use crate::compiler_error::{CompilerError, CompilerErrorCode};
+use crate::model::{PortalGraph, VisPortals, Winding};
+use log::debug;
+use std::fs;
+use std::path::PathBuf;
+
+/// Writes the portals between visibility clusters to a .prt file next to the
+/// map source file, in the PRT1 format read by VIS tools and level editors.
+pub fn write_portal_file(
+	source_file: &PathBuf,
+	vis_portals: &VisPortals,
+) -> Result<(), CompilerError>
+{
+	let portals: Vec<(&Winding, [usize; 2])> = vis_portals
+		.portals
+		.iter()
+		.map(|portal| (&portal.winding, portal.clusters))
+		.collect();
+
+	let path: PathBuf = source_file.with_extension("prt");
+	write_prt(&path, vis_portals.cluster_count, &portals)?;
+
+	debug!(
+		"Wrote {} portals to {}",
+		portals.len(),
+		path.to_string_lossy()
+	);
+	return Ok(());
+}
+
+/// Writes every portal between leaves of the tree to a .debug.prt file next to
+/// the map source file. Unlike the file used by VIS, this numbers leaves rather
+/// than clusters, and includes the portals within each cluster. Portals to the
+/// void outside the tree are left out, as they have no leaf on one side.
+pub fn write_debug_portal_file(
+	source_file: &PathBuf,
+	leaf_count: usize,
+	graph: &PortalGraph,
+) -> Result<(), CompilerError>
+{
+	let portals: Vec<(&Winding, [usize; 2])> = graph
+		.portals
+		.iter()
+		.filter(|portal| !portal.leaves.contains(&graph.outside_leaf))
+		.map(|portal| (&portal.winding, portal.leaves))
+		.collect();
+
+	let path: PathBuf = source_file.with_extension("debug.prt");
+	write_prt(&path, leaf_count, &portals)?;
+
+	debug!(
+		"Wrote {} debug portals to {}",
+		portals.len(),
+		path.to_string_lossy()
+	);
+	return Ok(());
+}
+
+fn write_prt(
+	path: &PathBuf,
+	region_count: usize,
+	portals: &[(&Winding, [usize; 2])],
+) -> Result<(), CompilerError>
+{
+	return fs::write(path, prt_text(region_count, portals)).map_err(|err| {
+		CompilerError::new(
+			CompilerErrorCode::IoError,
+			format!("Failed to write {}. {err}", path.to_string_lossy()),
+		)
+	});
+}
+
+// Each portal is written with the region that its winding faces first. The
+// regions are either clusters or leaves, depending on the file.
+fn prt_text(region_count: usize, portals: &[(&Winding, [usize; 2])]) -> String
+{
+	let mut text: String = format!("PRT1\n{region_count}\n{}\n", portals.len());
+
+	for (winding, [front, back]) in portals
+	{
+		text.push_str(&format!("{} {front} {back}", winding.points.len()));
+
+		for point in &winding.points
+		{
+			text.push_str(&format!(" ({} {} {} )", point.x, point.y, point.z));
+		}
+
+		text.push('\n');
+	}
+
+	return text;
+}
+
+#[cfg(test)]
+mod tests
+{
+	use super::*;
+	use glam::DVec3;
+
+	#[test]
+	fn format_prt1()
+	{
+		let winding: Winding = Winding {
+			points: vec![
+				DVec3::new(0.0, -64.0, 64.0),
+				DVec3::new(0.0, 64.0, 64.0),
+				DVec3::new(0.0, 64.0, -64.0),
+				DVec3::new(0.0, -64.0, -0.5),
+			],
+		};
+
+		assert_eq!(
+			prt_text(2, &[(&winding, [1, 0])]),
+			"PRT1\n\
+			2\n\
+			1\n\
+			4 1 0 (0 -64 64 ) (0 64 64 ) (0 64 -64 ) (0 -64 -0.5 )\n"
+		);
+	}
+}
@@ -0,0 +1,85 @@
+use crate::io::bsp30_builder::build_bsp30;
+use crate::io::bsp30_writer::encode_bsp30;
+use crate::model::bsp30::{
+	Bsp30, ExceededLimit, MAX_MAP_CLIPNODES, MAX_MAP_EDGES, MAX_MAP_ENTSTRING, MAX_MAP_FACES,
+	MAX_MAP_LEAFS, MAX_MAP_LIGHTING, MAX_MAP_MARKSURFACES, MAX_MAP_MIPTEX, MAX_MAP_MODELS,
+	MAX_MAP_NODES, MAX_MAP_PLANES, MAX_MAP_SURFEDGES, MAX_MAP_TEXINFO, MAX_MAP_TEXTURES,
+	MAX_MAP_VERTS, MAX_MAP_VISIBILITY, check_limit_overrides,
+};
+use bspextifc::StringRef;
+use bspextifc::bsp_writer_api::{BspWriterApi, FormatLimit, WriteBspArgs, WriteBspResult};
+use std::collections::HashMap;
+
+/// Limits of the engine, as reported to the core.
+pub static LIMITS: [FormatLimit; 16] = [
+	limit("MAX_MAP_MODELS", MAX_MAP_MODELS),
+	limit("MAX_MAP_ENTSTRING", MAX_MAP_ENTSTRING),
+	limit("MAX_MAP_PLANES", MAX_MAP_PLANES),
+	limit("MAX_MAP_NODES", MAX_MAP_NODES),
+	limit("MAX_MAP_CLIPNODES", MAX_MAP_CLIPNODES),
+	limit("MAX_MAP_LEAFS", MAX_MAP_LEAFS),
+	limit("MAX_MAP_VERTS", MAX_MAP_VERTS),
+	limit("MAX_MAP_FACES", MAX_MAP_FACES),
+	limit("MAX_MAP_MARKSURFACES", MAX_MAP_MARKSURFACES),
+	limit("MAX_MAP_TEXINFO", MAX_MAP_TEXINFO),
+	limit("MAX_MAP_EDGES", MAX_MAP_EDGES),
+	limit("MAX_MAP_SURFEDGES", MAX_MAP_SURFEDGES),
+	limit("MAX_MAP_TEXTURES", MAX_MAP_TEXTURES),
+	limit("MAX_MAP_MIPTEX", MAX_MAP_MIPTEX),
+	limit("MAX_MAP_LIGHTING", MAX_MAP_LIGHTING),
+	limit("MAX_MAP_VISIBILITY", MAX_MAP_VISIBILITY),
+];
+
+pub extern "C" fn write_bsp(api: &mut BspWriterApi, args: &WriteBspArgs) -> WriteBspResult
+{
+	let overrides: HashMap<String, usize> = args
+		.limits
+		.as_slice()
+		.iter()
+		.map(|limit| (limit.name.to_string(), limit.value))
+		.collect();
+
+	if let Err(message) = check_limit_overrides(&overrides)
+	{
+		api.set_error(&message);
+		return WriteBspResult::Failure;
+	}
+
+	let bsp: Bsp30 = match build_bsp30(args)
+	{
+		Ok(bsp) => bsp,
+		Err(message) =>
+		{
+			api.set_error(&message);
+			return WriteBspResult::Failure;
+		}
+	};
+
+	let exceeded_limits: Vec<ExceededLimit> = bsp.exceeded_limits(&overrides);
+
+	if !exceeded_limits.is_empty()
+	{
+		for limit in exceeded_limits
+		{
+			api.report_exceeded_limit(limit.name, limit.count, limit.limit);
+		}
+
+		return WriteBspResult::Failure;
+	}
+
+	if let Err(err) = std::fs::write(args.path.to_string(), encode_bsp30(&bsp))
+	{
+		api.set_error(&format!("Could not write file. {err}"));
+		return WriteBspResult::Failure;
+	}
+
+	return WriteBspResult::Success;
+}
+
+const fn limit(name: &'static str, value: usize) -> FormatLimit
+{
+	return FormatLimit {
+		name: StringRef::new(name),
+		value: value,
+	};
+}
@@ -0,0 +1,99 @@
+use crate::io::map_parser;
+use crate::model::map_blueprint::{BlueprintBrushFace, MapBlueprint, TextureAlignment};
+use bspextifc::StringRef;
+use bspextifc::map_reader_api::{
+	BrushFaceArgs, MapReaderApi, ReadMapArgs, ReadMapResult, SyntaxErrorArgs,
+};
+
+pub extern "C" fn read_map(api: &mut MapReaderApi, args: &ReadMapArgs) -> ReadMapResult
+{
+	let path: String = args.path.to_string();
+
+	// Map files produced by older editors are not guaranteed to be UTF-8
+	// (keyvalues may contain characters from the system code page), so we
+	// don't want to reject the whole file if this is the case.
+	let source: String = match std::fs::read(&path)
+	{
+		Ok(bytes) => String::from_utf8_lossy(&bytes).into_owned(),
+		Err(err) =>
+		{
+			api.set_error(&format!("Could not read file. {err}"));
+			return ReadMapResult::Failure;
+		}
+	};
+
+	let blueprint: MapBlueprint = match map_parser::parse_map(&source)
+	{
+		Ok(blueprint) => blueprint,
+		Err(err) =>
+		{
+			api.report_syntax_error(&SyntaxErrorArgs {
+				line: err.line,
+				column: err.column,
+				span_start: err.span.start,
+				span_end: err.span.end,
+				message: StringRef::from(err.message.as_str()),
+				line_text: StringRef::from(err.line_text.as_str()),
+			});
+
+			return ReadMapResult::Failure;
+		}
+	};
+
+	submit_blueprint(api, &blueprint);
+	return ReadMapResult::Success;
+}
+
+fn submit_blueprint(api: &mut MapReaderApi, blueprint: &MapBlueprint)
+{
+	for entity in &blueprint.entities
+	{
+		api.begin_entity(entity.line);
+
+		for (key, value) in &entity.keyvalues
+		{
+			api.add_keyvalue(key, value);
+		}
+
+		for brush in &entity.brushes
+		{
+			api.begin_brush(brush.line);
+
+			for face in &brush.faces
+			{
+				api.add_brush_face(&brush_face_args(face));
+			}
+
+			api.end_brush();
+		}
+
+		api.end_entity();
+	}
+}
+
+fn brush_face_args(face: &BlueprintBrushFace) -> BrushFaceArgs<'_>
+{
+	let (u_axis, u_offset, v_axis, v_offset, has_texture_axes) = match face.alignment
+	{
+		TextureAlignment::Standard { offset } => ([0.0; 3], offset.x, [0.0; 3], offset.y, false),
+		TextureAlignment::Valve220 { u_axis, v_axis } => (
+			u_axis.axis.to_array(),
+			u_axis.offset,
+			v_axis.axis.to_array(),
+			v_axis.offset,
+			true,
+		),
+	};
+
+	return BrushFaceArgs {
+		plane_points: face.plane_points.map(|point| point.to_array()),
+		material: StringRef::from(face.material.as_str()),
+		u_axis: u_axis,
+		u_offset: u_offset,
+		v_axis: v_axis,
+		v_offset: v_offset,
+		has_texture_axes: has_texture_axes,
+		rotation: face.rotation,
+		scale: face.scale.to_array(),
+	};
+}
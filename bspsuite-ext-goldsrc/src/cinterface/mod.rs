@@ -0,0 +1,4 @@
+pub mod bsp_writer;
+pub mod map_reader;
+pub mod stage_hooks;
+pub mod texture_provider;
@@ -0,0 +1,76 @@
+use crate::model::bsp30::MAX_LIGHTMAP_SIZE;
+use bspextifc::stage_hook_api::{BspFaceArgs, StageHookApi, StageHookResult};
+
+// Formats that are loaded by engines with the GoldSrc lightmap size limit.
+const LIMITED_FORMATS: [&str; 2] = ["bsp29", "bsp30"];
+
+/// Fails the compile if any face has a lightmap too large for the engine to
+/// load, which happens when faces are not subdivided finely enough.
+pub extern "C" fn check_surface_extents(api: &mut StageHookApi) -> StageHookResult
+{
+	if !LIMITED_FORMATS.contains(&api.bsp_format().as_str())
+	{
+		return StageHookResult::Success;
+	}
+
+	for model in 0..api.model_count()
+	{
+		let face_count: usize = api.model(model).map_or(0, |model| model.face_count);
+
+		for face in 0..face_count
+		{
+			let (Some(size), Some(source)) = (
+				api.face_lightmap_size(model, face),
+				api.model_face(model, face),
+			)
+			else
+			{
+				continue;
+			};
+
+			if let Some(message) = check_lightmap_size(size, &source)
+			{
+				api.set_error(&message);
+				return StageHookResult::Failure;
+			}
+		}
+	}
+
+	return StageHookResult::Success;
+}
+
+fn check_lightmap_size(size: [usize; 2], source: &BspFaceArgs) -> Option<String>
+{
+	if size.iter().all(|samples| *samples <= MAX_LIGHTMAP_SIZE)
+	{
+		return None;
+	}
+
+	return Some(format!(
+		"Face {} of brush {} in entity {} has a lightmap of {}x{} samples, but the engine allows no more than {MAX_LIGHTMAP_SIZE} along each axis. Lower the subdivide size, or raise the texture scale of the face",
+		source.face, source.brush, source.entity, size[0], size[1]
+	));
+}
+
+#[cfg(test)]
+mod tests
+{
+	use super::*;
+
+	#[test]
+	fn limit_lightmap_size()
+	{
+		let source: BspFaceArgs = BspFaceArgs {
+			entity: 2,
+			brush: 1,
+			face: 4,
+		};
+
+		assert_eq!(check_lightmap_size([17, 1], &source), None);
+		assert!(
+			check_lightmap_size([3, 18], &source)
+				.unwrap()
+				.starts_with("Face 4 of brush 1 in entity 2 has a lightmap of 3x18 samples")
+		);
+	}
+}
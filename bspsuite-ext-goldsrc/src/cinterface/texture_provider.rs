@@ -0,0 +1,115 @@
+use crate::io::wad_reader::{read_wad, wad_candidates};
+use crate::model::wad::WadTexture;
+use bspextifc::texture_api::{FindTexturesArgs, FindTexturesResult, TextureApi, TextureArgs};
+use bspextifc::{SliceRef, StringRef};
+use glam::DVec3;
+use log::{debug, warn};
+use std::collections::HashMap;
+use std::path::Path;
+
+/// Provides textures from the WADs listed in the world's wad keyvalue.
+pub extern "C" fn find_textures(api: &mut TextureApi, args: &FindTexturesArgs)
+-> FindTexturesResult
+{
+	let map_path: String = args.map_path.to_string();
+	let map_dir: &Path = Path::new(&map_path).parent().unwrap_or(Path::new(""));
+
+	let wad_list: String = args
+		.world_keyvalues
+		.as_slice()
+		.iter()
+		.rev()
+		.find(|keyvalue| keyvalue.key.to_string() == "wad")
+		.map(|keyvalue| keyvalue.value.to_string())
+		.unwrap_or_default();
+
+	let search_paths: Vec<String> = args
+		.search_paths
+		.as_slice()
+		.iter()
+		.map(StringRef::to_string)
+		.collect();
+
+	let textures: HashMap<String, WadTexture> = load_wads(&wad_list, map_dir, &search_paths);
+
+	for material in args.materials.as_slice()
+	{
+		let Some(texture) = textures.get(&material.to_string().to_ascii_lowercase())
+		else
+		{
+			continue;
+		};
+
+		let average_color: Option<DVec3> = texture.average_color();
+
+		api.add_texture(&TextureArgs {
+			name: StringRef::from(texture.name.as_str()),
+			width: texture.width,
+			height: texture.height,
+			average_color: average_color.unwrap_or_default().to_array(),
+			has_average_color: average_color.is_some(),
+			pixels: SliceRef::from(texture.pixels.as_slice()),
+			palette: SliceRef::from(texture.palette.as_slice()),
+		});
+	}
+
+	return FindTexturesResult::Success;
+}
+
+// Reads the textures from a list of WADs separated by semicolons, keyed by
+// their name in lower case. When more than one WAD holds a texture of the same
+// name, the first one listed wins. WADs that cannot be found or read are
+// reported and skipped, since the map may not need any of their textures.
+fn load_wads(wad_list: &str, map_dir: &Path, search_paths: &[String])
+-> HashMap<String, WadTexture>
+{
+	let mut textures: HashMap<String, WadTexture> = HashMap::new();
+
+	if wad_list.trim().is_empty()
+	{
+		warn!("The world does not list any WADs, so no textures can be loaded from them");
+		return textures;
+	}
+
+	for wad_path in wad_list
+		.split(';')
+		.map(str::trim)
+		.filter(|wad_path| !wad_path.is_empty())
+	{
+		let Some(path) = wad_candidates(wad_path, map_dir, search_paths)
+			.into_iter()
+			.find(|candidate| candidate.is_file())
+		else
+		{
+			warn!("Could not find WAD {wad_path}");
+			continue;
+		};
+
+		let wad_textures: Vec<WadTexture> = match std::fs::read(&path)
+			.map_err(|err| err.to_string())
+			.and_then(|bytes| read_wad(&bytes))
+		{
+			Ok(wad_textures) => wad_textures,
+			Err(err) =>
+			{
+				warn!("Could not read WAD {}. {err}", path.to_string_lossy());
+				continue;
+			}
+		};
+
+		debug!(
+			"Read {} textures from {}",
+			wad_textures.len(),
+			path.to_string_lossy()
+		);
+
+		for texture in wad_textures
+		{
+			textures
+				.entry(texture.name.to_ascii_lowercase())
+				.or_insert(texture);
+		}
+	}
+
+	return textures;
+}
@@ -0,0 +1,657 @@
+use crate::io::bsp30_writer::compress_vis_row;
+use crate::model::bsp30::{
+	Bsp30, CONTENTS_EMPTY, CONTENTS_LAVA, CONTENTS_SKY, CONTENTS_SLIME, CONTENTS_SOLID,
+	CONTENTS_WATER, ClipNode, EmbeddedMips, Face, Leaf, MIP_LEVELS, MIPTEX_NAME_LENGTH, MipTexture,
+	Model, Node, Plane, TEX_SPECIAL, TexInfo,
+};
+use bspextifc::bsp_writer_api::{
+	FaceArgs, LeafArgs, LeafContents, ModelArgs, NodeChild, TextureArgs, WriteBspArgs,
+};
+use std::collections::HashMap;
+
+/// Number of clipping hulls that each model has, after the point hull.
+const CLIP_HULL_COUNT: usize = 3;
+
+/// Converts the compiled map into the lumps of a version 30 file. Only the
+/// first plane of each pair is written, and faces record which side of it they
+/// are on. Every solid leaf is merged into leaf 0, as the engine expects.
+pub fn build_bsp30(args: &WriteBspArgs) -> Result<Bsp30, String>
+{
+	let mut builder: Bsp30Builder = Bsp30Builder {
+		bsp: Bsp30 {
+			entities: [args.entities.to_string().as_bytes(), &[0]].concat(),
+			planes: args
+				.planes
+				.as_slice()
+				.iter()
+				.step_by(2)
+				.map(|plane| Plane {
+					normal: plane.normal.map(|value| value as f32),
+					distance: plane.distance as f32,
+					kind: plane_kind(plane.normal),
+				})
+				.collect(),
+			lighting: args.lighting.as_slice().to_vec(),
+			..Bsp30::default()
+		},
+		leaf_indices: Vec::new(),
+		vertex_indices: HashMap::new(),
+		unshared_edges: HashMap::new(),
+	};
+
+	builder.bsp.textures = args
+		.textures
+		.as_slice()
+		.iter()
+		.map(mip_texture)
+		.collect::<Result<Vec<MipTexture>, String>>()?;
+
+	builder.bsp.texinfos = args
+		.texture_infos
+		.as_slice()
+		.iter()
+		.map(|info| TexInfo {
+			vecs: [
+				[
+					info.u_axis[0],
+					info.u_axis[1],
+					info.u_axis[2],
+					info.u_offset,
+				]
+				.map(|v| v as f32),
+				[
+					info.v_axis[0],
+					info.v_axis[1],
+					info.v_axis[2],
+					info.v_offset,
+				]
+				.map(|v| v as f32),
+			],
+			texture: info.texture as i32,
+			flags: if info.special { TEX_SPECIAL } else { 0 },
+		})
+		.collect();
+
+	// Edge 0 cannot be referred to by a signed index, so is left unused.
+	builder.bsp.edges.push([0, 0]);
+	for face in args.faces.as_slice()
+	{
+		builder.add_face(face)?;
+	}
+
+	builder.add_leaves(args.leaves.as_slice())?;
+	builder.add_visibility(args);
+
+	for node in args.nodes.as_slice()
+	{
+		let mut children: [i16; 2] = [
+			builder.node_child(node.children[0])?,
+			builder.node_child(node.children[1])?,
+		];
+
+		if node.plane % 2 == 1
+		{
+			children.swap(0, 1);
+		}
+
+		builder.bsp.nodes.push(Node {
+			plane: (node.plane / 2) as i32,
+			children: children,
+			mins: node.mins.map(|value| clamp_to_i16(value.floor())),
+			maxs: node.maxs.map(|value| clamp_to_i16(value.ceil())),
+			first_face: narrow(node.first_face, "first face of a node")?,
+			face_count: narrow(node.face_count, "face count of a node")?,
+		});
+	}
+
+	for node in args.clip_nodes.as_slice()
+	{
+		let mut children: [i16; 2] = [
+			narrow(clip_child(node.children[0])?, "clipping node index")?,
+			narrow(clip_child(node.children[1])?, "clipping node index")?,
+		];
+
+		if node.plane % 2 == 1
+		{
+			children.swap(0, 1);
+		}
+
+		builder.bsp.clip_nodes.push(ClipNode {
+			plane: (node.plane / 2) as i32,
+			children: children,
+		});
+	}
+
+	for model in args.models.as_slice()
+	{
+		let model: Model = builder.model(model, args.leaves.as_slice())?;
+		builder.bsp.models.push(model);
+	}
+
+	return Ok(builder.bsp);
+}
+
+struct Bsp30Builder
+{
+	bsp: Bsp30,
+
+	// Index in the file of each leaf passed to the writer.
+	leaf_indices: Vec<usize>,
+
+	// Vertices by the bits of their coordinates, and edges that have been used
+	// by one face and may be used in reverse by another.
+	vertex_indices: HashMap<[u32; 3], u16>,
+	unshared_edges: HashMap<[u16; 2], i32>,
+}
+
+impl Bsp30Builder
+{
+	fn add_face(&mut self, face: &FaceArgs) -> Result<(), String>
+	{
+		let mut vertices: Vec<u16> = face
+			.points
+			.as_slice()
+			.iter()
+			.map(|point| self.vertex_index(point))
+			.collect::<Result<Vec<u16>, String>>()?;
+
+		// Points that are distinct in double precision may not be once they
+		// are written.
+		vertices.dedup();
+		if vertices.len() > 1 && vertices.first() == vertices.last()
+		{
+			vertices.pop();
+		}
+
+		let first_edge: usize = self.bsp.surf_edges.len();
+
+		for (index, vertex) in vertices.iter().enumerate()
+		{
+			let next: u16 = vertices[(index + 1) % vertices.len()];
+			let surf_edge: i32 = self.edge(*vertex, next)?;
+			self.bsp.surf_edges.push(surf_edge);
+		}
+
+		self.bsp.faces.push(Face {
+			plane: narrow(face.plane / 2, "plane index")?,
+			side: (face.plane % 2) as u16,
+			first_edge: narrow(first_edge, "first edge of a face")?,
+			edge_count: narrow(
+				self.bsp.surf_edges.len() - first_edge,
+				"edge count of a face",
+			)?,
+			texinfo: narrow(face.texture_info, "texture info index")?,
+			styles: face.styles,
+			lighting_offset: face.lighting_offset,
+		});
+
+		return Ok(());
+	}
+
+	fn vertex_index(&mut self, point: &[f64; 3]) -> Result<u16, String>
+	{
+		// Adding zero turns negative zero into positive zero, so that both
+		// give the same key.
+		let vertex: [f32; 3] = point.map(|value| value as f32 + 0.0);
+		let key: [u32; 3] = vertex.map(f32::to_bits);
+
+		if let Some(index) = self.vertex_indices.get(&key)
+		{
+			return Ok(*index);
+		}
+
+		let index: u16 = narrow(self.bsp.vertices.len(), "vertex index")?;
+		self.bsp.vertices.push(vertex);
+		self.vertex_indices.insert(key, index);
+		return Ok(index);
+	}
+
+	// Each edge may be shared by two faces, the second of which uses it in
+	// reverse, as given by a negative surface edge.
+	fn edge(&mut self, from: u16, to: u16) -> Result<i32, String>
+	{
+		if let Some(index) = self.unshared_edges.remove(&[to, from])
+		{
+			return Ok(-index);
+		}
+
+		let index: i32 = narrow(self.bsp.edges.len(), "edge index")?;
+		self.bsp.edges.push([from, to]);
+		self.unshared_edges.insert([from, to], index);
+		return Ok(index);
+	}
+
+	fn add_leaves(&mut self, leaves: &[LeafArgs]) -> Result<(), String>
+	{
+		self.bsp.leaves.push(Leaf {
+			contents: CONTENTS_SOLID,
+			vis_offset: -1,
+			mins: [0; 3],
+			maxs: [0; 3],
+			first_mark_surface: 0,
+			mark_surface_count: 0,
+			ambient_levels: [0; 4],
+		});
+
+		for leaf in leaves
+		{
+			if leaf.contents == LeafContents::Solid
+			{
+				self.leaf_indices.push(0);
+				continue;
+			}
+
+			let first_mark_surface: usize = self.bsp.mark_surfaces.len();
+
+			for face in leaf.faces.as_slice()
+			{
+				let face: u16 = narrow(*face, "face index")?;
+				self.bsp.mark_surfaces.push(face);
+			}
+
+			self.leaf_indices.push(self.bsp.leaves.len());
+			self.bsp.leaves.push(Leaf {
+				contents: contents_code(leaf.contents),
+				vis_offset: -1,
+				mins: leaf.mins.map(|value| clamp_to_i16(value.floor())),
+				maxs: leaf.maxs.map(|value| clamp_to_i16(value.ceil())),
+				first_mark_surface: narrow(first_mark_surface, "first mark surface of a leaf")?,
+				mark_surface_count: narrow(
+					self.bsp.mark_surfaces.len() - first_mark_surface,
+					"mark surface count of a leaf",
+				)?,
+				ambient_levels: [0; 4],
+			});
+		}
+
+		return Ok(());
+	}
+
+	// The engine stores visibility per leaf rather than per cluster, so each
+	// cluster's row is expanded to one bit for every leaf of the world after
+	// leaf 0. Leaves of the same cluster, and clusters that see the same
+	// leaves, share a row.
+	fn add_visibility(&mut self, args: &WriteBspArgs)
+	{
+		let Some(world) = args.models.as_slice().first()
+		else
+		{
+			return;
+		};
+
+		let world_leaves: &[LeafArgs] = &args.leaves.as_slice()
+			[world.first_leaf as usize..(world.first_leaf + world.leaf_count) as usize];
+
+		// Clusters of the world's leaves in the file, from leaf 1 onwards.
+		let leaf_clusters: Vec<Option<usize>> = world_leaves
+			.iter()
+			.filter(|leaf| leaf.contents != LeafContents::Solid)
+			.map(|leaf| usize::try_from(leaf.cluster).ok())
+			.collect();
+
+		let cluster_row_length: usize = (args.cluster_count as usize).div_ceil(8);
+		let cluster_visibility: &[u8] = args.cluster_visibility.as_slice();
+		let is_visible = |from: usize, to: usize| -> bool {
+			return cluster_visibility[from * cluster_row_length + to / 8] & (1 << (to % 8)) != 0;
+		};
+
+		let mut cluster_offsets: HashMap<usize, i32> = HashMap::new();
+		let mut row_offsets: HashMap<Vec<u8>, i32> = HashMap::new();
+
+		for (index, cluster) in leaf_clusters.iter().enumerate()
+		{
+			let Some(cluster) = cluster
+			else
+			{
+				continue;
+			};
+
+			let offset: i32 = *cluster_offsets.entry(*cluster).or_insert_with(|| {
+				let mut row: Vec<u8> = vec![0; leaf_clusters.len().div_ceil(8)];
+
+				for (to, to_cluster) in leaf_clusters.iter().enumerate()
+				{
+					if to_cluster.is_some_and(|to_cluster| is_visible(*cluster, to_cluster))
+					{
+						row[to / 8] |= 1 << (to % 8);
+					}
+				}
+
+				*row_offsets
+					.entry(compress_vis_row(&row))
+					.or_insert_with_key(|compressed| {
+						let offset: i32 = self.bsp.visibility.len() as i32;
+						self.bsp.visibility.extend_from_slice(compressed);
+						offset
+					})
+			});
+
+			self.bsp.leaves[1 + index].vis_offset = offset;
+		}
+	}
+
+	fn node_child(&self, child: NodeChild) -> Result<i16, String>
+	{
+		return match child
+		{
+			NodeChild::Node(index) => narrow(index, "node index"),
+			NodeChild::Leaf(index) =>
+			{
+				let leaf: i16 = narrow(self.leaf_indices[index as usize], "leaf index")?;
+				Ok(-leaf - 1)
+			}
+			NodeChild::Contents(_) => Err(String::from("A BSP node had contents as a child")),
+		};
+	}
+
+	fn model(&self, model: &ModelArgs, leaves: &[LeafArgs]) -> Result<Model, String>
+	{
+		let clip_head_nodes: &[NodeChild] = model.clip_head_nodes.as_slice();
+
+		if clip_head_nodes.len() > CLIP_HULL_COUNT
+		{
+			return Err(format!(
+				"Models may have at most {CLIP_HULL_COUNT} clipping hulls, but {} were provided",
+				clip_head_nodes.len()
+			));
+		}
+
+		let mut head_nodes: [i32; 1 + CLIP_HULL_COUNT] = [CONTENTS_EMPTY; 1 + CLIP_HULL_COUNT];
+		head_nodes[0] = self.node_child(model.head_node)? as i32;
+
+		for (index, child) in clip_head_nodes.iter().enumerate()
+		{
+			head_nodes[1 + index] = clip_child(*child)?;
+		}
+
+		let model_leaves: &[LeafArgs] =
+			&leaves[model.first_leaf as usize..(model.first_leaf + model.leaf_count) as usize];
+
+		return Ok(Model {
+			mins: model.mins.map(|value| value as f32),
+			maxs: model.maxs.map(|value| value as f32),
+			origin: model.origin.map(|value| value as f32),
+			head_nodes: head_nodes,
+			vis_leaves: model_leaves
+				.iter()
+				.filter(|leaf| leaf.contents != LeafContents::Solid)
+				.count() as i32,
+			first_face: model.first_face as i32,
+			face_count: model.face_count as i32,
+		});
+	}
+}
+
+fn clip_child(child: NodeChild) -> Result<i32, String>
+{
+	return match child
+	{
+		NodeChild::Node(index) => Ok(index as i32),
+		NodeChild::Contents(contents) => Ok(contents_code(contents)),
+		NodeChild::Leaf(_) => Err(String::from("A clipping node had a leaf as a child")),
+	};
+}
+
+fn mip_texture(texture: &TextureArgs) -> Result<MipTexture, String>
+{
+	let name: String = texture.name.to_string();
+
+	if name.len() >= MIPTEX_NAME_LENGTH
+	{
+		return Err(format!(
+			"Texture name {name} is longer than the {} characters allowed",
+			MIPTEX_NAME_LENGTH - 1
+		));
+	}
+
+	let pixels: &[u8] = texture.pixels.as_slice();
+
+	if pixels.is_empty()
+	{
+		return Ok(MipTexture {
+			name: name,
+			width: texture.width,
+			height: texture.height,
+			embedded: None,
+		});
+	}
+
+	let (width, height): (usize, usize) = (texture.width as usize, texture.height as usize);
+
+	if width % 16 != 0 || height % 16 != 0 || pixels.len() != width * height
+	{
+		return Err(format!(
+			"Texture {name} cannot be embedded, as it is {width}x{height} with {} pixels, and \
+			its size must be a multiple of 16",
+			pixels.len()
+		));
+	}
+
+	if texture.palette.len() != 256 * 3
+	{
+		return Err(format!(
+			"Texture {name} cannot be embedded, as its palette has {} bytes rather than 768",
+			texture.palette.len()
+		));
+	}
+
+	// Smaller mip levels take every other pixel of the level before them.
+	let levels: [Vec<u8>; MIP_LEVELS] = std::array::from_fn(|level| {
+		(0..height >> level)
+			.flat_map(|y| {
+				(0..width >> level).map(move |x| pixels[(y << level) * width + (x << level)])
+			})
+			.collect()
+	});
+
+	return Ok(MipTexture {
+		name: name,
+		width: texture.width,
+		height: texture.height,
+		embedded: Some(EmbeddedMips {
+			levels: levels,
+			palette: texture.palette.as_slice().to_vec(),
+		}),
+	});
+}
+
+fn plane_kind(normal: [f64; 3]) -> i32
+{
+	if let Some(axis) = normal.iter().position(|value| value.abs() == 1.0)
+	{
+		return axis as i32;
+	}
+
+	let major_axis: usize = (0..3)
+		.max_by(|a, b| normal[*a].abs().total_cmp(&normal[*b].abs()))
+		.unwrap_or_default();
+
+	return 3 + major_axis as i32;
+}
+
+fn contents_code(contents: LeafContents) -> i32
+{
+	return match contents
+	{
+		LeafContents::Empty => CONTENTS_EMPTY,
+		LeafContents::Solid => CONTENTS_SOLID,
+		LeafContents::Water => CONTENTS_WATER,
+		LeafContents::Slime => CONTENTS_SLIME,
+		LeafContents::Lava => CONTENTS_LAVA,
+		LeafContents::Sky => CONTENTS_SKY,
+	};
+}
+
+// Converts an index or count into the narrower type of the field that stores
+// it, failing if the file cannot hold it.
+fn narrow<T, U>(value: U, description: &str) -> Result<T, String>
+where
+	T: TryFrom<U>,
+	U: Copy + std::fmt::Display,
+{
+	return T::try_from(value).map_err(|_| {
+		format!("The {description} {value} is too large to be stored in a version 30 file")
+	});
+}
+
+fn clamp_to_i16(value: f64) -> i16
+{
+	return value.clamp(i16::MIN as f64, i16::MAX as f64) as i16;
+}
+
+#[cfg(test)]
+mod tests
+{
+	use super::*;
+	use bspextifc::bsp_writer_api::{NodeArgs, PlaneArgs};
+	use bspextifc::{SliceRef, StringRef};
+
+	fn face_args(points: &[[f64; 3]]) -> FaceArgs<'_>
+	{
+		return FaceArgs {
+			plane: 1,
+			points: SliceRef::from(points),
+			texture_info: 0,
+			styles: [0, 255, 255, 255],
+			lighting_offset: -1,
+			lightmap_mins: [0; 2],
+			lightmap_size: [0; 2],
+		};
+	}
+
+	fn leaf_args(contents: LeafContents, cluster: i32, faces: &[u32]) -> LeafArgs<'_>
+	{
+		return LeafArgs {
+			contents: contents,
+			cluster: cluster,
+			mins: [-0.5; 3],
+			maxs: [0.5; 3],
+			faces: SliceRef::from(faces),
+			brushes: SliceRef::from([].as_slice()),
+		};
+	}
+
+	#[test]
+	fn convert_world_to_lumps()
+	{
+		let planes: [PlaneArgs; 2] = [
+			PlaneArgs {
+				normal: [0.0, 0.0, 1.0],
+				distance: 0.0,
+			},
+			PlaneArgs {
+				normal: [0.0, 0.0, -1.0],
+				distance: 0.0,
+			},
+		];
+
+		// Two squares that share an edge along the Y axis.
+		let left: [[f64; 3]; 4] = [
+			[0.0, 0.0, 0.0],
+			[0.0, 1.0, 0.0],
+			[1.0, 1.0, 0.0],
+			[1.0, 0.0, 0.0],
+		];
+		let right: [[f64; 3]; 4] = [
+			[0.0, 0.0, 0.0],
+			[-1.0, 0.0, 0.0],
+			[-1.0, 1.0, 0.0],
+			[0.0, 1.0, 0.0],
+		];
+		let faces: [FaceArgs; 2] = [face_args(&left), face_args(&right)];
+
+		let leaf_faces: [u32; 2] = [0, 1];
+		let leaves: [LeafArgs; 2] = [
+			leaf_args(LeafContents::Empty, 0, &leaf_faces),
+			leaf_args(LeafContents::Solid, -1, &[]),
+		];
+
+		let nodes: [NodeArgs; 1] = [NodeArgs {
+			plane: 1,
+			children: [NodeChild::Leaf(0), NodeChild::Leaf(1)],
+			mins: [-1.5, -0.5, 0.0],
+			maxs: [1.5, 1.0, 0.0],
+			first_face: 0,
+			face_count: 2,
+		}];
+
+		let models: [ModelArgs; 1] = [ModelArgs {
+			mins: [-1.0, 0.0, 0.0],
+			maxs: [1.0, 1.0, 0.0],
+			origin: [0.0; 3],
+			head_node: NodeChild::Node(0),
+			clip_head_nodes: SliceRef::from([NodeChild::Contents(LeafContents::Solid)].as_slice()),
+			first_face: 0,
+			face_count: 2,
+			first_node: 0,
+			node_count: 1,
+			first_leaf: 0,
+			leaf_count: 2,
+			first_brush: 0,
+			brush_count: 0,
+		}];
+
+		let cluster_visibility: [u8; 1] = [1];
+		let args: WriteBspArgs = WriteBspArgs {
+			path: StringRef::from(""),
+			entities: StringRef::from("{\n}\n"),
+			planes: SliceRef::from(planes.as_slice()),
+			textures: SliceRef::from([].as_slice()),
+			texture_infos: SliceRef::from([].as_slice()),
+			faces: SliceRef::from(faces.as_slice()),
+			nodes: SliceRef::from(nodes.as_slice()),
+			leaves: SliceRef::from(leaves.as_slice()),
+			clip_nodes: SliceRef::from([].as_slice()),
+			brushes: SliceRef::from([].as_slice()),
+			models: SliceRef::from(models.as_slice()),
+			cluster_count: 1,
+			cluster_visibility: SliceRef::from(cluster_visibility.as_slice()),
+			lighting: SliceRef::from([].as_slice()),
+			luxel_size: 16.0,
+			limits: SliceRef::from([].as_slice()),
+		};
+
+		let bsp: Bsp30 = build_bsp30(&args).unwrap();
+		assert_eq!(bsp.entities, b"{\n}\n\0");
+		assert_eq!(bsp.planes.len(), 1);
+		assert_eq!(bsp.planes[0].kind, 2);
+
+		// The faces are on the back of the plane, and share one edge, which
+		// the second face uses in reverse.
+		assert_eq!((bsp.faces[0].plane, bsp.faces[0].side), (0, 1));
+		assert_eq!(bsp.vertices.len(), 6);
+		assert_eq!(bsp.edges.len(), 1 + 7);
+		assert_eq!(bsp.surf_edges[..4], [1, 2, 3, 4]);
+		assert_eq!(bsp.surf_edges[4..], [5, 6, 7, -1]);
+
+		// The solid leaf is merged into leaf 0, and the node's children are
+		// swapped to face along the first plane of the pair.
+		assert_eq!(bsp.leaves.len(), 2);
+		assert_eq!(bsp.leaves[1].contents, CONTENTS_EMPTY);
+		assert_eq!(bsp.leaves[1].mins, [-1; 3]);
+		assert_eq!(bsp.leaves[1].maxs, [1; 3]);
+		assert_eq!(bsp.mark_surfaces, vec![0, 1]);
+		assert_eq!(bsp.nodes[0].children, [-1, -2]);
+
+		assert_eq!(bsp.leaves[1].vis_offset, 0);
+		assert_eq!(bsp.visibility, vec![1]);
+
+		assert_eq!(
+			bsp.models[0].head_nodes,
+			[0, CONTENTS_SOLID, CONTENTS_EMPTY, CONTENTS_EMPTY]
+		);
+		assert_eq!(bsp.models[0].vis_leaves, 1);
+	}
+
+	#[test]
+	fn reject_indices_wider_than_fields()
+	{
+		assert_eq!(narrow::<u16, usize>(65535, "vertex index"), Ok(65535));
+		assert_eq!(
+			narrow::<i16, u32>(32768, "node index"),
+			Err(String::from(
+				"The node index 32768 is too large to be stored in a version 30 file"
+			))
+		);
+	}
+}
@@ -0,0 +1,341 @@
+use crate::model::bsp30::{
+	BSP30_VERSION, Bsp30, ClipNode, Face, Leaf, MIPTEX_NAME_LENGTH, MipTexture, Model, Node, Plane,
+	TexInfo,
+};
+
+/// Number of lumps in a version 30 file.
+pub const LUMP_COUNT: usize = 15;
+
+/// Size of the file header: the version, followed by the offset and length of
+/// each lump.
+pub const HEADER_SIZE: usize = 4 + LUMP_COUNT * 8;
+
+/// Serialises the file. Lumps are written in the order in which they are
+/// listed in the header, each beginning on a four byte boundary.
+pub fn encode_bsp30(bsp: &Bsp30) -> Vec<u8>
+{
+	// In the order of the header.
+	let lumps: [Vec<u8>; LUMP_COUNT] = [
+		bsp.entities.clone(),
+		encode_records(&bsp.planes, encode_plane),
+		encode_textures(&bsp.textures),
+		encode_records(&bsp.vertices, |vertex, out| put_f32s(out, vertex)),
+		bsp.visibility.clone(),
+		encode_records(&bsp.nodes, encode_node),
+		encode_records(&bsp.texinfos, encode_texinfo),
+		encode_records(&bsp.faces, encode_face),
+		bsp.lighting.clone(),
+		encode_records(&bsp.clip_nodes, encode_clip_node),
+		encode_records(&bsp.leaves, encode_leaf),
+		encode_records(&bsp.mark_surfaces, |index, out| put_u16(out, *index)),
+		encode_records(&bsp.edges, |edge, out| {
+			edge.iter().for_each(|vertex| put_u16(out, *vertex))
+		}),
+		encode_records(&bsp.surf_edges, |edge, out| put_i32(out, *edge)),
+		encode_records(&bsp.models, encode_model),
+	];
+
+	let mut header: Vec<u8> = Vec::with_capacity(HEADER_SIZE);
+	let mut body: Vec<u8> = Vec::new();
+	put_i32(&mut header, BSP30_VERSION);
+
+	for lump in &lumps
+	{
+		put_i32(&mut header, (HEADER_SIZE + body.len()) as i32);
+		put_i32(&mut header, lump.len() as i32);
+
+		body.extend_from_slice(lump);
+		body.resize(body.len().next_multiple_of(4), 0);
+	}
+
+	header.append(&mut body);
+	return header;
+}
+
+/// Compresses a row of visibility bits, by replacing each run of zero bytes
+/// with a zero followed by the length of the run.
+pub fn compress_vis_row(row: &[u8]) -> Vec<u8>
+{
+	let mut out: Vec<u8> = Vec::new();
+	let mut index: usize = 0;
+
+	while index < row.len()
+	{
+		out.push(row[index]);
+
+		if row[index] != 0
+		{
+			index += 1;
+			continue;
+		}
+
+		let run: usize = row[index..]
+			.iter()
+			.take(255)
+			.take_while(|byte| **byte == 0)
+			.count();
+
+		out.push(run as u8);
+		index += run;
+	}
+
+	return out;
+}
+
+fn encode_records<T>(records: &[T], encode: impl Fn(&T, &mut Vec<u8>)) -> Vec<u8>
+{
+	let mut out: Vec<u8> = Vec::new();
+	records.iter().for_each(|record| encode(record, &mut out));
+	return out;
+}
+
+fn encode_plane(plane: &Plane, out: &mut Vec<u8>)
+{
+	put_f32s(out, &plane.normal);
+	put_f32s(out, &[plane.distance]);
+	put_i32(out, plane.kind);
+}
+
+// The lump begins with the number of textures and the offset of each, and is
+// followed by the textures themselves. Offsets within each texture are
+// relative to its start, and are zero for textures that are not embedded.
+fn encode_textures(textures: &[MipTexture]) -> Vec<u8>
+{
+	let mut out: Vec<u8> = Vec::new();
+	put_i32(&mut out, textures.len() as i32);
+
+	let mut offset: usize = 4 + 4 * textures.len();
+
+	for texture in textures
+	{
+		put_i32(&mut out, offset as i32);
+		offset += texture.encoded_size();
+	}
+
+	for texture in textures
+	{
+		let mut name: [u8; MIPTEX_NAME_LENGTH] = [0; MIPTEX_NAME_LENGTH];
+		let length: usize = texture.name.len().min(MIPTEX_NAME_LENGTH - 1);
+		name[..length].copy_from_slice(&texture.name.as_bytes()[..length]);
+
+		out.extend_from_slice(&name);
+		put_u32(&mut out, texture.width);
+		put_u32(&mut out, texture.height);
+
+		let Some(embedded) = &texture.embedded
+		else
+		{
+			out.extend_from_slice(&[0; 16]);
+			continue;
+		};
+
+		let mut level_offset: usize = MipTexture::HEADER_SIZE;
+
+		for level in &embedded.levels
+		{
+			put_u32(&mut out, level_offset as u32);
+			level_offset += level.len();
+		}
+
+		embedded
+			.levels
+			.iter()
+			.for_each(|level| out.extend_from_slice(level));
+		put_u16(&mut out, (embedded.palette.len() / 3) as u16);
+		out.extend_from_slice(&embedded.palette);
+		out.extend_from_slice(&[0; 2]);
+	}
+
+	return out;
+}
+
+fn encode_node(node: &Node, out: &mut Vec<u8>)
+{
+	put_i32(out, node.plane);
+	put_i16s(out, &node.children);
+	put_i16s(out, &node.mins);
+	put_i16s(out, &node.maxs);
+	put_u16(out, node.first_face);
+	put_u16(out, node.face_count);
+}
+
+fn encode_texinfo(texinfo: &TexInfo, out: &mut Vec<u8>)
+{
+	texinfo.vecs.iter().for_each(|vec| put_f32s(out, vec));
+	put_i32(out, texinfo.texture);
+	put_i32(out, texinfo.flags);
+}
+
+fn encode_face(face: &Face, out: &mut Vec<u8>)
+{
+	put_u16(out, face.plane);
+	put_u16(out, face.side);
+	put_i32(out, face.first_edge);
+	put_u16(out, face.edge_count);
+	put_u16(out, face.texinfo);
+	out.extend_from_slice(&face.styles);
+	put_i32(out, face.lighting_offset);
+}
+
+fn encode_clip_node(node: &ClipNode, out: &mut Vec<u8>)
+{
+	put_i32(out, node.plane);
+	put_i16s(out, &node.children);
+}
+
+fn encode_leaf(leaf: &Leaf, out: &mut Vec<u8>)
+{
+	put_i32(out, leaf.contents);
+	put_i32(out, leaf.vis_offset);
+	put_i16s(out, &leaf.mins);
+	put_i16s(out, &leaf.maxs);
+	put_u16(out, leaf.first_mark_surface);
+	put_u16(out, leaf.mark_surface_count);
+	out.extend_from_slice(&leaf.ambient_levels);
+}
+
+fn encode_model(model: &Model, out: &mut Vec<u8>)
+{
+	put_f32s(out, &model.mins);
+	put_f32s(out, &model.maxs);
+	put_f32s(out, &model.origin);
+	model.head_nodes.iter().for_each(|node| put_i32(out, *node));
+	put_i32(out, model.vis_leaves);
+	put_i32(out, model.first_face);
+	put_i32(out, model.face_count);
+}
+
+fn put_i32(out: &mut Vec<u8>, value: i32)
+{
+	out.extend_from_slice(&value.to_le_bytes());
+}
+
+fn put_u32(out: &mut Vec<u8>, value: u32)
+{
+	out.extend_from_slice(&value.to_le_bytes());
+}
+
+fn put_u16(out: &mut Vec<u8>, value: u16)
+{
+	out.extend_from_slice(&value.to_le_bytes());
+}
+
+fn put_i16s(out: &mut Vec<u8>, values: &[i16])
+{
+	values
+		.iter()
+		.for_each(|value| out.extend_from_slice(&value.to_le_bytes()));
+}
+
+fn put_f32s(out: &mut Vec<u8>, values: &[f32])
+{
+	values
+		.iter()
+		.for_each(|value| out.extend_from_slice(&value.to_le_bytes()));
+}
+
+#[cfg(test)]
+mod tests
+{
+	use super::*;
+	use crate::model::bsp30::{CONTENTS_SOLID, EmbeddedMips};
+
+	fn read_i32(bytes: &[u8], offset: usize) -> i32
+	{
+		return i32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap());
+	}
+
+	// Returns the offset and length of a lump, as given in the header.
+	fn lump(bytes: &[u8], index: usize) -> (usize, usize)
+	{
+		return (
+			read_i32(bytes, 4 + index * 8) as usize,
+			read_i32(bytes, 8 + index * 8) as usize,
+		);
+	}
+
+	#[test]
+	fn compress_runs_of_zeroes()
+	{
+		assert_eq!(
+			compress_vis_row(&[1, 0, 0, 0, 2, 0]),
+			vec![1, 0, 3, 2, 0, 1]
+		);
+		assert_eq!(compress_vis_row(&[0; 300]), vec![0, 255, 0, 45]);
+	}
+
+	#[test]
+	fn write_header_and_records()
+	{
+		let bsp: Bsp30 = Bsp30 {
+			entities: b"{\n\"classname\" \"worldspawn\"\n}\n\0".to_vec(),
+			planes: vec![Plane {
+				normal: [0.0, 0.0, 1.0],
+				distance: 16.0,
+				kind: 2,
+			}],
+			textures: vec![
+				MipTexture {
+					name: String::from("external"),
+					width: 64,
+					height: 32,
+					embedded: None,
+				},
+				MipTexture {
+					name: String::from("embedded"),
+					width: 16,
+					height: 16,
+					embedded: Some(EmbeddedMips {
+						levels: [vec![1; 256], vec![2; 64], vec![3; 16], vec![4; 4]],
+						palette: vec![9; 768],
+					}),
+				},
+			],
+			leaves: vec![Leaf {
+				contents: CONTENTS_SOLID,
+				vis_offset: -1,
+				mins: [0; 3],
+				maxs: [0; 3],
+				first_mark_surface: 0,
+				mark_surface_count: 0,
+				ambient_levels: [0; 4],
+			}],
+			edges: vec![[0, 0], [0, 1]],
+			..Bsp30::default()
+		};
+
+		let bytes: Vec<u8> = encode_bsp30(&bsp);
+		assert_eq!(read_i32(&bytes, 0), 30);
+
+		// The entity text is 30 bytes, so the planes begin after two bytes of
+		// padding.
+		assert_eq!(lump(&bytes, 0), (HEADER_SIZE, 30));
+		assert_eq!(lump(&bytes, 1), (HEADER_SIZE + 32, 20));
+		assert_eq!(read_i32(&bytes, HEADER_SIZE + 32 + 16), 2);
+
+		// Textures are listed by offset, and the external texture has no mip
+		// offsets.
+		let (textures, textures_length): (usize, usize) = lump(&bytes, 2);
+		assert_eq!(
+			textures_length,
+			4 + 8 + bsp.textures[0].encoded_size() + 40 + 340 + 772
+		);
+		assert_eq!(read_i32(&bytes, textures), 2);
+		assert_eq!(read_i32(&bytes, textures + 4), 12);
+		assert_eq!(read_i32(&bytes, textures + 8), 12 + 40);
+		assert_eq!(&bytes[textures + 12..textures + 20], b"external");
+		assert_eq!(read_i32(&bytes, textures + 12 + 24), 0);
+
+		let embedded: usize = textures + 52;
+		assert_eq!(read_i32(&bytes, embedded + 24), 40);
+		assert_eq!(read_i32(&bytes, embedded + 36), 40 + 256 + 64 + 16);
+		assert_eq!(bytes[embedded + 40 + 256], 2);
+		assert_eq!(read_i32(&bytes, embedded + 40 + 340) & 0xffff, 256);
+
+		assert_eq!(lump(&bytes, 10).1, 28);
+		assert_eq!(read_i32(&bytes, lump(&bytes, 10).0), CONTENTS_SOLID);
+		assert_eq!(lump(&bytes, 12).1, 8);
+		assert_eq!(lump(&bytes, 14).1, 0);
+		assert_eq!(bytes.len(), lump(&bytes, 12).0 + 8);
+	}
+}
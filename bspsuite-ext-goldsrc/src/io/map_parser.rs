@@ -1,4 +1,12 @@
-use logos::Logos;
+use crate::model::map_blueprint::{
+	BlueprintBrush, BlueprintBrushFace, BlueprintEntity, MapBlueprint, TextureAlignment,
+	TextureAxis,
+};
+use glam::{DVec2, DVec3};
+use logos::{Lexer, Logos};
+use std::error::Error;
+use std::fmt;
+use std::ops::Range;
 
 // A helpful example of how to define tokens:
 // https://logos.maciej.codes/examples/json.html
@@ -9,7 +17,7 @@ use logos::Logos;
 #[logos(skip r"\s+")]
 enum BaseContext
 {
-	#[regex(r"//[^\n]*\n")]
+	#[regex(r"//[^\n]*")]
 	Comment,
 
 	// Begins EntityContext
@@ -23,7 +31,7 @@ enum BaseContext
 #[logos(skip r"\s+")]
 enum EntityContext
 {
-	#[regex(r"//[^\n]*\n")]
+	#[regex(r"//[^\n]*")]
 	Comment,
 
 	// Begins BrushContext
@@ -34,9 +42,14 @@ enum EntityContext
 	#[token("}")]
 	CloseBrace,
 
-	// Borrowed from the JSON example. We can tweak this if it turns out not to be quite right.
-	//          ( Non-term.     |  ( Escaped   | Unicode       ))
-	#[regex(r#""([^"\\\x00-\x1F]|\\(["\\bnfrt/]|u[a-fA-F0-9]{4}))*""#, |lex| lex.slice().to_owned())]
+	// GoldSrc doesn't support escape sequences in strings, and paths in
+	// keyvalues (eg. the "wad" key) frequently contain backslashes, so
+	// everything up to the next quote is taken verbatim. The quotes
+	// themselves are stripped.
+	#[regex(r#""[^"\r\n]*""#, |lex| {
+		let slice: &str = lex.slice();
+		slice[1..slice.len() - 1].to_owned()
+	})]
 	QuotedString(String),
 }
 
@@ -45,7 +58,7 @@ enum EntityContext
 #[logos(skip r"\s+")]
 enum BrushContext
 {
-	#[regex(r"//[^\n]*\n")]
+	#[regex(r"//[^\n]*")]
 	Comment,
 
 	// Falls back to EntityContext
@@ -60,9 +73,15 @@ enum BrushContext
 	#[token("[")]
 	OpenSquareBracket,
 
+	// Numbers follow the texture axes in a face definition.
+	// This takes priority over a string if the lengths match.
+	#[regex(r"-?(?:\d+(?:\.\d*)?|\.\d+)(?:[eE][+-]?\d+)?", |lex| lex.slice().parse::<f64>().unwrap(), priority = 10)]
+	Number(f64),
+
 	// String covers any other chain of characters that does not
-	// open a new context.
-	#[regex(r"[A-Za-z0-9_][^\s]*")]
+	// open a new context. Texture names may begin with symbols
+	// like '!', '+', '{' or '~', so we're lenient here.
+	#[regex(r"[^\s()\[\]}][^\s]*")]
 	String,
 }
 
@@ -71,8 +90,7 @@ enum BrushContext
 #[logos(skip r"\s+")]
 enum Point3DContext
 {
-	// Borrowed from the JSON example.
-	#[regex(r"-?(?:0|[1-9]\d*)(?:\.\d+)?(?:[eE][+-]?\d+)?", |lex| lex.slice().parse::<f64>().unwrap())]
+	#[regex(r"-?(?:\d+(?:\.\d*)?|\.\d+)(?:[eE][+-]?\d+)?", |lex| lex.slice().parse::<f64>().unwrap())]
 	Number(f64),
 
 	// Falls back to BrushContext.
@@ -85,8 +103,7 @@ enum Point3DContext
 #[logos(skip r"\s+")]
 enum VectorContext
 {
-	// Borrowed from the JSON example.
-	#[regex(r"-?(?:0|[1-9]\d*)(?:\.\d+)?(?:[eE][+-]?\d+)?", |lex| lex.slice().parse::<f64>().unwrap())]
+	#[regex(r"-?(?:\d+(?:\.\d*)?|\.\d+)(?:[eE][+-]?\d+)?", |lex| lex.slice().parse::<f64>().unwrap())]
 	Number(f64),
 
 	// String covers any other chain of characters that is not a number.
@@ -98,4 +115,606 @@ enum VectorContext
 	CloseSquareBracket,
 }
 
-// TODO: Can't run tests for this until we have a MapBlueprint class.
+/// Error produced when a map file could not be parsed. The span is the byte
+/// range within the source text where the problem was encountered. The line
+/// and column are 1-based, and the column is counted in characters.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MapParseError
+{
+	pub message: String,
+	pub span: Range<usize>,
+	pub line: u32,
+	pub column: u32,
+
+	/// The full text of the line on which the error occurred, without the
+	/// line terminator.
+	pub line_text: String,
+}
+
+impl MapParseError
+{
+	fn unexpected<'s, T>(lexer: &Lexer<'s, T>, expected: &str) -> Self
+	where
+		T: Logos<'s, Source = str>,
+	{
+		let source: &str = lexer.source();
+		let start: usize = lexer.span().start;
+
+		// If the lexer failed to match a token, the span only covers the
+		// first character it didn't recognise. It's more helpful to report
+		// the whole word that the character belongs to.
+		let word_length: usize = source[start..]
+			.find(char::is_whitespace)
+			.unwrap_or(source.len() - start);
+
+		let span: Range<usize> = start..lexer.span().end.max(start + word_length);
+
+		let found: String = if span.is_empty()
+		{
+			String::from("end of file")
+		}
+		else
+		{
+			format!("'{}'", &source[span.clone()])
+		};
+
+		let line_start: usize = source[..start].rfind('\n').map_or(0, |index| index + 1);
+		let line_end: usize = source[start..]
+			.find('\n')
+			.map_or(source.len(), |index| start + index);
+
+		return Self {
+			message: format!("expected {expected}, found {found}"),
+			span: span,
+			line: (source[..line_start].matches('\n').count() + 1) as u32,
+			column: (source[line_start..start].chars().count() + 1) as u32,
+			line_text: source[line_start..line_end]
+				.trim_end_matches('\r')
+				.to_owned(),
+		};
+	}
+}
+
+impl fmt::Display for MapParseError
+{
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result
+	{
+		write!(f, "{}", self.message)
+	}
+}
+
+impl Error for MapParseError
+{
+}
+
+type ParseResult<'s, T, L> = Result<(T, Lexer<'s, L>), MapParseError>;
+
+// Converts byte offsets within the source text into 1-based line numbers.
+struct LineIndex
+{
+	line_starts: Vec<usize>,
+}
+
+impl LineIndex
+{
+	fn new(source: &str) -> Self
+	{
+		let line_starts: Vec<usize> = std::iter::once(0)
+			.chain(source.match_indices('\n').map(|(index, _)| index + 1))
+			.collect();
+
+		return Self {
+			line_starts: line_starts,
+		};
+	}
+
+	fn line_of(&self, offset: usize) -> u32
+	{
+		return self.line_starts.partition_point(|start| *start <= offset) as u32;
+	}
+}
+
+/// Parses the contents of a .map file into a blueprint. Brush faces may use
+/// either the Valve 220 or the standard Quake texture alignment syntax.
+pub fn parse_map(source: &str) -> Result<MapBlueprint, MapParseError>
+{
+	let mut blueprint: MapBlueprint = MapBlueprint {
+		entities: Vec::new(),
+	};
+
+	let lines: LineIndex = LineIndex::new(source);
+	let mut lexer: Lexer<BaseContext> = BaseContext::lexer(source);
+
+	while let Some(token) = lexer.next()
+	{
+		match token
+		{
+			Ok(BaseContext::Comment) => continue,
+			Ok(BaseContext::OpenBrace) =>
+			{
+				let (entity, entity_lexer) = parse_entity(lexer.morph(), &lines)?;
+				blueprint.entities.push(entity);
+				lexer = entity_lexer.morph();
+			}
+			Err(_) => return Err(MapParseError::unexpected(&lexer, "'{' to begin an entity")),
+		}
+	}
+
+	return Ok(blueprint);
+}
+
+// Assumes the opening brace of the entity has already been consumed.
+fn parse_entity<'s>(
+	mut lexer: Lexer<'s, EntityContext>,
+	lines: &LineIndex,
+) -> ParseResult<'s, BlueprintEntity, EntityContext>
+{
+	let mut entity: BlueprintEntity = BlueprintEntity {
+		brushes: Vec::new(),
+		line: lines.line_of(lexer.span().start),
+		keyvalues: Vec::new(),
+	};
+
+	loop
+	{
+		match lexer.next()
+		{
+			Some(Ok(EntityContext::Comment)) => continue,
+			Some(Ok(EntityContext::CloseBrace)) => break,
+			Some(Ok(EntityContext::QuotedString(key))) => match lexer.next()
+			{
+				Some(Ok(EntityContext::QuotedString(value))) =>
+				{
+					entity.keyvalues.push((key, value));
+				}
+				_ =>
+				{
+					return Err(MapParseError::unexpected(
+						&lexer,
+						&format!("quoted value for key \"{key}\""),
+					));
+				}
+			},
+			Some(Ok(EntityContext::OpenBrace)) =>
+			{
+				let line: u32 = lines.line_of(lexer.span().start);
+				let (brush, brush_lexer) = parse_brush(lexer.morph(), line)?;
+				entity.brushes.push(brush);
+				lexer = brush_lexer.morph();
+			}
+			_ =>
+			{
+				return Err(MapParseError::unexpected(
+					&lexer,
+					"quoted key, '{' to begin a brush, or '}' to end the entity",
+				));
+			}
+		}
+	}
+
+	return Ok((entity, lexer));
+}
+
+// Assumes the opening brace of the brush has already been consumed.
+fn parse_brush(
+	mut lexer: Lexer<'_, BrushContext>,
+	line: u32,
+) -> ParseResult<'_, BlueprintBrush, BrushContext>
+{
+	let mut brush: BlueprintBrush = BlueprintBrush {
+		faces: Vec::new(),
+		line: line,
+	};
+
+	loop
+	{
+		match lexer.next()
+		{
+			Some(Ok(BrushContext::Comment)) => continue,
+			Some(Ok(BrushContext::CloseBrace)) => break,
+			Some(Ok(BrushContext::OpenRoundBracket)) =>
+			{
+				let (face, face_lexer) = parse_face(lexer)?;
+				brush.faces.push(face);
+				lexer = face_lexer;
+			}
+			_ =>
+			{
+				return Err(MapParseError::unexpected(
+					&lexer,
+					"'(' to begin a brush face, or '}' to end the brush",
+				));
+			}
+		}
+	}
+
+	return Ok((brush, lexer));
+}
+
+// Assumes the opening bracket of the first plane point has already been
+// consumed.
+fn parse_face(lexer: Lexer<'_, BrushContext>) -> ParseResult<'_, BlueprintBrushFace, BrushContext>
+{
+	let (point0, mut lexer) = parse_point(lexer.morph())?;
+	let mut plane_points: [DVec3; 3] = [point0, DVec3::ZERO, DVec3::ZERO];
+
+	for point in plane_points.iter_mut().skip(1)
+	{
+		match lexer.next()
+		{
+			Some(Ok(BrushContext::OpenRoundBracket)) =>
+			{
+				let (parsed_point, point_lexer) = parse_point(lexer.morph())?;
+				*point = parsed_point;
+				lexer = point_lexer;
+			}
+			_ =>
+			{
+				return Err(MapParseError::unexpected(
+					&lexer,
+					"'(' to begin a plane point",
+				));
+			}
+		}
+	}
+
+	let material: String = match lexer.next()
+	{
+		Some(Ok(BrushContext::String)) | Some(Ok(BrushContext::Number(_))) =>
+		{
+			lexer.slice().to_owned()
+		}
+		_ => return Err(MapParseError::unexpected(&lexer, "texture name")),
+	};
+
+	// A Valve 220 face specifies its texture axes explicitly in square
+	// brackets, whereas a standard Quake face goes straight on to the texture
+	// offsets. Both are allowed to appear in the same file.
+	let (face, lexer) = match lexer.next()
+	{
+		Some(Ok(BrushContext::OpenSquareBracket)) =>
+		{
+			parse_valve_220_face_alignment(plane_points, material, lexer)?
+		}
+		Some(Ok(BrushContext::Number(x_offset))) =>
+		{
+			parse_standard_face_alignment(plane_points, material, x_offset, lexer)?
+		}
+		_ =>
+		{
+			return Err(MapParseError::unexpected(
+				&lexer,
+				"'[' to begin a texture axis, or a number to begin a texture offset",
+			));
+		}
+	};
+
+	return Ok((face, lexer));
+}
+
+// Parses "[ ux uy uz uoffset ] [ vx vy vz voffset ] rotation xscale yscale".
+// Assumes the opening bracket of the first texture axis has already been
+// consumed.
+fn parse_valve_220_face_alignment(
+	plane_points: [DVec3; 3],
+	material: String,
+	lexer: Lexer<'_, BrushContext>,
+) -> ParseResult<'_, BlueprintBrushFace, BrushContext>
+{
+	let (u_axis, mut lexer) = parse_texture_axis(lexer)?;
+
+	match lexer.next()
+	{
+		Some(Ok(BrushContext::OpenSquareBracket)) => (),
+		_ =>
+		{
+			return Err(MapParseError::unexpected(
+				&lexer,
+				"'[' to begin a texture axis",
+			));
+		}
+	}
+
+	let (v_axis, lexer) = parse_texture_axis(lexer)?;
+	let ([rotation, x_scale, y_scale], lexer) =
+		parse_numbers(lexer, ["rotation", "texture X scale", "texture Y scale"])?;
+
+	let face: BlueprintBrushFace = BlueprintBrushFace {
+		plane_points: plane_points,
+		material: material,
+		alignment: TextureAlignment::Valve220 {
+			u_axis: u_axis,
+			v_axis: v_axis,
+		},
+		rotation: rotation,
+		scale: DVec2::new(x_scale, y_scale),
+	};
+
+	return Ok((face, lexer));
+}
+
+// Parses "xoffset yoffset rotation xscale yscale". Assumes the X offset has
+// already been consumed.
+fn parse_standard_face_alignment(
+	plane_points: [DVec3; 3],
+	material: String,
+	x_offset: f64,
+	lexer: Lexer<'_, BrushContext>,
+) -> ParseResult<'_, BlueprintBrushFace, BrushContext>
+{
+	let ([y_offset, rotation, x_scale, y_scale], lexer) = parse_numbers(
+		lexer,
+		[
+			"texture Y offset",
+			"rotation",
+			"texture X scale",
+			"texture Y scale",
+		],
+	)?;
+
+	let face: BlueprintBrushFace = BlueprintBrushFace {
+		plane_points: plane_points,
+		material: material,
+		alignment: TextureAlignment::Standard {
+			offset: DVec2::new(x_offset, y_offset),
+		},
+		rotation: rotation,
+		scale: DVec2::new(x_scale, y_scale),
+	};
+
+	return Ok((face, lexer));
+}
+
+// Parses a fixed number of consecutive numbers. The descriptions are used when
+// reporting which number was missing.
+fn parse_numbers<'s, const N: usize>(
+	mut lexer: Lexer<'s, BrushContext>,
+	descriptions: [&str; N],
+) -> ParseResult<'s, [f64; N], BrushContext>
+{
+	let mut values: [f64; N] = [0.0; N];
+
+	for (value, description) in values.iter_mut().zip(descriptions)
+	{
+		*value = match lexer.next()
+		{
+			Some(Ok(BrushContext::Number(number))) => number,
+			_ => return Err(MapParseError::unexpected(&lexer, description)),
+		};
+	}
+
+	return Ok((values, lexer));
+}
+
+// Assumes the opening bracket has already been consumed.
+fn parse_point(mut lexer: Lexer<'_, Point3DContext>) -> ParseResult<'_, DVec3, BrushContext>
+{
+	let mut components: [f64; 3] = [0.0; 3];
+
+	for component in components.iter_mut()
+	{
+		match lexer.next()
+		{
+			Some(Ok(Point3DContext::Number(number))) => *component = number,
+			_ => return Err(MapParseError::unexpected(&lexer, "number in plane point")),
+		}
+	}
+
+	match lexer.next()
+	{
+		Some(Ok(Point3DContext::CloseRoundBracket)) => (),
+		_ => return Err(MapParseError::unexpected(&lexer, "')' after plane point")),
+	}
+
+	return Ok((DVec3::from_array(components), lexer.morph()));
+}
+
+// Parses a Valve 220 texture axis, of the form "[ x y z offset ]". Assumes the
+// opening bracket has already been consumed.
+fn parse_texture_axis(lexer: Lexer<'_, BrushContext>)
+-> ParseResult<'_, TextureAxis, BrushContext>
+{
+	let mut lexer: Lexer<VectorContext> = lexer.morph();
+	let mut components: [f64; 4] = [0.0; 4];
+
+	for component in components.iter_mut()
+	{
+		match lexer.next()
+		{
+			Some(Ok(VectorContext::Number(number))) => *component = number,
+			_ => return Err(MapParseError::unexpected(&lexer, "number in texture axis")),
+		}
+	}
+
+	match lexer.next()
+	{
+		Some(Ok(VectorContext::CloseSquareBracket)) => (),
+		_ => return Err(MapParseError::unexpected(&lexer, "']' after texture axis")),
+	}
+
+	let axis: TextureAxis = TextureAxis {
+		axis: DVec3::new(components[0], components[1], components[2]),
+		offset: components[3],
+	};
+
+	return Ok((axis, lexer.morph()));
+}
+
+#[cfg(test)]
+mod tests
+{
+	use super::*;
+
+	const VALVE_220_MAP: &str = r#"
+// Game: Half-Life
+// Format: Valve
+// entity 0
+{
+"classname" "worldspawn"
+"mapversion" "220"
+"wad" "\half-life\valve\halflife.wad"
+// brush 0
+{
+( -64 -64 -16 ) ( -64 -63 -16 ) ( -64 -64 -15 ) +0~LIGHT [ 0 1 0 16 ] [ 0 0 -1 -8.5 ] 0 1 1
+( 64 64 16 ) ( 64 64 17 ) ( 64 65 16 ) {GRATE [ 0 -1 0 0 ] [ 0 0 -1 0 ] 90 0.5 -0.25
+( -64 -64 -16 ) ( -64 -64 -15 ) ( -63 -64 -16 ) AAATRIGGER [ 1 0 0 0 ] [ 0 0 -1 0 ] 0 1 1
+( 64 64 16 ) ( 65 64 16 ) ( 64 64 17 ) AAATRIGGER [ -1 0 0 0 ] [ 0 0 -1 0 ] 0 1 1
+( -64 -64 -16 ) ( -63 -64 -16 ) ( -64 -63 -16 ) !WATER [ -1 0 0 0 ] [ 0 -1 0 0 ] 0 1 1
+( 64 64 16 ) ( 64 65 16 ) ( 65 64 16 ) !WATER [ 1 0 0 0 ] [ 0 -1 0 0 ] 0 1 1
+}
+}
+// entity 1
+{
+"classname" "multi_manager"
+"targetname" "mm"
+"door" "0"
+"door" "3"
+}
+"#;
+
+	#[test]
+	fn parse_valve_220_map()
+	{
+		let blueprint: MapBlueprint = parse_map(VALVE_220_MAP).unwrap();
+		assert_eq!(blueprint.entities.len(), 2);
+
+		let worldspawn: &BlueprintEntity = &blueprint.entities[0];
+		assert_eq!(
+			worldspawn.keyvalues[0],
+			(String::from("classname"), String::from("worldspawn"))
+		);
+		assert_eq!(
+			worldspawn.keyvalues[2],
+			(
+				String::from("wad"),
+				String::from(r"\half-life\valve\halflife.wad")
+			)
+		);
+		assert_eq!(worldspawn.brushes.len(), 1);
+		assert_eq!(worldspawn.line, 5);
+		assert_eq!(worldspawn.brushes[0].line, 10);
+
+		let faces: &Vec<BlueprintBrushFace> = &worldspawn.brushes[0].faces;
+		assert_eq!(faces.len(), 6);
+
+		assert_eq!(faces[0].material, "+0~LIGHT");
+		assert_eq!(
+			faces[0].plane_points,
+			[
+				DVec3::new(-64.0, -64.0, -16.0),
+				DVec3::new(-64.0, -63.0, -16.0),
+				DVec3::new(-64.0, -64.0, -15.0)
+			]
+		);
+		assert_eq!(
+			faces[0].alignment,
+			TextureAlignment::Valve220 {
+				u_axis: TextureAxis {
+					axis: DVec3::new(0.0, 1.0, 0.0),
+					offset: 16.0,
+				},
+				v_axis: TextureAxis {
+					axis: DVec3::new(0.0, 0.0, -1.0),
+					offset: -8.5,
+				},
+			}
+		);
+
+		assert_eq!(faces[1].material, "{GRATE");
+		assert_eq!(faces[1].rotation, 90.0);
+		assert_eq!(faces[1].scale, DVec2::new(0.5, -0.25));
+
+		let manager: &BlueprintEntity = &blueprint.entities[1];
+		let keys: Vec<&str> = manager
+			.keyvalues
+			.iter()
+			.map(|(key, _)| key.as_str())
+			.collect();
+		assert_eq!(keys, vec!["classname", "targetname", "door", "door"]);
+		assert_eq!(manager.keyvalues[3].1, "3");
+		assert!(manager.brushes.is_empty());
+		assert_eq!(manager.line, 20);
+	}
+
+	#[test]
+	fn parse_mixed_face_syntax()
+	{
+		let source: &str = r#"
+{
+"classname" "worldspawn"
+{
+( 0 0 16 ) ( 0 1 16 ) ( 1 0 16 ) -0FLOOR 8 -4 90 0.5 2
+( 0 0 0 ) ( 1 0 0 ) ( 0 1 0 ) AAATRIGGER [ 1 0 0 0 ] [ 0 -1 0 0 ] 0 1 1
+}
+}
+"#;
+
+		let blueprint: MapBlueprint = parse_map(source).unwrap();
+		let faces: &Vec<BlueprintBrushFace> = &blueprint.entities[0].brushes[0].faces;
+
+		assert_eq!(faces[0].material, "-0FLOOR");
+		assert_eq!(
+			faces[0].alignment,
+			TextureAlignment::Standard {
+				offset: DVec2::new(8.0, -4.0),
+			}
+		);
+		assert_eq!(faces[0].rotation, 90.0);
+		assert_eq!(faces[0].scale, DVec2::new(0.5, 2.0));
+
+		assert_eq!(faces[1].material, "AAATRIGGER");
+		assert!(matches!(
+			faces[1].alignment,
+			TextureAlignment::Valve220 { u_axis, .. } if u_axis.axis == DVec3::X
+		));
+	}
+
+	#[test]
+	fn parse_empty_map()
+	{
+		let blueprint: MapBlueprint = parse_map("// Nothing but a comment").unwrap();
+		assert!(blueprint.entities.is_empty());
+	}
+
+	#[test]
+	fn report_malformed_plane_point()
+	{
+		let source: &str =
+			"{\n{\n( 0 0 0 ( 0 1 0 ) ( 1 0 0 ) AAATRIGGER [ 1 0 0 0 ] [ 0 -1 0 0 ] 0 1 1\n}\n}";
+		let err: MapParseError = parse_map(source).unwrap_err();
+
+		assert_eq!(err.message, "expected ')' after plane point, found '('");
+		assert_eq!(&source[err.span], "(");
+		assert_eq!((err.line, err.column), (3, 9));
+		assert!(err.line_text.starts_with("( 0 0 0 ( 0 1 0 )"));
+	}
+
+	#[test]
+	fn report_whole_unexpected_word()
+	{
+		let source: &str =
+			"{\r\n{\r\n( 0 0 0 ) ( 0 1 0 AAATRIGGER [ 1 0 0 0 ] [ 0 -1 0 0 ] 0 1 1\r\n}\r\n}";
+		let err: MapParseError = parse_map(source).unwrap_err();
+
+		assert_eq!(
+			err.message,
+			"expected ')' after plane point, found 'AAATRIGGER'"
+		);
+		assert_eq!((err.line, err.column), (3, 19));
+		assert_eq!(
+			err.line_text,
+			"( 0 0 0 ) ( 0 1 0 AAATRIGGER [ 1 0 0 0 ] [ 0 -1 0 0 ] 0 1 1"
+		);
+	}
+
+	#[test]
+	fn report_unterminated_entity()
+	{
+		let err: MapParseError = parse_map("{\n\"classname\" \"worldspawn\"\n").unwrap_err();
+
+		assert_eq!(
+			err.message,
+			"expected quoted key, '{' to begin a brush, or '}' to end the entity, found end of file"
+		);
+	}
+}
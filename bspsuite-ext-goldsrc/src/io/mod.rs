@@ -1 +1,4 @@
+pub mod bsp30_builder;
+pub mod bsp30_writer;
 pub mod map_parser;
+pub mod wad_reader;
@@ -0,0 +1,347 @@
+use crate::model::bsp30::MIP_LEVELS;
+use crate::model::wad::{
+	LUMP_TYPE_MIPTEX_WAD2, LUMP_TYPE_MIPTEX_WAD3, LUMP_TYPE_PALETTE, WAD_DIRECTORY_ENTRY_SIZE,
+	WAD_HEADER_SIZE, WAD_NAME_LENGTH, WAD2_MAGIC, WAD3_MAGIC, WadTexture,
+};
+use std::path::{Path, PathBuf};
+
+// Size of the header at the start of each texture lump: the name, the width
+// and height, and the offset of each mip level.
+const MIPTEX_HEADER_SIZE: usize = WAD_NAME_LENGTH + 4 * 2 + 4 * MIP_LEVELS;
+
+// Size of a palette of 256 RGB colours.
+const PALETTE_SIZE: usize = 256 * 3;
+
+/// Parses the textures held in a WAD2 or WAD3 archive, in the order in which
+/// they are listed in its directory. Lumps that are not textures are skipped.
+pub fn read_wad(bytes: &[u8]) -> Result<Vec<WadTexture>, String>
+{
+	if bytes.len() < WAD_HEADER_SIZE
+	{
+		return Err(format!(
+			"File is {} bytes, which is too short for the header",
+			bytes.len()
+		));
+	}
+
+	let magic: &[u8] = &bytes[0..4];
+	let is_wad3: bool = magic == WAD3_MAGIC;
+
+	if !is_wad3 && magic != WAD2_MAGIC
+	{
+		return Err(String::from("File is not a WAD2 or WAD3 archive"));
+	}
+
+	let lump_count: usize = read_u32(bytes, 4)? as usize;
+	let directory_offset: usize = read_u32(bytes, 8)? as usize;
+
+	let directory: &[u8] = lump_count
+		.checked_mul(WAD_DIRECTORY_ENTRY_SIZE)
+		.and_then(|size| bytes.get(directory_offset..directory_offset.checked_add(size)?))
+		.ok_or_else(|| format!("Directory of {lump_count} lumps lies outside of the file"))?;
+
+	let mut lumps: Vec<(u8, &[u8])> = Vec::with_capacity(lump_count);
+
+	for entry in directory.chunks_exact(WAD_DIRECTORY_ENTRY_SIZE)
+	{
+		let offset: usize = read_u32(entry, 0)? as usize;
+		let size: usize = read_u32(entry, 4)? as usize;
+		let kind: u8 = entry[12];
+		let compression: u8 = entry[13];
+		let name: String = read_name(&entry[16..]);
+
+		if compression != 0
+		{
+			return Err(format!("Lump {name} is compressed, which is not supported"));
+		}
+
+		let lump: &[u8] = offset
+			.checked_add(size)
+			.and_then(|end| bytes.get(offset..end))
+			.ok_or_else(|| format!("Lump {name} lies outside of the file"))?;
+
+		lumps.push((kind, lump));
+	}
+
+	// WAD2 textures share the archive's palette, if it has one.
+	let shared_palette: Vec<u8> = lumps
+		.iter()
+		.find(|(kind, lump)| *kind == LUMP_TYPE_PALETTE && lump.len() == PALETTE_SIZE)
+		.map(|(_, lump)| lump.to_vec())
+		.unwrap_or_default();
+
+	let texture_kind: u8 = if is_wad3
+	{
+		LUMP_TYPE_MIPTEX_WAD3
+	}
+	else
+	{
+		LUMP_TYPE_MIPTEX_WAD2
+	};
+
+	return lumps
+		.iter()
+		.filter(|(kind, _)| *kind == texture_kind)
+		.map(|(_, lump)| match is_wad3
+		{
+			true => read_miptex(lump, None),
+			false => read_miptex(lump, Some(&shared_palette)),
+		})
+		.collect();
+}
+
+/// Lists the places in which to look for a WAD referred to by a map, in order
+/// of preference. Maps usually give the path that the archive had on the
+/// mapper's machine, with backslashes, so the path is first tried as it is,
+/// relative to the map's directory. It is then tried within each of the search
+/// paths, keeping the archive's parent directory (usually the mod directory),
+/// and finally with just the file name.
+pub fn wad_candidates(wad_path: &str, map_dir: &Path, search_paths: &[String]) -> Vec<PathBuf>
+{
+	let wad_path: String = wad_path.trim().replace('\\', "/");
+	let components: Vec<&str> = wad_path
+		.split('/')
+		.filter(|component| !component.is_empty())
+		.collect();
+
+	let mut candidates: Vec<PathBuf> = vec![map_dir.join(&wad_path)];
+
+	for search_path in search_paths
+	{
+		let root: PathBuf = map_dir.join(search_path);
+
+		for suffix in [
+			components.len().saturating_sub(2),
+			components.len().saturating_sub(1),
+		]
+		{
+			let candidate: PathBuf = components[suffix..]
+				.iter()
+				.fold(root.clone(), |path, component| path.join(component));
+
+			if !candidates.contains(&candidate)
+			{
+				candidates.push(candidate);
+			}
+		}
+	}
+
+	return candidates;
+}
+
+// Reads a texture lump. WAD3 textures are followed by their own palette, and
+// WAD2 textures use the one given.
+fn read_miptex(lump: &[u8], shared_palette: Option<&[u8]>) -> Result<WadTexture, String>
+{
+	if lump.len() < MIPTEX_HEADER_SIZE
+	{
+		return Err(format!(
+			"Texture lump of {} bytes is too short for its header",
+			lump.len()
+		));
+	}
+
+	let name: String = read_name(lump);
+	let width: u32 = read_u32(lump, WAD_NAME_LENGTH)?;
+	let height: u32 = read_u32(lump, WAD_NAME_LENGTH + 4)?;
+	let offsets: Vec<usize> = (0..MIP_LEVELS)
+		.map(|level| read_u32(lump, WAD_NAME_LENGTH + 8 + level * 4).map(|offset| offset as usize))
+		.collect::<Result<Vec<usize>, String>>()?;
+
+	let pixel_count: usize = width as usize * height as usize;
+	let pixels: &[u8] = lump
+		.get(offsets[0]..offsets[0].saturating_add(pixel_count))
+		.ok_or_else(|| format!("Pixels of texture {name} lie outside of its lump"))?;
+
+	let palette: Vec<u8> = match shared_palette
+	{
+		Some(palette) => palette.to_vec(),
+		None =>
+		{
+			// The smallest mip level is followed by the number of colours in
+			// the palette, then the colours themselves.
+			let palette_offset: usize = offsets[MIP_LEVELS - 1].saturating_add(pixel_count / 64);
+			let color_count: usize = read_u16(lump, palette_offset)? as usize;
+
+			lump.get(palette_offset + 2..palette_offset.saturating_add(2 + color_count * 3))
+				.ok_or_else(|| format!("Palette of texture {name} lies outside of its lump"))?
+				.to_vec()
+		}
+	};
+
+	return Ok(WadTexture {
+		name: name,
+		width: width,
+		height: height,
+		pixels: pixels.to_vec(),
+		palette: palette,
+	});
+}
+
+fn read_name(bytes: &[u8]) -> String
+{
+	let name: &[u8] = &bytes[..WAD_NAME_LENGTH.min(bytes.len())];
+	let length: usize = name
+		.iter()
+		.position(|byte| *byte == 0)
+		.unwrap_or(name.len());
+	return String::from_utf8_lossy(&name[..length]).to_string();
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> Result<u32, String>
+{
+	return bytes
+		.get(offset..offset + 4)
+		.map(|value| u32::from_le_bytes(value.try_into().unwrap()))
+		.ok_or_else(|| format!("Expected four bytes at offset {offset}"));
+}
+
+fn read_u16(bytes: &[u8], offset: usize) -> Result<u16, String>
+{
+	return bytes
+		.get(offset..offset + 2)
+		.map(|value| u16::from_le_bytes(value.try_into().unwrap()))
+		.ok_or_else(|| format!("Expected two bytes at offset {offset}"));
+}
+
+#[cfg(test)]
+mod tests
+{
+	use super::*;
+
+	// Builds a texture lump holding a 16x16 texture filled with the given
+	// palette index.
+	fn miptex_lump(name: &str, index: u8, palette: Option<&[u8]>) -> Vec<u8>
+	{
+		let mut lump: Vec<u8> = vec![0; MIPTEX_HEADER_SIZE];
+		lump[..name.len()].copy_from_slice(name.as_bytes());
+		lump[16..20].copy_from_slice(&16u32.to_le_bytes());
+		lump[20..24].copy_from_slice(&16u32.to_le_bytes());
+
+		for level in 0..MIP_LEVELS
+		{
+			let offset: u32 = lump.len() as u32;
+			lump[24 + level * 4..28 + level * 4].copy_from_slice(&offset.to_le_bytes());
+			lump.resize(lump.len() + (256 >> (level * 2)), index);
+		}
+
+		if let Some(palette) = palette
+		{
+			lump.extend_from_slice(&((palette.len() / 3) as u16).to_le_bytes());
+			lump.extend_from_slice(palette);
+			lump.extend_from_slice(&[0, 0]);
+		}
+
+		return lump;
+	}
+
+	fn wad(magic: [u8; 4], lumps: &[(u8, &str, Vec<u8>)]) -> Vec<u8>
+	{
+		let mut bytes: Vec<u8> = vec![0; WAD_HEADER_SIZE];
+		bytes[..4].copy_from_slice(&magic);
+		bytes[4..8].copy_from_slice(&(lumps.len() as u32).to_le_bytes());
+
+		let mut directory: Vec<u8> = Vec::new();
+
+		for (kind, name, lump) in lumps
+		{
+			let mut entry: Vec<u8> = vec![0; WAD_DIRECTORY_ENTRY_SIZE];
+			entry[0..4].copy_from_slice(&(bytes.len() as u32).to_le_bytes());
+			entry[4..8].copy_from_slice(&(lump.len() as u32).to_le_bytes());
+			entry[8..12].copy_from_slice(&(lump.len() as u32).to_le_bytes());
+			entry[12] = *kind;
+			entry[16..16 + name.len()].copy_from_slice(name.as_bytes());
+
+			bytes.extend_from_slice(lump);
+			directory.append(&mut entry);
+		}
+
+		let directory_offset: u32 = bytes.len() as u32;
+		bytes[8..12].copy_from_slice(&directory_offset.to_le_bytes());
+		bytes.append(&mut directory);
+		return bytes;
+	}
+
+	#[test]
+	fn read_wad3_textures()
+	{
+		let palette: Vec<u8> = (0..PALETTE_SIZE).map(|index| index as u8).collect();
+		let bytes: Vec<u8> = wad(
+			WAD3_MAGIC,
+			&[
+				(
+					LUMP_TYPE_MIPTEX_WAD3,
+					"CRATE1",
+					miptex_lump("CRATE1", 3, Some(&palette)),
+				),
+				(0x42, "CONCHARS", vec![0; 8]),
+				(
+					LUMP_TYPE_MIPTEX_WAD3,
+					"{FENCE",
+					miptex_lump("{FENCE", 255, Some(&palette)),
+				),
+			],
+		);
+
+		let textures: Vec<WadTexture> = read_wad(&bytes).unwrap();
+
+		assert_eq!(textures.len(), 2);
+		assert_eq!(textures[0].name, "CRATE1");
+		assert_eq!([textures[0].width, textures[0].height], [16, 16]);
+		assert_eq!(textures[0].pixels, vec![3; 256]);
+		assert_eq!(textures[0].palette, palette);
+		assert_eq!(textures[1].name, "{FENCE");
+		assert_eq!(textures[1].average_color(), None);
+
+		assert!(read_wad(&bytes[..bytes.len() - 1]).is_err());
+		assert!(read_wad(b"PACK").is_err());
+	}
+
+	#[test]
+	fn read_wad2_textures_with_shared_palette()
+	{
+		let palette: Vec<u8> = vec![7; PALETTE_SIZE];
+		let bytes: Vec<u8> = wad(
+			WAD2_MAGIC,
+			&[
+				(LUMP_TYPE_MIPTEX_WAD2, "SKY1", miptex_lump("SKY1", 1, None)),
+				(LUMP_TYPE_PALETTE, "PALETTE", palette.clone()),
+			],
+		);
+
+		let textures: Vec<WadTexture> = read_wad(&bytes).unwrap();
+
+		assert_eq!(textures.len(), 1);
+		assert_eq!(textures[0].name, "SKY1");
+		assert_eq!(textures[0].palette, palette);
+	}
+
+	#[test]
+	fn list_wad_candidates()
+	{
+		let candidates: Vec<PathBuf> = wad_candidates(
+			r"\sierra\half-life\valve\halflife.wad",
+			Path::new("/maps"),
+			&[String::from("../wads"), String::from("/opt/hl")],
+		);
+
+		assert_eq!(
+			candidates,
+			vec![
+				PathBuf::from("/sierra/half-life/valve/halflife.wad"),
+				PathBuf::from("/maps/../wads/valve/halflife.wad"),
+				PathBuf::from("/maps/../wads/halflife.wad"),
+				PathBuf::from("/opt/hl/valve/halflife.wad"),
+				PathBuf::from("/opt/hl/halflife.wad"),
+			]
+		);
+
+		assert_eq!(
+			wad_candidates("decals.wad", Path::new("maps"), &[String::from("wads")]),
+			vec![
+				PathBuf::from("maps/decals.wad"),
+				PathBuf::from("maps/wads/decals.wad"),
+			]
+		);
+	}
+}
@@ -1,11 +1,29 @@
 use bspextifc::log_api::{self, ExtensionLogger};
-use bspextifc::{dummy_api, implement_extension_info, probe_api};
-use log::{error, info};
+use bspextifc::{
+	SliceRef, StringRef, bsp_writer_api, implement_extension_info, map_reader_api, probe_api,
+	stage_hook_api, texture_api,
+};
+use log::error;
 
+mod cinterface;
 mod io;
+mod model;
 
 implement_extension_info!(probe);
 
+// Data that registered callbacks refer to must live as long as the library.
+static MAP_FILE_EXTENSIONS: [StringRef; 1] = [StringRef::new("map")];
+static TEXTURE_PROVIDERS: [texture_api::TextureProvider; 1] = [texture_api::TextureProvider {
+	name: StringRef::new("wad"),
+	priority: 0,
+	find_textures_fn: cinterface::texture_provider::find_textures,
+}];
+static STAGE_HOOKS: [stage_hook_api::StageHook; 1] = [stage_hook_api::StageHook {
+	name: StringRef::new("check_surface_extents"),
+	point: stage_hook_api::HookPoint::PreWrite,
+	hook_fn: cinterface::stage_hooks::check_surface_extents,
+}];
+
 extern "C" fn probe(api: &mut probe_api::ProbeApi) -> probe_api::ProbeResult
 {
 	if !set_up_logger(api)
@@ -13,26 +31,58 @@ extern "C" fn probe(api: &mut probe_api::ProbeApi) -> probe_api::ProbeResult
 		return probe_api::ProbeResult::Failure;
 	}
 
-	let dummy_callbacks: dummy_api::DummyCallbacks = dummy_api::DummyCallbacks {
-		entry_point: dummyapi_entry_point,
-	};
+	let map_reader_callbacks: map_reader_api::MapReaderCallbacks =
+		map_reader_api::MapReaderCallbacks {
+			file_extensions: SliceRef::new(&MAP_FILE_EXTENSIONS),
+			read_map_fn: cinterface::map_reader::read_map,
+		};
 
-	if let Err(_) = api.register_dummy_api_callbacks(dummy_api::API_INFO.version, dummy_callbacks)
+	if let Err(_) = api
+		.register_map_reader_api_callbacks(map_reader_api::API_INFO.version, map_reader_callbacks)
 	{
-		error!("Failed to register for dummy API");
+		error!("Failed to register for map reader API");
 		return probe_api::ProbeResult::Failure;
 	}
 
-	return probe_api::ProbeResult::Success;
-}
+	let bsp_writer_callbacks: bsp_writer_api::BspWriterCallbacks =
+		bsp_writer_api::BspWriterCallbacks {
+			format: StringRef::new("bsp30"),
+			write_bsp_fn: cinterface::bsp_writer::write_bsp,
+			limits: SliceRef::new(&cinterface::bsp_writer::LIMITS),
+		};
 
-extern "C" fn dummyapi_entry_point(api: &mut dummy_api::DummyApi)
-{
-	let magic_number: i32 = api.get_magic_number();
-	info!("Magic number from dummy API: {magic_number}");
+	if let Err(_) = api
+		.register_bsp_writer_api_callbacks(bsp_writer_api::API_INFO.version, bsp_writer_callbacks)
+	{
+		error!("Failed to register for BSP writer API");
+		return probe_api::ProbeResult::Failure;
+	}
+
+	let texture_callbacks: texture_api::TextureProviderCallbacks =
+		texture_api::TextureProviderCallbacks {
+			providers: SliceRef::new(&TEXTURE_PROVIDERS),
+		};
+
+	if let Err(_) =
+		api.register_texture_api_callbacks(texture_api::API_INFO.version, texture_callbacks)
+	{
+		error!("Failed to register for texture API");
+		return probe_api::ProbeResult::Failure;
+	}
 
-	info!("Storing number 99 in dummy API");
-	api.store_number(99);
+	let stage_hook_callbacks: stage_hook_api::StageHookCallbacks =
+		stage_hook_api::StageHookCallbacks {
+			hooks: SliceRef::new(&STAGE_HOOKS),
+		};
+
+	if let Err(_) = api
+		.register_stage_hook_api_callbacks(stage_hook_api::API_INFO.version, stage_hook_callbacks)
+	{
+		error!("Failed to register for stage hook API");
+		return probe_api::ProbeResult::Failure;
+	}
+
+	return probe_api::ProbeResult::Success;
 }
 
 fn set_up_logger(api: &mut probe_api::ProbeApi) -> bool
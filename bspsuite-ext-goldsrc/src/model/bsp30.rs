@@ -0,0 +1,408 @@
+// Structures of a GoldSrc BSP file, version 30. These hold values exactly as
+// they are written to the file, so that the writer only has to serialise
+// them.
+
+use std::collections::HashMap;
+
+pub const BSP30_VERSION: i32 = 30;
+
+pub const CONTENTS_EMPTY: i32 = -1;
+pub const CONTENTS_SOLID: i32 = -2;
+pub const CONTENTS_WATER: i32 = -3;
+pub const CONTENTS_SLIME: i32 = -4;
+pub const CONTENTS_LAVA: i32 = -5;
+pub const CONTENTS_SKY: i32 = -6;
+
+/// Texture info flag for faces that have no lightmap.
+pub const TEX_SPECIAL: i32 = 1;
+
+/// Number of bytes in a texture name, including the terminating NUL.
+pub const MIPTEX_NAME_LENGTH: usize = 16;
+
+/// Number of mip levels stored for each embedded texture.
+pub const MIP_LEVELS: usize = 4;
+
+/// Largest number of lightmap samples that the engine accepts along either
+/// texture axis of a face. Larger faces fail to load with "Bad surface
+/// extents".
+pub const MAX_LIGHTMAP_SIZE: usize = 17;
+
+// Limits imposed by the GoldSrc engine. Byte limits apply to the size of the
+// lump in the file.
+pub const MAX_MAP_MODELS: usize = 400;
+pub const MAX_MAP_ENTSTRING: usize = 128 * 1024;
+pub const MAX_MAP_PLANES: usize = 32767;
+pub const MAX_MAP_NODES: usize = 32767;
+pub const MAX_MAP_CLIPNODES: usize = 32767;
+pub const MAX_MAP_LEAFS: usize = 8192;
+pub const MAX_MAP_VERTS: usize = 65535;
+pub const MAX_MAP_FACES: usize = 65535;
+pub const MAX_MAP_MARKSURFACES: usize = 65535;
+pub const MAX_MAP_TEXINFO: usize = 8192;
+pub const MAX_MAP_EDGES: usize = 256000;
+pub const MAX_MAP_SURFEDGES: usize = 512000;
+pub const MAX_MAP_TEXTURES: usize = 512;
+pub const MAX_MAP_MIPTEX: usize = 0x200000;
+pub const MAX_MAP_LIGHTING: usize = 0x200000;
+pub const MAX_MAP_VISIBILITY: usize = 0x200000;
+
+// Largest values that engines may raise the limits to. Beyond these, indices
+// into a lump no longer fit into the fields that refer to them, or lump sizes
+// no longer fit into the header.
+const LIMIT_CAPACITIES: [(&str, usize); 16] = [
+	("MAX_MAP_MODELS", i32::MAX as usize),
+	("MAX_MAP_ENTSTRING", i32::MAX as usize),
+	("MAX_MAP_PLANES", u16::MAX as usize + 1),
+	("MAX_MAP_NODES", i16::MAX as usize + 1),
+	("MAX_MAP_CLIPNODES", i16::MAX as usize + 1),
+	("MAX_MAP_LEAFS", i16::MAX as usize + 1),
+	("MAX_MAP_VERTS", u16::MAX as usize + 1),
+	("MAX_MAP_FACES", u16::MAX as usize + 1),
+	("MAX_MAP_MARKSURFACES", u16::MAX as usize + 1),
+	("MAX_MAP_TEXINFO", u16::MAX as usize + 1),
+	("MAX_MAP_EDGES", i32::MAX as usize),
+	("MAX_MAP_SURFEDGES", i32::MAX as usize),
+	("MAX_MAP_TEXTURES", i32::MAX as usize),
+	("MAX_MAP_MIPTEX", i32::MAX as usize),
+	("MAX_MAP_LIGHTING", i32::MAX as usize),
+	("MAX_MAP_VISIBILITY", i32::MAX as usize),
+];
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Plane
+{
+	pub normal: [f32; 3],
+	pub distance: f32,
+
+	/// 0 to 2 for planes facing along the X, Y or Z axis, and 3 to 5 for
+	/// other planes, depending on which axis they face closest to.
+	pub kind: i32,
+}
+
+/// Mip levels and palette of a texture that is embedded in the file.
+#[derive(Clone, PartialEq, Debug)]
+pub struct EmbeddedMips
+{
+	/// Palette indices of each mip level, beginning with the full size
+	/// texture. Each level is half the size of the one before it.
+	pub levels: [Vec<u8>; MIP_LEVELS],
+
+	/// 256 RGB colours.
+	pub palette: Vec<u8>,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct MipTexture
+{
+	pub name: String,
+	pub width: u32,
+	pub height: u32,
+
+	/// Textures that are not embedded are loaded from WAD files by the game.
+	pub embedded: Option<EmbeddedMips>,
+}
+
+impl MipTexture
+{
+	/// Size of the header that precedes each texture in the textures lump.
+	pub const HEADER_SIZE: usize = MIPTEX_NAME_LENGTH + 4 * 2 + 4 * MIP_LEVELS;
+
+	/// Number of bytes the texture takes up in the textures lump.
+	pub fn encoded_size(&self) -> usize
+	{
+		return Self::HEADER_SIZE
+			+ self.embedded.as_ref().map_or(0, |embedded| {
+				// The palette is preceded by its colour count, and followed by
+				// two bytes of padding.
+				embedded.levels.iter().map(Vec::len).sum::<usize>() + 2 + embedded.palette.len() + 2
+			});
+	}
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Node
+{
+	pub plane: i32,
+
+	/// Children in front of and behind the plane. Negative values refer to
+	/// leaf -(child + 1).
+	pub children: [i16; 2],
+
+	pub mins: [i16; 3],
+	pub maxs: [i16; 3],
+	pub first_face: u16,
+	pub face_count: u16,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct TexInfo
+{
+	/// U and V axes, each followed by its offset.
+	pub vecs: [[f32; 4]; 2],
+	pub texture: i32,
+	pub flags: i32,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Face
+{
+	pub plane: u16,
+
+	/// Non-zero if the face points away from its plane.
+	pub side: u16,
+
+	pub first_edge: i32,
+	pub edge_count: u16,
+	pub texinfo: u16,
+	pub styles: [u8; 4],
+
+	/// Byte offset of the face's lightmap, or -1 if it has none.
+	pub lighting_offset: i32,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct ClipNode
+{
+	pub plane: i32,
+
+	/// Children in front of and behind the plane. Negative values are
+	/// contents.
+	pub children: [i16; 2],
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Leaf
+{
+	pub contents: i32,
+
+	/// Byte offset of the leaf's compressed visibility row, or -1 if it has
+	/// none.
+	pub vis_offset: i32,
+
+	pub mins: [i16; 3],
+	pub maxs: [i16; 3],
+	pub first_mark_surface: u16,
+	pub mark_surface_count: u16,
+	pub ambient_levels: [u8; 4],
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Model
+{
+	pub mins: [f32; 3],
+	pub maxs: [f32; 3],
+	pub origin: [f32; 3],
+
+	/// Root of the BSP tree, followed by the roots of the three clipping
+	/// hulls.
+	pub head_nodes: [i32; 4],
+
+	/// Number of leaves in the model, not counting the shared solid leaf.
+	pub vis_leaves: i32,
+
+	pub first_face: i32,
+	pub face_count: i32,
+}
+
+/// Every lump of a version 30 BSP file.
+#[derive(Clone, Default, PartialEq, Debug)]
+pub struct Bsp30
+{
+	/// Entity text, including its terminating NUL.
+	pub entities: Vec<u8>,
+
+	pub planes: Vec<Plane>,
+	pub textures: Vec<MipTexture>,
+	pub vertices: Vec<[f32; 3]>,
+	pub visibility: Vec<u8>,
+	pub nodes: Vec<Node>,
+	pub texinfos: Vec<TexInfo>,
+	pub faces: Vec<Face>,
+	pub lighting: Vec<u8>,
+	pub clip_nodes: Vec<ClipNode>,
+
+	/// Leaf 0 is the solid leaf that every solid part of the map refers to.
+	pub leaves: Vec<Leaf>,
+
+	pub mark_surfaces: Vec<u16>,
+
+	/// Edge 0 is unused, as surface edges refer to edges by signed index.
+	pub edges: Vec<[u16; 2]>,
+
+	pub surf_edges: Vec<i32>,
+	pub models: Vec<Model>,
+}
+
+/// Limit of the format that a file exceeds.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct ExceededLimit
+{
+	pub name: &'static str,
+	pub count: usize,
+	pub limit: usize,
+}
+
+impl Bsp30
+{
+	/// Returns every limit of the engine that the file exceeds. Limits named
+	/// in the overrides take the given values in place of the engine's.
+	pub fn exceeded_limits(&self, overrides: &HashMap<String, usize>) -> Vec<ExceededLimit>
+	{
+		let miptex_size: usize = 4
+			+ 4 * self.textures.len()
+			+ self
+				.textures
+				.iter()
+				.map(MipTexture::encoded_size)
+				.sum::<usize>();
+
+		let counts: [(&'static str, usize, usize); 16] = [
+			("MAX_MAP_MODELS", self.models.len(), MAX_MAP_MODELS),
+			("MAX_MAP_ENTSTRING", self.entities.len(), MAX_MAP_ENTSTRING),
+			("MAX_MAP_PLANES", self.planes.len(), MAX_MAP_PLANES),
+			("MAX_MAP_NODES", self.nodes.len(), MAX_MAP_NODES),
+			(
+				"MAX_MAP_CLIPNODES",
+				self.clip_nodes.len(),
+				MAX_MAP_CLIPNODES,
+			),
+			("MAX_MAP_LEAFS", self.leaves.len(), MAX_MAP_LEAFS),
+			("MAX_MAP_VERTS", self.vertices.len(), MAX_MAP_VERTS),
+			("MAX_MAP_FACES", self.faces.len(), MAX_MAP_FACES),
+			(
+				"MAX_MAP_MARKSURFACES",
+				self.mark_surfaces.len(),
+				MAX_MAP_MARKSURFACES,
+			),
+			("MAX_MAP_TEXINFO", self.texinfos.len(), MAX_MAP_TEXINFO),
+			("MAX_MAP_EDGES", self.edges.len(), MAX_MAP_EDGES),
+			(
+				"MAX_MAP_SURFEDGES",
+				self.surf_edges.len(),
+				MAX_MAP_SURFEDGES,
+			),
+			("MAX_MAP_TEXTURES", self.textures.len(), MAX_MAP_TEXTURES),
+			("MAX_MAP_MIPTEX", miptex_size, MAX_MAP_MIPTEX),
+			("MAX_MAP_LIGHTING", self.lighting.len(), MAX_MAP_LIGHTING),
+			(
+				"MAX_MAP_VISIBILITY",
+				self.visibility.len(),
+				MAX_MAP_VISIBILITY,
+			),
+		];
+
+		return counts
+			.into_iter()
+			.map(|(name, count, limit)| (name, count, *overrides.get(name).unwrap_or(&limit)))
+			.filter(|(_, count, limit)| count > limit)
+			.map(|(name, count, limit)| ExceededLimit {
+				name: name,
+				count: count,
+				limit: limit,
+			})
+			.collect();
+	}
+}
+
+/// Checks that the limits raised by the overrides can still be stored by the
+/// fields of the file.
+pub fn check_limit_overrides(overrides: &HashMap<String, usize>) -> Result<(), String>
+{
+	for (name, capacity) in LIMIT_CAPACITIES
+	{
+		let Some(value) = overrides.get(name)
+		else
+		{
+			continue;
+		};
+
+		if *value > capacity
+		{
+			return Err(format!(
+				"{name} cannot be raised to {value}, as a version 30 file can store at most {capacity}"
+			));
+		}
+	}
+
+	return Ok(());
+}
+
+#[cfg(test)]
+mod tests
+{
+	use super::*;
+
+	#[test]
+	fn report_every_exceeded_limit()
+	{
+		let mut bsp: Bsp30 = Bsp30::default();
+		assert!(bsp.exceeded_limits(&HashMap::new()).is_empty());
+
+		bsp.entities = vec![b' '; MAX_MAP_ENTSTRING + 10];
+		bsp.mark_surfaces = vec![0; MAX_MAP_MARKSURFACES];
+		bsp.textures = vec![
+			MipTexture {
+				name: String::from("big"),
+				width: 1024,
+				height: 1024,
+				embedded: Some(EmbeddedMips {
+					levels: [
+						vec![0; 1024 * 1024],
+						vec![0; 512 * 512],
+						vec![0; 256 * 256],
+						vec![0; 128 * 128],
+					],
+					palette: vec![0; 768],
+				}),
+			};
+			2
+		];
+
+		assert_eq!(
+			bsp.exceeded_limits(&HashMap::new()),
+			vec![
+				ExceededLimit {
+					name: "MAX_MAP_ENTSTRING",
+					count: MAX_MAP_ENTSTRING + 10,
+					limit: MAX_MAP_ENTSTRING,
+				},
+				ExceededLimit {
+					name: "MAX_MAP_MIPTEX",
+					count: 4 + 2 * (4 + 40 + 1392640 + 772),
+					limit: MAX_MAP_MIPTEX,
+				},
+			]
+		);
+
+		// Engines that raise a limit may override it.
+		let overrides: HashMap<String, usize> =
+			HashMap::from([(String::from("MAX_MAP_MIPTEX"), 0x400000)]);
+
+		assert_eq!(
+			bsp.exceeded_limits(&overrides)
+				.iter()
+				.map(|limit| limit.name)
+				.collect::<Vec<&str>>(),
+			vec!["MAX_MAP_ENTSTRING"]
+		);
+	}
+
+	#[test]
+	fn reject_overrides_beyond_field_widths()
+	{
+		let raised: HashMap<String, usize> = HashMap::from([
+			(String::from("MAX_MAP_VERTS"), 65536),
+			(String::from("MAX_MAP_LIGHTING"), 0x800000),
+		]);
+		assert!(check_limit_overrides(&raised).is_ok());
+
+		let too_wide: HashMap<String, usize> =
+			HashMap::from([(String::from("MAX_MAP_CLIPNODES"), 40000)]);
+		assert_eq!(
+			check_limit_overrides(&too_wide),
+			Err(String::from(
+				"MAX_MAP_CLIPNODES cannot be raised to 40000, as a version 30 file can store at most 32768"
+			))
+		);
+	}
+}
@@ -0,0 +1,74 @@
+use glam::{DVec2, DVec3};
+
+// These types mirror the MapBlueprint types in bspcore. We can't depend on
+// bspcore from an extension, so the parser builds these first, and they are
+// then handed over to the core library.
+
+/// One texture axis of a brush face, as specified in a Valve 220 map file.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct TextureAxis
+{
+	pub axis: DVec3,
+	pub offset: f64,
+}
+
+/// How a texture is aligned on a brush face.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum TextureAlignment
+{
+	/// Standard Quake alignment, where the texture axes are implied by the
+	/// face's plane. The core library works them out when the map is handed
+	/// over.
+	Standard
+	{
+		offset: DVec2
+	},
+
+	/// Valve 220 alignment, where the texture axes are given explicitly.
+	Valve220
+	{
+		u_axis: TextureAxis,
+		v_axis: TextureAxis,
+	},
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct BlueprintBrushFace
+{
+	/// Three points on the face's plane, in the order they were specified in
+	/// the map file. By convention, these are wound clockwise when looking at
+	/// the front of the face.
+	pub plane_points: [DVec3; 3],
+	pub material: String,
+	pub alignment: TextureAlignment,
+	pub rotation: f64,
+	pub scale: DVec2,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct BlueprintBrush
+{
+	pub faces: Vec<BlueprintBrushFace>,
+
+	/// 1-based line in the map file on which the brush begins.
+	pub line: u32,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct BlueprintEntity
+{
+	pub brushes: Vec<BlueprintBrush>,
+
+	/// 1-based line in the map file on which the entity begins.
+	pub line: u32,
+
+	/// Keyvalues in the order in which they appear in the map file. Keys may
+	/// be repeated, and this must be preserved.
+	pub keyvalues: Vec<(String, String)>,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct MapBlueprint
+{
+	pub entities: Vec<BlueprintEntity>,
+}
@@ -0,0 +1,3 @@
+pub mod bsp30;
+pub mod map_blueprint;
+pub mod wad;
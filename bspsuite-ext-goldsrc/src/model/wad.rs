@@ -0,0 +1,106 @@
+use glam::DVec3;
+
+// Structures of WAD2 (Quake) and WAD3 (GoldSrc) texture archives.
+
+pub const WAD2_MAGIC: [u8; 4] = *b"WAD2";
+pub const WAD3_MAGIC: [u8; 4] = *b"WAD3";
+
+/// Size of the archive header: the magic, followed by the number of lumps and
+/// the offset of the lump directory.
+pub const WAD_HEADER_SIZE: usize = 12;
+
+/// Size of each entry in the lump directory.
+pub const WAD_DIRECTORY_ENTRY_SIZE: usize = 32;
+
+/// Number of bytes in a lump name, including the terminating NUL.
+pub const WAD_NAME_LENGTH: usize = 16;
+
+/// Lump type of the palette shared by the textures in a WAD2 archive.
+pub const LUMP_TYPE_PALETTE: u8 = 0x40;
+
+/// Lump type of a WAD3 texture, which carries its own palette.
+pub const LUMP_TYPE_MIPTEX_WAD3: u8 = 0x43;
+
+/// Lump type of a WAD2 texture, which uses the archive's palette.
+pub const LUMP_TYPE_MIPTEX_WAD2: u8 = 0x44;
+
+/// Palette index that is transparent in textures whose name begins with '{'.
+pub const TRANSPARENT_INDEX: u8 = 255;
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct WadTexture
+{
+	pub name: String,
+	pub width: u32,
+	pub height: u32,
+
+	/// Palette indices of the full size mip level, row by row.
+	pub pixels: Vec<u8>,
+
+	/// 256 RGB colours, or empty if the archive did not provide a palette.
+	pub palette: Vec<u8>,
+}
+
+impl WadTexture
+{
+	/// Average colour of the texture's pixels, with components ranging from 0
+	/// to 255. Transparent pixels are left out. Returns [`None`] if there is
+	/// no palette, or no opaque pixels.
+	pub fn average_color(&self) -> Option<DVec3>
+	{
+		let transparent: bool = self.name.starts_with('{');
+		let mut total: DVec3 = DVec3::ZERO;
+		let mut count: usize = 0;
+
+		for index in &self.pixels
+		{
+			if transparent && *index == TRANSPARENT_INDEX
+			{
+				continue;
+			}
+
+			let offset: usize = *index as usize * 3;
+			let color: &[u8] = self.palette.get(offset..offset + 3)?;
+
+			total += DVec3::new(color[0] as f64, color[1] as f64, color[2] as f64);
+			count += 1;
+		}
+
+		if count == 0
+		{
+			return None;
+		}
+
+		return Some(total / count as f64);
+	}
+}
+
+#[cfg(test)]
+mod tests
+{
+	use super::*;
+
+	#[test]
+	fn average_opaque_pixels()
+	{
+		let mut palette: Vec<u8> = vec![0; 768];
+		palette[3..6].copy_from_slice(&[200, 100, 0]);
+		palette[765..768].copy_from_slice(&[0, 0, 255]);
+
+		let mut texture: WadTexture = WadTexture {
+			name: String::from("{FENCE"),
+			width: 2,
+			height: 2,
+			pixels: vec![0, 1, 255, 255],
+			palette: palette,
+		};
+
+		assert_eq!(texture.average_color(), Some(DVec3::new(100.0, 50.0, 0.0)));
+
+		texture.name = String::from("FENCE");
+		assert_eq!(texture.average_color(), Some(DVec3::new(50.0, 25.0, 127.5)));
+
+		texture.palette.clear();
+		assert_eq!(texture.average_color(), None);
+	}
+}
@@ -0,0 +1,25 @@
+use crate::io::bsp42_builder::build_bsp42;
+use crate::io::bsp42_writer::encode_bsp42;
+use crate::model::bsp42::Bsp42;
+use bspextifc::bsp_writer_api::{BspWriterApi, WriteBspArgs, WriteBspResult};
+
+pub extern "C" fn write_bsp(api: &mut BspWriterApi, args: &WriteBspArgs) -> WriteBspResult
+{
+	let bsp: Bsp42 = match build_bsp42(args)
+	{
+		Ok(bsp) => bsp,
+		Err(message) =>
+		{
+			api.set_error(&message);
+			return WriteBspResult::Failure;
+		}
+	};
+
+	if let Err(err) = std::fs::write(args.path.to_string(), encode_bsp42(&bsp))
+	{
+		api.set_error(&format!("Could not write file. {err}"));
+		return WriteBspResult::Failure;
+	}
+
+	return WriteBspResult::Success;
+}
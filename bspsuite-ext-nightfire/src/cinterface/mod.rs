@@ -0,0 +1,2 @@
+pub mod bsp_writer;
+pub mod texture_provider;
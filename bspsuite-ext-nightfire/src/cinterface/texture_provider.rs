@@ -0,0 +1,75 @@
+use crate::io::texture_search::{loose_image, material_image, search_roots};
+use crate::model::image::Image;
+use bspextifc::texture_api::{FindTexturesArgs, FindTexturesResult, TextureApi, TextureArgs};
+use bspextifc::{SliceRef, StringRef};
+use std::path::PathBuf;
+
+/// Provides textures from the images named by the material definitions that
+/// share their material's name.
+pub extern "C" fn find_materials(
+	api: &mut TextureApi,
+	args: &FindTexturesArgs,
+) -> FindTexturesResult
+{
+	let roots: Vec<PathBuf> = roots(args);
+
+	for material in args.materials.as_slice()
+	{
+		let name: String = material.to_string();
+
+		if let Some(image) = material_image(&name, &roots)
+		{
+			add_image(api, &name, &image);
+		}
+	}
+
+	return FindTexturesResult::Success;
+}
+
+/// Provides textures from the loose TGA and PNG images that share their
+/// material's name.
+pub extern "C" fn find_images(api: &mut TextureApi, args: &FindTexturesArgs) -> FindTexturesResult
+{
+	let roots: Vec<PathBuf> = roots(args);
+
+	for material in args.materials.as_slice()
+	{
+		let name: String = material.to_string();
+
+		if let Some(image) = loose_image(&name, &roots)
+		{
+			add_image(api, &name, &image);
+		}
+	}
+
+	return FindTexturesResult::Success;
+}
+
+// Loose images carry no palette, so only their size and colour are provided.
+fn add_image(api: &mut TextureApi, name: &str, image: &Image)
+{
+	let average_color: Option<[f64; 3]> = image.average_color();
+
+	api.add_texture(&TextureArgs {
+		name: StringRef::from(name),
+		width: image.width,
+		height: image.height,
+		average_color: average_color.unwrap_or_default(),
+		has_average_color: average_color.is_some(),
+		pixels: SliceRef::from(&[][..]),
+		palette: SliceRef::from(&[][..]),
+	});
+}
+
+// Converts the map path and search paths into the directories to search.
+fn roots(args: &FindTexturesArgs) -> Vec<PathBuf>
+{
+	let search_paths: Vec<String> = args
+		.search_paths
+		.as_slice()
+		.iter()
+		.map(StringRef::to_string)
+		.collect();
+
+	return search_roots(&args.map_path.to_string(), &search_paths);
+}
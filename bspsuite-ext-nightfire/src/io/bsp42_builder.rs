@@ -0,0 +1,596 @@
+use crate::io::bsp42_writer::compress_vis_row;
+use crate::model::bsp42::{
+	Brush, BrushSide, Bsp42, CONTENTS_EMPTY, CONTENTS_LAVA, CONTENTS_SKY, CONTENTS_SLIME,
+	CONTENTS_SOLID, CONTENTS_WATER, FULLBRIGHT_MATERIAL, Face, LIGHTMAPPED_MATERIAL, Leaf, Model,
+	NAME_LENGTH, Node, Plane, TextureMatrix,
+};
+use bspextifc::bsp_writer_api::{
+	BrushArgs, FaceArgs, LeafArgs, LeafContents, ModelArgs, NodeChild, TextureInfoArgs,
+	WriteBspArgs,
+};
+use std::collections::HashMap;
+
+/// Converts the compiled map into the lumps of a version 42 file. Both planes
+/// of each pair are written, faces are split into triangle fans over their own
+/// vertices, and leaves are written after an unused solid leaf 0. Solid leaves
+/// are kept, since collision traces the tree down to them and tests their
+/// brushes.
+pub fn build_bsp42(args: &WriteBspArgs) -> Result<Bsp42, String>
+{
+	let mut builder: Bsp42Builder = Bsp42Builder {
+		bsp: Bsp42 {
+			entities: [args.entities.to_string().as_bytes(), &[0]].concat(),
+			planes: args
+				.planes
+				.as_slice()
+				.iter()
+				.map(|plane| Plane {
+					normal: plane.normal.map(|value| value as f32),
+					distance: plane.distance as f32,
+					kind: plane_kind(plane.normal),
+				})
+				.collect(),
+			lighting: args.lighting.as_slice().to_vec(),
+			..Bsp42::default()
+		},
+		material_indices: HashMap::new(),
+		texture_matrix_indices: HashMap::new(),
+	};
+
+	builder.bsp.textures = args
+		.textures
+		.as_slice()
+		.iter()
+		.map(|texture| checked_name(texture.name.to_string()))
+		.collect::<Result<Vec<String>, String>>()?;
+
+	for face in args.faces.as_slice()
+	{
+		builder.add_face(face, args)?;
+	}
+
+	builder.add_leaves(args.leaves.as_slice());
+	builder.add_visibility(args);
+
+	for node in args.nodes.as_slice()
+	{
+		let children: [i32; 2] = [node_child(node.children[0])?, node_child(node.children[1])?];
+
+		builder.bsp.nodes.push(Node {
+			plane: node.plane as i32,
+			children: children,
+			mins: node.mins.map(|value| value as f32),
+			maxs: node.maxs.map(|value| value as f32),
+		});
+	}
+
+	args.brushes
+		.as_slice()
+		.iter()
+		.for_each(|brush| builder.add_brush(brush));
+
+	for model in args.models.as_slice()
+	{
+		builder.bsp.models.push(convert_model(model)?);
+	}
+
+	return Ok(builder.bsp);
+}
+
+struct Bsp42Builder
+{
+	bsp: Bsp42,
+
+	// Materials by name, and texture matrices by the bits of their values, so
+	// that faces which use the same ones share them.
+	material_indices: HashMap<&'static str, i32>,
+	texture_matrix_indices: HashMap<[[u32; 4]; 2], i32>,
+}
+
+impl Bsp42Builder
+{
+	fn add_face(&mut self, face: &FaceArgs, args: &WriteBspArgs) -> Result<(), String>
+	{
+		let Some(info) = args
+			.texture_infos
+			.as_slice()
+			.get(face.texture_info as usize)
+		else
+		{
+			return Err(format!(
+				"A face referred to texture info {}, which does not exist",
+				face.texture_info
+			));
+		};
+
+		let points: &[[f64; 3]] = face.points.as_slice();
+		let normal: [f32; 3] = args.planes.as_slice()[face.plane as usize]
+			.normal
+			.map(|value| value as f32);
+
+		let first_vertex: usize = self.bsp.vertices.len();
+		let first_index: usize = self.bsp.indices.len();
+
+		self.bsp
+			.vertices
+			.extend(points.iter().map(|point| point.map(|value| value as f32)));
+		self.bsp
+			.normals
+			.extend(std::iter::repeat_n(normal, points.len()));
+
+		for index in 1..points.len().saturating_sub(1)
+		{
+			self.bsp.indices.extend([0, index as u32, index as u32 + 1]);
+		}
+
+		let lit: bool = face.lighting_offset >= 0;
+		let texture_matrix: i32 = self.texture_matrix(projection(info, 1.0, [0; 2]));
+		let lightmap_matrix: i32 = match lit
+		{
+			true => self.texture_matrix(projection(info, args.luxel_size, face.lightmap_mins)),
+			false => -1,
+		};
+
+		let material: i32 = self.material(match lit
+		{
+			true => LIGHTMAPPED_MATERIAL,
+			false => FULLBRIGHT_MATERIAL,
+		});
+
+		self.bsp.faces.push(Face {
+			plane: face.plane as i32,
+			first_vertex: first_vertex as i32,
+			vertex_count: points.len() as i32,
+			first_index: first_index as i32,
+			index_count: (self.bsp.indices.len() - first_index) as i32,
+			flags: 0,
+			texture: info.texture as i32,
+			material: material,
+			texture_matrix: texture_matrix,
+			lightmap_matrix: lightmap_matrix,
+			lighting_offset: face.lighting_offset,
+			styles: face.styles,
+		});
+
+		return Ok(());
+	}
+
+	fn material(&mut self, name: &'static str) -> i32
+	{
+		return *self.material_indices.entry(name).or_insert_with(|| {
+			self.bsp.materials.push(String::from(name));
+			(self.bsp.materials.len() - 1) as i32
+		});
+	}
+
+	fn texture_matrix(&mut self, matrix: TextureMatrix) -> i32
+	{
+		let key: [[u32; 4]; 2] = matrix.vecs.map(|vec| vec.map(f32::to_bits));
+
+		return *self.texture_matrix_indices.entry(key).or_insert_with(|| {
+			self.bsp.texture_matrices.push(matrix);
+			(self.bsp.texture_matrices.len() - 1) as i32
+		});
+	}
+
+	fn add_leaves(&mut self, leaves: &[LeafArgs])
+	{
+		self.bsp.leaves.push(Leaf {
+			contents: CONTENTS_SOLID,
+			vis_offset: -1,
+			mins: [0.0; 3],
+			maxs: [0.0; 3],
+			first_leaf_face: 0,
+			leaf_face_count: 0,
+			first_leaf_brush: 0,
+			leaf_brush_count: 0,
+		});
+
+		for leaf in leaves
+		{
+			let first_leaf_face: usize = self.bsp.leaf_faces.len();
+			let first_leaf_brush: usize = self.bsp.leaf_brushes.len();
+			self.bsp.leaf_faces.extend(leaf.faces.as_slice());
+			self.bsp.leaf_brushes.extend(leaf.brushes.as_slice());
+
+			self.bsp.leaves.push(Leaf {
+				contents: contents_code(leaf.contents),
+				vis_offset: -1,
+				mins: leaf.mins.map(|value| value as f32),
+				maxs: leaf.maxs.map(|value| value as f32),
+				first_leaf_face: first_leaf_face as u32,
+				leaf_face_count: (self.bsp.leaf_faces.len() - first_leaf_face) as u32,
+				first_leaf_brush: first_leaf_brush as u32,
+				leaf_brush_count: (self.bsp.leaf_brushes.len() - first_leaf_brush) as u32,
+			});
+		}
+	}
+
+	// As with GoldSrc, visibility is stored per leaf rather than per cluster,
+	// so each cluster's row is expanded to one bit for every leaf of the world
+	// after leaf 0, including solid leaves, which are never visible. Leaves of the
+	// same cluster, and clusters that see the same leaves, share a row.
+	fn add_visibility(&mut self, args: &WriteBspArgs)
+	{
+		let Some(world) = args.models.as_slice().first()
+		else
+		{
+			return;
+		};
+
+		let world_leaves: &[LeafArgs] = &args.leaves.as_slice()
+			[world.first_leaf as usize..(world.first_leaf + world.leaf_count) as usize];
+
+		// Clusters of the world's leaves in the file, from leaf 1 onwards.
+		let leaf_clusters: Vec<Option<usize>> = world_leaves
+			.iter()
+			.map(|leaf| usize::try_from(leaf.cluster).ok())
+			.collect();
+
+		let cluster_row_length: usize = (args.cluster_count as usize).div_ceil(8);
+		let cluster_visibility: &[u8] = args.cluster_visibility.as_slice();
+		let is_visible = |from: usize, to: usize| -> bool {
+			return cluster_visibility[from * cluster_row_length + to / 8] & (1 << (to % 8)) != 0;
+		};
+
+		let mut cluster_offsets: HashMap<usize, i32> = HashMap::new();
+		let mut row_offsets: HashMap<Vec<u8>, i32> = HashMap::new();
+
+		for (index, cluster) in leaf_clusters.iter().enumerate()
+		{
+			let Some(cluster) = cluster
+			else
+			{
+				continue;
+			};
+
+			let offset: i32 = *cluster_offsets.entry(*cluster).or_insert_with(|| {
+				let mut row: Vec<u8> = vec![0; leaf_clusters.len().div_ceil(8)];
+
+				for (to, to_cluster) in leaf_clusters.iter().enumerate()
+				{
+					if to_cluster.is_some_and(|to_cluster| is_visible(*cluster, to_cluster))
+					{
+						row[to / 8] |= 1 << (to % 8);
+					}
+				}
+
+				*row_offsets
+					.entry(compress_vis_row(&row))
+					.or_insert_with_key(|compressed| {
+						let offset: i32 = self.bsp.visibility.len() as i32;
+						self.bsp.visibility.extend_from_slice(compressed);
+						offset
+					})
+			});
+
+			self.bsp.leaves[1 + index].vis_offset = offset;
+		}
+	}
+
+	fn add_brush(&mut self, brush: &BrushArgs)
+	{
+		let first_side: usize = self.bsp.brush_sides.len();
+
+		self.bsp
+			.brush_sides
+			.extend(brush.sides.as_slice().iter().map(|side| BrushSide {
+				plane: side.plane as i32,
+				face: side.face,
+			}));
+
+		self.bsp.brushes.push(Brush {
+			contents: contents_code(brush.contents),
+			first_side: first_side as u32,
+			side_count: (self.bsp.brush_sides.len() - first_side) as u32,
+		});
+	}
+}
+
+fn convert_model(model: &ModelArgs) -> Result<Model, String>
+{
+	return Ok(Model {
+		mins: model.mins.map(|value| value as f32),
+		maxs: model.maxs.map(|value| value as f32),
+		head_node: node_child(model.head_node)?,
+		vis_leaves: model.leaf_count as i32,
+		first_leaf: 1 + model.first_leaf as i32,
+		leaf_count: model.leaf_count as i32,
+		first_brush: model.first_brush as i32,
+		brush_count: model.brush_count as i32,
+		first_face: model.first_face as i32,
+		face_count: model.face_count as i32,
+	});
+}
+
+// Leaves are written one place after where they were given, following leaf 0.
+fn node_child(child: NodeChild) -> Result<i32, String>
+{
+	return match child
+	{
+		NodeChild::Node(index) => Ok(index as i32),
+		NodeChild::Leaf(index) => Ok(-(index as i32) - 2),
+		NodeChild::Contents(_) => Err(String::from("A BSP node had contents as a child")),
+	};
+}
+
+// Texture coordinates are scaled down by the size of each sample, and the
+// lightmap's first sample is moved to the origin.
+fn projection(info: &TextureInfoArgs, sample_size: f64, mins: [i32; 2]) -> TextureMatrix
+{
+	let vec = |axis: [f64; 3], offset: f64, min: i32| -> [f32; 4] {
+		return [
+			axis[0] / sample_size,
+			axis[1] / sample_size,
+			axis[2] / sample_size,
+			offset / sample_size - min as f64,
+		]
+		.map(|value| value as f32);
+	};
+
+	return TextureMatrix {
+		vecs: [
+			vec(info.u_axis, info.u_offset, mins[0]),
+			vec(info.v_axis, info.v_offset, mins[1]),
+		],
+	};
+}
+
+fn checked_name(name: String) -> Result<String, String>
+{
+	if name.len() >= NAME_LENGTH
+	{
+		return Err(format!(
+			"Texture name {name} is longer than the {} characters allowed",
+			NAME_LENGTH - 1
+		));
+	}
+
+	return Ok(name);
+}
+
+fn plane_kind(normal: [f64; 3]) -> i32
+{
+	if let Some(axis) = normal.iter().position(|value| value.abs() == 1.0)
+	{
+		return axis as i32;
+	}
+
+	let major_axis: usize = (0..3)
+		.max_by(|a, b| normal[*a].abs().total_cmp(&normal[*b].abs()))
+		.unwrap_or_default();
+
+	return 3 + major_axis as i32;
+}
+
+fn contents_code(contents: LeafContents) -> i32
+{
+	return match contents
+	{
+		LeafContents::Empty => CONTENTS_EMPTY,
+		LeafContents::Solid => CONTENTS_SOLID,
+		LeafContents::Water => CONTENTS_WATER,
+		LeafContents::Slime => CONTENTS_SLIME,
+		LeafContents::Lava => CONTENTS_LAVA,
+		LeafContents::Sky => CONTENTS_SKY,
+	};
+}
+
+#[cfg(test)]
+mod tests
+{
+	use super::*;
+	use bspextifc::bsp_writer_api::{BrushSideArgs, NodeArgs, PlaneArgs, TextureArgs};
+	use bspextifc::{SliceRef, StringRef};
+
+	fn leaf_args<'l>(
+		contents: LeafContents,
+		cluster: i32,
+		faces: &'l [u32],
+		brushes: &'l [u32],
+	) -> LeafArgs<'l>
+	{
+		return LeafArgs {
+			contents: contents,
+			cluster: cluster,
+			mins: [-0.5; 3],
+			maxs: [0.5; 3],
+			faces: SliceRef::from(faces),
+			brushes: SliceRef::from(brushes),
+		};
+	}
+
+	#[test]
+	fn convert_world_to_lumps()
+	{
+		let planes: [PlaneArgs; 2] = [
+			PlaneArgs {
+				normal: [0.0, 0.0, 1.0],
+				distance: 0.0,
+			},
+			PlaneArgs {
+				normal: [0.0, 0.0, -1.0],
+				distance: 0.0,
+			},
+		];
+
+		let textures: [TextureArgs; 1] = [TextureArgs {
+			name: StringRef::from("generic/floor01"),
+			width: 64,
+			height: 64,
+			pixels: SliceRef::from([].as_slice()),
+			palette: SliceRef::from([].as_slice()),
+		}];
+
+		let texture_infos: [TextureInfoArgs; 1] = [TextureInfoArgs {
+			u_axis: [1.0, 0.0, 0.0],
+			u_offset: 8.0,
+			v_axis: [0.0, -1.0, 0.0],
+			v_offset: 0.0,
+			texture: 0,
+			special: false,
+		}];
+
+		// A lit square and an unlit triangle, both on the back of the plane.
+		let square: [[f64; 3]; 4] = [
+			[0.0, 0.0, 0.0],
+			[0.0, 32.0, 0.0],
+			[32.0, 32.0, 0.0],
+			[32.0, 0.0, 0.0],
+		];
+		let triangle: [[f64; 3]; 3] = [[0.0, 0.0, 0.0], [-32.0, 0.0, 0.0], [0.0, 32.0, 0.0]];
+		let faces: [FaceArgs; 2] = [
+			FaceArgs {
+				plane: 1,
+				points: SliceRef::from(square.as_slice()),
+				texture_info: 0,
+				styles: [0, 255, 255, 255],
+				lighting_offset: 0,
+				lightmap_mins: [1, -2],
+				lightmap_size: [3, 3],
+			},
+			FaceArgs {
+				plane: 1,
+				points: SliceRef::from(triangle.as_slice()),
+				texture_info: 0,
+				styles: [255; 4],
+				lighting_offset: -1,
+				lightmap_mins: [0; 2],
+				lightmap_size: [0; 2],
+			},
+		];
+
+		let leaf_faces: [u32; 2] = [0, 1];
+		let leaf_brushes: [u32; 1] = [0];
+		let leaves: [LeafArgs; 2] = [
+			leaf_args(LeafContents::Solid, -1, &[], &leaf_brushes),
+			leaf_args(LeafContents::Empty, 0, &leaf_faces, &[]),
+		];
+
+		let nodes: [NodeArgs; 1] = [NodeArgs {
+			plane: 0,
+			children: [NodeChild::Leaf(1), NodeChild::Leaf(0)],
+			mins: [-32.0, 0.0, 0.0],
+			maxs: [32.0, 32.0, 0.0],
+			first_face: 0,
+			face_count: 2,
+		}];
+
+		let brush_sides: [BrushSideArgs; 2] = [
+			BrushSideArgs { plane: 0, face: -1 },
+			BrushSideArgs { plane: 1, face: 0 },
+		];
+		let brushes: [BrushArgs; 1] = [BrushArgs {
+			contents: LeafContents::Solid,
+			sides: SliceRef::from(brush_sides.as_slice()),
+			mins: [-32.0, 0.0, -16.0],
+			maxs: [32.0, 32.0, 0.0],
+		}];
+
+		let models: [ModelArgs; 1] = [ModelArgs {
+			mins: [-32.0, 0.0, 0.0],
+			maxs: [32.0, 32.0, 0.0],
+			origin: [0.0; 3],
+			head_node: NodeChild::Node(0),
+			clip_head_nodes: SliceRef::from([].as_slice()),
+			first_face: 0,
+			face_count: 2,
+			first_node: 0,
+			node_count: 1,
+			first_leaf: 0,
+			leaf_count: 2,
+			first_brush: 0,
+			brush_count: 1,
+		}];
+
+		let cluster_visibility: [u8; 1] = [1];
+		let args: WriteBspArgs = WriteBspArgs {
+			path: StringRef::from(""),
+			entities: StringRef::from("{\n}\n"),
+			planes: SliceRef::from(planes.as_slice()),
+			textures: SliceRef::from(textures.as_slice()),
+			texture_infos: SliceRef::from(texture_infos.as_slice()),
+			faces: SliceRef::from(faces.as_slice()),
+			nodes: SliceRef::from(nodes.as_slice()),
+			leaves: SliceRef::from(leaves.as_slice()),
+			clip_nodes: SliceRef::from([].as_slice()),
+			brushes: SliceRef::from(brushes.as_slice()),
+			models: SliceRef::from(models.as_slice()),
+			cluster_count: 1,
+			cluster_visibility: SliceRef::from(cluster_visibility.as_slice()),
+			lighting: SliceRef::from([128; 27].as_slice()),
+			luxel_size: 16.0,
+			limits: SliceRef::from([].as_slice()),
+		};
+
+		let bsp: Bsp42 = build_bsp42(&args).unwrap();
+		assert_eq!(bsp.entities, b"{\n}\n\0");
+		assert_eq!(bsp.planes.len(), 2);
+		assert_eq!(bsp.planes[1].kind, 2);
+		assert_eq!(bsp.textures, vec![String::from("generic/floor01")]);
+
+		// Each face has its own vertices, which take the normal of its plane,
+		// and is split into a fan of triangles.
+		assert_eq!(bsp.vertices.len(), 7);
+		assert_eq!(bsp.normals, vec![[0.0, 0.0, -1.0]; 7]);
+		assert_eq!(bsp.indices, vec![0, 1, 2, 0, 2, 3, 0, 1, 2]);
+		assert_eq!(
+			(bsp.faces[1].first_vertex, bsp.faces[1].vertex_count),
+			(4, 3)
+		);
+		assert_eq!((bsp.faces[1].first_index, bsp.faces[1].index_count), (6, 3));
+		assert_eq!(bsp.faces[0].plane, 1);
+
+		// Only the lit face has a lightmap, and the two faces share their
+		// texture projection.
+		assert_eq!(
+			bsp.materials,
+			vec![
+				String::from(LIGHTMAPPED_MATERIAL),
+				String::from(FULLBRIGHT_MATERIAL)
+			]
+		);
+		assert_eq!((bsp.faces[0].material, bsp.faces[1].material), (0, 1));
+		assert_eq!(bsp.faces[0].texture_matrix, bsp.faces[1].texture_matrix);
+		assert_eq!(bsp.faces[1].lightmap_matrix, -1);
+		assert_eq!(
+			bsp.texture_matrices[bsp.faces[0].lightmap_matrix as usize].vecs,
+			[[0.0625, 0.0, 0.0, -0.5], [0.0, -0.0625, 0.0, 2.0]]
+		);
+
+		// Leaves follow leaf 0, with the solid leaf keeping its brush, and
+		// only the empty leaf being visible.
+		assert_eq!(bsp.leaves.len(), 3);
+		assert_eq!(bsp.leaves[1].contents, CONTENTS_SOLID);
+		assert_eq!(bsp.leaves[1].leaf_brush_count, 1);
+		assert_eq!(bsp.leaves[2].contents, CONTENTS_EMPTY);
+		assert_eq!(bsp.leaves[2].leaf_face_count, 2);
+		assert_eq!(bsp.leaf_brushes, vec![0]);
+		assert_eq!(bsp.nodes[0].children, [-3, -2]);
+		assert_eq!(
+			(bsp.leaves[1].vis_offset, bsp.leaves[2].vis_offset),
+			(-1, 0)
+		);
+		assert_eq!(bsp.visibility, vec![0b10]);
+
+		assert_eq!(bsp.brushes[0].contents, CONTENTS_SOLID);
+		assert_eq!(
+			bsp.brush_sides,
+			vec![
+				BrushSide { plane: 0, face: -1 },
+				BrushSide { plane: 1, face: 0 }
+			]
+		);
+
+		assert_eq!(bsp.models[0].head_node, 0);
+		assert_eq!(
+			(
+				bsp.models[0].first_leaf,
+				bsp.models[0].leaf_count,
+				bsp.models[0].vis_leaves
+			),
+			(1, 2, 2)
+		);
+		assert_eq!(bsp.models[0].brush_count, 1);
+	}
+}
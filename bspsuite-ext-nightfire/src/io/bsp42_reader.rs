@@ -0,0 +1,186 @@
+// Test-only decoder for version 42 files, used to check that the writer
+// produces what it was given.
+
+use crate::io::bsp42_writer::{HEADER_SIZE, LUMP_COUNT};
+use crate::model::bsp42::{
+	BSP42_VERSION, Brush, BrushSide, Bsp42, Face, Leaf, Model, NAME_LENGTH, Node, Plane,
+	TextureMatrix,
+};
+
+// Reads little endian values from the start of a record.
+struct RecordReader<'l>
+{
+	bytes: &'l [u8],
+	offset: usize,
+}
+
+impl<'l> RecordReader<'l>
+{
+	fn new(bytes: &'l [u8]) -> Self
+	{
+		return Self {
+			bytes: bytes,
+			offset: 0,
+		};
+	}
+
+	fn take<const N: usize>(&mut self) -> [u8; N]
+	{
+		let bytes: [u8; N] = self.bytes[self.offset..self.offset + N].try_into().unwrap();
+		self.offset += N;
+		return bytes;
+	}
+
+	fn i32(&mut self) -> i32
+	{
+		return i32::from_le_bytes(self.take());
+	}
+
+	fn u32(&mut self) -> u32
+	{
+		return u32::from_le_bytes(self.take());
+	}
+
+	fn f32s<const N: usize>(&mut self) -> [f32; N]
+	{
+		return std::array::from_fn(|_| f32::from_le_bytes(self.take()));
+	}
+}
+
+/// Parses a file written by [crate::io::bsp42_writer::encode_bsp42].
+pub fn decode_bsp42(bytes: &[u8]) -> Result<Bsp42, String>
+{
+	if bytes.len() < HEADER_SIZE
+	{
+		return Err(format!(
+			"File is {} bytes, which is too short for the header",
+			bytes.len()
+		));
+	}
+
+	let mut header: RecordReader = RecordReader::new(bytes);
+	let version: i32 = header.i32();
+
+	if version != BSP42_VERSION
+	{
+		return Err(format!("Unexpected version {version}"));
+	}
+
+	let mut lumps: Vec<&[u8]> = Vec::with_capacity(LUMP_COUNT);
+
+	for index in 0..LUMP_COUNT
+	{
+		let offset: usize = header.i32() as usize;
+		let length: usize = header.i32() as usize;
+
+		let Some(lump) = bytes.get(offset..offset + length)
+		else
+		{
+			return Err(format!("Lump {index} lies outside of the file"));
+		};
+
+		lumps.push(lump);
+	}
+
+	return Ok(Bsp42 {
+		entities: lumps[0].to_vec(),
+		planes: decode_records(lumps[1], 20, |reader| Plane {
+			normal: reader.f32s(),
+			distance: reader.f32s::<1>()[0],
+			kind: reader.i32(),
+		})?,
+		textures: decode_records(lumps[2], NAME_LENGTH, decode_name)?,
+		materials: decode_records(lumps[3], NAME_LENGTH, decode_name)?,
+		vertices: decode_records(lumps[4], 12, |reader| reader.f32s())?,
+		normals: decode_records(lumps[5], 12, |reader| reader.f32s())?,
+		indices: decode_records(lumps[6], 4, |reader| reader.u32())?,
+		visibility: lumps[7].to_vec(),
+		nodes: decode_records(lumps[8], 36, |reader| Node {
+			plane: reader.i32(),
+			children: [reader.i32(), reader.i32()],
+			mins: reader.f32s(),
+			maxs: reader.f32s(),
+		})?,
+		faces: decode_records(lumps[9], 48, |reader| Face {
+			plane: reader.i32(),
+			first_vertex: reader.i32(),
+			vertex_count: reader.i32(),
+			first_index: reader.i32(),
+			index_count: reader.i32(),
+			flags: reader.i32(),
+			texture: reader.i32(),
+			material: reader.i32(),
+			texture_matrix: reader.i32(),
+			lightmap_matrix: reader.i32(),
+			lighting_offset: reader.i32(),
+			styles: reader.take(),
+		})?,
+		lighting: lumps[10].to_vec(),
+		leaves: decode_records(lumps[11], 48, |reader| Leaf {
+			contents: reader.i32(),
+			vis_offset: reader.i32(),
+			mins: reader.f32s(),
+			maxs: reader.f32s(),
+			first_leaf_face: reader.u32(),
+			leaf_face_count: reader.u32(),
+			first_leaf_brush: reader.u32(),
+			leaf_brush_count: reader.u32(),
+		})?,
+		leaf_faces: decode_records(lumps[12], 4, |reader| reader.u32())?,
+		leaf_brushes: decode_records(lumps[13], 4, |reader| reader.u32())?,
+		models: decode_records(lumps[14], 56, |reader| Model {
+			mins: reader.f32s(),
+			maxs: reader.f32s(),
+			head_node: reader.i32(),
+			vis_leaves: reader.i32(),
+			first_leaf: reader.i32(),
+			leaf_count: reader.i32(),
+			first_brush: reader.i32(),
+			brush_count: reader.i32(),
+			first_face: reader.i32(),
+			face_count: reader.i32(),
+		})?,
+		brushes: decode_records(lumps[15], 12, |reader| Brush {
+			contents: reader.i32(),
+			first_side: reader.u32(),
+			side_count: reader.u32(),
+		})?,
+		brush_sides: decode_records(lumps[16], 8, |reader| BrushSide {
+			plane: reader.i32(),
+			face: reader.i32(),
+		})?,
+		texture_matrices: decode_records(lumps[17], 32, |reader| TextureMatrix {
+			vecs: [reader.f32s(), reader.f32s()],
+		})?,
+	});
+}
+
+fn decode_records<T>(
+	lump: &[u8],
+	record_size: usize,
+	decode: impl Fn(&mut RecordReader) -> T,
+) -> Result<Vec<T>, String>
+{
+	if !lump.len().is_multiple_of(record_size)
+	{
+		return Err(format!(
+			"Lump of {} bytes does not hold a whole number of {record_size} byte records",
+			lump.len()
+		));
+	}
+
+	return Ok(lump
+		.chunks_exact(record_size)
+		.map(|record| decode(&mut RecordReader::new(record)))
+		.collect());
+}
+
+fn decode_name(reader: &mut RecordReader) -> String
+{
+	let bytes: [u8; NAME_LENGTH] = reader.take();
+	let length: usize = bytes
+		.iter()
+		.position(|byte| *byte == 0)
+		.unwrap_or(NAME_LENGTH);
+	return String::from_utf8_lossy(&bytes[..length]).to_string();
+}
@@ -0,0 +1,355 @@
+use crate::model::bsp42::{
+	BSP42_VERSION, Brush, BrushSide, Bsp42, Face, Leaf, Model, NAME_LENGTH, Node, Plane,
+	TextureMatrix,
+};
+
+/// Number of lumps in a version 42 file.
+pub const LUMP_COUNT: usize = 18;
+
+/// Size of the file header: the version, followed by the offset and length of
+/// each lump.
+pub const HEADER_SIZE: usize = 4 + LUMP_COUNT * 8;
+
+/// Serialises the file. Lumps are written in the order in which they are
+/// listed in the header, each beginning on a four byte boundary.
+pub fn encode_bsp42(bsp: &Bsp42) -> Vec<u8>
+{
+	// In the order of the header.
+	let lumps: [Vec<u8>; LUMP_COUNT] = [
+		bsp.entities.clone(),
+		encode_records(&bsp.planes, encode_plane),
+		encode_records(&bsp.textures, |name, out| put_name(out, name)),
+		encode_records(&bsp.materials, |name, out| put_name(out, name)),
+		encode_records(&bsp.vertices, |vertex, out| put_f32s(out, vertex)),
+		encode_records(&bsp.normals, |normal, out| put_f32s(out, normal)),
+		encode_records(&bsp.indices, |index, out| put_u32(out, *index)),
+		bsp.visibility.clone(),
+		encode_records(&bsp.nodes, encode_node),
+		encode_records(&bsp.faces, encode_face),
+		bsp.lighting.clone(),
+		encode_records(&bsp.leaves, encode_leaf),
+		encode_records(&bsp.leaf_faces, |face, out| put_u32(out, *face)),
+		encode_records(&bsp.leaf_brushes, |brush, out| put_u32(out, *brush)),
+		encode_records(&bsp.models, encode_model),
+		encode_records(&bsp.brushes, encode_brush),
+		encode_records(&bsp.brush_sides, encode_brush_side),
+		encode_records(&bsp.texture_matrices, encode_texture_matrix),
+	];
+
+	let mut header: Vec<u8> = Vec::with_capacity(HEADER_SIZE);
+	let mut body: Vec<u8> = Vec::new();
+	put_i32(&mut header, BSP42_VERSION);
+
+	for lump in &lumps
+	{
+		put_i32(&mut header, (HEADER_SIZE + body.len()) as i32);
+		put_i32(&mut header, lump.len() as i32);
+
+		body.extend_from_slice(lump);
+		body.resize(body.len().next_multiple_of(4), 0);
+	}
+
+	header.append(&mut body);
+	return header;
+}
+
+/// Compresses a row of visibility bits, by replacing each run of zero bytes
+/// with a zero followed by the length of the run.
+pub fn compress_vis_row(row: &[u8]) -> Vec<u8>
+{
+	let mut out: Vec<u8> = Vec::new();
+	let mut index: usize = 0;
+
+	while index < row.len()
+	{
+		out.push(row[index]);
+
+		if row[index] != 0
+		{
+			index += 1;
+			continue;
+		}
+
+		let run: usize = row[index..]
+			.iter()
+			.take(255)
+			.take_while(|byte| **byte == 0)
+			.count();
+
+		out.push(run as u8);
+		index += run;
+	}
+
+	return out;
+}
+
+fn encode_records<T>(records: &[T], encode: impl Fn(&T, &mut Vec<u8>)) -> Vec<u8>
+{
+	let mut out: Vec<u8> = Vec::new();
+	records.iter().for_each(|record| encode(record, &mut out));
+	return out;
+}
+
+fn encode_plane(plane: &Plane, out: &mut Vec<u8>)
+{
+	put_f32s(out, &plane.normal);
+	put_f32s(out, &[plane.distance]);
+	put_i32(out, plane.kind);
+}
+
+fn encode_node(node: &Node, out: &mut Vec<u8>)
+{
+	put_i32(out, node.plane);
+	node.children.iter().for_each(|child| put_i32(out, *child));
+	put_f32s(out, &node.mins);
+	put_f32s(out, &node.maxs);
+}
+
+fn encode_face(face: &Face, out: &mut Vec<u8>)
+{
+	[
+		face.plane,
+		face.first_vertex,
+		face.vertex_count,
+		face.first_index,
+		face.index_count,
+		face.flags,
+		face.texture,
+		face.material,
+		face.texture_matrix,
+		face.lightmap_matrix,
+		face.lighting_offset,
+	]
+	.iter()
+	.for_each(|value| put_i32(out, *value));
+
+	out.extend_from_slice(&face.styles);
+}
+
+fn encode_leaf(leaf: &Leaf, out: &mut Vec<u8>)
+{
+	put_i32(out, leaf.contents);
+	put_i32(out, leaf.vis_offset);
+	put_f32s(out, &leaf.mins);
+	put_f32s(out, &leaf.maxs);
+	put_u32(out, leaf.first_leaf_face);
+	put_u32(out, leaf.leaf_face_count);
+	put_u32(out, leaf.first_leaf_brush);
+	put_u32(out, leaf.leaf_brush_count);
+}
+
+fn encode_model(model: &Model, out: &mut Vec<u8>)
+{
+	put_f32s(out, &model.mins);
+	put_f32s(out, &model.maxs);
+
+	[
+		model.head_node,
+		model.vis_leaves,
+		model.first_leaf,
+		model.leaf_count,
+		model.first_brush,
+		model.brush_count,
+		model.first_face,
+		model.face_count,
+	]
+	.iter()
+	.for_each(|value| put_i32(out, *value));
+}
+
+fn encode_brush(brush: &Brush, out: &mut Vec<u8>)
+{
+	put_i32(out, brush.contents);
+	put_u32(out, brush.first_side);
+	put_u32(out, brush.side_count);
+}
+
+fn encode_brush_side(side: &BrushSide, out: &mut Vec<u8>)
+{
+	put_i32(out, side.plane);
+	put_i32(out, side.face);
+}
+
+fn encode_texture_matrix(matrix: &TextureMatrix, out: &mut Vec<u8>)
+{
+	matrix.vecs.iter().for_each(|vec| put_f32s(out, vec));
+}
+
+// Names are padded with NULs to a fixed length. Callers must make sure that
+// the name leaves room for at least one NUL.
+fn put_name(out: &mut Vec<u8>, name: &str)
+{
+	let mut bytes: [u8; NAME_LENGTH] = [0; NAME_LENGTH];
+	let length: usize = name.len().min(NAME_LENGTH - 1);
+	bytes[..length].copy_from_slice(&name.as_bytes()[..length]);
+	out.extend_from_slice(&bytes);
+}
+
+fn put_i32(out: &mut Vec<u8>, value: i32)
+{
+	out.extend_from_slice(&value.to_le_bytes());
+}
+
+fn put_u32(out: &mut Vec<u8>, value: u32)
+{
+	out.extend_from_slice(&value.to_le_bytes());
+}
+
+fn put_f32s(out: &mut Vec<u8>, values: &[f32])
+{
+	values
+		.iter()
+		.for_each(|value| out.extend_from_slice(&value.to_le_bytes()));
+}
+
+#[cfg(test)]
+mod tests
+{
+	use super::*;
+	use crate::io::bsp42_reader::decode_bsp42;
+	use crate::model::bsp42::{CONTENTS_EMPTY, CONTENTS_SOLID, CONTENTS_WATER};
+
+	// A single room, with one face on its floor and a brush below it.
+	fn sample_bsp() -> Bsp42
+	{
+		return Bsp42 {
+			entities: b"{\n\"classname\" \"worldspawn\"\n}\n\0".to_vec(),
+			planes: vec![
+				Plane {
+					normal: [0.0, 0.0, 1.0],
+					distance: 0.0,
+					kind: 2,
+				},
+				Plane {
+					normal: [0.0, 0.0, -1.0],
+					distance: 0.0,
+					kind: 2,
+				},
+			],
+			textures: vec![String::from("generic/floor01")],
+			materials: vec![String::from("wld_lightmap")],
+			vertices: vec![
+				[0.0, 0.0, 0.0],
+				[0.0, 64.0, 0.0],
+				[64.0, 64.0, 0.0],
+				[64.0, 0.0, 0.0],
+			],
+			normals: vec![[0.0, 0.0, 1.0]; 4],
+			indices: vec![0, 1, 2, 0, 2, 3],
+			visibility: vec![1],
+			nodes: vec![Node {
+				plane: 0,
+				children: [-2, -1],
+				mins: [0.0; 3],
+				maxs: [64.0, 64.0, 0.0],
+			}],
+			faces: vec![Face {
+				plane: 0,
+				first_vertex: 0,
+				vertex_count: 4,
+				first_index: 0,
+				index_count: 6,
+				flags: 0,
+				texture: 0,
+				material: 0,
+				texture_matrix: 0,
+				lightmap_matrix: 1,
+				lighting_offset: 0,
+				styles: [0, 255, 255, 255],
+			}],
+			lighting: vec![255; 5 * 5 * 3],
+			leaves: vec![
+				Leaf {
+					contents: CONTENTS_SOLID,
+					vis_offset: -1,
+					mins: [0.0; 3],
+					maxs: [0.0; 3],
+					first_leaf_face: 0,
+					leaf_face_count: 0,
+					first_leaf_brush: 0,
+					leaf_brush_count: 0,
+				},
+				Leaf {
+					contents: CONTENTS_EMPTY,
+					vis_offset: 0,
+					mins: [0.0; 3],
+					maxs: [64.0; 3],
+					first_leaf_face: 0,
+					leaf_face_count: 1,
+					first_leaf_brush: 0,
+					leaf_brush_count: 1,
+				},
+			],
+			leaf_faces: vec![0],
+			leaf_brushes: vec![0],
+			models: vec![Model {
+				mins: [0.0; 3],
+				maxs: [64.0, 64.0, 0.0],
+				head_node: 0,
+				vis_leaves: 1,
+				first_leaf: 1,
+				leaf_count: 1,
+				first_brush: 0,
+				brush_count: 1,
+				first_face: 0,
+				face_count: 1,
+			}],
+			brushes: vec![Brush {
+				contents: CONTENTS_WATER,
+				first_side: 0,
+				side_count: 1,
+			}],
+			brush_sides: vec![BrushSide { plane: 0, face: 0 }],
+			texture_matrices: vec![
+				TextureMatrix {
+					vecs: [[1.0, 0.0, 0.0, 8.0], [0.0, -1.0, 0.0, 0.0]],
+				},
+				TextureMatrix {
+					vecs: [[0.0625, 0.0, 0.0, 0.5], [0.0, -0.0625, 0.0, 4.0]],
+				},
+			],
+		};
+	}
+
+	#[test]
+	fn compress_runs_of_zeroes()
+	{
+		assert_eq!(
+			compress_vis_row(&[1, 0, 0, 0, 2, 0]),
+			vec![1, 0, 3, 2, 0, 1]
+		);
+		assert_eq!(compress_vis_row(&[0; 300]), vec![0, 255, 0, 45]);
+	}
+
+	#[test]
+	fn round_trip_every_lump()
+	{
+		let bsp: Bsp42 = sample_bsp();
+		let bytes: Vec<u8> = encode_bsp42(&bsp);
+
+		assert_eq!(i32::from_le_bytes(bytes[0..4].try_into().unwrap()), 42);
+		assert_eq!(decode_bsp42(&bytes), Ok(bsp));
+	}
+
+	#[test]
+	fn write_fixed_size_records()
+	{
+		let bsp: Bsp42 = sample_bsp();
+		let bytes: Vec<u8> = encode_bsp42(&bsp);
+		let lump_length = |index: usize| -> i32 {
+			return i32::from_le_bytes(bytes[8 + index * 8..12 + index * 8].try_into().unwrap());
+		};
+
+		// Planes, texture names, nodes, faces, leaves, models, brushes, brush
+		// sides and texture matrices.
+		assert_eq!(lump_length(1), 2 * 20);
+		assert_eq!(lump_length(2), 64);
+		assert_eq!(lump_length(8), 36);
+		assert_eq!(lump_length(9), 48);
+		assert_eq!(lump_length(11), 2 * 48);
+		assert_eq!(lump_length(14), 56);
+		assert_eq!(lump_length(15), 12);
+		assert_eq!(lump_length(16), 8);
+		assert_eq!(lump_length(17), 2 * 32);
+	}
+}
@@ -0,0 +1,284 @@
+use crate::model::image::Image;
+use png::{ColorType, Decoder, Reader, Transformations};
+use std::io::Cursor;
+use std::path::Path;
+
+// Size of the header at the start of a TGA file.
+const TGA_HEADER_SIZE: usize = 18;
+
+// TGA image types that are supported. Colour mapped images are not, since
+// tools do not save textures that way.
+const TGA_TRUE_COLOR: u8 = 2;
+const TGA_GRAYSCALE: u8 = 3;
+const TGA_RLE_TRUE_COLOR: u8 = 10;
+const TGA_RLE_GRAYSCALE: u8 = 11;
+
+// Bit of the TGA image descriptor that is set if rows are stored from the top
+// rather than from the bottom.
+const TGA_TOP_TO_BOTTOM: u8 = 0x20;
+
+/// Decodes a TGA or PNG image, chosen by the extension of its path.
+pub fn read_image(path: &Path) -> Result<Image, String>
+{
+	let extension: String = path
+		.extension()
+		.map(|extension| extension.to_string_lossy().to_ascii_lowercase())
+		.unwrap_or_default();
+
+	let bytes: Vec<u8> = std::fs::read(path).map_err(|err| err.to_string())?;
+
+	return match extension.as_str()
+	{
+		"tga" => read_tga(&bytes),
+		"png" => read_png(&bytes),
+		_ => Err(format!("Images of type .{extension} are not supported")),
+	};
+}
+
+/// Decodes an uncompressed or run-length encoded TGA image, which may be
+/// greyscale, or true colour with or without alpha.
+pub fn read_tga(bytes: &[u8]) -> Result<Image, String>
+{
+	let header: &[u8] = bytes.get(..TGA_HEADER_SIZE).ok_or_else(|| {
+		format!(
+			"File is {} bytes, which is too short for the header",
+			bytes.len()
+		)
+	})?;
+
+	let id_length: usize = header[0] as usize;
+	let has_color_map: bool = header[1] != 0;
+	let image_type: u8 = header[2];
+	let color_map_length: usize = u16::from_le_bytes([header[5], header[6]]) as usize;
+	let color_map_entry_bits: usize = header[7] as usize;
+	let width: u32 = u16::from_le_bytes([header[12], header[13]]) as u32;
+	let height: u32 = u16::from_le_bytes([header[14], header[15]]) as u32;
+	let depth: u8 = header[16];
+	let top_to_bottom: bool = header[17] & TGA_TOP_TO_BOTTOM != 0;
+
+	let (grayscale, run_length_encoded): (bool, bool) = match image_type
+	{
+		TGA_TRUE_COLOR => (false, false),
+		TGA_GRAYSCALE => (true, false),
+		TGA_RLE_TRUE_COLOR => (false, true),
+		TGA_RLE_GRAYSCALE => (true, true),
+		_ => return Err(format!("TGA image type {image_type} is not supported")),
+	};
+
+	let bytes_per_pixel: usize = match (grayscale, depth)
+	{
+		(true, 8) => 1,
+		(false, 24) => 3,
+		(false, 32) => 4,
+		_ =>
+		{
+			return Err(format!(
+				"TGA images of {depth} bits per pixel are not supported"
+			));
+		}
+	};
+
+	let color_map_size: usize = match has_color_map
+	{
+		true => color_map_length * color_map_entry_bits.div_ceil(8),
+		false => 0,
+	};
+
+	let data: &[u8] = bytes
+		.get(TGA_HEADER_SIZE + id_length + color_map_size..)
+		.ok_or_else(|| String::from("Image data lies outside of the file"))?;
+
+	let row_size: usize = width as usize * bytes_per_pixel;
+	let stored: Vec<u8> = match run_length_encoded
+	{
+		true => decode_tga_runs(data, row_size * height as usize, bytes_per_pixel)?,
+		false => data
+			.get(..row_size * height as usize)
+			.ok_or_else(|| format!("Pixels of {width}x{height} image lie outside of the file"))?
+			.to_vec(),
+	};
+
+	let mut pixels: Vec<u8> = Vec::with_capacity(width as usize * height as usize * 4);
+
+	for row in 0..height as usize
+	{
+		let stored_row: usize = match top_to_bottom
+		{
+			true => row,
+			false => height as usize - 1 - row,
+		};
+
+		for pixel in
+			stored[stored_row * row_size..(stored_row + 1) * row_size].chunks_exact(bytes_per_pixel)
+		{
+			// Colours are stored in BGR order.
+			let rgba: [u8; 4] = match *pixel
+			{
+				[b, g, r, a] => [r, g, b, a],
+				[b, g, r] => [r, g, b, 255],
+				_ => [pixel[0], pixel[0], pixel[0], 255],
+			};
+
+			pixels.extend_from_slice(&rgba);
+		}
+	}
+
+	return Ok(Image {
+		width: width,
+		height: height,
+		pixels: pixels,
+	});
+}
+
+/// Decodes a PNG image of any colour type and bit depth.
+pub fn read_png(bytes: &[u8]) -> Result<Image, String>
+{
+	let mut decoder: Decoder<Cursor<&[u8]>> = Decoder::new(Cursor::new(bytes));
+	decoder.set_transformations(Transformations::EXPAND | Transformations::STRIP_16);
+
+	let mut reader: Reader<Cursor<&[u8]>> = decoder.read_info().map_err(|err| err.to_string())?;
+	let buffer_size: usize = reader
+		.output_buffer_size()
+		.ok_or_else(|| String::from("Image is too large to decode"))?;
+
+	let mut buffer: Vec<u8> = vec![0; buffer_size];
+	let info: png::OutputInfo = reader
+		.next_frame(&mut buffer)
+		.map_err(|err| err.to_string())?;
+	buffer.truncate(info.buffer_size());
+
+	let pixels: Vec<u8> = match info.color_type
+	{
+		ColorType::Rgba => buffer,
+		ColorType::Rgb => buffer
+			.chunks_exact(3)
+			.flat_map(|pixel| [pixel[0], pixel[1], pixel[2], 255])
+			.collect(),
+		ColorType::GrayscaleAlpha => buffer
+			.chunks_exact(2)
+			.flat_map(|pixel| [pixel[0], pixel[0], pixel[0], pixel[1]])
+			.collect(),
+		ColorType::Grayscale => buffer
+			.iter()
+			.flat_map(|value| [*value, *value, *value, 255])
+			.collect(),
+		ColorType::Indexed => return Err(String::from("Palette of image could not be expanded")),
+	};
+
+	return Ok(Image {
+		width: info.width,
+		height: info.height,
+		pixels: pixels,
+	});
+}
+
+// Expands the packets of a run-length encoded TGA image. Each packet is either
+// one pixel repeated, or a run of pixels stored as they are.
+fn decode_tga_runs(data: &[u8], size: usize, bytes_per_pixel: usize) -> Result<Vec<u8>, String>
+{
+	let mut stored: Vec<u8> = Vec::new();
+	let mut offset: usize = 0;
+
+	while stored.len() < size
+	{
+		let Some(header) = data.get(offset)
+		else
+		{
+			return Err(String::from("Run-length encoded pixels end early"));
+		};
+
+		let count: usize = (header & 0x7f) as usize + 1;
+		let repeated: bool = header & 0x80 != 0;
+		let packet_size: usize = match repeated
+		{
+			true => bytes_per_pixel,
+			false => count * bytes_per_pixel,
+		};
+
+		let Some(packet) = data.get(offset + 1..offset + 1 + packet_size)
+		else
+		{
+			return Err(String::from("Run-length encoded pixels end early"));
+		};
+
+		match repeated
+		{
+			true => (0..count).for_each(|_| stored.extend_from_slice(packet)),
+			false => stored.extend_from_slice(packet),
+		}
+
+		offset += 1 + packet_size;
+	}
+
+	stored.truncate(size);
+	return Ok(stored);
+}
+
+#[cfg(test)]
+mod tests
+{
+	use super::*;
+
+	fn tga_header(image_type: u8, width: u16, height: u16, depth: u8, descriptor: u8) -> Vec<u8>
+	{
+		let mut header: Vec<u8> = vec![0; TGA_HEADER_SIZE];
+		header[2] = image_type;
+		header[12..14].copy_from_slice(&width.to_le_bytes());
+		header[14..16].copy_from_slice(&height.to_le_bytes());
+		header[16] = depth;
+		header[17] = descriptor;
+		return header;
+	}
+
+	#[test]
+	fn read_tga_images()
+	{
+		// Rows are stored from the bottom, with colours in BGR order.
+		let mut bytes: Vec<u8> = tga_header(TGA_TRUE_COLOR, 2, 2, 24, 0);
+		bytes.extend_from_slice(&[0, 0, 255, 0, 255, 0, 255, 0, 0, 255, 255, 255]);
+
+		let image: Image = read_tga(&bytes).unwrap();
+		assert_eq!((image.width, image.height), (2, 2));
+		assert_eq!(
+			image.pixels,
+			vec![
+				0, 0, 255, 255, 255, 255, 255, 255, 255, 0, 0, 255, 0, 255, 0, 255
+			]
+		);
+
+		// A run of three transparent pixels, then one stored as it is.
+		let mut bytes: Vec<u8> = tga_header(TGA_RLE_TRUE_COLOR, 4, 1, 32, TGA_TOP_TO_BOTTOM);
+		bytes.extend_from_slice(&[0x82, 0, 0, 0, 0, 0x00, 10, 20, 30, 255]);
+
+		let image: Image = read_tga(&bytes).unwrap();
+		assert_eq!(&image.pixels[12..], &[30, 20, 10, 255]);
+		assert_eq!(image.average_color(), Some([30.0, 20.0, 10.0]));
+
+		// The same run is cut short.
+		bytes.truncate(bytes.len() - 1);
+		assert!(read_tga(&bytes).is_err());
+
+		let bytes: Vec<u8> = tga_header(1, 1, 1, 8, 0);
+		assert!(read_tga(&bytes).is_err());
+	}
+
+	#[test]
+	fn read_png_images()
+	{
+		let mut bytes: Vec<u8> = Vec::new();
+		let mut encoder: png::Encoder<&mut Vec<u8>> = png::Encoder::new(&mut bytes, 2, 1);
+		encoder.set_color(ColorType::Rgb);
+		encoder.set_depth(png::BitDepth::Eight);
+
+		let mut writer: png::Writer<&mut Vec<u8>> = encoder.write_header().unwrap();
+		writer.write_image_data(&[255, 0, 0, 0, 0, 255]).unwrap();
+		writer.finish().unwrap();
+
+		let image: Image = read_png(&bytes).unwrap();
+		assert_eq!((image.width, image.height), (2, 1));
+		assert_eq!(image.pixels, vec![255, 0, 0, 255, 0, 0, 255, 255]);
+		assert_eq!(image.average_color(), Some([127.5, 0.0, 127.5]));
+
+		assert!(read_png(&bytes[..bytes.len() / 2]).is_err());
+	}
+}
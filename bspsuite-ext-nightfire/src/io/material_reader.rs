@@ -0,0 +1,54 @@
+/// Finds the image drawn by a material definition, which is the value of its
+/// first `texture` key. Each line holds a key and a value separated by
+/// whitespace, and values may be quoted. Braces, blank lines and `//`
+/// comments are skipped. Returns [`None`] if the material does not name an
+/// image.
+pub fn read_material_texture(text: &str) -> Option<String>
+{
+	for line in text.lines()
+	{
+		let line: &str = line.split("//").next().unwrap_or_default().trim();
+		let Some((key, value)) = line.split_once(char::is_whitespace)
+		else
+		{
+			continue;
+		};
+
+		if !key.eq_ignore_ascii_case("texture")
+		{
+			continue;
+		}
+
+		let value: &str = value.trim().trim_matches('"');
+
+		if !value.is_empty()
+		{
+			return Some(value.replace('\\', "/"));
+		}
+	}
+
+	return None;
+}
+
+#[cfg(test)]
+mod tests
+{
+	use super::*;
+
+	#[test]
+	fn read_texture_key()
+	{
+		let text: &str = "// Brick wall\n\
+			{\n\
+			\tsurface concrete\n\
+			\tTexture \"walls\\brick01.tga\" // Diffuse\n\
+			\ttexture walls/brick02\n\
+			}\n";
+
+		assert_eq!(
+			read_material_texture(text),
+			Some(String::from("walls/brick01.tga"))
+		);
+		assert_eq!(read_material_texture("{\n\tsurface metal\n}\n"), None);
+	}
+}
@@ -0,0 +1,7 @@
+pub mod bsp42_builder;
+#[cfg(test)]
+pub mod bsp42_reader;
+pub mod bsp42_writer;
+pub mod image_reader;
+pub mod material_reader;
+pub mod texture_search;
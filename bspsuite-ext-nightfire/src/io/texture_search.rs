@@ -0,0 +1,169 @@
+use crate::io::image_reader::read_image;
+use crate::io::material_reader::read_material_texture;
+use crate::model::image::Image;
+use log::warn;
+use std::path::{Path, PathBuf};
+
+// Extensions of the loose images that textures are read from, in the order in
+// which they are looked for.
+const IMAGE_EXTENSIONS: [&str; 2] = ["tga", "png"];
+
+/// Directories to look for files in: the directory of the map, followed by
+/// each of the search paths relative to it.
+pub fn search_roots(map_path: &str, search_paths: &[String]) -> Vec<PathBuf>
+{
+	let map_dir: &Path = Path::new(map_path).parent().unwrap_or(Path::new(""));
+
+	return std::iter::once(map_dir.to_path_buf())
+		.chain(
+			search_paths
+				.iter()
+				.map(|search_path| map_dir.join(search_path)),
+		)
+		.collect();
+}
+
+/// Reads the image named by the material's definition. Definitions or images
+/// that cannot be read are reported and skipped, so that a provider of lower
+/// priority may be asked for the material instead.
+pub fn material_image(material: &str, roots: &[PathBuf]) -> Option<Image>
+{
+	let path: PathBuf = find_file(material, &["mat"], roots)?;
+
+	let text: String = match std::fs::read_to_string(&path)
+	{
+		Ok(text) => text,
+		Err(err) =>
+		{
+			warn!("Could not read material {}. {err}", path.to_string_lossy());
+			return None;
+		}
+	};
+
+	let Some(texture) = read_material_texture(&text)
+	else
+	{
+		warn!(
+			"Material {} does not name a texture",
+			path.to_string_lossy()
+		);
+		return None;
+	};
+
+	let image: Option<Image> = loose_image(&texture, roots);
+
+	if image.is_none()
+	{
+		warn!(
+			"Could not find texture {texture} named by material {}",
+			path.to_string_lossy()
+		);
+	}
+
+	return image;
+}
+
+/// Reads the loose TGA or PNG image with the given name. Images that cannot be
+/// read are reported and skipped.
+pub fn loose_image(name: &str, roots: &[PathBuf]) -> Option<Image>
+{
+	let path: PathBuf = find_file(name, &IMAGE_EXTENSIONS, roots)?;
+
+	return match read_image(&path)
+	{
+		Ok(image) => Some(image),
+		Err(err) =>
+		{
+			warn!("Could not read image {}. {err}", path.to_string_lossy());
+			None
+		}
+	};
+}
+
+// Finds the first file under any of the roots that is named after the given
+// name with one of the extensions, or with no change if the name already ends
+// in one of them. Names are tried as written, then in lower case, since
+// materials are compared without regard to case.
+fn find_file(name: &str, extensions: &[&str], roots: &[PathBuf]) -> Option<PathBuf>
+{
+	let name: String = name.replace('\\', "/").trim_start_matches('/').to_string();
+	let lowercase: String = name.to_ascii_lowercase();
+
+	let has_extension: bool = extensions
+		.iter()
+		.any(|extension| lowercase.ends_with(&format!(".{extension}")));
+
+	let file_names: Vec<String> = match has_extension
+	{
+		true => vec![name, lowercase],
+		false => extensions
+			.iter()
+			.flat_map(|extension| {
+				[
+					format!("{name}.{extension}"),
+					format!("{lowercase}.{extension}"),
+				]
+			})
+			.collect(),
+	};
+
+	return roots
+		.iter()
+		.flat_map(|root| file_names.iter().map(move |file_name| root.join(file_name)))
+		.find(|path| path.is_file());
+}
+
+#[cfg(test)]
+mod tests
+{
+	use super::*;
+
+	// An uncompressed 24-bit TGA image of a single pixel.
+	fn one_pixel_tga(bgr: [u8; 3]) -> Vec<u8>
+	{
+		let mut bytes: Vec<u8> = vec![0, 0, 2, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 0, 1, 0, 24, 0];
+		bytes.extend_from_slice(&bgr);
+		return bytes;
+	}
+
+	#[test]
+	fn find_materials_and_loose_images()
+	{
+		let dir: PathBuf = std::env::temp_dir().join(format!(
+			"bspsuite-nightfire-textures-{}",
+			std::process::id()
+		));
+		let _ = std::fs::remove_dir_all(&dir);
+		std::fs::create_dir_all(dir.join("maps")).unwrap();
+		std::fs::create_dir_all(dir.join("textures/walls")).unwrap();
+
+		std::fs::write(
+			dir.join("textures/brick.mat"),
+			"{\n\ttexture walls/brick01\n}\n",
+		)
+		.unwrap();
+		std::fs::write(dir.join("textures/broken.mat"), "{\n}\n").unwrap();
+		std::fs::write(
+			dir.join("textures/walls/brick01.tga"),
+			one_pixel_tga([0, 0, 200]),
+		)
+		.unwrap();
+		std::fs::write(dir.join("textures/floor.tga"), one_pixel_tga([100, 0, 0])).unwrap();
+		std::fs::write(dir.join("textures/bad.png"), b"not a png").unwrap();
+
+		let roots: Vec<PathBuf> = vec![dir.join("maps"), dir.join("maps").join("../textures")];
+
+		let brick: Image = material_image("BRICK", &roots).unwrap();
+		assert_eq!(brick.average_color(), Some([200.0, 0.0, 0.0]));
+		assert!(material_image("broken", &roots).is_none());
+		assert!(material_image("floor", &roots).is_none());
+
+		let floor: Image = loose_image("Floor", &roots).unwrap();
+		assert_eq!(floor.average_color(), Some([0.0, 0.0, 100.0]));
+		assert!(loose_image("walls\\brick01.tga", &roots).is_some());
+		assert!(loose_image("bad", &roots).is_none());
+		assert!(loose_image("missing", &roots).is_none());
+
+		std::fs::remove_dir_all(&dir).unwrap();
+	}
+}
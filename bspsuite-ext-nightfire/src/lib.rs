@@ -0,0 +1,75 @@
+use bspextifc::log_api::{self, ExtensionLogger};
+use bspextifc::{
+	SliceRef, StringRef, bsp_writer_api, implement_extension_info, probe_api, texture_api,
+};
+use log::error;
+
+mod cinterface;
+mod io;
+mod model;
+
+implement_extension_info!(probe);
+
+// Data that registered callbacks refer to must live as long as the library.
+//
+// Material definitions are asked first, since they may name an image other
+// than the one that shares their material's name. Both are asked before
+// texture archives.
+static TEXTURE_PROVIDERS: [texture_api::TextureProvider; 2] = [
+	texture_api::TextureProvider {
+		name: StringRef::new("material"),
+		priority: 20,
+		find_textures_fn: cinterface::texture_provider::find_materials,
+	},
+	texture_api::TextureProvider {
+		name: StringRef::new("image"),
+		priority: 10,
+		find_textures_fn: cinterface::texture_provider::find_images,
+	},
+];
+
+extern "C" fn probe(api: &mut probe_api::ProbeApi) -> probe_api::ProbeResult
+{
+	if !set_up_logger(api)
+	{
+		return probe_api::ProbeResult::Failure;
+	}
+
+	let bsp_writer_callbacks: bsp_writer_api::BspWriterCallbacks =
+		bsp_writer_api::BspWriterCallbacks {
+			format: StringRef::new("bsp42"),
+			write_bsp_fn: cinterface::bsp_writer::write_bsp,
+
+			// The limits of the Nightfire engine are not documented.
+			limits: SliceRef::new(&[]),
+		};
+
+	if let Err(_) = api
+		.register_bsp_writer_api_callbacks(bsp_writer_api::API_INFO.version, bsp_writer_callbacks)
+	{
+		error!("Failed to register for BSP writer API");
+		return probe_api::ProbeResult::Failure;
+	}
+
+	let texture_callbacks: texture_api::TextureProviderCallbacks =
+		texture_api::TextureProviderCallbacks {
+			providers: SliceRef::new(&TEXTURE_PROVIDERS),
+		};
+
+	if let Err(_) =
+		api.register_texture_api_callbacks(texture_api::API_INFO.version, texture_callbacks)
+	{
+		error!("Failed to register for texture API");
+		return probe_api::ProbeResult::Failure;
+	}
+
+	return probe_api::ProbeResult::Success;
+}
+
+fn set_up_logger(api: &mut probe_api::ProbeApi) -> bool
+{
+	return api
+		.request_log_api(log_api::API_INFO.version)
+		.map(|api| ExtensionLogger::assign_static_logger(api).is_ok())
+		.is_ok();
+}
@@ -0,0 +1,181 @@
+// Structures of a Nightfire BSP file, version 42. These hold values exactly as
+// they are written to the file, so that the writer only has to serialise
+// them.
+//
+// Unlike GoldSrc, faces are stored as triangles over their own vertices, and
+// collision uses the brushes that the map was built from rather than clipping
+// hulls. Materials name the shader used to draw each face.
+
+pub const BSP42_VERSION: i32 = 42;
+
+pub const CONTENTS_EMPTY: i32 = -1;
+pub const CONTENTS_SOLID: i32 = -2;
+pub const CONTENTS_WATER: i32 = -3;
+pub const CONTENTS_SLIME: i32 = -4;
+pub const CONTENTS_LAVA: i32 = -5;
+pub const CONTENTS_SKY: i32 = -6;
+
+/// Number of bytes in a texture or material name, including the terminating
+/// NUL.
+pub const NAME_LENGTH: usize = 64;
+
+/// Material used by faces that have a lightmap.
+pub const LIGHTMAPPED_MATERIAL: &str = "wld_lightmap";
+
+/// Material used by faces that are drawn without lighting.
+pub const FULLBRIGHT_MATERIAL: &str = "wld_fullbright";
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Plane
+{
+	pub normal: [f32; 3],
+	pub distance: f32,
+
+	/// 0 to 2 for planes facing along the X, Y or Z axis, and 3 to 5 for
+	/// other planes, depending on which axis they face closest to.
+	pub kind: i32,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Node
+{
+	pub plane: i32,
+
+	/// Children in front of and behind the plane. Negative values refer to
+	/// leaf -(child + 1).
+	pub children: [i32; 2],
+
+	pub mins: [f32; 3],
+	pub maxs: [f32; 3],
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Face
+{
+	/// Plane that the face lies on and faces along.
+	pub plane: i32,
+
+	pub first_vertex: i32,
+	pub vertex_count: i32,
+
+	/// Range of the indices that make up the face's triangles. Indices are
+	/// relative to the face's first vertex.
+	pub first_index: i32,
+	pub index_count: i32,
+
+	/// Flags are not used by the compiler, and are written as zero.
+	pub flags: i32,
+
+	pub texture: i32,
+	pub material: i32,
+	pub texture_matrix: i32,
+
+	/// Projection from world space to lightmap samples, or -1 if the face has
+	/// no lightmap.
+	pub lightmap_matrix: i32,
+
+	/// Byte offset of the face's lightmap, or -1 if it has none.
+	pub lighting_offset: i32,
+
+	pub styles: [u8; 4],
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Leaf
+{
+	pub contents: i32,
+
+	/// Byte offset of the leaf's compressed visibility row, or -1 if it has
+	/// none.
+	pub vis_offset: i32,
+
+	pub mins: [f32; 3],
+	pub maxs: [f32; 3],
+	pub first_leaf_face: u32,
+	pub leaf_face_count: u32,
+	pub first_leaf_brush: u32,
+	pub leaf_brush_count: u32,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Model
+{
+	pub mins: [f32; 3],
+	pub maxs: [f32; 3],
+
+	/// Root of the model's tree. Negative values refer to leaf -(node + 1).
+	pub head_node: i32,
+
+	/// Number of leaves in the model that have rows of visibility.
+	pub vis_leaves: i32,
+
+	pub first_leaf: i32,
+	pub leaf_count: i32,
+	pub first_brush: i32,
+	pub brush_count: i32,
+	pub first_face: i32,
+	pub face_count: i32,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Brush
+{
+	pub contents: i32,
+	pub first_side: u32,
+	pub side_count: u32,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct BrushSide
+{
+	/// Plane of the side, which faces out of the brush.
+	pub plane: i32,
+
+	/// One of the faces produced by the side, or -1 if none are visible.
+	pub face: i32,
+}
+
+/// Projection from world space to texture or lightmap coordinates.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct TextureMatrix
+{
+	/// U and V axes, each followed by its offset.
+	pub vecs: [[f32; 4]; 2],
+}
+
+/// Every lump of a version 42 BSP file.
+#[derive(Clone, Default, PartialEq, Debug)]
+pub struct Bsp42
+{
+	/// Entity text, including its terminating NUL.
+	pub entities: Vec<u8>,
+
+	/// Planes are stored in pairs, so that the plane at an even index faces
+	/// along the positive direction of its major axis, and the plane at the
+	/// following odd index is its flipped twin.
+	pub planes: Vec<Plane>,
+
+	pub textures: Vec<String>,
+	pub materials: Vec<String>,
+	pub vertices: Vec<[f32; 3]>,
+
+	/// Normal of each vertex.
+	pub normals: Vec<[f32; 3]>,
+
+	pub indices: Vec<u32>,
+	pub visibility: Vec<u8>,
+	pub nodes: Vec<Node>,
+	pub faces: Vec<Face>,
+	pub lighting: Vec<u8>,
+
+	/// Leaf 0 is a solid leaf that belongs to no model, and is not referred to
+	/// by any node.
+	pub leaves: Vec<Leaf>,
+
+	pub leaf_faces: Vec<u32>,
+	pub leaf_brushes: Vec<u32>,
+	pub models: Vec<Model>,
+	pub brushes: Vec<Brush>,
+	pub brush_sides: Vec<BrushSide>,
+	pub texture_matrices: Vec<TextureMatrix>,
+}
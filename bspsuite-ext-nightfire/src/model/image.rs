@@ -0,0 +1,69 @@
+/// A texture image read from a loose file.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Image
+{
+	pub width: u32,
+	pub height: u32,
+
+	/// RGBA colours of the pixels, row by row from the top.
+	pub pixels: Vec<u8>,
+}
+
+impl Image
+{
+	/// Average colour of the image's pixels, with components ranging from 0 to
+	/// 255. Fully transparent pixels are left out. Returns [`None`] if there
+	/// are no pixels that can be seen.
+	pub fn average_color(&self) -> Option<[f64; 3]>
+	{
+		let mut total: [f64; 3] = [0.0; 3];
+		let mut count: usize = 0;
+
+		for pixel in self.pixels.chunks_exact(4)
+		{
+			if pixel[3] == 0
+			{
+				continue;
+			}
+
+			for (sum, component) in total.iter_mut().zip(pixel)
+			{
+				*sum += *component as f64;
+			}
+
+			count += 1;
+		}
+
+		if count == 0
+		{
+			return None;
+		}
+
+		return Some(total.map(|sum| sum / count as f64));
+	}
+}
+
+#[cfg(test)]
+mod tests
+{
+	use super::*;
+
+	#[test]
+	fn average_visible_pixels()
+	{
+		let mut image: Image = Image {
+			width: 2,
+			height: 1,
+			pixels: vec![200, 100, 0, 255, 0, 0, 255, 0],
+		};
+
+		assert_eq!(image.average_color(), Some([200.0, 100.0, 0.0]));
+
+		image.pixels[7] = 128;
+		assert_eq!(image.average_color(), Some([100.0, 50.0, 127.5]));
+
+		image.pixels[3] = 0;
+		image.pixels[7] = 0;
+		assert_eq!(image.average_color(), None);
+	}
+}
@@ -0,0 +1,2 @@
+pub mod bsp42;
+pub mod image;
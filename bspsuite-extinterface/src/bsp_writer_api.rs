@@ -0,0 +1,369 @@
+use super::api_info::ApiInfo;
+use super::slice_ref::SliceRef;
+use super::string_ref::StringRef;
+use std::ffi::c_void;
+use std::marker::PhantomData;
+
+pub const API_INFO: ApiInfo = ApiInfo::new("BspWriterApi", 4);
+
+pub type WriteBspFn = extern "C" fn(&mut BspWriterApi, &WriteBspArgs) -> WriteBspResult;
+
+/// Enum representing the result of asking an extension to write a BSP file.
+#[repr(C)]
+pub enum WriteBspResult
+{
+	/// The file was written successfully.
+	Success,
+
+	/// The file could not be written. The extension should have called
+	/// [`BspWriterApi::set_error`] or [`BspWriterApi::report_exceeded_limit`]
+	/// to describe why.
+	Failure,
+}
+
+/// Callbacks registered by an extension that is able to write compiled maps
+/// to disk.
+#[repr(C)]
+#[derive(Clone)]
+pub struct BspWriterCallbacks
+{
+	/// Name of the format that the extension writes, which game
+	/// configurations refer to when choosing how to write their maps.
+	/// Comparisons against this are case-insensitive.
+	pub format: StringRef<'static>,
+
+	/// Called when a compiled map needs to be written in the format.
+	pub write_bsp_fn: WriteBspFn,
+
+	/// Limits of the format, before any overrides from the game
+	/// configuration. These are used to report how much of each limit a
+	/// compiled map uses.
+	pub limits: SliceRef<'static, FormatLimit>,
+}
+
+/// Value of a limit of the format that a writer enforces, such as
+/// MAX_MAP_PLANES.
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct FormatLimit
+{
+	pub name: StringRef<'static>,
+	pub value: usize,
+}
+
+/// Contents of the space within a leaf or below a clipping node.
+#[repr(C)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum LeafContents
+{
+	Empty,
+	Solid,
+	Water,
+	Slime,
+	Lava,
+	Sky,
+}
+
+/// Reference to a child of a node. Indices refer to the arrays passed in
+/// [`WriteBspArgs`]. Children of BSP nodes are always nodes or leaves, and
+/// children of clipping nodes are always nodes or contents.
+#[repr(C)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum NodeChild
+{
+	Node(u32),
+	Leaf(u32),
+	Contents(LeafContents),
+}
+
+/// Planes come in pairs, as described for [`WriteBspArgs::planes`].
+#[repr(C)]
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct PlaneArgs
+{
+	pub normal: [f64; 3],
+	pub distance: f64,
+}
+
+#[repr(C)]
+pub struct TextureArgs<'l>
+{
+	pub name: StringRef<'l>,
+	pub width: u32,
+	pub height: u32,
+
+	/// Palette indices of the texture's pixels, row by row, if the texture
+	/// should be embedded in the file. This is empty if the texture is to be
+	/// loaded from elsewhere by the game.
+	pub pixels: SliceRef<'l, u8>,
+
+	/// RGB colours of the palette used by the pixels. This is empty if the
+	/// texture is not embedded.
+	pub palette: SliceRef<'l, u8>,
+}
+
+/// Projection of a texture onto faces. The axes are in world space, and are
+/// scaled so that they give texture coordinates in texels once the offsets
+/// are added.
+#[repr(C)]
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct TextureInfoArgs
+{
+	pub u_axis: [f64; 3],
+	pub u_offset: f64,
+	pub v_axis: [f64; 3],
+	pub v_offset: f64,
+	pub texture: u32,
+
+	/// Set for faces that have no lightmap, such as sky and liquid surfaces.
+	pub special: bool,
+}
+
+#[repr(C)]
+pub struct FaceArgs<'l>
+{
+	/// Plane that the face lies on and faces along. This may be either plane
+	/// of a pair.
+	pub plane: u32,
+
+	/// Corners of the face, wound clockwise when viewed from the front.
+	pub points: SliceRef<'l, [f64; 3]>,
+
+	pub texture_info: u32,
+
+	/// Light styles of the face's lightmap, with unused styles set to 255.
+	pub styles: [u8; 4],
+
+	/// Byte offset of the face's lightmap within the lighting data, or -1 if
+	/// the face has no lightmap.
+	pub lighting_offset: i32,
+
+	/// Position of the lightmap's first sample along each texture axis, and
+	/// its number of samples, in luxels. These are zero if the face has no
+	/// lightmap.
+	pub lightmap_mins: [i32; 2],
+	pub lightmap_size: [u32; 2],
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct NodeArgs
+{
+	/// Splitting plane, which is always the first of its pair.
+	pub plane: u32,
+
+	/// Children in front of and behind the plane, in that order.
+	pub children: [NodeChild; 2],
+
+	pub mins: [f64; 3],
+	pub maxs: [f64; 3],
+
+	/// Range of faces that lie on the node's plane.
+	pub first_face: u32,
+	pub face_count: u32,
+}
+
+#[repr(C)]
+pub struct LeafArgs<'l>
+{
+	pub contents: LeafContents,
+
+	/// Visibility cluster of the leaf, or -1 if it does not have one. Only
+	/// leaves of the world have clusters.
+	pub cluster: i32,
+
+	pub mins: [f64; 3],
+	pub maxs: [f64; 3],
+
+	/// Indices of the faces that can be seen from within the leaf.
+	pub faces: SliceRef<'l, u32>,
+
+	/// Indices of the brushes that overlap the leaf.
+	pub brushes: SliceRef<'l, u32>,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct ClipNodeArgs
+{
+	pub plane: u32,
+
+	/// Children in front of and behind the plane, in that order.
+	pub children: [NodeChild; 2],
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct BrushSideArgs
+{
+	/// Plane of the side, which faces out of the brush.
+	pub plane: u32,
+
+	/// One of the faces produced by the side, or -1 if none are visible.
+	pub face: i32,
+}
+
+/// Brush that a model was built from, for formats that use brushes for
+/// collision.
+#[repr(C)]
+pub struct BrushArgs<'l>
+{
+	pub contents: LeafContents,
+	pub sides: SliceRef<'l, BrushSideArgs>,
+	pub mins: [f64; 3],
+	pub maxs: [f64; 3],
+}
+
+#[repr(C)]
+pub struct ModelArgs<'l>
+{
+	pub mins: [f64; 3],
+	pub maxs: [f64; 3],
+	pub origin: [f64; 3],
+
+	/// Root of the model's BSP tree.
+	pub head_node: NodeChild,
+
+	/// Roots of the model's clipping trees, beginning with hull 1.
+	pub clip_head_nodes: SliceRef<'l, NodeChild>,
+
+	/// Range of the model's faces, nodes, leaves and brushes.
+	pub first_face: u32,
+	pub face_count: u32,
+	pub first_node: u32,
+	pub node_count: u32,
+	pub first_leaf: u32,
+	pub leaf_count: u32,
+	pub first_brush: u32,
+	pub brush_count: u32,
+}
+
+/// Everything that makes up a compiled map. Models are listed in order,
+/// beginning with the world, and the nodes, leaves, faces and brushes of each
+/// model follow those of the model before it.
+#[repr(C)]
+pub struct WriteBspArgs<'l>
+{
+	/// Path of the file that should be written.
+	pub path: StringRef<'l>,
+
+	/// Text of the entities, in the standard Quake format.
+	pub entities: StringRef<'l>,
+
+	/// Planes are stored in pairs, so that the plane at an even index faces
+	/// along the positive direction of its major axis, and the plane at the
+	/// following odd index is its flipped twin.
+	pub planes: SliceRef<'l, PlaneArgs>,
+
+	pub textures: SliceRef<'l, TextureArgs<'l>>,
+	pub texture_infos: SliceRef<'l, TextureInfoArgs>,
+	pub faces: SliceRef<'l, FaceArgs<'l>>,
+	pub nodes: SliceRef<'l, NodeArgs>,
+	pub leaves: SliceRef<'l, LeafArgs<'l>>,
+	pub clip_nodes: SliceRef<'l, ClipNodeArgs>,
+	pub brushes: SliceRef<'l, BrushArgs<'l>>,
+	pub models: SliceRef<'l, ModelArgs<'l>>,
+
+	/// Number of visibility clusters, and the clusters that each can see, as
+	/// one row per cluster. Each row holds one bit per cluster, least
+	/// significant bit first, and is padded to a whole number of bytes.
+	pub cluster_count: u32,
+	pub cluster_visibility: SliceRef<'l, u8>,
+
+	/// Lightmaps of every face, as RGB bytes.
+	pub lighting: SliceRef<'l, u8>,
+
+	/// Size of each lightmap sample, in texels.
+	pub luxel_size: f64,
+
+	/// Limits of the format that the game configuration overrides, such as
+	/// for engines that raise them. Writers should use these in place of
+	/// their own values for limits of the same name.
+	pub limits: SliceRef<'l, LimitArgs<'l>>,
+}
+
+/// Value of a limit of the format, such as MAX_MAP_PLANES.
+#[repr(C)]
+pub struct LimitArgs<'l>
+{
+	pub name: StringRef<'l>,
+	pub value: usize,
+}
+
+/// Description of a limit of the format that the map exceeds.
+#[repr(C)]
+pub struct ExceededLimitArgs<'l>
+{
+	/// Name of the limit, such as MAX_MAP_PLANES.
+	pub name: StringRef<'l>,
+
+	/// Number of items that the map has, and the most that the format allows.
+	pub count: usize,
+	pub limit: usize,
+}
+
+/// API passed to an extension's BSP writer callback.
+#[repr(C)]
+pub struct BspWriterApi<'l>
+{
+	fns: &'l mut internal::BspWriterApiCoreFns<'l>,
+}
+
+impl<'l> BspWriterApi<'l>
+{
+	/// Describes why writing the file failed. This should be called before
+	/// returning [`WriteBspResult::Failure`].
+	pub fn set_error(&mut self, message: &str)
+	{
+		self.fns.set_error(StringRef::from(message));
+	}
+
+	/// Reports a limit of the format that the map exceeds. This should be
+	/// called for every such limit before returning
+	/// [`WriteBspResult::Failure`].
+	pub fn report_exceeded_limit(&mut self, name: &str, count: usize, limit: usize)
+	{
+		self.fns.report_exceeded_limit(&ExceededLimitArgs {
+			name: StringRef::from(name),
+			count: count,
+			limit: limit,
+		});
+	}
+}
+
+pub mod internal
+{
+	use super::*;
+
+	#[repr(C)]
+	pub struct BspWriterApiCoreFns<'l>
+	{
+		pub context: *mut c_void,
+		pub phantom: PhantomData<&'l c_void>,
+
+		// All unsafe functions here expect that the function implementation
+		// converts the type-erased context back into the correct object.
+		pub set_error_fn: unsafe extern "C" fn(*mut c_void, StringRef),
+		pub report_exceeded_limit_fn: unsafe extern "C" fn(*mut c_void, &ExceededLimitArgs),
+	}
+
+	impl<'l> BspWriterApiCoreFns<'l>
+	{
+		pub fn set_error(&mut self, message: StringRef)
+		{
+			unsafe { (self.set_error_fn)(self.context, message) };
+		}
+
+		pub fn report_exceeded_limit(&mut self, args: &ExceededLimitArgs)
+		{
+			unsafe { (self.report_exceeded_limit_fn)(self.context, args) };
+		}
+	}
+
+	pub fn create_bsp_writer_api<'l>(
+		fns: &'l mut internal::BspWriterApiCoreFns<'l>,
+	) -> BspWriterApi<'l>
+	{
+		return BspWriterApi { fns: fns };
+	}
+}
@@ -0,0 +1,118 @@
+use super::api_info::ApiInfo;
+use std::ffi::c_void;
+use std::marker::PhantomData;
+
+pub const API_INFO: ApiInfo = ApiInfo::new("HullApi", 1);
+
+pub type DefineHullsFn = extern "C" fn(&mut HullApi);
+
+/// Size of the box that a clipping hull is built for. Vectors are specified
+/// as arrays of X, Y and Z components, relative to the origin of an entity
+/// colliding with the world.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct HullArgs
+{
+	pub mins: [f64; 3],
+	pub maxs: [f64; 3],
+}
+
+/// Callbacks registered by an extension that wishes to change the clipping
+/// hulls built for the map.
+#[repr(C)]
+#[derive(Clone)]
+pub struct HullCallbacks
+{
+	/// Called before the clipping hulls are built. The API initially holds
+	/// the hulls from the game configuration, as modified by any extensions
+	/// called previously.
+	pub define_hulls_fn: DefineHullsFn,
+}
+
+/// API passed to an extension's hull definition callback. Hulls are listed in
+/// the order in which they are written to the BSP, beginning with hull 1.
+/// Hull 0 is always the point hull, and cannot be changed.
+#[repr(C)]
+pub struct HullApi<'l>
+{
+	fns: &'l mut internal::HullApiCoreFns<'l>,
+}
+
+impl<'l> HullApi<'l>
+{
+	pub fn hull_count(&self) -> usize
+	{
+		return self.fns.hull_count();
+	}
+
+	/// Returns the hull at the given index, or [`None`] if the index is out of
+	/// range.
+	pub fn hull(&self, index: usize) -> Option<HullArgs>
+	{
+		return self.fns.hull(index);
+	}
+
+	/// Removes all hulls, so that they can be replaced.
+	pub fn clear_hulls(&mut self)
+	{
+		self.fns.clear_hulls();
+	}
+
+	pub fn add_hull(&mut self, args: &HullArgs)
+	{
+		self.fns.add_hull(args);
+	}
+}
+
+pub mod internal
+{
+	use super::*;
+
+	#[repr(C)]
+	pub struct HullApiCoreFns<'l>
+	{
+		pub context: *mut c_void,
+		pub phantom: PhantomData<&'l c_void>,
+
+		// All unsafe functions here expect that the function implementation
+		// converts the type-erased context back into the correct object.
+		pub hull_count_fn: unsafe extern "C" fn(*const c_void) -> usize,
+		pub hull_fn: unsafe extern "C" fn(*const c_void, usize, &mut HullArgs) -> bool,
+		pub clear_hulls_fn: unsafe extern "C" fn(*mut c_void),
+		pub add_hull_fn: unsafe extern "C" fn(*mut c_void, &HullArgs),
+	}
+
+	impl<'l> HullApiCoreFns<'l>
+	{
+		pub fn hull_count(&self) -> usize
+		{
+			return unsafe { (self.hull_count_fn)(self.context) };
+		}
+
+		pub fn hull(&self, index: usize) -> Option<HullArgs>
+		{
+			let mut args: HullArgs = HullArgs {
+				mins: [0.0; 3],
+				maxs: [0.0; 3],
+			};
+
+			let found: bool = unsafe { (self.hull_fn)(self.context, index, &mut args) };
+			return found.then_some(args);
+		}
+
+		pub fn clear_hulls(&mut self)
+		{
+			unsafe { (self.clear_hulls_fn)(self.context) };
+		}
+
+		pub fn add_hull(&mut self, args: &HullArgs)
+		{
+			unsafe { (self.add_hull_fn)(self.context, args) };
+		}
+	}
+
+	pub fn create_hull_api<'l>(fns: &'l mut internal::HullApiCoreFns<'l>) -> HullApi<'l>
+	{
+		return HullApi { fns: fns };
+	}
+}
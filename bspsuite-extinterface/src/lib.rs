@@ -2,13 +2,19 @@
 // https://users.rust-lang.org/t/linking-issues-when-designing-a-dynamic-plugin-based-architecture/136388
 
 mod api_info;
+mod slice_ref;
 mod string_ref;
 
-pub mod dummy_api;
+pub mod bsp_writer_api;
+pub mod hull_api;
 pub mod log_api;
+pub mod map_reader_api;
 pub mod probe_api;
+pub mod stage_hook_api;
+pub mod texture_api;
 
 pub use api_info::ApiInfo;
+pub use slice_ref::SliceRef;
 pub use string_ref::StringRef;
 
 /// Struct whose sole responsibility is to expose a versioned entry point API to
@@ -0,0 +1,229 @@
+use super::api_info::ApiInfo;
+use super::slice_ref::SliceRef;
+use super::string_ref::StringRef;
+use std::ffi::c_void;
+use std::marker::PhantomData;
+
+pub const API_INFO: ApiInfo = ApiInfo::new("MapReaderApi", 3);
+
+pub type ReadMapFn = extern "C" fn(&mut MapReaderApi, &ReadMapArgs) -> ReadMapResult;
+
+/// Enum representing the result of asking an extension to read a map file.
+#[repr(C)]
+pub enum ReadMapResult
+{
+	/// The map was read successfully, and all of its contents have been passed
+	/// to the API.
+	Success,
+
+	/// The map could not be read. The extension should have called
+	/// [`MapReaderApi::set_error`] or [`MapReaderApi::report_syntax_error`] to
+	/// describe why.
+	Failure,
+}
+
+#[repr(C)]
+pub struct ReadMapArgs<'l>
+{
+	/// Path to the map source file that should be read.
+	pub path: StringRef<'l>,
+}
+
+/// Callbacks registered by an extension that is able to read map source files.
+#[repr(C)]
+#[derive(Clone)]
+pub struct MapReaderCallbacks
+{
+	/// File extensions, without the leading dot, that this extension is able
+	/// to read. Comparisons against these are case-insensitive.
+	pub file_extensions: SliceRef<'static, StringRef<'static>>,
+
+	/// Called when a map file with one of the supported extensions needs to
+	/// be read.
+	pub read_map_fn: ReadMapFn,
+}
+
+/// Description of a single brush face. Vectors are specified as arrays of X, Y
+/// and Z components.
+#[repr(C)]
+pub struct BrushFaceArgs<'l>
+{
+	/// Three points on the face's plane, wound clockwise when viewed from the
+	/// front of the face.
+	pub plane_points: [[f64; 3]; 3],
+	pub material: StringRef<'l>,
+
+	/// Texture axes, which are only used if `has_texture_axes` is set.
+	/// Otherwise the face uses standard Quake alignment, and the axes are
+	/// worked out from the face's plane and rotation.
+	pub u_axis: [f64; 3],
+	pub u_offset: f64,
+	pub v_axis: [f64; 3],
+	pub v_offset: f64,
+	pub has_texture_axes: bool,
+	pub rotation: f64,
+	pub scale: [f64; 2],
+}
+
+/// Description of a syntax error encountered in a map source file.
+#[repr(C)]
+pub struct SyntaxErrorArgs<'l>
+{
+	/// 1-based line number on which the error occurred.
+	pub line: u32,
+
+	/// 1-based column, in characters, at which the error occurred.
+	pub column: u32,
+
+	/// Byte offset of the start of the offending text within the file.
+	pub span_start: usize,
+
+	/// Byte offset of the end of the offending text within the file. If this
+	/// is the same as the start offset, the error does not refer to any
+	/// particular text (eg. the file ended unexpectedly).
+	pub span_end: usize,
+
+	pub message: StringRef<'l>,
+
+	/// Full text of the line on which the error occurred, without the line
+	/// terminator.
+	pub line_text: StringRef<'l>,
+}
+
+/// API passed to an extension's map reader callback. Map contents are built
+/// up by calling these functions in the order in which the contents appear
+/// in the source file. Brushes must be begun and ended within an entity.
+#[repr(C)]
+pub struct MapReaderApi<'l>
+{
+	fns: &'l mut internal::MapReaderApiCoreFns<'l>,
+}
+
+// These are plain Rust functions rather than extern "C" ones, since they take
+// string slices. They are compiled into the extension, and convert their
+// arguments to C-compatible types before calling across the library boundary.
+impl<'l> MapReaderApi<'l>
+{
+	/// Begins a new entity. The line is the 1-based line in the source file on
+	/// which the entity begins, or 0 if this is not known.
+	pub fn begin_entity(&mut self, line: u32)
+	{
+		self.fns.begin_entity(line);
+	}
+
+	pub fn add_keyvalue(&mut self, key: &str, value: &str)
+	{
+		self.fns
+			.add_keyvalue(StringRef::from(key), StringRef::from(value));
+	}
+
+	/// Begins a new brush within the current entity. The line is the 1-based
+	/// line in the source file on which the brush begins, or 0 if this is not
+	/// known.
+	pub fn begin_brush(&mut self, line: u32)
+	{
+		self.fns.begin_brush(line);
+	}
+
+	pub fn add_brush_face(&mut self, args: &BrushFaceArgs)
+	{
+		self.fns.add_brush_face(args);
+	}
+
+	pub fn end_brush(&mut self)
+	{
+		self.fns.end_brush();
+	}
+
+	pub fn end_entity(&mut self)
+	{
+		self.fns.end_entity();
+	}
+
+	/// Describes why reading the map failed. This should be called before
+	/// returning [`ReadMapResult::Failure`].
+	pub fn set_error(&mut self, message: &str)
+	{
+		self.fns.set_error(StringRef::from(message));
+	}
+
+	/// Describes a syntax error that caused reading the map to fail. This
+	/// should be called before returning [`ReadMapResult::Failure`].
+	pub fn report_syntax_error(&mut self, args: &SyntaxErrorArgs)
+	{
+		self.fns.report_syntax_error(args);
+	}
+}
+
+pub mod internal
+{
+	use super::*;
+
+	#[repr(C)]
+	pub struct MapReaderApiCoreFns<'l>
+	{
+		pub context: *mut c_void,
+		pub phantom: PhantomData<&'l c_void>,
+
+		// All unsafe functions here expect that the function implementation
+		// converts the type-erased context back into the correct object.
+		pub begin_entity_fn: unsafe extern "C" fn(*mut c_void, u32),
+		pub add_keyvalue_fn: unsafe extern "C" fn(*mut c_void, StringRef, StringRef),
+		pub begin_brush_fn: unsafe extern "C" fn(*mut c_void, u32),
+		pub add_brush_face_fn: unsafe extern "C" fn(*mut c_void, &BrushFaceArgs),
+		pub end_brush_fn: unsafe extern "C" fn(*mut c_void),
+		pub end_entity_fn: unsafe extern "C" fn(*mut c_void),
+		pub set_error_fn: unsafe extern "C" fn(*mut c_void, StringRef),
+		pub report_syntax_error_fn: unsafe extern "C" fn(*mut c_void, &SyntaxErrorArgs),
+	}
+
+	impl<'l> MapReaderApiCoreFns<'l>
+	{
+		pub fn begin_entity(&mut self, line: u32)
+		{
+			unsafe { (self.begin_entity_fn)(self.context, line) };
+		}
+
+		pub fn add_keyvalue(&mut self, key: StringRef, value: StringRef)
+		{
+			unsafe { (self.add_keyvalue_fn)(self.context, key, value) };
+		}
+
+		pub fn begin_brush(&mut self, line: u32)
+		{
+			unsafe { (self.begin_brush_fn)(self.context, line) };
+		}
+
+		pub fn add_brush_face(&mut self, args: &BrushFaceArgs)
+		{
+			unsafe { (self.add_brush_face_fn)(self.context, args) };
+		}
+
+		pub fn end_brush(&mut self)
+		{
+			unsafe { (self.end_brush_fn)(self.context) };
+		}
+
+		pub fn end_entity(&mut self)
+		{
+			unsafe { (self.end_entity_fn)(self.context) };
+		}
+
+		pub fn set_error(&mut self, message: StringRef)
+		{
+			unsafe { (self.set_error_fn)(self.context, message) };
+		}
+
+		pub fn report_syntax_error(&mut self, args: &SyntaxErrorArgs)
+		{
+			unsafe { (self.report_syntax_error_fn)(self.context, args) };
+		}
+	}
+
+	pub fn create_map_reader_api<'l>(
+		fns: &'l mut internal::MapReaderApiCoreFns<'l>,
+	) -> MapReaderApi<'l>
+	{
+		return MapReaderApi { fns: fns };
+	}
+}
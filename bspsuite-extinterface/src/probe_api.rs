@@ -1,5 +1,5 @@
 use super::string_ref::StringRef;
-use super::{dummy_api, log_api};
+use super::{bsp_writer_api, hull_api, log_api, map_reader_api, stage_hook_api, texture_api};
 use log::{error, trace};
 use std::result::Result;
 
@@ -51,15 +51,71 @@ impl<'l> ProbeApi<'l>
 		);
 	}
 
-	pub fn register_dummy_api_callbacks(
+	pub fn register_map_reader_api_callbacks(
 		&mut self,
 		requested_version: usize,
-		callbacks: dummy_api::DummyCallbacks,
+		callbacks: map_reader_api::MapReaderCallbacks,
 	) -> Result<(), RequestError>
 	{
 		return internal::ExportedApis::request_set_callbacks(
 			self.extension_name.to_string().as_str(),
-			&mut self.apis.dummy_api,
+			&mut self.apis.map_reader_api,
+			requested_version,
+			callbacks,
+		);
+	}
+
+	pub fn register_hull_api_callbacks(
+		&mut self,
+		requested_version: usize,
+		callbacks: hull_api::HullCallbacks,
+	) -> Result<(), RequestError>
+	{
+		return internal::ExportedApis::request_set_callbacks(
+			self.extension_name.to_string().as_str(),
+			&mut self.apis.hull_api,
+			requested_version,
+			callbacks,
+		);
+	}
+
+	pub fn register_bsp_writer_api_callbacks(
+		&mut self,
+		requested_version: usize,
+		callbacks: bsp_writer_api::BspWriterCallbacks,
+	) -> Result<(), RequestError>
+	{
+		return internal::ExportedApis::request_set_callbacks(
+			self.extension_name.to_string().as_str(),
+			&mut self.apis.bsp_writer_api,
+			requested_version,
+			callbacks,
+		);
+	}
+
+	pub fn register_texture_api_callbacks(
+		&mut self,
+		requested_version: usize,
+		callbacks: texture_api::TextureProviderCallbacks,
+	) -> Result<(), RequestError>
+	{
+		return internal::ExportedApis::request_set_callbacks(
+			self.extension_name.to_string().as_str(),
+			&mut self.apis.texture_api,
+			requested_version,
+			callbacks,
+		);
+	}
+
+	pub fn register_stage_hook_api_callbacks(
+		&mut self,
+		requested_version: usize,
+		callbacks: stage_hook_api::StageHookCallbacks,
+	) -> Result<(), RequestError>
+	{
+		return internal::ExportedApis::request_set_callbacks(
+			self.extension_name.to_string().as_str(),
+			&mut self.apis.stage_hook_api,
 			requested_version,
 			callbacks,
 		);
@@ -105,7 +161,11 @@ pub mod internal
 	pub struct ExportedApis
 	{
 		pub log_api: ApiProvider<log_api::LogApi>,
-		pub dummy_api: CallbacksContainer<dummy_api::DummyCallbacks>,
+		pub map_reader_api: CallbacksContainer<map_reader_api::MapReaderCallbacks>,
+		pub hull_api: CallbacksContainer<hull_api::HullCallbacks>,
+		pub bsp_writer_api: CallbacksContainer<bsp_writer_api::BspWriterCallbacks>,
+		pub texture_api: CallbacksContainer<texture_api::TextureProviderCallbacks>,
+		pub stage_hook_api: CallbacksContainer<stage_hook_api::StageHookCallbacks>,
 	}
 
 	#[doc(hidden)]
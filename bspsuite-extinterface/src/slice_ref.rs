@@ -0,0 +1,75 @@
+use std::marker::PhantomData;
+use std::slice;
+
+/// Shim wrapper to allow passing a slice reference across a library boundary.
+/// The elements themselves must be safe to pass across the boundary.
+#[repr(C)]
+pub struct SliceRef<'l, T>
+{
+	begin: *const T,
+	length: usize,
+	phantom: PhantomData<&'l T>,
+}
+
+impl<'l, T> From<&'l [T]> for SliceRef<'l, T>
+{
+	fn from(value: &'l [T]) -> Self
+	{
+		return Self::new(value);
+	}
+}
+
+// These are implemented by hand, since deriving them would require T to
+// implement them too. Copying a SliceRef only copies the reference.
+impl<T> Clone for SliceRef<'_, T>
+{
+	fn clone(&self) -> Self
+	{
+		return *self;
+	}
+}
+
+impl<T> Copy for SliceRef<'_, T>
+{
+}
+
+// SAFETY: A SliceRef behaves like the shared slice reference that it was
+// constructed from, which may be sent between threads if its elements may.
+unsafe impl<T: Sync> Send for SliceRef<'_, T>
+{
+}
+
+unsafe impl<T: Sync> Sync for SliceRef<'_, T>
+{
+}
+
+impl<'l, T> SliceRef<'l, T>
+{
+	/// Equivalent to [`From::from`], but usable in constant expressions, such
+	/// as when building the callbacks that an extension registers.
+	pub const fn new(value: &'l [T]) -> Self
+	{
+		return Self {
+			begin: value.as_ptr(),
+			length: value.len(),
+			phantom: PhantomData,
+		};
+	}
+
+	pub fn as_slice(&self) -> &'l [T]
+	{
+		// SAFETY: Self can only be constructed from a slice, whose lifetime it
+		// is tied to, so the elements are still alive.
+		return unsafe { slice::from_raw_parts(self.begin, self.length) };
+	}
+
+	pub fn len(&self) -> usize
+	{
+		return self.length;
+	}
+
+	pub fn is_empty(&self) -> bool
+	{
+		return self.length == 0;
+	}
+}
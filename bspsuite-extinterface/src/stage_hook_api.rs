@@ -0,0 +1,451 @@
+use super::api_info::ApiInfo;
+use super::slice_ref::SliceRef;
+use super::string_ref::StringRef;
+use std::ffi::c_void;
+use std::marker::PhantomData;
+
+pub const API_INFO: ApiInfo = ApiInfo::new("StageHookApi", 1);
+
+pub type StageHookFn = extern "C" fn(&mut StageHookApi) -> StageHookResult;
+
+/// Points in the compile pipeline at which hooks may be run. The compiled
+/// data that each stage builds may be read from every point after it.
+#[repr(C)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum HookPoint
+{
+	/// After the map has been read and its brushes validated, but before the
+	/// textures of its materials are looked up and CSG is run. This is the
+	/// only point at which brush face materials may be changed.
+	PreCsg,
+
+	/// After the BSP models and visibility portals have been built, but before
+	/// the clipping hulls are.
+	PostBsp,
+
+	/// After visibility has been computed, but before lighting. Light
+	/// entities are read from the map after this point.
+	PreLighting,
+
+	/// After lightmaps have been computed, but before the compiled map is
+	/// assembled and written. The entities lump is built from the map's
+	/// keyvalues after this point.
+	PreWrite,
+}
+
+/// Enum representing the result of running a stage hook.
+#[repr(C)]
+pub enum StageHookResult
+{
+	/// The hook completed successfully, and compiling can continue.
+	Success,
+
+	/// The hook failed, and compiling should stop. The extension should have
+	/// called [`StageHookApi::set_error`] to describe why.
+	Failure,
+}
+
+/// A callback to run at a point in the pipeline.
+#[repr(C)]
+#[derive(Clone)]
+pub struct StageHook
+{
+	/// Name of the hook, used when reporting what it is doing.
+	pub name: StringRef<'static>,
+	pub point: HookPoint,
+	pub hook_fn: StageHookFn,
+}
+
+/// Callbacks registered by an extension that wishes to inspect or modify the
+/// map as it is being compiled.
+#[repr(C)]
+#[derive(Clone)]
+pub struct StageHookCallbacks
+{
+	/// Hooks are run in the order in which their extensions were loaded, then
+	/// in the order in which they are listed here.
+	pub hooks: SliceRef<'static, StageHook>,
+}
+
+/// Key and value of one of an entity's keyvalues.
+#[repr(C)]
+pub struct KeyValueArgs<'l>
+{
+	pub key: StringRef<'l>,
+	pub value: StringRef<'l>,
+}
+
+/// Description of one of the compiled BSP models.
+#[repr(C)]
+#[derive(Clone, Copy, Default, Debug)]
+pub struct BspModelArgs
+{
+	/// Index of the entity that the model was built from.
+	pub entity: usize,
+	pub mins: [f64; 3],
+	pub maxs: [f64; 3],
+	pub node_count: usize,
+	pub leaf_count: usize,
+	pub face_count: usize,
+}
+
+/// The brush face that a face of a compiled BSP model was cut from.
+#[repr(C)]
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub struct BspFaceArgs
+{
+	pub entity: usize,
+	pub brush: usize,
+	pub face: usize,
+}
+
+/// API passed to a stage hook, giving access to the map being compiled.
+/// Entities, brushes and faces are referred to by their index, in the order in
+/// which they appear in the map. Brushes that were dropped when validating the
+/// map are not included.
+#[repr(C)]
+pub struct StageHookApi<'l>
+{
+	fns: &'l mut internal::StageHookApiCoreFns<'l>,
+}
+
+// These are plain Rust functions rather than extern "C" ones, since they take
+// and return strings. They are compiled into the extension, and convert their
+// arguments to C-compatible types before calling across the library boundary.
+impl<'l> StageHookApi<'l>
+{
+	/// The point in the pipeline at which the hook is being run.
+	pub fn hook_point(&self) -> HookPoint
+	{
+		return self.fns.hook_point();
+	}
+
+	/// Name of the BSP format that the map is being compiled to, which may be
+	/// used to apply tweaks for a particular game.
+	pub fn bsp_format(&self) -> String
+	{
+		return self.fns.bsp_format();
+	}
+
+	pub fn entity_count(&self) -> usize
+	{
+		return self.fns.entity_count();
+	}
+
+	/// Returns the number of keyvalues on the entity, or 0 if the index is
+	/// out of range.
+	pub fn keyvalue_count(&self, entity: usize) -> usize
+	{
+		return self.fns.keyvalue_count(entity);
+	}
+
+	/// Returns the key and value of the entity's keyvalue at the given index,
+	/// or [`None`] if either index is out of range.
+	pub fn keyvalue(&self, entity: usize, index: usize) -> Option<(String, String)>
+	{
+		return self.fns.keyvalue(entity, index);
+	}
+
+	/// Sets the value of the last keyvalue on the entity with the given key,
+	/// adding the keyvalue if the entity does not have it. Returns false if the
+	/// entity index is out of range.
+	pub fn set_keyvalue(&mut self, entity: usize, key: &str, value: &str) -> bool
+	{
+		return self
+			.fns
+			.set_keyvalue(entity, StringRef::from(key), StringRef::from(value));
+	}
+
+	/// Removes every keyvalue on the entity with the given key, and returns
+	/// how many there were.
+	pub fn remove_keyvalues(&mut self, entity: usize, key: &str) -> usize
+	{
+		return self.fns.remove_keyvalues(entity, StringRef::from(key));
+	}
+
+	/// Returns the number of brushes in the entity, or 0 if the index is out
+	/// of range.
+	pub fn brush_count(&self, entity: usize) -> usize
+	{
+		return self.fns.brush_count(entity);
+	}
+
+	/// Returns the number of faces on the brush, or 0 if either index is out
+	/// of range.
+	pub fn face_count(&self, entity: usize, brush: usize) -> usize
+	{
+		return self.fns.face_count(entity, brush);
+	}
+
+	/// Returns the material of the face, or [`None`] if any index is out of
+	/// range.
+	pub fn face_material(&self, entity: usize, brush: usize, face: usize) -> Option<String>
+	{
+		return self.fns.face_material(entity, brush, face);
+	}
+
+	/// Changes the material of the face. Returns false if any index is out of
+	/// range, or if the hook is not being run at [`HookPoint::PreCsg`].
+	pub fn set_face_material(
+		&mut self,
+		entity: usize,
+		brush: usize,
+		face: usize,
+		material: &str,
+	) -> bool
+	{
+		return self
+			.fns
+			.set_face_material(entity, brush, face, StringRef::from(material));
+	}
+
+	/// Returns the number of BSP models that have been built, which is 0
+	/// before [`HookPoint::PostBsp`]. The world is always model 0.
+	pub fn model_count(&self) -> usize
+	{
+		return self.fns.model_count();
+	}
+
+	/// Describes the BSP model, or returns [`None`] if the index is out of
+	/// range.
+	pub fn model(&self, model: usize) -> Option<BspModelArgs>
+	{
+		return self.fns.model(model);
+	}
+
+	/// Returns where a face of the BSP model came from, or [`None`] if either
+	/// index is out of range.
+	pub fn model_face(&self, model: usize, face: usize) -> Option<BspFaceArgs>
+	{
+		return self.fns.model_face(model, face);
+	}
+
+	/// Returns the number of visibility clusters in the world, which is 0
+	/// before [`HookPoint::PostBsp`].
+	pub fn cluster_count(&self) -> usize
+	{
+		return self.fns.cluster_count();
+	}
+
+	/// Returns the number of portals between visibility clusters, which is 0
+	/// before [`HookPoint::PostBsp`].
+	pub fn portal_count(&self) -> usize
+	{
+		return self.fns.portal_count();
+	}
+
+	/// Returns the visibility cluster of a leaf of the world, or [`None`] if
+	/// the leaf seals the map or the index is out of range.
+	pub fn leaf_cluster(&self, leaf: usize) -> Option<usize>
+	{
+		return self.fns.leaf_cluster(leaf);
+	}
+
+	/// Returns whether one visibility cluster can see another, or [`None`]
+	/// before [`HookPoint::PreLighting`] or if either index is out of range.
+	pub fn is_cluster_visible(&self, from: usize, to: usize) -> Option<bool>
+	{
+		return self.fns.is_cluster_visible(from, to);
+	}
+
+	/// Returns the number of lightmap samples along each texture axis of a
+	/// face of the BSP model. This is [`None`] before [`HookPoint::PreWrite`],
+	/// if either index is out of range, or if the face is not lit.
+	pub fn face_lightmap_size(&self, model: usize, face: usize) -> Option<[usize; 2]>
+	{
+		return self.fns.face_lightmap_size(model, face);
+	}
+
+	/// Describes why the hook failed. This should be called before returning
+	/// [`StageHookResult::Failure`].
+	pub fn set_error(&mut self, message: &str)
+	{
+		self.fns.set_error(StringRef::from(message));
+	}
+}
+
+pub mod internal
+{
+	use super::*;
+
+	#[repr(C)]
+	pub struct StageHookApiCoreFns<'l>
+	{
+		pub context: *mut c_void,
+		pub phantom: PhantomData<&'l c_void>,
+
+		// All unsafe functions here expect that the function implementation
+		// converts the type-erased context back into the correct object.
+		// Strings passed back to the extension refer to data owned by the
+		// core, and must be copied before the core is called again.
+		pub hook_point_fn: unsafe extern "C" fn(*const c_void) -> HookPoint,
+		pub bsp_format_fn: unsafe extern "C" fn(*const c_void, &mut StringRef),
+		pub entity_count_fn: unsafe extern "C" fn(*const c_void) -> usize,
+		pub keyvalue_count_fn: unsafe extern "C" fn(*const c_void, usize) -> usize,
+		pub keyvalue_fn:
+			unsafe extern "C" fn(*const c_void, usize, usize, &mut KeyValueArgs) -> bool,
+		pub set_keyvalue_fn: unsafe extern "C" fn(*mut c_void, usize, StringRef, StringRef) -> bool,
+		pub remove_keyvalues_fn: unsafe extern "C" fn(*mut c_void, usize, StringRef) -> usize,
+		pub brush_count_fn: unsafe extern "C" fn(*const c_void, usize) -> usize,
+		pub face_count_fn: unsafe extern "C" fn(*const c_void, usize, usize) -> usize,
+		pub face_material_fn:
+			unsafe extern "C" fn(*const c_void, usize, usize, usize, &mut StringRef) -> bool,
+		pub set_face_material_fn:
+			unsafe extern "C" fn(*mut c_void, usize, usize, usize, StringRef) -> bool,
+		pub model_count_fn: unsafe extern "C" fn(*const c_void) -> usize,
+		pub model_fn: unsafe extern "C" fn(*const c_void, usize, &mut BspModelArgs) -> bool,
+		pub model_face_fn:
+			unsafe extern "C" fn(*const c_void, usize, usize, &mut BspFaceArgs) -> bool,
+		pub cluster_count_fn: unsafe extern "C" fn(*const c_void) -> usize,
+		pub portal_count_fn: unsafe extern "C" fn(*const c_void) -> usize,
+		pub leaf_cluster_fn: unsafe extern "C" fn(*const c_void, usize, &mut usize) -> bool,
+		pub cluster_visible_fn:
+			unsafe extern "C" fn(*const c_void, usize, usize, &mut bool) -> bool,
+		pub face_lightmap_size_fn:
+			unsafe extern "C" fn(*const c_void, usize, usize, &mut [usize; 2]) -> bool,
+		pub set_error_fn: unsafe extern "C" fn(*mut c_void, StringRef),
+	}
+
+	impl<'l> StageHookApiCoreFns<'l>
+	{
+		pub fn hook_point(&self) -> HookPoint
+		{
+			return unsafe { (self.hook_point_fn)(self.context) };
+		}
+
+		pub fn bsp_format(&self) -> String
+		{
+			let mut format: StringRef = StringRef::from("");
+			unsafe { (self.bsp_format_fn)(self.context, &mut format) };
+			return format.to_string();
+		}
+
+		pub fn entity_count(&self) -> usize
+		{
+			return unsafe { (self.entity_count_fn)(self.context) };
+		}
+
+		pub fn keyvalue_count(&self, entity: usize) -> usize
+		{
+			return unsafe { (self.keyvalue_count_fn)(self.context, entity) };
+		}
+
+		pub fn keyvalue(&self, entity: usize, index: usize) -> Option<(String, String)>
+		{
+			let mut args: KeyValueArgs = KeyValueArgs {
+				key: StringRef::from(""),
+				value: StringRef::from(""),
+			};
+
+			let found: bool = unsafe { (self.keyvalue_fn)(self.context, entity, index, &mut args) };
+			return found.then(|| (args.key.to_string(), args.value.to_string()));
+		}
+
+		pub fn set_keyvalue(&mut self, entity: usize, key: StringRef, value: StringRef) -> bool
+		{
+			return unsafe { (self.set_keyvalue_fn)(self.context, entity, key, value) };
+		}
+
+		pub fn remove_keyvalues(&mut self, entity: usize, key: StringRef) -> usize
+		{
+			return unsafe { (self.remove_keyvalues_fn)(self.context, entity, key) };
+		}
+
+		pub fn brush_count(&self, entity: usize) -> usize
+		{
+			return unsafe { (self.brush_count_fn)(self.context, entity) };
+		}
+
+		pub fn face_count(&self, entity: usize, brush: usize) -> usize
+		{
+			return unsafe { (self.face_count_fn)(self.context, entity, brush) };
+		}
+
+		pub fn face_material(&self, entity: usize, brush: usize, face: usize) -> Option<String>
+		{
+			let mut material: StringRef = StringRef::from("");
+			let found: bool = unsafe {
+				(self.face_material_fn)(self.context, entity, brush, face, &mut material)
+			};
+
+			return found.then(|| material.to_string());
+		}
+
+		pub fn set_face_material(
+			&mut self,
+			entity: usize,
+			brush: usize,
+			face: usize,
+			material: StringRef,
+		) -> bool
+		{
+			return unsafe {
+				(self.set_face_material_fn)(self.context, entity, brush, face, material)
+			};
+		}
+
+		pub fn model_count(&self) -> usize
+		{
+			return unsafe { (self.model_count_fn)(self.context) };
+		}
+
+		pub fn model(&self, model: usize) -> Option<BspModelArgs>
+		{
+			let mut args: BspModelArgs = BspModelArgs::default();
+			let found: bool = unsafe { (self.model_fn)(self.context, model, &mut args) };
+			return found.then_some(args);
+		}
+
+		pub fn model_face(&self, model: usize, face: usize) -> Option<BspFaceArgs>
+		{
+			let mut args: BspFaceArgs = BspFaceArgs::default();
+			let found: bool = unsafe { (self.model_face_fn)(self.context, model, face, &mut args) };
+			return found.then_some(args);
+		}
+
+		pub fn cluster_count(&self) -> usize
+		{
+			return unsafe { (self.cluster_count_fn)(self.context) };
+		}
+
+		pub fn portal_count(&self) -> usize
+		{
+			return unsafe { (self.portal_count_fn)(self.context) };
+		}
+
+		pub fn leaf_cluster(&self, leaf: usize) -> Option<usize>
+		{
+			let mut cluster: usize = 0;
+			let found: bool = unsafe { (self.leaf_cluster_fn)(self.context, leaf, &mut cluster) };
+			return found.then_some(cluster);
+		}
+
+		pub fn is_cluster_visible(&self, from: usize, to: usize) -> Option<bool>
+		{
+			let mut visible: bool = false;
+			let found: bool =
+				unsafe { (self.cluster_visible_fn)(self.context, from, to, &mut visible) };
+			return found.then_some(visible);
+		}
+
+		pub fn face_lightmap_size(&self, model: usize, face: usize) -> Option<[usize; 2]>
+		{
+			let mut size: [usize; 2] = [0; 2];
+			let found: bool =
+				unsafe { (self.face_lightmap_size_fn)(self.context, model, face, &mut size) };
+			return found.then_some(size);
+		}
+
+		pub fn set_error(&mut self, message: StringRef)
+		{
+			unsafe { (self.set_error_fn)(self.context, message) };
+		}
+	}
+
+	pub fn create_stage_hook_api<'l>(
+		fns: &'l mut internal::StageHookApiCoreFns<'l>,
+	) -> StageHookApi<'l>
+	{
+		return StageHookApi { fns: fns };
+	}
+}
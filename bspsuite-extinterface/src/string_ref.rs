@@ -6,6 +6,7 @@ use std::slice;
 /// Shim wrapper to allow passing a string reference across a library boundary.
 /// Requires taking ownership of the string in order to access its data.
 #[repr(C)]
+#[derive(Clone, Copy)]
 pub struct StringRef<'l>
 {
 	begin: *const c_uchar,
@@ -27,6 +28,40 @@ impl<'l> From<&str> for StringRef<'l>
 	}
 }
 
+// SAFETY: A StringRef behaves like the shared string reference that it was
+// constructed from, which may be sent between threads.
+unsafe impl Send for StringRef<'_>
+{
+}
+
+unsafe impl Sync for StringRef<'_>
+{
+}
+
+impl<'l> StringRef<'l>
+{
+	/// Equivalent to [`From::from`], but usable in constant expressions, such
+	/// as when building the callbacks that an extension registers.
+	pub const fn new(value: &'l str) -> Self
+	{
+		return Self {
+			begin: value.as_ptr(),
+			length: value.len(),
+			phantom: PhantomData,
+		};
+	}
+
+	/// Borrows the string without copying it.
+	pub fn as_str(&self) -> &'l str
+	{
+		// SAFETY: As for to_string() below, the string that Self was
+		// constructed from is valid UTF-8, and outlives 'l.
+		return unsafe {
+			std::str::from_utf8_unchecked(slice::from_raw_parts(self.begin, self.length))
+		};
+	}
+}
+
 impl<'l> Into<String> for StringRef<'l>
 {
 	fn into(self) -> String
@@ -0,0 +1,165 @@
+use super::api_info::ApiInfo;
+use super::slice_ref::SliceRef;
+use super::string_ref::StringRef;
+use std::ffi::c_void;
+use std::marker::PhantomData;
+
+pub const API_INFO: ApiInfo = ApiInfo::new("TextureApi", 1);
+
+pub type FindTexturesFn = extern "C" fn(&mut TextureApi, &FindTexturesArgs) -> FindTexturesResult;
+
+/// Enum representing the result of asking an extension for textures.
+#[repr(C)]
+pub enum FindTexturesResult
+{
+	/// The extension provided whichever of the textures it was able to find.
+	/// This does not have to be all of them.
+	Success,
+
+	/// The extension was unable to look for textures. It should have called
+	/// [`TextureApi::set_error`] to describe why.
+	Failure,
+}
+
+/// A source of textures, such as texture archives, loose image files or
+/// material definitions.
+#[repr(C)]
+#[derive(Clone)]
+pub struct TextureProvider
+{
+	/// Name of the provider, used when reporting which textures it found.
+	pub name: StringRef<'static>,
+
+	/// Providers are asked for textures in order of descending priority.
+	/// Materials found by one provider are not asked for from any providers
+	/// after it.
+	pub priority: i32,
+
+	/// Called with the materials that providers of higher priority did not
+	/// find.
+	pub find_textures_fn: FindTexturesFn,
+}
+
+/// Callbacks registered by an extension that is able to provide textures.
+#[repr(C)]
+#[derive(Clone)]
+pub struct TextureProviderCallbacks
+{
+	/// Providers of equal priority are asked in the order in which their
+	/// extensions were loaded, then in the order in which they are listed
+	/// here.
+	pub providers: SliceRef<'static, TextureProvider>,
+}
+
+#[repr(C)]
+pub struct KeyValueArgs<'l>
+{
+	pub key: StringRef<'l>,
+	pub value: StringRef<'l>,
+}
+
+#[repr(C)]
+pub struct FindTexturesArgs<'l>
+{
+	/// Path to the map source file that the textures are used by.
+	pub map_path: StringRef<'l>,
+
+	/// Keyvalues of the world entity, in the order in which they appear in
+	/// the map. These may refer to where the map's textures are kept.
+	pub world_keyvalues: SliceRef<'l, KeyValueArgs<'l>>,
+
+	/// Directories in which to look for textures, from the game
+	/// configuration. Relative directories are relative to the directory of
+	/// the map source file.
+	pub search_paths: SliceRef<'l, StringRef<'l>>,
+
+	/// Names of the materials to look for, as they are written in the map.
+	/// Comparisons against these are case-insensitive.
+	pub materials: SliceRef<'l, StringRef<'l>>,
+}
+
+/// Description of a texture found by a provider.
+#[repr(C)]
+pub struct TextureArgs<'l>
+{
+	/// Name of the material that the texture is for.
+	pub name: StringRef<'l>,
+	pub width: u32,
+	pub height: u32,
+
+	/// Average RGB colour of the texture, with components ranging from 0 to
+	/// 255. This is only used if `has_average_color` is set.
+	pub average_color: [f64; 3],
+	pub has_average_color: bool,
+
+	/// Palette indices of the texture's pixels, row by row, or empty if they
+	/// are not available.
+	pub pixels: SliceRef<'l, u8>,
+
+	/// RGB colours of the palette used by the pixels, or empty if there is
+	/// none.
+	pub palette: SliceRef<'l, u8>,
+}
+
+/// API passed to an extension's texture provider callback.
+#[repr(C)]
+pub struct TextureApi<'l>
+{
+	fns: &'l mut internal::TextureApiCoreFns<'l>,
+}
+
+// These are plain Rust functions rather than extern "C" ones, since they take
+// string slices. They are compiled into the extension, and convert their
+// arguments to C-compatible types before calling across the library boundary.
+impl<'l> TextureApi<'l>
+{
+	/// Provides the texture for one of the requested materials. Textures for
+	/// materials that were not requested, or that have already been
+	/// provided, are ignored.
+	pub fn add_texture(&mut self, args: &TextureArgs)
+	{
+		self.fns.add_texture(args);
+	}
+
+	/// Describes why looking for textures failed. This should be called
+	/// before returning [`FindTexturesResult::Failure`].
+	pub fn set_error(&mut self, message: &str)
+	{
+		self.fns.set_error(StringRef::from(message));
+	}
+}
+
+pub mod internal
+{
+	use super::*;
+
+	#[repr(C)]
+	pub struct TextureApiCoreFns<'l>
+	{
+		pub context: *mut c_void,
+		pub phantom: PhantomData<&'l c_void>,
+
+		// All unsafe functions here expect that the function implementation
+		// converts the type-erased context back into the correct object.
+		pub add_texture_fn: unsafe extern "C" fn(*mut c_void, &TextureArgs),
+		pub set_error_fn: unsafe extern "C" fn(*mut c_void, StringRef),
+	}
+
+	impl<'l> TextureApiCoreFns<'l>
+	{
+		pub fn add_texture(&mut self, args: &TextureArgs)
+		{
+			unsafe { (self.add_texture_fn)(self.context, args) };
+		}
+
+		pub fn set_error(&mut self, message: StringRef)
+		{
+			unsafe { (self.set_error_fn)(self.context, message) };
+		}
+	}
+
+	pub fn create_texture_api<'l>(fns: &'l mut internal::TextureApiCoreFns<'l>) -> TextureApi<'l>
+	{
+		return TextureApi { fns: fns };
+	}
+}